@@ -0,0 +1,949 @@
+use crate::conflicts::{ConflictPolicy, ConflictResolver, ConflictSide, Resolution, SetAside};
+use crate::jobs::JobHandle;
+use crate::utils::dbg_log;
+use bzip2::read::MultiBzDecoder;
+use chrono::{Local, NaiveDate};
+use flate2::read::MultiGzDecoder;
+use lz4_flex::frame::FrameDecoder;
+use serde::Deserialize;
+use sevenz_rust::{Password, SevenZArchiveEntry, SevenZReader};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tar::EntryType;
+use xz2::read::XzDecoder;
+use zip::result::ZipError;
+
+/// Compression of a tarball or of a single compressed file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    None,
+    Gz,
+    Bz2,
+    Xz,
+    Zst,
+    Lz4,
+}
+
+/// Archive formats that can be listed and read member by member
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    SevenZ,
+    Rar,
+    Tar(Codec),
+    /// A single compressed file like `log.gz`, its only member is the
+    /// decompressed file
+    Stream(Codec),
+}
+
+const TAR_MAGIC_OFFSET: usize = 257;
+
+impl ArchiveKind {
+    /// Detects the format by the magic bytes, falling back on the extension.
+    /// Compressed files are tarballs if their extension says so (`.tar.xz`,
+    /// `.tgz`, ...) or if the decompressed data starts with a tar header.
+    pub fn of(path: &Path) -> Option<ArchiveKind> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        let Ok(head) = read_head(open(path).ok()?) else {
+            return ArchiveKind::by_extension(&name);
+        };
+        let codec = if head.starts_with(b"PK\x03\x04") || head.starts_with(b"PK\x05\x06") {
+            return Some(ArchiveKind::Zip);
+        } else if head.starts_with(&[b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C]) {
+            return Some(ArchiveKind::SevenZ);
+        } else if head.starts_with(b"Rar!\x1A\x07") {
+            return Some(ArchiveKind::Rar);
+        } else if is_tar_header(&head) {
+            return Some(ArchiveKind::Tar(Codec::None));
+        } else if head.starts_with(&[0x1F, 0x8B]) {
+            Codec::Gz
+        } else if head.starts_with(b"BZh") {
+            Codec::Bz2
+        } else if head.starts_with(&[0xFD, b'7', b'z', b'X', b'Z', 0x00]) {
+            Codec::Xz
+        } else if head.starts_with(&[0x28, 0xB5, 0x2F, 0xFD]) {
+            Codec::Zst
+        } else if head.starts_with(&[0x04, 0x22, 0x4D, 0x18]) {
+            Codec::Lz4
+        } else {
+            return ArchiveKind::by_extension(&name);
+        };
+
+        // Old tar formats have no magic, only the extension tells then
+        let is_tar = matches!(ArchiveKind::by_extension(&name), Some(ArchiveKind::Tar(_)))
+            || open(path)
+                .and_then(|file| decoder(codec, BufReader::new(file)))
+                .and_then(|data| read_head(data).map_err(|e| e.to_string()))
+                .is_ok_and(|head| is_tar_header(&head));
+        match is_tar {
+            true => Some(ArchiveKind::Tar(codec)),
+            false => Some(ArchiveKind::Stream(codec)),
+        }
+    }
+
+    /// The format by the extension, including compound ones like `.tar.gz`
+    fn by_extension(name: &str) -> Option<ArchiveKind> {
+        let codec_of = |ext: &str| match ext {
+            "gz" => Some(Codec::Gz),
+            "bz2" => Some(Codec::Bz2),
+            "xz" => Some(Codec::Xz),
+            "zst" => Some(Codec::Zst),
+            "lz4" => Some(Codec::Lz4),
+            _ => None,
+        };
+        let (rest, ext) = name.rsplit_once('.')?;
+        let kind = match ext {
+            "zip" => ArchiveKind::Zip,
+            "7z" => ArchiveKind::SevenZ,
+            "rar" => ArchiveKind::Rar,
+            "tar" => ArchiveKind::Tar(Codec::None),
+            "tgz" => ArchiveKind::Tar(Codec::Gz),
+            "tbz" | "tbz2" => ArchiveKind::Tar(Codec::Bz2),
+            "txz" => ArchiveKind::Tar(Codec::Xz),
+            "tzst" => ArchiveKind::Tar(Codec::Zst),
+            ext => {
+                let codec = codec_of(ext)?;
+                match rest.ends_with(".tar") {
+                    true => ArchiveKind::Tar(codec),
+                    false => ArchiveKind::Stream(codec),
+                }
+            }
+        };
+        Some(kind)
+    }
+}
+
+/// A member of an archive
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    /// Name exactly as stored in the archive
+    pub name: String,
+    /// Name as stored in the archive with `/` separators and without empty,
+    /// `.` or `..` parts
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+    /// Epoch millis
+    pub modified: Option<u64>,
+    /// Unix permissions, restored on unpacked files
+    pub mode: Option<u32>,
+    pub link: Option<ArchiveLink>,
+}
+
+/// Target of a link member
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchiveLink {
+    /// Relative to the dir of the link
+    Symlink(String),
+    /// Name of a member stored earlier in the archive
+    Hardlink(String),
+}
+
+impl ArchiveEntry {
+    fn new(name: String, is_dir: bool, size: u64, modified: Option<u64>) -> ArchiveEntry {
+        ArchiveEntry {
+            path: member_path(&name),
+            name,
+            is_dir,
+            size: if is_dir { 0 } else { size },
+            modified,
+            mode: None,
+            link: None,
+        }
+    }
+}
+
+/// How `extract_archive` unpacks an archive
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ExtractOptions {
+    /// Dir to extract to, the dir of the archive if empty
+    pub target_dir: String,
+    /// Extracts into a new dir named like the archive, `foo` for `foo.tar.gz`.
+    /// Single compressed files always end up right in the target dir.
+    pub create_subfolder: bool,
+    pub conflict_policy: ConflictPolicy,
+    pub password: Option<String>,
+    /// Members to extract including everything below them, all if empty
+    pub members: Vec<String>,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        ExtractOptions {
+            target_dir: String::new(),
+            create_subfolder: true,
+            conflict_policy: ConflictPolicy::default(),
+            password: None,
+            members: vec![],
+        }
+    }
+}
+
+/// Lists the members without unpacking them, tarballs are read once
+pub fn list_entries(path: &Path, kind: ArchiveKind) -> Result<Vec<ArchiveEntry>, String> {
+    match kind {
+        ArchiveKind::Zip => {
+            let mut archive =
+                zip::ZipArchive::new(BufReader::new(open(path)?)).map_err(|e| e.to_string())?;
+            (0..archive.len())
+                .map(|i| zip_entry(&mut archive, i))
+                .collect()
+        }
+        ArchiveKind::SevenZ => Ok(sevenz_rust::Archive::open(path)
+            .map_err(sevenz_error)?
+            .files
+            .iter()
+            .map(sevenz_entry)
+            .collect()),
+        ArchiveKind::Rar => unrar::Archive::new(path)
+            .open_for_listing()
+            .map_err(|e| e.to_string())?
+            .map(|header| Ok(rar_entry(&header.map_err(|e| e.to_string())?)))
+            .collect(),
+        ArchiveKind::Tar(codec) => {
+            let mut archive = tar::Archive::new(decoder(codec, BufReader::new(open(path)?))?);
+            let mut entries = vec![];
+            for entry in archive.entries().map_err(|e| e.to_string())? {
+                entries.push(tar_entry(&entry.map_err(|e| e.to_string())?)?);
+            }
+            Ok(entries)
+        }
+        ArchiveKind::Stream(codec) => {
+            // The size is only known after decompressing everything
+            let mut data = decoder(codec, BufReader::new(open(path)?))?;
+            let size = io::copy(&mut data, &mut io::sink()).map_err(|e| e.to_string())?;
+            Ok(vec![stream_entry(path, size)])
+        }
+    }
+}
+
+/// Extracts an archive as described by `options`. Members that would end up
+/// outside of the target dir fail the whole extraction.
+pub fn extract_archive(
+    job: &JobHandle,
+    path: &Path,
+    options: &ExtractOptions,
+) -> Result<(), String> {
+    let kind = ArchiveKind::of(path).ok_or(format!(
+        "{} isn't a supported archive",
+        path.to_string_lossy()
+    ))?;
+    let mut target_dir = match options.target_dir.is_empty() {
+        true => path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        false => PathBuf::from(&options.target_dir),
+    };
+    if options.create_subfolder && !matches!(kind, ArchiveKind::Stream(_)) {
+        target_dir = target_dir.join(archive_stem(path));
+    }
+    let members = options
+        .members
+        .iter()
+        .map(|member| member_path(member))
+        .collect::<Vec<_>>();
+    dbg_log(format!(
+        "Extracting {:?} of {:?} to {:?}",
+        members, path, target_dir
+    ));
+
+    let mut resolver = ConflictResolver::new(options.conflict_policy);
+    let mut replaced = SetAside::default();
+    let mut matched = false;
+    let extracted = extract_entries(
+        path,
+        kind,
+        options.password.as_deref(),
+        Some(job),
+        &target_dir,
+        |entry| {
+            check_member_name(&entry.name)?;
+            let is_selected = members.is_empty()
+                || members.iter().any(|member| {
+                    entry.path == *member || entry.path.starts_with(&format!("{}/", member))
+                });
+            if !is_selected || entry.path.is_empty() {
+                return Ok(None);
+            }
+            matched = true;
+
+            let dest = target_dir.join(&entry.path);
+            resolve_member(job, &mut resolver, &mut replaced, path, entry, dest)
+        },
+    );
+    replaced.finish(extracted)?;
+    if !matched && !members.is_empty() {
+        return Err("None of the selected members are in the archive".into());
+    }
+    Ok(())
+}
+
+/// Where the member `entry` of the archive at `path` gets unpacked to instead
+/// of `dest`, or None if it's skipped. Replaced targets are set aside in
+/// `replaced` until the extraction finished.
+pub fn resolve_member(
+    job: &JobHandle,
+    resolver: &mut ConflictResolver,
+    replaced: &mut SetAside,
+    path: &Path,
+    entry: &ArchiveEntry,
+    dest: PathBuf,
+) -> Result<Option<PathBuf>, String> {
+    // Dirs are merged, they usually exist already as parents of earlier members
+    if entry.is_dir && dest.is_dir() {
+        return Ok(Some(dest));
+    }
+    let source = ConflictSide {
+        path: format!("{}/{}", path.to_string_lossy(), entry.path),
+        name: entry
+            .path
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+        is_dir: entry.is_dir,
+        size: entry.size,
+        last_modified: entry.modified.unwrap_or(0) as u128,
+    };
+    match resolver.resolve_source(job, source, &dest)? {
+        Resolution::CopyTo(dest) => Ok(Some(dest)),
+        Resolution::Replace(dest) => {
+            replaced.set_aside(&dest)?;
+            Ok(Some(dest))
+        }
+        Resolution::Skip => Ok(None),
+    }
+}
+
+/// Unpacks the members `target` returns a destination for in a single pass
+/// over the archive, reporting each of them to the job. Links must not point
+/// outside of `root`. Returns how many members were unpacked.
+pub fn extract_entries<F>(
+    path: &Path,
+    kind: ArchiveKind,
+    password: Option<&str>,
+    job: Option<&JobHandle>,
+    root: &Path,
+    mut target: F,
+) -> Result<usize, String>
+where
+    F: FnMut(&ArchiveEntry) -> Result<Option<PathBuf>, String>,
+{
+    let begin = |entry: &ArchiveEntry| {
+        if let Some(job) = job {
+            job.checkpoint()?;
+            job.begin_item(&entry.path);
+        }
+        Ok::<(), String>(())
+    };
+    let mut count = 0;
+    let mut finish = |bytes: u64| {
+        if let Some(job) = job {
+            job.add_bytes(bytes);
+            job.finish_item();
+        }
+        count += 1;
+    };
+    let mut unpacked = HashMap::new();
+    match kind {
+        ArchiveKind::Zip => {
+            let mut archive =
+                zip::ZipArchive::new(BufReader::new(open(path)?)).map_err(|e| e.to_string())?;
+            for i in 0..archive.len() {
+                let mut entry = zip_entry(&mut archive, i)?;
+                let Some(dest) = target(&entry)? else {
+                    continue;
+                };
+                begin(&entry)?;
+                let mut file =
+                    match password {
+                        Some(password) => archive
+                            .by_index_decrypt(i, password.as_bytes())
+                            .map_err(|e| match e {
+                                ZipError::InvalidPassword => {
+                                    format!("Wrong password for {}", entry.path)
+                                }
+                                e => e.to_string(),
+                            })?,
+                        None => archive.by_index(i).map_err(|e| e.to_string())?,
+                    };
+                // Zip stores the target as the content of a symlink
+                if file.is_symlink() {
+                    let mut link = String::new();
+                    file.read_to_string(&mut link).map_err(|e| e.to_string())?;
+                    entry.link = Some(ArchiveLink::Symlink(link));
+                }
+                let bytes = write_entry(&entry, &mut file, &dest, root, &mut unpacked)?;
+                finish(bytes);
+            }
+        }
+        ArchiveKind::SevenZ => {
+            let mut reader =
+                SevenZReader::open(path, password.map_or(Password::empty(), Password::from))
+                    .map_err(sevenz_error)?;
+            let mut result = Ok(());
+            reader
+                .for_each_entries(|file, data| {
+                    // Stopping only ends the current block
+                    if result.is_err() {
+                        return Ok(false);
+                    }
+                    let mut entry = sevenz_entry(file);
+                    let written = target(&entry).and_then(|dest| match dest {
+                        Some(dest) => {
+                            begin(&entry)?;
+                            // Like in zip the target is the content of a symlink
+                            if entry.mode.is_some_and(is_symlink_mode) {
+                                let mut link = String::new();
+                                data.read_to_string(&mut link).map_err(|e| e.to_string())?;
+                                entry.link = Some(ArchiveLink::Symlink(link));
+                            }
+                            let bytes = write_entry(&entry, data, &dest, root, &mut unpacked)?;
+                            finish(bytes);
+                            Ok(())
+                        }
+                        // Members of a solid block can only be read in order
+                        None => io::copy(data, &mut io::sink())
+                            .map(|_| ())
+                            .map_err(|e| e.to_string()),
+                    });
+                    if written.is_err() {
+                        result = written;
+                        return Ok(false);
+                    }
+                    Ok(true)
+                })
+                .map_err(sevenz_error)?;
+            result?;
+        }
+        ArchiveKind::Rar => {
+            let archive = match password {
+                Some(password) => unrar::Archive::with_password(path, password),
+                None => unrar::Archive::new(path),
+            };
+            let mut archive = archive.open_for_processing().map_err(|e| e.to_string())?;
+            while let Some(header) = archive.read_header().map_err(|e| e.to_string())? {
+                let entry = rar_entry(header.entry());
+                archive = match target(&entry)? {
+                    Some(dest) if entry.is_dir => {
+                        begin(&entry)?;
+                        fs::create_dir_all(&dest).map_err(|e| e.to_string())?;
+                        finish(0);
+                        header.skip().map_err(|e| e.to_string())?
+                    }
+                    Some(dest) => {
+                        begin(&entry)?;
+                        if let Some(parent) = dest.parent() {
+                            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                        }
+                        let archive = header.extract_to(&dest).map_err(|e| e.to_string())?;
+                        finish(entry.size);
+                        archive
+                    }
+                    None => header.skip().map_err(|e| e.to_string())?,
+                };
+            }
+        }
+        ArchiveKind::Tar(codec) => {
+            let mut archive = tar::Archive::new(decoder(codec, BufReader::new(open(path)?))?);
+            for file in archive.entries().map_err(|e| e.to_string())? {
+                let mut file = file.map_err(|e| e.to_string())?;
+                let entry = tar_entry(&file)?;
+                let Some(dest) = target(&entry)? else {
+                    continue;
+                };
+                begin(&entry)?;
+                let bytes = write_entry(&entry, &mut file, &dest, root, &mut unpacked)?;
+                finish(bytes);
+            }
+        }
+        ArchiveKind::Stream(codec) => {
+            let entry = stream_entry(path, 0);
+            if let Some(dest) = target(&entry)? {
+                begin(&entry)?;
+                let mut data = decoder(codec, BufReader::new(open(path)?))?;
+                let bytes = write_entry(&entry, &mut data, &dest, root, &mut unpacked)?;
+                finish(bytes);
+            }
+        }
+    }
+    Ok(count)
+}
+
+/// `name` with `/` separators and without empty, `.` or `..` parts
+pub fn member_path(name: &str) -> String {
+    name.replace('\\', "/")
+        .split('/')
+        .filter(|part| !part.is_empty() && *part != "." && *part != "..")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Name of the dir a whole archive is extracted to, `foo` for `foo.tar.gz`.
+/// Also the name of the only member of a single compressed file.
+pub fn archive_stem(archive: &Path) -> String {
+    let name = archive
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let stem = [".tar.gz", ".tar.bz2", ".tar.xz", ".tar.zst", ".tar.lz4"]
+        .iter()
+        .find_map(|suffix| name.strip_suffix(suffix));
+    match stem {
+        Some(stem) => stem.to_string(),
+        None => archive
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+    }
+}
+
+/// Zip-slip protection, a member must not point above or outside of the dir
+/// it's extracted to
+fn check_member_name(name: &str) -> Result<(), String> {
+    let name = name.replace('\\', "/");
+    let bytes = name.as_bytes();
+    let is_absolute = name.starts_with('/')
+        || (bytes.len() > 1 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':');
+    if is_absolute || name.split('/').any(|part| part == "..") {
+        return Err(format!(
+            "Refusing to extract {}, it would end up outside of the target dir",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Raw access doesn't need the password of encrypted members
+fn zip_entry<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    index: usize,
+) -> Result<ArchiveEntry, String> {
+    let file = archive.by_index_raw(index).map_err(|e| e.to_string())?;
+    Ok(ArchiveEntry {
+        mode: file.unix_mode(),
+        ..ArchiveEntry::new(
+            file.name().to_string(),
+            file.is_dir(),
+            file.size(),
+            file.last_modified()
+                .and_then(|modified| dos_millis(modified.datepart(), modified.timepart())),
+        )
+    })
+}
+
+/// p7zip keeps the unix mode in the upper bits of the attributes
+fn sevenz_entry(file: &SevenZArchiveEntry) -> ArchiveEntry {
+    let attributes = file.windows_attributes();
+    ArchiveEntry {
+        mode: (file.has_windows_attributes && attributes & 0x8000 != 0).then_some(attributes >> 16),
+        ..ArchiveEntry::new(
+            file.name().to_string(),
+            file.is_directory(),
+            file.size(),
+            file.has_last_modified_date
+                .then(|| filetime_millis(file.last_modified_date().to_raw()))
+                .flatten(),
+        )
+    }
+}
+
+fn rar_entry(header: &unrar::FileHeader) -> ArchiveEntry {
+    ArchiveEntry::new(
+        header.filename.to_string_lossy().to_string(),
+        header.is_directory(),
+        header.unpacked_size,
+        dos_millis((header.file_time >> 16) as u16, header.file_time as u16),
+    )
+}
+
+fn tar_entry<R: Read>(entry: &tar::Entry<R>) -> Result<ArchiveEntry, String> {
+    let header = entry.header();
+    let link_name = || {
+        entry
+            .link_name()
+            .ok()
+            .flatten()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    let link = match header.entry_type() {
+        EntryType::Symlink => Some(ArchiveLink::Symlink(link_name())),
+        EntryType::Link => Some(ArchiveLink::Hardlink(link_name())),
+        _ => None,
+    };
+    Ok(ArchiveEntry {
+        mode: header.mode().ok(),
+        link,
+        ..ArchiveEntry::new(
+            entry
+                .path()
+                .map_err(|e| e.to_string())?
+                .to_string_lossy()
+                .to_string(),
+            header.entry_type().is_dir(),
+            header.size().unwrap_or(0),
+            header.mtime().ok().map(|secs| secs * 1000),
+        )
+    })
+}
+
+/// The only member of a single compressed file, named like the file
+/// without the compression extension
+fn stream_entry(path: &Path, size: u64) -> ArchiveEntry {
+    let modified = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_millis() as u64);
+    ArchiveEntry::new(archive_stem(path), false, size, modified)
+}
+
+/// The errors of sevenz_rust only have debug output
+fn sevenz_error(e: sevenz_rust::Error) -> String {
+    match e {
+        sevenz_rust::Error::PasswordRequired => "The archive is protected by a password".into(),
+        sevenz_rust::Error::ChecksumVerificationFailed => {
+            "The archive is damaged or the password is wrong".into()
+        }
+        e => e.to_string(),
+    }
+}
+
+fn open(path: &Path) -> Result<File, String> {
+    File::open(path).map_err(|e| format!("{}: {}", path.to_string_lossy(), e))
+}
+
+/// Decompresses `data`, `Codec::None` passes it through
+fn decoder<R: Read + 'static>(codec: Codec, data: R) -> Result<Box<dyn Read>, String> {
+    Ok(match codec {
+        Codec::None => Box::new(data),
+        Codec::Gz => Box::new(MultiGzDecoder::new(data)),
+        Codec::Bz2 => Box::new(MultiBzDecoder::new(data)),
+        Codec::Xz => Box::new(XzDecoder::new_multi_decoder(data)),
+        Codec::Zst => Box::new(zstd::stream::read::Decoder::new(data).map_err(|e| e.to_string())?),
+        Codec::Lz4 => Box::new(FrameDecoder::new(data)),
+    })
+}
+
+/// Up to the first 512 bytes, enough for the magic of any format and a tar header
+fn read_head<R: Read>(data: R) -> io::Result<Vec<u8>> {
+    let mut head = Vec::with_capacity(512);
+    data.take(512).read_to_end(&mut head)?;
+    Ok(head)
+}
+
+fn is_tar_header(head: &[u8]) -> bool {
+    head.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + 5) == Some(b"ustar")
+}
+
+/// Writes a member to `dest`, returns how many bytes were written. Hardlinks
+/// point at members written earlier, `unpacked` maps their paths to where
+/// they went.
+fn write_entry(
+    entry: &ArchiveEntry,
+    data: &mut dyn Read,
+    dest: &Path,
+    root: &Path,
+    unpacked: &mut HashMap<String, PathBuf>,
+) -> Result<u64, String> {
+    if entry.is_dir {
+        return fs::create_dir_all(dest)
+            .map(|_| 0)
+            .map_err(|e| e.to_string());
+    }
+    let dir = dest.parent().unwrap_or(root);
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let bytes = match &entry.link {
+        Some(ArchiveLink::Symlink(target)) => {
+            check_link_target(root, dir, target)?;
+            create_link(target, dest).map_err(|e| e.to_string())?;
+            0
+        }
+        Some(ArchiveLink::Hardlink(target)) => {
+            check_member_name(target)?;
+            let original = unpacked.get(&member_path(target)).ok_or(format!(
+                "{} links to {}, which wasn't extracted",
+                entry.path, target
+            ))?;
+            // Hardlinks don't work across devices, a copy does
+            match fs::hard_link(original, dest) {
+                Ok(()) => 0,
+                Err(_) => fs::copy(original, dest).map_err(|e| e.to_string())?,
+            }
+        }
+        None => {
+            let mut file = File::create(dest).map_err(|e| e.to_string())?;
+            let bytes = io::copy(data, &mut file).map_err(|e| e.to_string())?;
+            if let Some(mode) = entry.mode {
+                set_mode(&file, mode).map_err(|e| e.to_string())?;
+            }
+            bytes
+        }
+    };
+    unpacked.insert(entry.path.clone(), dest.to_path_buf());
+    Ok(bytes)
+}
+
+/// Symlinks unpacked to `dir` must not point outside of `root`, neither
+/// directly nor through links unpacked earlier. `dir` is canonicalized so
+/// those are followed, `..` after a name is refused as that name could be
+/// another link.
+fn check_link_target(root: &Path, dir: &Path, target: &str) -> Result<(), String> {
+    let refused = || {
+        format!(
+            "Refusing to extract a link to {}, it would point outside of the target dir",
+            target
+        )
+    };
+    let target = target.replace('\\', "/");
+    // Absolute targets and drive letters are refused like member names
+    let names = target
+        .split('/')
+        .filter(|part| *part != "..")
+        .collect::<Vec<_>>()
+        .join("/");
+    check_member_name(&names).map_err(|_| refused())?;
+
+    let root = root.canonicalize().map_err(|e| e.to_string())?;
+    let dir = dir.canonicalize().map_err(|e| e.to_string())?;
+    let dir = dir.strip_prefix(&root).map_err(|_| refused())?;
+    let mut resolved = dir
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    let mut after_name = false;
+    for part in target.split('/') {
+        match part {
+            "" | "." => {}
+            ".." if !after_name && resolved.last().is_some_and(|last| last != "..") => {
+                resolved.pop();
+            }
+            part => {
+                after_name |= part != "..";
+                resolved.push(part.to_string());
+            }
+        }
+    }
+    check_member_name(&resolved.join("/")).map_err(|_| refused())
+}
+
+#[cfg(unix)]
+fn create_link(target: &str, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(not(unix))]
+fn create_link(target: &str, link: &Path) -> io::Result<()> {
+    // Windows tells links to dirs apart from links to files
+    let dir = link.parent().unwrap_or(Path::new(""));
+    match dir.join(target).is_dir() {
+        true => std::os::windows::fs::symlink_dir(target, link),
+        false => std::os::windows::fs::symlink_file(target, link),
+    }
+}
+
+/// Whether a unix mode as zip and 7z store it is the one of a symlink
+fn is_symlink_mode(mode: u32) -> bool {
+    mode & 0o170000 == 0o120000
+}
+
+/// Restores the permissions of a file, without setuid, setgid and sticky bits
+#[cfg(unix)]
+fn set_mode(file: &File, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    // Zip also stores the file type, S_IFMT and S_IFREG
+    const S_IFMT: u32 = 0o170000;
+    const S_IFREG: u32 = 0o100000;
+    if mode & S_IFMT != 0 && mode & S_IFMT != S_IFREG {
+        return Ok(());
+    }
+    file.set_permissions(fs::Permissions::from_mode(mode & 0o777))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_file: &File, _mode: u32) -> io::Result<()> {
+    Ok(())
+}
+
+/// MS-DOS date and time as used by zip and rar, in local time
+fn dos_millis(date: u16, time: u16) -> Option<u64> {
+    let date = NaiveDate::from_ymd_opt(
+        (date >> 9) as i32 + 1980,
+        ((date >> 5) & 0xF) as u32,
+        (date & 0x1F) as u32,
+    )?;
+    let date = date.and_hms_opt(
+        (time >> 11) as u32,
+        ((time >> 5) & 0x3F) as u32,
+        ((time & 0x1F) * 2) as u32,
+    )?;
+    let millis = date
+        .and_local_timezone(Local)
+        .earliest()?
+        .timestamp_millis();
+    u64::try_from(millis).ok()
+}
+
+/// Windows file time, 100ns ticks since 1601
+fn filetime_millis(ticks: u64) -> Option<u64> {
+    (ticks / 10_000).checked_sub(11_644_473_600_000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const EVIL_NAMES: [&str; 5] = ["../x", "a/../../x", "/etc/passwd", "C:\\x", "a\\..\\..\\x"];
+
+    /// A tar header named `name` as is, the tar crate refuses to write `..`
+    fn raw_header(name: &str, entry_type: EntryType, size: u64) -> tar::Header {
+        let mut header = tar::Header::new_gnu();
+        header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_entry_type(entry_type);
+        header.set_size(size);
+        header.set_mode(0o644);
+        header.set_cksum();
+        header
+    }
+
+    fn extract(archive: &Path, target_dir: &Path) -> Result<(), String> {
+        let options = ExtractOptions {
+            target_dir: target_dir.to_string_lossy().to_string(),
+            create_subfolder: false,
+            ..ExtractOptions::default()
+        };
+        extract_archive(&JobHandle::detached(), archive, &options)
+    }
+
+    #[test]
+    fn member_names_outside_of_the_target_are_refused() {
+        for name in EVIL_NAMES {
+            assert!(check_member_name(name).is_err(), "{}", name);
+        }
+        for name in ["x", "./a/x", "a/b/x", "a..b/x", "a\\b\\x"] {
+            assert!(check_member_name(name).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn member_paths_drop_empty_and_dot_parts() {
+        let paths = EVIL_NAMES.map(member_path);
+        assert_eq!(paths, ["x", "a/x", "etc/passwd", "C:/x", "a/x"]);
+        assert_eq!(member_path("./a//b/"), "a/b");
+    }
+
+    #[test]
+    fn tar_members_outside_of_the_target_fail_the_extraction() {
+        let dir = TempDir::new().unwrap();
+        let archive = dir.path().join("evil.tar");
+        let mut builder = tar::Builder::new(File::create(&archive).unwrap());
+        for name in ["good.txt", "../evil.txt"] {
+            builder
+                .append(&raw_header(name, EntryType::Regular, 4), &b"data"[..])
+                .unwrap();
+        }
+        builder.finish().unwrap();
+        drop(builder);
+
+        let target = dir.path().join("out");
+        assert!(extract(&archive, &target).is_err());
+        assert!(!dir.path().join("evil.txt").exists());
+        assert!(!target.join("evil.txt").exists());
+    }
+
+    #[test]
+    fn zip_members_outside_of_the_target_fail_the_extraction() {
+        let dir = TempDir::new().unwrap();
+        let archive = dir.path().join("evil.zip");
+        let mut writer = zip::ZipWriter::new(File::create(&archive).unwrap());
+        for name in ["a/../../evil.txt", "/evil.txt"] {
+            writer
+                .start_file(name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            io::Write::write_all(&mut writer, b"data").unwrap();
+        }
+        writer.finish().unwrap();
+
+        let target = dir.path().join("out/deep");
+        assert!(extract(&archive, &target).is_err());
+        assert!(!dir.path().join("evil.txt").exists());
+        assert!(!dir.path().join("out/evil.txt").exists());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_and_modes_are_restored() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = TempDir::new().unwrap();
+        let archive = dir.path().join("links.tar");
+        let mut builder = tar::Builder::new(File::create(&archive).unwrap());
+        let mut header = raw_header("bin/run", EntryType::Regular, 2);
+        header.set_mode(0o4755);
+        header.set_cksum();
+        builder.append(&header, &b"#!"[..]).unwrap();
+        let mut header = raw_header("bin/again", EntryType::Link, 0);
+        header.set_link_name("bin/run").unwrap();
+        header.set_cksum();
+        builder.append(&header, io::empty()).unwrap();
+        let mut header = raw_header("lib/run", EntryType::Symlink, 0);
+        header.set_link_name("../bin/run").unwrap();
+        header.set_cksum();
+        builder.append(&header, io::empty()).unwrap();
+        builder.finish().unwrap();
+        drop(builder);
+
+        let target = dir.path().join("out");
+        extract(&archive, &target).unwrap();
+        let mode = fs::metadata(target.join("bin/run"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o7777, 0o755);
+        assert_eq!(fs::read_to_string(target.join("bin/again")).unwrap(), "#!");
+        assert_eq!(
+            fs::read_link(target.join("lib/run")).unwrap(),
+            Path::new("../bin/run")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_outside_of_the_target_are_refused() {
+        let dir = TempDir::new().unwrap();
+        // `here` points at the target dir, the second link climbs out of it
+        let links = [
+            [("up", "../..")].as_slice(),
+            &[("abs", "/etc")],
+            &[("a/inner", "b/../../..")],
+            &[("here", "."), ("here/up", "../escape")],
+        ];
+        for (i, links) in links.iter().enumerate() {
+            let archive = dir.path().join(format!("{}.tar", i));
+            let mut builder = tar::Builder::new(File::create(&archive).unwrap());
+            for (name, target) in links.iter() {
+                let mut header = raw_header(name, EntryType::Symlink, 0);
+                header.set_link_name_literal(target).unwrap();
+                header.set_cksum();
+                builder.append(&header, io::empty()).unwrap();
+            }
+            builder.finish().unwrap();
+            drop(builder);
+
+            let target = dir.path().join(format!("out{}/target", i));
+            assert!(extract(&archive, &target).is_err(), "{:?}", links);
+            let (name, _) = links.last().unwrap();
+            assert!(
+                fs::symlink_metadata(target.join(name)).is_err(),
+                "{:?}",
+                links
+            );
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use crate::archive::{
+    archive_stem, extract_entries, list_entries, member_path, resolve_member, ArchiveEntry,
+    ArchiveKind,
+};
+use crate::conflicts::{ConflictResolver, SetAside};
+use crate::fdir::{extension_of, FDir, FileType};
+use crate::jobs::JobHandle;
+use crate::utils::{dbg_log, DirWalkerEntry};
+use crate::vfs::CloudProvider;
+use crate::SimpleDirInfo;
+use chrono::DateTime;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+pub const ARCHIVE_SCHEME: &str = "archive://";
+
+const READ_ONLY: &str = "Archives can only be read, extract them to change their content";
+
+/// Members of an archive, valid as long as the file doesn't change
+struct ArchiveIndex {
+    modified: Option<SystemTime>,
+    len: u64,
+    entries: Arc<Vec<ArchiveEntry>>,
+}
+
+/// Archives on the local disk as read-only dirs. Paths look like
+/// `archive:///home/user/foo.zip/dir/file`, the first part of the path that
+/// is a file is the archive.
+pub struct ArchiveFs {
+    indexes: Mutex<HashMap<PathBuf, ArchiveIndex>>,
+}
+
+impl CloudProvider for ArchiveFs {
+    fn new() -> Self {
+        ArchiveFs {
+            indexes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn authenticate(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn sign_out(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn read_dir(&mut self, path: &PathBuf) -> Result<Vec<FDir>, String> {
+        let (archive, _, inner) = resolve(&path.to_string_lossy())?;
+        let entries = self.entries(&archive)?;
+        let prefix = match inner.is_empty() {
+            true => "".to_string(),
+            false => format!("{}/", inner),
+        };
+
+        // Dirs often only exist as the parents of their members
+        let mut children: BTreeMap<String, FDir> = BTreeMap::new();
+        for entry in entries.iter() {
+            let Some(rest) = entry.path.strip_prefix(&prefix) else {
+                continue;
+            };
+            if rest.is_empty() {
+                continue;
+            }
+            match rest.split_once('/') {
+                Some((dir, _)) => {
+                    children
+                        .entry(dir.to_string())
+                        .or_insert_with(|| fdir_of(&archive, &format!("{}{}", prefix, dir), None));
+                }
+                None => {
+                    children.insert(
+                        rest.to_string(),
+                        fdir_of(&archive, &entry.path, Some(entry)),
+                    );
+                }
+            }
+        }
+        if children.is_empty() && !inner.is_empty() && !entries.iter().any(|e| e.path == inner) {
+            return Err(format!("{} doesn't exist in {:?}", inner, archive));
+        }
+        Ok(children.into_values().collect())
+    }
+
+    /// Only used for the temp copies files are opened and previewed from, so
+    /// an older copy is simply refreshed
+    fn download(&mut self, from_path: &str, to_path: &str) -> Result<String, String> {
+        let (archive, kind, inner) = resolve(from_path)?;
+        let saved_path = PathBuf::from(to_path).join(name_of(&inner));
+        dbg_log(format!(
+            "Extracting {} of {:?} to {:?}",
+            inner, archive, saved_path
+        ));
+        let count = extract_entries(&archive, kind, None, None, Path::new(to_path), |entry| {
+            Ok((entry.path == inner && !entry.is_dir).then(|| saved_path.clone()))
+        })?;
+        if count == 0 {
+            return Err(format!("{} isn't a file in {:?}", inner, archive));
+        }
+        Ok(saved_path.to_string_lossy().to_string())
+    }
+
+    fn upload(&mut self, _from_path: &str, _to_path: &str) -> Result<(), String> {
+        Err(READ_ONLY.into())
+    }
+
+    fn create_dir(&mut self, _from_path: &str, _to_path: &str) -> Result<(), String> {
+        Err(READ_ONLY.into())
+    }
+
+    /// Matches the members of the archives that were opened before
+    fn search(&mut self, fname: &str) -> Result<Vec<DirWalkerEntry>, String> {
+        let needle = fname.to_lowercase();
+        let indexes = self.indexes.lock().map_err(|e| e.to_string())?;
+        Ok(indexes
+            .iter()
+            .flat_map(|(archive, index)| {
+                index
+                    .entries
+                    .iter()
+                    .filter(|entry| name_of(&entry.path).to_lowercase().contains(&needle))
+                    .map(|entry| {
+                        let item = fdir_of(archive, &entry.path, Some(entry));
+                        DirWalkerEntry {
+                            last_modified: item
+                                .modified
+                                .and_then(|ms| DateTime::from_timestamp_millis(ms as i64))
+                                .map(|date| date.to_rfc3339())
+                                .unwrap_or_default(),
+                            depth: entry.path.matches('/').count() as u32,
+                            name: item.name,
+                            path: item.path,
+                            is_dir: entry.is_dir,
+                            is_file: !entry.is_dir,
+                            size: entry.size,
+                            extension: item.extension,
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect())
+    }
+
+    fn get_item_size(&self, path: &str) -> Result<SimpleDirInfo, String> {
+        let (archive, _, inner) = resolve(path)?;
+        let prefix = format!("{}/", inner);
+        let mut info = SimpleDirInfo {
+            size: 0,
+            count_elements: 0,
+        };
+        for entry in self.entries(&archive)?.iter().filter(|entry| {
+            !entry.is_dir
+                && (inner.is_empty() || entry.path == inner || entry.path.starts_with(&prefix))
+        }) {
+            info.size += entry.size;
+            info.count_elements += 1;
+        }
+        Ok(info)
+    }
+
+    /// Extracts the members into the local dir `copy_to_path`, the archives
+    /// themselves are never changed
+    fn copy_items(
+        &mut self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        arr_items: Vec<FDir>,
+        copy_to_path: &str,
+    ) -> Result<(), String> {
+        if copy_to_path.starts_with(ARCHIVE_SCHEME) {
+            return Err(READ_ONLY.into());
+        }
+        // Members of the same archive are extracted in one pass
+        let mut by_archive: Vec<(PathBuf, ArchiveKind, Vec<String>)> = vec![];
+        for item in &arr_items {
+            let info = self.get_item_size(&item.path)?;
+            job.add_total(info.count_elements, info.size);
+            let (archive, kind, inner) = resolve(&item.path)?;
+            match by_archive.iter_mut().find(|(path, _, _)| *path == archive) {
+                Some((_, _, members)) => members.push(inner),
+                None => by_archive.push((archive, kind, vec![inner])),
+            }
+        }
+
+        let to = PathBuf::from(copy_to_path);
+        for (archive, kind, members) in by_archive {
+            dbg_log(format!(
+                "Extracting {:?} of {:?} to {:?}",
+                members, archive, to
+            ));
+            let mut replaced = SetAside::default();
+            let extracted = extract_entries(&archive, kind, None, Some(job), &to, |entry| {
+                let dest = members.iter().find_map(|member| {
+                    // The whole archive, a member or everything below a dir
+                    let rel = match member.as_str() {
+                        "" => Some(entry.path.as_str()),
+                        _ if entry.path == *member => Some(""),
+                        _ => entry.path.strip_prefix(&format!("{}/", member)),
+                    }?;
+                    let name = match member.as_str() {
+                        "" => archive_stem(&archive),
+                        _ => name_of(member),
+                    };
+                    Some(match rel {
+                        "" => to.join(name),
+                        rel => to.join(name).join(rel),
+                    })
+                });
+                let Some(dest) = dest else {
+                    return Ok(None);
+                };
+                let dest = resolve_member(job, resolver, &mut replaced, &archive, entry, dest)?;
+                if dest.is_none() && !entry.is_dir {
+                    job.add_done(1, entry.size);
+                }
+                Ok(dest)
+            });
+            replaced.finish(extracted)?;
+        }
+        Ok(())
+    }
+
+    fn move_items(
+        &mut self,
+        _job: &JobHandle,
+        _resolver: &mut ConflictResolver,
+        _arr_items: Vec<FDir>,
+        _move_to_path: &str,
+    ) -> Result<(), String> {
+        Err(READ_ONLY.into())
+    }
+
+    fn delete(&mut self, _path: &str) -> Result<(), String> {
+        Err(READ_ONLY.into())
+    }
+
+    fn rename(&mut self, _path: &str, _new_name: &str) -> Result<(), String> {
+        Err(READ_ONLY.into())
+    }
+}
+
+impl ArchiveFs {
+    /// The members of an archive, only read again once the file changed
+    fn entries(&self, archive: &Path) -> Result<Arc<Vec<ArchiveEntry>>, String> {
+        let metadata = fs::metadata(archive).map_err(|e| e.to_string())?;
+        let modified = metadata.modified().ok();
+        if let Some(index) = self.indexes.lock().map_err(|e| e.to_string())?.get(archive) {
+            if index.modified == modified && index.len == metadata.len() {
+                return Ok(index.entries.clone());
+            }
+        }
+
+        let kind = ArchiveKind::of(archive).ok_or(format!("{:?} isn't an archive", archive))?;
+        dbg_log(format!("Reading the members of {:?}", archive));
+        let entries = Arc::new(list_entries(archive, kind)?);
+        self.indexes.lock().map_err(|e| e.to_string())?.insert(
+            archive.to_path_buf(),
+            ArchiveIndex {
+                modified,
+                len: metadata.len(),
+                entries: entries.clone(),
+            },
+        );
+        Ok(entries)
+    }
+}
+
+/// Splits an `archive://` path into the archive on the local disk, its kind
+/// and the path of the member
+fn resolve(path: &str) -> Result<(PathBuf, ArchiveKind, String), String> {
+    let full = PathBuf::from(
+        path.strip_prefix(ARCHIVE_SCHEME)
+            .ok_or(format!("{} is not an archive path", path))?,
+    );
+    let archive = full
+        .ancestors()
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .find(|ancestor| ancestor.is_file())
+        .ok_or(format!("There's no archive in {}", path))?
+        .to_path_buf();
+    let kind = ArchiveKind::of(&archive).ok_or(format!("{:?} isn't an archive", archive))?;
+    let inner = full
+        .strip_prefix(&archive)
+        .map_err(|e| e.to_string())?
+        .to_string_lossy()
+        .to_string();
+    Ok((archive, kind, member_path(&inner)))
+}
+
+fn fdir_of(archive: &Path, member: &str, entry: Option<&ArchiveEntry>) -> FDir {
+    let name = name_of(member);
+    let is_dir = entry.map_or(true, |entry| entry.is_dir);
+    FDir {
+        path: format!(
+            "{}{}",
+            ARCHIVE_SCHEME,
+            archive.join(member).to_string_lossy()
+        ),
+        file_type: if is_dir {
+            FileType::Dir
+        } else {
+            FileType::File
+        },
+        is_dir,
+        extension: extension_of(&name, is_dir),
+        size: entry.map_or(0, |entry| entry.size),
+        modified: entry.and_then(|entry| entry.modified),
+        is_hidden: name.starts_with('.'),
+        // Nothing inside of an archive can be changed in place
+        is_readonly: true,
+        mime_type: match is_dir {
+            true => "inode/directory".to_string(),
+            false => mime_guess::from_path(&name)
+                .first_or_octet_stream()
+                .essence_str()
+                .to_string(),
+        },
+        name,
+        ..Default::default()
+    }
+}
+
+fn name_of(member: &str) -> String {
+    member.rsplit('/').next().unwrap_or_default().to_string()
+}
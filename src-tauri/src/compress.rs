@@ -0,0 +1,394 @@
+use crate::jobs::JobHandle;
+use crate::utils::{dbg_log, wng_log};
+use chrono::{DateTime, Local};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Deserialize;
+use sevenz_rust::lzma::LZMA2Options;
+use sevenz_rust::{SevenZArchiveEntry, SevenZWriter};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+use xz2::write::XzEncoder;
+use zip::write::{FileOptions, SimpleFileOptions};
+use zip::{AesMode, CompressionMethod, ZipWriter};
+
+/// Formats `create_archive` can write
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveFormat {
+    #[default]
+    Zip,
+    #[serde(rename = "7z")]
+    SevenZ,
+    TarGz,
+    TarXz,
+    TarZst,
+}
+
+impl ArchiveFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => ".zip",
+            ArchiveFormat::SevenZ => ".7z",
+            ArchiveFormat::TarGz => ".tar.gz",
+            ArchiveFormat::TarXz => ".tar.xz",
+            ArchiveFormat::TarZst => ".tar.zst",
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct CompressOptions {
+    pub format: ArchiveFormat,
+    /// 0 - 9, mapped onto the levels of the format
+    pub level: u32,
+    /// Encrypts the members of zip archives with AES-256
+    pub password: Option<String>,
+}
+
+impl Default for CompressOptions {
+    fn default() -> Self {
+        CompressOptions {
+            format: ArchiveFormat::default(),
+            level: 6,
+            password: None,
+        }
+    }
+}
+
+/// A file or dir on the disk and its name inside of the archive
+struct Member {
+    path: PathBuf,
+    name: String,
+    is_dir: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+    mode: Option<u32>,
+    /// Target of a symlink, stored as a link instead of what it points at
+    link: Option<String>,
+}
+
+/// Unix file type bits of a symlink, 7z and zip keep them with the mode
+const S_IFLNK: u32 = 0o120000;
+/// Flags 7z attributes that carry a unix mode in their upper 16 bits
+const FILE_ATTRIBUTE_UNIX_EXTENSION: u32 = 0x8000;
+
+/// Writes `sources` including everything below them into a new archive.
+/// The files are read straight into the archive, a partially written
+/// archive is removed again on errors and cancellation.
+pub fn create_archive(
+    job: &JobHandle,
+    sources: &[PathBuf],
+    archive: &Path,
+    options: &CompressOptions,
+) -> Result<(), String> {
+    if options.password.is_some() && options.format != ArchiveFormat::Zip {
+        return Err("Only zip archives can be encrypted".into());
+    }
+    let members = collect_members(sources, archive)?;
+    job.add_total(
+        members.iter().filter(|member| !member.is_dir).count() as u64,
+        members.iter().map(|member| member.size).sum(),
+    );
+    dbg_log(format!(
+        "Compressing {} members into {:?}",
+        members.len(),
+        archive
+    ));
+
+    let file = File::create(archive).map_err(|e| e.to_string())?;
+    let written = write_archive(job, file, &members, options);
+    if written.is_err() {
+        let _ = fs::remove_file(archive);
+    }
+    written
+}
+
+fn write_archive(
+    job: &JobHandle,
+    file: File,
+    members: &[Member],
+    options: &CompressOptions,
+) -> Result<(), String> {
+    let level = options.level.min(9);
+    let out = BufWriter::new(file);
+    let out = match options.format {
+        ArchiveFormat::Zip => write_zip(job, out, members, level, options.password.as_deref())?,
+        ArchiveFormat::SevenZ => write_7z(job, out, members, level)?,
+        ArchiveFormat::TarGz => {
+            write_tar(job, GzEncoder::new(out, Compression::new(level)), members)?
+                .finish()
+                .map_err(|e| e.to_string())?
+        }
+        ArchiveFormat::TarXz => write_tar(job, XzEncoder::new(out, level), members)?
+            .finish()
+            .map_err(|e| e.to_string())?,
+        ArchiveFormat::TarZst => {
+            // zstd goes up to 19 without the memory hungry ultra levels
+            let encoder =
+                zstd::Encoder::new(out, (level * 2 + 1) as i32).map_err(|e| e.to_string())?;
+            write_tar(job, encoder, members)?
+                .finish()
+                .map_err(|e| e.to_string())?
+        }
+    };
+    out.into_inner()
+        .map_err(|e| e.to_string())?
+        .sync_all()
+        .map_err(|e| e.to_string())
+}
+
+fn write_zip<W: Write + io::Seek>(
+    job: &JobHandle,
+    out: W,
+    members: &[Member],
+    level: u32,
+    password: Option<&str>,
+) -> Result<W, String> {
+    let mut zip = ZipWriter::new(out);
+    // Deflate has no level 0, those files are stored as they are
+    let base = match level {
+        0 => SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+        level => SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .compression_level(Some(level.clamp(1, 9) as i64)),
+    };
+    let base: FileOptions<()> = match password {
+        Some(password) => base.with_aes_encryption(AesMode::Aes256, password),
+        None => base,
+    };
+    for member in members {
+        job.checkpoint()?;
+        job.begin_item(&member.name);
+        let mut options = base.large_file(member.size >= u32::MAX as u64);
+        if let Some(modified) = member.modified.and_then(zip_time) {
+            options = options.last_modified_time(modified);
+        }
+        if let Some(mode) = member.mode {
+            options = options.unix_permissions(mode);
+        }
+        if member.is_dir {
+            zip.add_directory(member.name.as_str(), options)
+                .map_err(|e| e.to_string())?;
+            continue;
+        }
+        if let Some(target) = &member.link {
+            zip.add_symlink(member.name.as_str(), target, options)
+                .map_err(|e| e.to_string())?;
+            job.finish_item();
+            continue;
+        }
+        zip.start_file(member.name.as_str(), options)
+            .map_err(|e| e.to_string())?;
+        io::copy(&mut member_reader(job, member)?, &mut zip).map_err(|e| e.to_string())?;
+        job.finish_item();
+    }
+    zip.finish().map_err(|e| e.to_string())
+}
+
+fn write_7z<W: Write + io::Seek>(
+    job: &JobHandle,
+    out: W,
+    members: &[Member],
+    level: u32,
+) -> Result<W, String> {
+    let mut archive = SevenZWriter::new(out).map_err(|e| e.to_string())?;
+    archive.set_content_methods(vec![LZMA2Options::with_preset(level).into()]);
+    for member in members {
+        job.checkpoint()?;
+        job.begin_item(&member.name);
+        if let Some(target) = &member.link {
+            // Stored like p7zip does, the target is the content
+            let mut entry = SevenZArchiveEntry::new();
+            entry.name = member.name.clone();
+            entry.has_stream = true;
+            entry.has_windows_attributes = true;
+            entry.windows_attributes =
+                FILE_ATTRIBUTE_UNIX_EXTENSION | (member.mode.unwrap_or(S_IFLNK | 0o777) << 16);
+            archive
+                .push_archive_entry(entry, Some(target.as_bytes()))
+                .map_err(|e| e.to_string())?;
+            job.finish_item();
+            continue;
+        }
+        let entry = SevenZArchiveEntry::from_path(&member.path, member.name.clone());
+        let reader = match member.is_dir {
+            true => None,
+            false => Some(member_reader(job, member)?),
+        };
+        archive
+            .push_archive_entry(entry, reader)
+            .map_err(|e| e.to_string())?;
+        if !member.is_dir {
+            job.finish_item();
+        }
+    }
+    archive.finish().map_err(|e| e.to_string())
+}
+
+fn write_tar<W: Write>(job: &JobHandle, out: W, members: &[Member]) -> Result<W, String> {
+    let mut builder = tar::Builder::new(out);
+    for member in members {
+        job.checkpoint()?;
+        job.begin_item(&member.name);
+        if member.is_dir {
+            builder
+                .append_dir(&member.name, &member.path)
+                .map_err(|e| e.to_string())?;
+            continue;
+        }
+        let metadata = fs::symlink_metadata(&member.path).map_err(|e| e.to_string())?;
+        let mut header = tar::Header::new_gnu();
+        header.set_metadata(&metadata);
+        if let Some(target) = &member.link {
+            header.set_size(0);
+            builder
+                .append_link(&mut header, &member.name, target)
+                .map_err(|e| e.to_string())?;
+            job.finish_item();
+            continue;
+        }
+        builder
+            .append_data(&mut header, &member.name, member_reader(job, member)?)
+            .map_err(|e| e.to_string())?;
+        job.finish_item();
+    }
+    builder.into_inner().map_err(|e| e.to_string())
+}
+
+/// Everything below `sources`, named relative to the dir of each source.
+/// Only files, dirs and symlinks are added, the archive itself is left out.
+fn collect_members(sources: &[PathBuf], archive: &Path) -> Result<Vec<Member>, String> {
+    let mut members = vec![];
+    for source in sources {
+        let base = source.parent().unwrap_or(source);
+        for entry in WalkDir::new(source).follow_links(false) {
+            let entry = entry.map_err(|e| e.to_string())?;
+            let metadata = entry.metadata().map_err(|e| e.to_string())?;
+            if entry.path() == archive {
+                continue;
+            }
+            let link = match metadata.is_symlink() {
+                true => Some(fs::read_link(entry.path()).map_err(|e| e.to_string())?),
+                false => None,
+            };
+            if !metadata.is_file() && !metadata.is_dir() && link.is_none() {
+                wng_log(format!("Not compressing {:?}, it's no file", entry.path()));
+                continue;
+            }
+            let name = entry
+                .path()
+                .strip_prefix(base)
+                .map_err(|e| e.to_string())?
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            members.push(Member {
+                path: entry.path().to_path_buf(),
+                name,
+                is_dir: metadata.is_dir(),
+                size: if metadata.is_file() {
+                    metadata.len()
+                } else {
+                    0
+                },
+                modified: metadata.modified().ok(),
+                mode: mode_of(&metadata),
+                link: link.map(|link| link.to_string_lossy().replace('\\', "/")),
+            });
+        }
+    }
+    Ok(members)
+}
+
+fn member_reader<'a>(job: &'a JobHandle, member: &Member) -> Result<ProgressReader<'a>, String> {
+    Ok(ProgressReader {
+        job,
+        file: File::open(&member.path).map_err(|e| format!("{:?}: {}", member.path, e))?,
+    })
+}
+
+/// Reports the bytes read to the job, fails once the job got canceled
+struct ProgressReader<'a> {
+    job: &'a JobHandle,
+    file: File,
+}
+
+impl Read for ProgressReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.job.checkpoint().map_err(io::Error::other)?;
+        let read = self.file.read(buf)?;
+        self.job.add_bytes(read as u64);
+        Ok(read)
+    }
+}
+
+/// zip stores the local time without a timezone
+fn zip_time(time: SystemTime) -> Option<zip::DateTime> {
+    let local: DateTime<Local> = time.into();
+    zip::DateTime::try_from(local.naive_local()).ok()
+}
+
+#[cfg(unix)]
+fn mode_of(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode())
+}
+
+#[cfg(not(unix))]
+fn mode_of(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::{extract_archive, ExtractOptions};
+    use tempfile::TempDir;
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_stored_as_links() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("docs");
+        fs::create_dir_all(source.join("sub")).unwrap();
+        fs::write(source.join("sub/a.txt"), "a").unwrap();
+        std::os::unix::fs::symlink("sub/a.txt", source.join("link")).unwrap();
+        std::os::unix::fs::symlink("nowhere", source.join("dangling")).unwrap();
+
+        let formats = [
+            ArchiveFormat::Zip,
+            ArchiveFormat::SevenZ,
+            ArchiveFormat::TarGz,
+            ArchiveFormat::TarXz,
+            ArchiveFormat::TarZst,
+        ];
+        for format in formats {
+            let archive = dir.path().join(format!("docs{}", format.extension()));
+            let options = CompressOptions {
+                format,
+                ..CompressOptions::default()
+            };
+            let job = JobHandle::detached();
+            create_archive(&job, std::slice::from_ref(&source), &archive, &options).unwrap();
+
+            let target = dir.path().join(format!("out{}", format.extension()));
+            let options = ExtractOptions {
+                target_dir: target.to_string_lossy().to_string(),
+                create_subfolder: false,
+                ..ExtractOptions::default()
+            };
+            extract_archive(&job, &archive, &options).unwrap();
+            let read_link = |name: &str| fs::read_link(target.join("docs").join(name)).unwrap();
+            assert_eq!(read_link("link"), Path::new("sub/a.txt"), "{:?}", format);
+            assert_eq!(read_link("dangling"), Path::new("nowhere"), "{:?}", format);
+            let content = fs::read_to_string(target.join("docs/link")).unwrap();
+            assert_eq!(content, "a", "{:?}", format);
+        }
+    }
+}
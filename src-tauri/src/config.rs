@@ -0,0 +1,480 @@
+use crate::listing_spec::ListingSpec;
+use crate::s3::S3Account;
+use crate::saved_search::SavedSearch;
+use crate::sftp::SftpProfile;
+use crate::utils::{dbg_log, err_log, wng_log};
+use crate::webdav::WebDavAccount;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::api::path::config_dir;
+
+/// Bump this and add a migration whenever the layout of `AppConfig` changes
+pub const CONFIG_VERSION: u64 = 2;
+
+/// Migrations from version `n` (index `n - 1`) to `n + 1`
+const MIGRATIONS: [fn(&mut Map<String, Value>); 1] = [migrate_v1_to_v2];
+
+/// Serializes read-modify-write cycles of different windows
+static CONFIG_LOCK: Mutex<()> = Mutex::new(());
+
+/// Listing specs of the saved config, read again after every save. Every
+/// listing needs its spec, this saves parsing the whole config each time.
+static LISTING_SPECS: Mutex<Option<HashMap<String, ListingSpec>>> = Mutex::new(None);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ViewMode {
+    #[default]
+    Wrap,
+    Column,
+    Miller,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct AppConfig {
+    pub config_version: u64,
+    pub view_mode: ViewMode,
+    pub last_modified: String,
+    pub configured_path_one: String,
+    pub configured_path_two: String,
+    pub configured_path_three: String,
+    pub is_open_in_terminal: bool,
+    pub is_dual_pane_enabled: bool,
+    pub launch_path: String,
+    pub is_dual_pane_active: bool,
+    pub search_depth: u32,
+    pub max_items: u32,
+    pub is_image_preview: bool,
+    pub is_select_mode: bool,
+    pub arr_favorites: Vec<String>,
+    /// Index into the installed themes
+    pub current_theme: u32,
+    pub gdrive_enabled: bool,
+    /// Listing specs by directory, see `listing_spec_key`
+    pub listing_specs: HashMap<String, ListingSpec>,
+    pub sftp_profiles: Vec<SftpProfile>,
+    /// Including the passwords
+    pub webdav_accounts: Vec<WebDavAccount>,
+    /// Including the secret keys
+    pub s3_accounts: Vec<S3Account>,
+    /// Listed as dirs at `search:<name>`
+    pub saved_searches: Vec<SavedSearch>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            config_version: CONFIG_VERSION,
+            view_mode: ViewMode::default(),
+            last_modified: chrono::offset::Local::now().to_string(),
+            configured_path_one: "".into(),
+            configured_path_two: "".into(),
+            configured_path_three: "".into(),
+            is_open_in_terminal: false,
+            is_dual_pane_enabled: false,
+            launch_path: "".into(),
+            is_dual_pane_active: false,
+            search_depth: 10,
+            max_items: 1000,
+            is_image_preview: true,
+            is_select_mode: true,
+            arr_favorites: vec![],
+            current_theme: 0,
+            gdrive_enabled: false,
+            listing_specs: HashMap::new(),
+            sftp_profiles: vec![],
+            webdav_accounts: vec![],
+            s3_accounts: vec![],
+            saved_searches: vec![],
+        }
+    }
+}
+
+pub fn config_path() -> PathBuf {
+    config_dir()
+        .unwrap_or_default()
+        .join("com.codriver.dev")
+        .join("app_config.json")
+}
+
+/// Reads the config, migrates it to the current version and writes it back
+/// if anything had to be changed. Never fails, broken values fall back to defaults.
+pub fn load() -> AppConfig {
+    read().unwrap_or_else(|e| {
+        err_log(format!("Using the default config: {}", e));
+        AppConfig::default()
+    })
+}
+
+/// Like `load`, but fails if an existing config can't be read, so changes are
+/// never saved on top of defaults that replaced it
+fn read() -> Result<AppConfig, String> {
+    let path = config_path();
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            let config = AppConfig::default();
+            if let Err(e) = save(&config) {
+                err_log(format!("Failed to create app_config: {}", e));
+            }
+            return Ok(config);
+        }
+        Err(e) => return Err(format!("Can't read {:?}: {}", path, e)),
+    };
+
+    let mut raw = match serde_json::from_str::<Value>(&content) {
+        Ok(Value::Object(raw)) => raw,
+        _ => {
+            err_log("app_config is corrupt, backing it up and using defaults".into());
+            let _ = fs::rename(&path, path.with_extension("json.bak"));
+            let config = AppConfig::default();
+            let _ = save(&config);
+            return Ok(config);
+        }
+    };
+
+    let version = raw
+        .get("config_version")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(1);
+    for migration in MIGRATIONS.iter().skip(version.saturating_sub(1) as usize) {
+        migration(&mut raw);
+    }
+    raw.insert("config_version".into(), Value::from(CONFIG_VERSION));
+
+    let config = from_lenient(raw);
+    if version != CONFIG_VERSION {
+        dbg_log(format!(
+            "Migrated app_config from version {} to {}",
+            version, CONFIG_VERSION
+        ));
+        if let Err(e) = save(&config) {
+            err_log(format!("Failed to save migrated app_config: {}", e));
+        }
+    }
+    Ok(config)
+}
+
+/// Writes the config atomically, so a crash can't leave a half written file behind
+pub fn save(config: &AppConfig) -> Result<(), String> {
+    let path = config_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let tmp_path = path.with_extension("json.tmp");
+    let content = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
+    // Flushed to disk before the rename, otherwise a crash can still leave
+    // an empty file behind on some file systems
+    let mut file = File::create(&tmp_path).map_err(|e| e.to_string())?;
+    file.write_all(content.as_bytes())
+        .and_then(|_| file.sync_all())
+        .map_err(|e| e.to_string())?;
+    fs::rename(&tmp_path, &path).map_err(|e| e.to_string())?;
+    *LISTING_SPECS.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    dbg_log(format!("app_config was saved to {:?}", path));
+    Ok(())
+}
+
+/// Merges a partial config object into the current config.
+/// Unknown keys and invalid values reject the whole patch.
+pub fn apply_patch(patch: Value) -> Result<AppConfig, String> {
+    let patch = match patch {
+        Value::Object(patch) => patch,
+        _ => return Err("A config patch has to be an object".into()),
+    };
+    let _lock = CONFIG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut merged = match serde_json::to_value(read()?) {
+        Ok(Value::Object(merged)) => merged,
+        _ => return Err("Failed to serialize the current config".into()),
+    };
+    for (key, value) in patch {
+        if key == "config_version" || key == "last_modified" || !merged.contains_key(&key) {
+            return Err(format!("Unknown config key: {}", key));
+        }
+        merged.insert(key, value);
+    }
+    merged.insert(
+        "last_modified".into(),
+        Value::from(chrono::offset::Local::now().to_string()),
+    );
+    let config: AppConfig = serde_json::from_value(Value::Object(merged))
+        .map_err(|e| format!("Invalid config: {}", e))?;
+    validate(&config)?;
+    save(&config)?;
+    Ok(config)
+}
+
+/// The spec a directory gets listed with, the default one if none was stored
+pub fn listing_spec_for(dir: &Path) -> ListingSpec {
+    let key = listing_spec_key(dir);
+    let cached = LISTING_SPECS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(|specs| specs.get(&key).cloned());
+    if let Some(spec) = cached {
+        return spec.unwrap_or_default();
+    }
+    // Keeps a save from slipping in between reading and caching the specs
+    let _lock = CONFIG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let specs = match read() {
+        Ok(config) => config.listing_specs,
+        // Not cached, the config might be readable again next time
+        Err(e) => {
+            err_log(format!("Using the default listing spec: {}", e));
+            return ListingSpec::default();
+        }
+    };
+    let spec = specs.get(&key).cloned().unwrap_or_default();
+    *LISTING_SPECS.lock().unwrap_or_else(|e| e.into_inner()) = Some(specs);
+    spec
+}
+
+/// Stores the spec for a directory, `None` goes back to the default one
+pub fn set_listing_spec(dir: &Path, spec: Option<ListingSpec>) -> Result<AppConfig, String> {
+    let _lock = CONFIG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut config = read()?;
+    let key = listing_spec_key(dir);
+    match spec {
+        Some(spec) if spec != ListingSpec::default() => config.listing_specs.insert(key, spec),
+        _ => config.listing_specs.remove(&key),
+    };
+    config.last_modified = chrono::offset::Local::now().to_string();
+    save(&config)?;
+    Ok(config)
+}
+
+/// Adds the profile or replaces the saved one with the same name
+pub fn save_sftp_profile(profile: SftpProfile) -> Result<AppConfig, String> {
+    let _lock = CONFIG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut config = read()?;
+    config
+        .sftp_profiles
+        .retain(|saved| saved.name != profile.name);
+    config.sftp_profiles.push(profile);
+    config.last_modified = chrono::offset::Local::now().to_string();
+    save(&config)?;
+    Ok(config)
+}
+
+pub fn remove_sftp_profile(name: &str) -> Result<AppConfig, String> {
+    let _lock = CONFIG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut config = read()?;
+    config.sftp_profiles.retain(|saved| saved.name != name);
+    config.last_modified = chrono::offset::Local::now().to_string();
+    save(&config)?;
+    Ok(config)
+}
+
+/// Adds the account or replaces the saved one with the same name
+pub fn save_webdav_account(account: WebDavAccount) -> Result<AppConfig, String> {
+    let _lock = CONFIG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut config = read()?;
+    config
+        .webdav_accounts
+        .retain(|saved| saved.name != account.name);
+    config.webdav_accounts.push(account);
+    config.last_modified = chrono::offset::Local::now().to_string();
+    save(&config)?;
+    Ok(config)
+}
+
+pub fn remove_webdav_account(name: &str) -> Result<AppConfig, String> {
+    let _lock = CONFIG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut config = read()?;
+    config.webdav_accounts.retain(|saved| saved.name != name);
+    config.last_modified = chrono::offset::Local::now().to_string();
+    save(&config)?;
+    Ok(config)
+}
+
+/// Adds the account or replaces the saved one with the same name
+pub fn save_s3_account(account: S3Account) -> Result<AppConfig, String> {
+    let _lock = CONFIG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut config = read()?;
+    config
+        .s3_accounts
+        .retain(|saved| saved.name != account.name);
+    config.s3_accounts.push(account);
+    config.last_modified = chrono::offset::Local::now().to_string();
+    save(&config)?;
+    Ok(config)
+}
+
+pub fn remove_s3_account(name: &str) -> Result<AppConfig, String> {
+    let _lock = CONFIG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut config = read()?;
+    config.s3_accounts.retain(|saved| saved.name != name);
+    config.last_modified = chrono::offset::Local::now().to_string();
+    save(&config)?;
+    Ok(config)
+}
+
+/// Adds the search or replaces the saved one with the same name
+pub fn save_search(search: SavedSearch) -> Result<AppConfig, String> {
+    let _lock = CONFIG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut config = read()?;
+    config
+        .saved_searches
+        .retain(|saved| saved.name != search.name);
+    config.saved_searches.push(search);
+    config.last_modified = chrono::offset::Local::now().to_string();
+    save(&config)?;
+    Ok(config)
+}
+
+pub fn remove_saved_search(name: &str) -> Result<AppConfig, String> {
+    let _lock = CONFIG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut config = read()?;
+    config.saved_searches.retain(|saved| saved.name != name);
+    config.last_modified = chrono::offset::Local::now().to_string();
+    save(&config)?;
+    Ok(config)
+}
+
+fn listing_spec_key(dir: &Path) -> String {
+    let key = dir.to_string_lossy().replace("\\", "/");
+    // Keeps "/" itself
+    let trimmed = key.trim_end_matches('/');
+    if trimmed.is_empty() {
+        key
+    } else {
+        trimmed.to_string()
+    }
+}
+
+fn validate(config: &AppConfig) -> Result<(), String> {
+    if config.max_items == 0 {
+        return Err("max_items has to be at least 1".into());
+    }
+    Ok(())
+}
+
+/// Deserializes field by field, so a single invalid value only resets itself
+fn from_lenient(raw: Map<String, Value>) -> AppConfig {
+    let mut merged = match serde_json::to_value(AppConfig::default()) {
+        Ok(Value::Object(merged)) => merged,
+        _ => return AppConfig::default(),
+    };
+    for (key, value) in raw {
+        if !merged.contains_key(&key) {
+            continue;
+        }
+        let mut candidate = merged.clone();
+        candidate.insert(key.clone(), value.clone());
+        if serde_json::from_value::<AppConfig>(Value::Object(candidate)).is_ok() {
+            merged.insert(key, value);
+        } else {
+            wng_log(format!(
+                "Invalid value for {} in app_config: {}",
+                key, value
+            ));
+        }
+    }
+    serde_json::from_value(Value::Object(merged)).unwrap_or_default()
+}
+
+/// Version 1 stored booleans as "0" / "1" and some numbers as strings
+fn migrate_v1_to_v2(raw: &mut Map<String, Value>) {
+    for key in [
+        "is_open_in_terminal",
+        "is_dual_pane_enabled",
+        "is_dual_pane_active",
+        "is_image_preview",
+        "is_select_mode",
+        "gdrive_enabled",
+    ] {
+        if let Some(Value::String(flag)) = raw.get(key) {
+            let flag = flag.trim_matches('"') == "1";
+            raw.insert(key.into(), Value::Bool(flag));
+        }
+    }
+    for key in ["search_depth", "max_items", "current_theme"] {
+        if let Some(Value::String(number)) = raw.get(key) {
+            match number.trim_matches('"').parse::<u32>() {
+                Ok(number) => raw.insert(key.into(), Value::from(number)),
+                Err(_) => raw.remove(key),
+            };
+        }
+    }
+    // An empty view mode was written for fresh configs
+    if raw.get("view_mode") == Some(&Value::String("".into())) {
+        raw.remove("view_mode");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn object(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            _ => panic!("Not an object: {}", value),
+        }
+    }
+
+    #[test]
+    fn v1_flags_and_numbers_become_typed_values() {
+        let mut raw = object(json!({
+            "is_open_in_terminal": "1",
+            "is_dual_pane_enabled": "0",
+            "is_image_preview": "\"1\"",
+            "gdrive_enabled": true,
+            "search_depth": "\"5\"",
+            "max_items": "many",
+            "current_theme": 2,
+            "view_mode": "",
+        }));
+        migrate_v1_to_v2(&mut raw);
+        assert_eq!(
+            Value::Object(raw),
+            json!({
+                "is_open_in_terminal": true,
+                "is_dual_pane_enabled": false,
+                "is_image_preview": true,
+                "gdrive_enabled": true,
+                "search_depth": 5,
+                "current_theme": 2,
+            })
+        );
+    }
+
+    #[test]
+    fn migrated_v1_configs_load() {
+        let mut raw = object(json!({
+            "is_select_mode": "0",
+            "max_items": "250",
+            "view_mode": "",
+            "arr_favorites": ["/home"],
+        }));
+        migrate_v1_to_v2(&mut raw);
+        let config = from_lenient(raw);
+        assert!(!config.is_select_mode);
+        assert_eq!(config.max_items, 250);
+        assert_eq!(config.view_mode, ViewMode::Wrap);
+        assert_eq!(config.arr_favorites, ["/home"]);
+    }
+
+    #[test]
+    fn invalid_values_only_reset_themselves() {
+        let config = from_lenient(object(json!({
+            "view_mode": "miller",
+            "max_items": -1,
+            "search_depth": 3,
+            "is_image_preview": "yes",
+            "no_such_key": 1,
+        })));
+        assert_eq!(config.view_mode, ViewMode::Miller);
+        assert_eq!(config.max_items, AppConfig::default().max_items);
+        assert_eq!(config.search_depth, 3);
+        assert!(config.is_image_preview);
+    }
+}
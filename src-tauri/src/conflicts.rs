@@ -0,0 +1,550 @@
+use crate::fdir::FDir;
+use crate::jobs::JobHandle;
+use crate::utils::{dbg_log, err_log, wng_log};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// What to do when the target of a copy / move already exists
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    Skip,
+    Overwrite,
+    OverwriteIfNewer,
+    #[default]
+    KeepBoth,
+    Ask,
+}
+
+/// The user's answer to a `job-conflict` event
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ConflictAnswer {
+    pub resolution: ConflictPolicy,
+    pub apply_to_all: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ConflictSide {
+    pub path: String,
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    /// Epoch millis
+    pub last_modified: u128,
+}
+
+impl ConflictSide {
+    pub fn from_path(path: &Path) -> ConflictSide {
+        let metadata = fs::metadata(path).ok();
+        ConflictSide {
+            path: path.to_string_lossy().to_string().replace("\\", "/"),
+            name: path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            is_dir: metadata.as_ref().is_some_and(|m| m.is_dir()),
+            size: metadata.as_ref().map_or(0, |m| m.len()),
+            last_modified: metadata
+                .and_then(|m| m.modified().ok())
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_millis()),
+        }
+    }
+
+    /// For items listed by a provider
+    pub fn from_item(item: &FDir) -> ConflictSide {
+        ConflictSide {
+            path: item.path.clone(),
+            name: item.name.clone(),
+            is_dir: item.is_dir,
+            size: item.size,
+            last_modified: item.modified.unwrap_or(0) as u128,
+        }
+    }
+}
+
+/// Payload of the `job-conflict` event
+#[derive(Serialize, Clone, Debug)]
+pub struct ConflictInfo {
+    pub job_id: String,
+    pub source: ConflictSide,
+    pub target: ConflictSide,
+}
+
+pub enum Resolution {
+    /// Copy to the given target. Existing directories get merged.
+    CopyTo(PathBuf),
+    /// Copy to the given target, which exists and gets replaced. The old one
+    /// has to be kept with `SetAside` until the copy succeeded.
+    Replace(PathBuf),
+    Skip,
+}
+
+/// Decides per item where it should end up, remembering "apply to all" answers
+pub struct ConflictResolver {
+    policy: ConflictPolicy,
+}
+
+impl ConflictResolver {
+    pub fn new(policy: ConflictPolicy) -> ConflictResolver {
+        ConflictResolver { policy }
+    }
+
+    pub fn resolve(
+        &mut self,
+        job: &JobHandle,
+        from: &Path,
+        to: &Path,
+    ) -> Result<Resolution, String> {
+        self.resolve_with(from, to, |source, target| ask(job, source, target))
+    }
+
+    fn resolve_with(
+        &mut self,
+        from: &Path,
+        to: &Path,
+        ask: impl FnOnce(ConflictSide, ConflictSide) -> Result<ConflictAnswer, String>,
+    ) -> Result<Resolution, String> {
+        if fs::symlink_metadata(to).is_err() {
+            return Ok(Resolution::CopyTo(to.to_path_buf()));
+        }
+        // Overwriting an item with itself would truncate it, pasting it into
+        // its own dir makes a copy instead
+        if from.canonicalize().map_err(|e| e.to_string())? == to.canonicalize().unwrap_or_default()
+        {
+            return Ok(Resolution::CopyTo(unique_filename(to)));
+        }
+        self.decide(
+            ConflictSide::from_path(from),
+            ConflictSide::from_path(to),
+            to,
+            |path| fs::symlink_metadata(path).is_ok(),
+            ask,
+        )
+    }
+
+    /// Like `resolve` for sources that aren't on the disk, e.g. archive members
+    pub fn resolve_source(
+        &mut self,
+        job: &JobHandle,
+        source: ConflictSide,
+        to: &Path,
+    ) -> Result<Resolution, String> {
+        let target = fs::symlink_metadata(to)
+            .ok()
+            .map(|_| ConflictSide::from_path(to));
+        self.resolve_target(job, source, target, to, |path| {
+            fs::symlink_metadata(path).is_ok()
+        })
+    }
+
+    /// Like `resolve_source` for targets that aren't on the disk either, e.g.
+    /// on a server. `target` is None if nothing is at `to`, `exists` tells
+    /// which names are taken for keeping both.
+    pub fn resolve_target(
+        &mut self,
+        job: &JobHandle,
+        source: ConflictSide,
+        target: Option<ConflictSide>,
+        to: &Path,
+        exists: impl Fn(&Path) -> bool,
+    ) -> Result<Resolution, String> {
+        match target {
+            Some(target) => self.decide(source, target, to, exists, |source, target| {
+                ask(job, source, target)
+            }),
+            None => Ok(Resolution::CopyTo(to.to_path_buf())),
+        }
+    }
+
+    /// What to do with `source` now that `target` is in its way, `ask` gets
+    /// the answer of the user for the `Ask` policy
+    fn decide(
+        &mut self,
+        source: ConflictSide,
+        target: ConflictSide,
+        to: &Path,
+        exists: impl Fn(&Path) -> bool,
+        ask: impl FnOnce(ConflictSide, ConflictSide) -> Result<ConflictAnswer, String>,
+    ) -> Result<Resolution, String> {
+        let is_merge = source.is_dir && target.is_dir;
+
+        let mut resolution = self.policy;
+        if resolution == ConflictPolicy::Ask {
+            // Directories are merged without asking, their files will ask instead
+            if is_merge {
+                return Ok(Resolution::CopyTo(to.to_path_buf()));
+            }
+            let answer = ask(source.clone(), target.clone())?;
+            if answer.resolution == ConflictPolicy::Ask {
+                return Err("A conflict has to be answered with a resolution".into());
+            }
+            if answer.apply_to_all {
+                self.policy = answer.resolution;
+            }
+            resolution = answer.resolution;
+        }
+        dbg_log(format!(
+            "Conflict on {:?} resolved with {:?}",
+            to, resolution
+        ));
+
+        match resolution {
+            ConflictPolicy::Skip => Ok(Resolution::Skip),
+            ConflictPolicy::KeepBoth => Ok(Resolution::CopyTo(unique_name(to, exists))),
+            ConflictPolicy::OverwriteIfNewer if !is_merge && !is_newer(&source, &target) => {
+                Ok(Resolution::Skip)
+            }
+            _ if is_merge => Ok(Resolution::CopyTo(to.to_path_buf())),
+            _ => Ok(Resolution::Replace(to.to_path_buf())),
+        }
+    }
+}
+
+/// Targets that get replaced, moved next to themselves until their
+/// replacement was written completely
+#[derive(Default)]
+pub struct SetAside {
+    /// Target and where it was moved to
+    moved: Vec<(PathBuf, PathBuf)>,
+}
+
+impl SetAside {
+    pub fn set_aside(&mut self, target: &Path) -> Result<(), String> {
+        let name = target.file_name().unwrap_or_default().to_string_lossy();
+        let backup = unique_filename(&target.with_file_name(format!(".{}.codriver-old", name)));
+        fs::rename(target, &backup).map_err(|e| e.to_string())?;
+        self.moved.push((target.to_path_buf(), backup));
+        Ok(())
+    }
+
+    /// Removes the old targets if `result` is ok, puts them back otherwise
+    pub fn finish<T>(self, result: Result<T, String>) -> Result<T, String> {
+        match result {
+            Ok(_) => self.discard(),
+            Err(_) => self.restore(),
+        }
+        result
+    }
+
+    pub fn discard(self) {
+        for (_, backup) in self.moved {
+            if let Err(e) = remove_path(&backup) {
+                wng_log(format!("Failed to remove the replaced {:?}: {}", backup, e));
+            }
+        }
+    }
+
+    /// Removes whatever was written to the targets and moves the old ones back
+    pub fn restore(self) {
+        for (target, backup) in self.moved.into_iter().rev() {
+            let _ = remove_path(&target);
+            if let Err(e) = fs::rename(&backup, &target) {
+                err_log(format!(
+                    "Failed to restore {:?}, it was kept as {:?}: {}",
+                    target, backup, e
+                ));
+            }
+        }
+    }
+}
+
+fn ask(
+    job: &JobHandle,
+    source: ConflictSide,
+    target: ConflictSide,
+) -> Result<ConflictAnswer, String> {
+    job.ask_conflict(ConflictInfo {
+        job_id: job.id(),
+        source,
+        target,
+    })
+}
+
+fn remove_path(path: &Path) -> std::io::Result<()> {
+    match fs::symlink_metadata(path)?.is_dir() {
+        true => fs::remove_dir_all(path),
+        false => fs::remove_file(path),
+    }
+}
+
+/// Unknown modification times never count as newer
+fn is_newer(source: &ConflictSide, target: &ConflictSide) -> bool {
+    source.last_modified > 0
+        && target.last_modified > 0
+        && source.last_modified > target.last_modified
+}
+
+/// Appends `_1`, `_2`, ... to the file stem until the path is unused
+pub fn unique_filename(path: &Path) -> PathBuf {
+    unique_name(path, |path| fs::symlink_metadata(path).is_ok())
+}
+
+/// Like `unique_filename` for paths that aren't on the disk
+pub fn unique_name(path: &Path, exists: impl Fn(&Path) -> bool) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let ext = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    let mut counter = 1;
+    let mut final_filename = path.to_path_buf();
+    while exists(&final_filename) {
+        final_filename = path.with_file_name(format!("{}_{}{}", stem, counter, ext));
+        counter += 1;
+    }
+    final_filename
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn never_asked(_: ConflictSide, _: ConflictSide) -> Result<ConflictAnswer, String> {
+        panic!("Only the Ask policy asks")
+    }
+
+    fn answer(
+        resolution: ConflictPolicy,
+        apply_to_all: bool,
+    ) -> impl FnOnce(ConflictSide, ConflictSide) -> Result<ConflictAnswer, String> {
+        move |_, _| {
+            Ok(ConflictAnswer {
+                resolution,
+                apply_to_all,
+            })
+        }
+    }
+
+    fn side(name: &str, is_dir: bool, last_modified: u128) -> ConflictSide {
+        ConflictSide {
+            path: format!("/from/{}", name),
+            name: name.into(),
+            is_dir,
+            size: 1,
+            last_modified,
+        }
+    }
+
+    fn target_of(resolution: Resolution) -> Option<PathBuf> {
+        match resolution {
+            Resolution::CopyTo(path) => Some(path),
+            Resolution::Replace(_) => panic!("Expected a copy, not a replacement"),
+            Resolution::Skip => None,
+        }
+    }
+
+    fn decide(
+        resolver: &mut ConflictResolver,
+        source: ConflictSide,
+        target: ConflictSide,
+        ask: impl FnOnce(ConflictSide, ConflictSide) -> Result<ConflictAnswer, String>,
+    ) -> Resolution {
+        let to = Path::new("/to").join(&target.name);
+        resolver
+            .decide(source, target, &to, |_| false, ask)
+            .unwrap()
+    }
+
+    #[test]
+    fn unique_names_count_up_before_the_extension() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("report.pdf");
+        assert_eq!(unique_filename(&path), path);
+
+        fs::write(&path, "").unwrap();
+        assert_eq!(unique_filename(&path), dir.path().join("report_1.pdf"));
+        fs::write(dir.path().join("report_1.pdf"), "").unwrap();
+        assert_eq!(unique_filename(&path), dir.path().join("report_2.pdf"));
+    }
+
+    #[test]
+    fn unique_names_keep_only_the_last_extension() {
+        let taken = |path: &Path| !path.to_string_lossy().contains('_');
+        assert_eq!(
+            unique_name(Path::new("/a/archive.tar.gz"), taken),
+            Path::new("/a/archive.tar_1.gz")
+        );
+        assert_eq!(
+            unique_name(Path::new("/a/Makefile"), taken),
+            Path::new("/a/Makefile_1")
+        );
+        // Dotfiles are all stem
+        assert_eq!(
+            unique_name(Path::new("/a/.bashrc"), taken),
+            Path::new("/a/.bashrc_1")
+        );
+    }
+
+    #[test]
+    fn unique_names_count_dirs_like_files() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("photos")).unwrap();
+        fs::create_dir(dir.path().join("photos_1")).unwrap();
+        assert_eq!(
+            unique_filename(&dir.path().join("photos")),
+            dir.path().join("photos_2")
+        );
+    }
+
+    #[test]
+    fn free_targets_are_copied_to() {
+        let dir = TempDir::new().unwrap();
+        let from = dir.path().join("a.txt");
+        fs::write(&from, "a").unwrap();
+        let to = dir.path().join("b.txt");
+        let mut resolver = ConflictResolver::new(ConflictPolicy::Skip);
+        let resolution = resolver.resolve_with(&from, &to, never_asked).unwrap();
+        assert_eq!(target_of(resolution), Some(to));
+    }
+
+    #[test]
+    fn pasting_an_item_into_its_own_dir_keeps_both() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "a").unwrap();
+        // Even when told to overwrite, which would truncate the source
+        for policy in [ConflictPolicy::Overwrite, ConflictPolicy::Ask] {
+            let mut resolver = ConflictResolver::new(policy);
+            let resolution = resolver.resolve_with(&path, &path, never_asked).unwrap();
+            assert_eq!(target_of(resolution), Some(dir.path().join("a_1.txt")));
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a");
+    }
+
+    #[test]
+    fn existing_targets_follow_the_policy() {
+        let dir = TempDir::new().unwrap();
+        let from = dir.path().join("from.txt");
+        let to = dir.path().join("to.txt");
+        fs::write(&from, "new").unwrap();
+        fs::write(&to, "old").unwrap();
+
+        let mut resolver = ConflictResolver::new(ConflictPolicy::KeepBoth);
+        let resolution = resolver.resolve_with(&from, &to, never_asked).unwrap();
+        assert_eq!(target_of(resolution), Some(dir.path().join("to_1.txt")));
+
+        let mut resolver = ConflictResolver::new(ConflictPolicy::Skip);
+        let resolution = resolver.resolve_with(&from, &to, never_asked).unwrap();
+        assert_eq!(target_of(resolution), None);
+
+        let mut resolver = ConflictResolver::new(ConflictPolicy::Overwrite);
+        let resolution = resolver.resolve_with(&from, &to, never_asked).unwrap();
+        assert!(matches!(resolution, Resolution::Replace(path) if path == to));
+    }
+
+    #[test]
+    fn overwrites_only_older_targets_if_newer() {
+        let mut resolver = ConflictResolver::new(ConflictPolicy::OverwriteIfNewer);
+        let newer = decide(
+            &mut resolver,
+            side("a", false, 2),
+            side("a", false, 1),
+            never_asked,
+        );
+        assert!(matches!(newer, Resolution::Replace(_)));
+        let older = decide(
+            &mut resolver,
+            side("a", false, 1),
+            side("a", false, 2),
+            never_asked,
+        );
+        assert!(matches!(older, Resolution::Skip));
+        // Unknown times never count as newer
+        let unknown = decide(
+            &mut resolver,
+            side("a", false, 2),
+            side("a", false, 0),
+            never_asked,
+        );
+        assert!(matches!(unknown, Resolution::Skip));
+    }
+
+    #[test]
+    fn dirs_are_merged() {
+        for policy in [
+            ConflictPolicy::Overwrite,
+            ConflictPolicy::OverwriteIfNewer,
+            ConflictPolicy::Ask,
+        ] {
+            let mut resolver = ConflictResolver::new(policy);
+            let merged = decide(
+                &mut resolver,
+                side("d", true, 1),
+                side("d", true, 2),
+                never_asked,
+            );
+            assert_eq!(target_of(merged), Some(PathBuf::from("/to/d")));
+        }
+    }
+
+    #[test]
+    fn answers_apply_to_all_only_if_asked_to() {
+        let mut resolver = ConflictResolver::new(ConflictPolicy::Ask);
+        let once = answer(ConflictPolicy::Skip, false);
+        let skipped = decide(
+            &mut resolver,
+            side("a", false, 1),
+            side("a", false, 1),
+            once,
+        );
+        assert!(matches!(skipped, Resolution::Skip));
+        assert_eq!(resolver.policy, ConflictPolicy::Ask);
+
+        let all = answer(ConflictPolicy::Overwrite, true);
+        let replaced = decide(&mut resolver, side("a", false, 1), side("a", false, 1), all);
+        assert!(matches!(replaced, Resolution::Replace(_)));
+        let next = decide(
+            &mut resolver,
+            side("b", false, 1),
+            side("b", false, 1),
+            never_asked,
+        );
+        assert!(matches!(next, Resolution::Replace(_)));
+    }
+
+    #[test]
+    fn answering_with_ask_fails() {
+        let mut resolver = ConflictResolver::new(ConflictPolicy::Ask);
+        let to = Path::new("/to/a");
+        let result = resolver.decide(
+            side("a", false, 1),
+            side("a", false, 1),
+            to,
+            |_| false,
+            answer(ConflictPolicy::Ask, false),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn set_aside_targets_come_back_on_failure() {
+        let dir = TempDir::new().unwrap();
+        let target = dir.path().join("a.txt");
+        fs::write(&target, "old").unwrap();
+
+        let mut replaced = SetAside::default();
+        replaced.set_aside(&target).unwrap();
+        assert!(!target.exists());
+        fs::write(&target, "partial").unwrap();
+        let result: Result<(), String> = replaced.finish(Err("failed".into()));
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "old");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let mut replaced = SetAside::default();
+        replaced.set_aside(&target).unwrap();
+        fs::write(&target, "new").unwrap();
+        replaced.finish(Ok(())).unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,182 @@
+use regex::bytes::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+/// Files are read in chunks of this size, the first one decides whether a
+/// file is binary
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Longer lines are cut off, matches after the cut aren't found
+const MAX_LINE_LEN: usize = 4096;
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ContentOptions {
+    /// Treat the text as a regex instead of literal text
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    /// Lines shown before and after each match
+    pub context_lines: usize,
+    /// Matches reported per file, 0 for all of them
+    pub max_matches: usize,
+}
+
+impl Default for ContentOptions {
+    fn default() -> Self {
+        ContentOptions {
+            regex: false,
+            case_sensitive: false,
+            whole_word: false,
+            context_lines: 2,
+            max_matches: 100,
+        }
+    }
+}
+
+/// A line of a file with a match
+#[derive(Serialize, Clone, Debug)]
+pub struct ContentMatch {
+    /// Starts at 1
+    pub line_number: u64,
+    /// Char offset of the match in the line, starts at 1
+    pub column: usize,
+    pub line: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+/// Looks for text in files like grep, line by line
+pub struct ContentSearcher {
+    regex: Regex,
+    options: ContentOptions,
+}
+
+impl ContentSearcher {
+    pub fn new(text: &str, options: &ContentOptions) -> Result<ContentSearcher, String> {
+        if text.is_empty() {
+            return Err("There's no text to search for".into());
+        }
+        let mut pattern = match options.regex {
+            true => text.to_string(),
+            false => regex::escape(text),
+        };
+        if options.whole_word {
+            pattern = format!(r"\b(?:{})\b", pattern);
+        }
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!options.case_sensitive)
+            .build()
+            .map_err(|e| {
+                format!(
+                    "Invalid content regex {}: {}",
+                    text,
+                    // The first lines repeat the pattern with a marker
+                    e.to_string()
+                        .lines()
+                        .last()
+                        .unwrap_or_default()
+                        .trim_start_matches("error: ")
+                )
+            })?;
+        Ok(ContentSearcher {
+            regex,
+            options: options.clone(),
+        })
+    }
+
+    /// The matching lines of a file, binary files never match. Lines don't
+    /// have to be valid UTF-8, invalid parts are replaced in the result.
+    pub fn search_file(&self, path: &Path) -> io::Result<Vec<ContentMatch>> {
+        let mut reader = BufReader::with_capacity(CHUNK_SIZE, File::open(path)?);
+        if reader.fill_buf()?.contains(&0) {
+            return Ok(vec![]);
+        }
+
+        let context = self.options.context_lines;
+        let max_matches = match self.options.max_matches {
+            0 => usize::MAX,
+            max => max,
+        };
+        let mut matches = vec![];
+        // Matches still waiting for the lines after them
+        let mut pending: VecDeque<ContentMatch> = VecDeque::new();
+        let mut before: VecDeque<String> = VecDeque::with_capacity(context + 1);
+        let mut line = Vec::new();
+        let mut line_number = 0;
+        while next_line(&mut reader, &mut line)? {
+            line_number += 1;
+            let text = String::from_utf8_lossy(&line).to_string();
+            for waiting in pending.iter_mut() {
+                waiting.after.push(text.clone());
+            }
+            while pending
+                .front()
+                .is_some_and(|waiting| waiting.after.len() >= context)
+            {
+                matches.extend(pending.pop_front());
+            }
+
+            if matches.len() + pending.len() < max_matches {
+                if let Some(found) = self.regex.find(&line) {
+                    pending.push_back(ContentMatch {
+                        line_number,
+                        column: String::from_utf8_lossy(&line[..found.start()])
+                            .chars()
+                            .count()
+                            + 1,
+                        line: text.clone(),
+                        before: before.iter().cloned().collect(),
+                        after: vec![],
+                    });
+                    if context == 0 {
+                        matches.extend(pending.pop_front());
+                    }
+                }
+            } else if pending.is_empty() {
+                break;
+            }
+
+            if context > 0 {
+                if before.len() == context {
+                    before.pop_front();
+                }
+                before.push_back(text);
+            }
+        }
+        // Matches at the end of the file have fewer lines after them
+        matches.extend(pending);
+        Ok(matches)
+    }
+}
+
+/// Reads the next line into `line` without its line break, keeping at most
+/// `MAX_LINE_LEN` bytes of it. Returns false at the end of the file.
+fn next_line(reader: &mut impl BufRead, line: &mut Vec<u8>) -> io::Result<bool> {
+    line.clear();
+    let mut read_any = false;
+    loop {
+        let chunk = reader.fill_buf()?;
+        if chunk.is_empty() {
+            break;
+        }
+        read_any = true;
+        let (part, used, ended) = match chunk.iter().position(|b| *b == b'\n') {
+            Some(end) => (&chunk[..end], end + 1, true),
+            None => (chunk, chunk.len(), false),
+        };
+        let room = MAX_LINE_LEN.saturating_sub(line.len());
+        line.extend_from_slice(&part[..part.len().min(room)]);
+        reader.consume(used);
+        if ended {
+            break;
+        }
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    Ok(read_any)
+}
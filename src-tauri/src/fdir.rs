@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, Metadata};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FileType {
+    #[default]
+    File,
+    Dir,
+    Symlink,
+    Other,
+}
+
+/// An entry of a directory listing
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct FDir {
+    pub name: String,
+    pub path: String,
+    /// Type of the entry itself, symlinks are not followed
+    pub file_type: FileType,
+    /// Whether the entry can be opened as a directory, also true for symlinks to dirs
+    pub is_dir: bool,
+    /// Where a symlink points to, for gdrive shortcuts the id of the target
+    pub symlink_target: Option<String>,
+    /// Including the dot, e.g. ".txt". Empty for dirs and names without one
+    pub extension: String,
+    /// Size of the file a symlink points to, 0 for dirs
+    pub size: u64,
+    /// Epoch millis, `None` if the platform or provider doesn't know it
+    pub created: Option<u64>,
+    pub accessed: Option<u64>,
+    pub modified: Option<u64>,
+    /// Unix mode bits, e.g. 0o644
+    pub permissions: Option<u32>,
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    pub is_hidden: bool,
+    pub is_readonly: bool,
+    pub mime_type: String,
+    /// Set when a listing spec groups the entries
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group_key: Option<String>,
+}
+
+pub fn fdir_from_path(item_path: &Path) -> Option<FDir> {
+    let name = item_path.file_name()?.to_string_lossy().to_string();
+    let link_metadata = fs::symlink_metadata(item_path).ok()?;
+    let file_type = file_type_of(&link_metadata);
+    let symlink_target = match file_type {
+        FileType::Symlink => fs::read_link(item_path)
+            .ok()
+            .map(|target| target.to_string_lossy().to_string()),
+        _ => None,
+    };
+    // Broken symlinks describe themselves
+    let metadata = fs::metadata(item_path).unwrap_or_else(|_| link_metadata.clone());
+    let is_dir = metadata.is_dir();
+
+    let mime_type = if is_dir {
+        "inode/directory".to_string()
+    } else if file_type == FileType::Symlink && !metadata.is_file() {
+        "inode/symlink".to_string()
+    } else {
+        mime_guess::from_path(item_path)
+            .first_or_octet_stream()
+            .essence_str()
+            .to_string()
+    };
+
+    Some(FDir {
+        is_hidden: is_hidden(&name, &link_metadata),
+        extension: extension_of(&name, is_dir),
+        name,
+        path: item_path.to_string_lossy().to_string().replace("\\", "/"),
+        file_type,
+        is_dir,
+        symlink_target,
+        size: if is_dir { 0 } else { metadata.len() },
+        created: metadata.created().ok().and_then(epoch_millis),
+        accessed: metadata.accessed().ok().and_then(epoch_millis),
+        modified: metadata.modified().ok().and_then(epoch_millis),
+        permissions: permissions_of(&link_metadata),
+        owner: owner_of(&link_metadata),
+        group: group_of(&link_metadata),
+        is_readonly: metadata.permissions().readonly(),
+        mime_type,
+        group_key: None,
+    })
+}
+
+/// The extension of a file name including the dot, dirs and names like
+/// `Makefile` or `.bashrc` don't have one
+pub fn extension_of(name: &str, is_dir: bool) -> String {
+    if is_dir {
+        return "".into();
+    }
+    match Path::new(name).extension() {
+        Some(ext) => format!(".{}", ext.to_string_lossy()),
+        None => "".into(),
+    }
+}
+
+pub fn epoch_millis(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_millis() as u64)
+}
+
+fn file_type_of(metadata: &Metadata) -> FileType {
+    let file_type = metadata.file_type();
+    if file_type.is_symlink() {
+        FileType::Symlink
+    } else if file_type.is_dir() {
+        FileType::Dir
+    } else if file_type.is_file() {
+        FileType::File
+    } else {
+        FileType::Other
+    }
+}
+
+#[cfg(windows)]
+fn is_hidden(name: &str, metadata: &Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    name.starts_with('.') || metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0
+}
+
+#[cfg(not(windows))]
+fn is_hidden(name: &str, _metadata: &Metadata) -> bool {
+    name.starts_with('.')
+}
+
+#[cfg(unix)]
+fn permissions_of(metadata: &Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn permissions_of(_metadata: &Metadata) -> Option<u32> {
+    None
+}
+
+/// Names of users and groups by id, read once since listings need them for every entry
+#[cfg(unix)]
+fn account_names() -> &'static (
+    std::collections::HashMap<u32, String>,
+    std::collections::HashMap<u32, String>,
+) {
+    use std::collections::HashMap;
+    use std::sync::OnceLock;
+    static NAMES: OnceLock<(HashMap<u32, String>, HashMap<u32, String>)> = OnceLock::new();
+    NAMES.get_or_init(|| {
+        let users = sysinfo::Users::new_with_refreshed_list()
+            .list()
+            .iter()
+            .map(|user| (**user.id(), user.name().to_string()))
+            .collect();
+        let groups = sysinfo::Groups::new_with_refreshed_list()
+            .list()
+            .iter()
+            .map(|group| (**group.id(), group.name().to_string()))
+            .collect();
+        (users, groups)
+    })
+}
+
+/// Falls back to the numeric id if there's no user with that id
+#[cfg(unix)]
+fn owner_of(metadata: &Metadata) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+    let uid = metadata.uid();
+    Some(
+        account_names()
+            .0
+            .get(&uid)
+            .cloned()
+            .unwrap_or(uid.to_string()),
+    )
+}
+
+#[cfg(unix)]
+fn group_of(metadata: &Metadata) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+    let gid = metadata.gid();
+    Some(
+        account_names()
+            .1
+            .get(&gid)
+            .cloned()
+            .unwrap_or(gid.to_string()),
+    )
+}
+
+#[cfg(not(unix))]
+fn owner_of(_metadata: &Metadata) -> Option<String> {
+    None
+}
+
+#[cfg(not(unix))]
+fn group_of(_metadata: &Metadata) -> Option<String> {
+    None
+}
@@ -0,0 +1,271 @@
+use chrono::Utc;
+
+const SCORE_MATCH: i64 = 16;
+const SCORE_GAP_START: i64 = -3;
+const SCORE_GAP_EXTENSION: i64 = -1;
+/// Matches right after a separator like `_`, `-`, `.` or a space
+const BONUS_BOUNDARY: i64 = SCORE_MATCH / 2;
+const BONUS_NON_WORD: i64 = SCORE_MATCH / 2;
+/// Matches at `aB` or `a1`
+const BONUS_CAMEL: i64 = BONUS_BOUNDARY - 1;
+/// Matching the chars after each other beats skipping some of them
+const BONUS_CONSECUTIVE: i64 = -(SCORE_GAP_START + SCORE_GAP_EXTENSION);
+const BONUS_FIRST_CHAR_MULTIPLIER: i64 = 2;
+
+/// Lost for every dir between the search root and a result
+const PENALTY_DEPTH: i64 = 2;
+
+/// How well a pattern matches a text, like fzf
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    pub score: i64,
+    /// Char indices of the matched chars in the text
+    pub positions: Vec<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Lower,
+    Upper,
+    Digit,
+    Letter,
+    NonWord,
+}
+
+fn class_of(c: char) -> CharClass {
+    if c.is_lowercase() {
+        CharClass::Lower
+    } else if c.is_uppercase() {
+        CharClass::Upper
+    } else if c.is_numeric() {
+        CharClass::Digit
+    } else if c.is_alphabetic() {
+        CharClass::Letter
+    } else {
+        CharClass::NonWord
+    }
+}
+
+fn bonus_of(prev: CharClass, class: CharClass) -> i64 {
+    match (prev, class) {
+        (_, CharClass::NonWord) => BONUS_NON_WORD,
+        (CharClass::NonWord, _) => BONUS_BOUNDARY,
+        (CharClass::Lower, CharClass::Upper) => BONUS_CAMEL,
+        (CharClass::Lower | CharClass::Upper | CharClass::Letter, CharClass::Digit) => BONUS_CAMEL,
+        _ => 0,
+    }
+}
+
+/// Finds the best way to match every char of `pattern` in order somewhere in
+/// `text`. The case is ignored unless the pattern has upper case chars.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<FuzzyMatch> {
+    let case_sensitive = pattern.chars().any(char::is_uppercase);
+    let fold = |c: char| match case_sensitive {
+        true => c,
+        false => c.to_lowercase().next().unwrap_or(c),
+    };
+    let pattern: Vec<char> = pattern.chars().map(fold).collect();
+    let chars: Vec<char> = text.chars().collect();
+    if pattern.is_empty() {
+        return Some(FuzzyMatch {
+            score: 0,
+            positions: vec![],
+        });
+    }
+
+    // Cheap check before scoring, most texts don't match at all
+    let mut rest = pattern.iter().peekable();
+    for c in chars.iter().map(|c| fold(*c)) {
+        if rest.peek() == Some(&&c) {
+            rest.next();
+        }
+    }
+    if rest.peek().is_some() {
+        return None;
+    }
+
+    let folded: Vec<char> = chars.iter().map(|c| fold(*c)).collect();
+    let bonuses: Vec<i64> = chars
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let prev = match i {
+                0 => CharClass::NonWord,
+                i => class_of(chars[i - 1]),
+            };
+            bonus_of(prev, class_of(*c))
+        })
+        .collect();
+
+    // scores[i][j] is the best score with the pattern up to i matched and
+    // its char i at text char j, from[i][j] where char i - 1 was matched.
+    // Chars matched after each other share the bonus of the first one.
+    let (n, m) = (pattern.len(), chars.len());
+    let mut scores = vec![vec![None; m]; n];
+    let mut from = vec![vec![0; m]; n];
+    let mut chunk_bonus = vec![vec![0; m]; n];
+    for i in 0..n {
+        // Best score of an earlier match with at least one char skipped
+        // since then, and where that match was
+        let mut gapped: Option<(i64, usize)> = None;
+        for j in 0..m {
+            if i > 0 && j > 1 {
+                if let Some(score) = scores[i - 1][j - 2] {
+                    let extended = gapped.map(|(best, _)| best + SCORE_GAP_EXTENSION);
+                    if extended.map_or(true, |best| score + SCORE_GAP_START > best) {
+                        gapped = Some((score + SCORE_GAP_START, j - 2));
+                    } else {
+                        gapped = gapped.map(|(best, at)| (best + SCORE_GAP_EXTENSION, at));
+                    }
+                } else {
+                    gapped = gapped.map(|(best, at)| (best + SCORE_GAP_EXTENSION, at));
+                }
+            }
+            if folded[j] != pattern[i] {
+                continue;
+            }
+            if i == 0 {
+                scores[0][j] = Some(SCORE_MATCH + bonuses[j] * BONUS_FIRST_CHAR_MULTIPLIER);
+                chunk_bonus[0][j] = bonuses[j];
+                continue;
+            }
+            let chunk = match j {
+                0 => 0,
+                j => match bonuses[j] >= BONUS_BOUNDARY {
+                    true => bonuses[j].max(chunk_bonus[i - 1][j - 1]),
+                    false => chunk_bonus[i - 1][j - 1],
+                },
+            };
+            let consecutive = match j {
+                0 => None,
+                j => scores[i - 1][j - 1].map(|score| {
+                    score + SCORE_MATCH + bonuses[j].max(BONUS_CONSECUTIVE).max(chunk)
+                }),
+            };
+            let skipped = gapped.map(|(best, at)| (best + SCORE_MATCH + bonuses[j], at));
+            match (consecutive, skipped) {
+                (Some(consecutive), Some((skipped, _))) if consecutive >= skipped => {
+                    scores[i][j] = Some(consecutive);
+                    from[i][j] = j - 1;
+                    chunk_bonus[i][j] = chunk;
+                }
+                (_, Some((skipped, at))) => {
+                    scores[i][j] = Some(skipped);
+                    from[i][j] = at;
+                    chunk_bonus[i][j] = bonuses[j];
+                }
+                (Some(consecutive), None) => {
+                    scores[i][j] = Some(consecutive);
+                    from[i][j] = j - 1;
+                    chunk_bonus[i][j] = chunk;
+                }
+                (None, None) => {}
+            }
+        }
+    }
+
+    let (mut at, score) = scores[n - 1]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, score)| score.map(|score| (j, score)))
+        .max_by_key(|(j, score)| (*score, std::cmp::Reverse(*j)))?;
+    let mut positions = vec![at; n];
+    for i in (1..n).rev() {
+        at = from[i][at];
+        positions[i - 1] = at;
+    }
+    Some(FuzzyMatch { score, positions })
+}
+
+/// Score of a search result, better matches come first, then results
+/// closer to the search root and recently modified ones
+pub fn rank(score: i64, depth: u32, modified: Option<i64>) -> i64 {
+    let age_days = modified.map(|modified| (Utc::now().timestamp_millis() - modified) / 86_400_000);
+    let recency = match age_days {
+        Some(days) if days < 1 => 12,
+        Some(days) if days < 7 => 8,
+        Some(days) if days < 30 => 4,
+        Some(days) if days < 365 => 1,
+        _ => 0,
+    };
+    score - depth.saturating_sub(1) as i64 * PENALTY_DEPTH + recency
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(pattern: &str, text: &str) -> Vec<usize> {
+        fuzzy_match(pattern, text).unwrap().positions
+    }
+
+    #[test]
+    fn matches_chars_in_order() {
+        assert_eq!(
+            fuzzy_match("mr", "main.rs"),
+            Some(FuzzyMatch {
+                score: 50,
+                positions: vec![0, 5]
+            })
+        );
+        assert_eq!(fuzzy_match("rm", "main.rs"), None);
+        assert_eq!(fuzzy_match("mrx", "main.rs"), None);
+        assert_eq!(fuzzy_match("a", ""), None);
+    }
+
+    #[test]
+    fn empty_pattern_matches_everything() {
+        assert_eq!(
+            fuzzy_match("", "main.rs"),
+            Some(FuzzyMatch {
+                score: 0,
+                positions: vec![]
+            })
+        );
+    }
+
+    #[test]
+    fn ignores_case_unless_the_pattern_has_upper_case() {
+        assert_eq!(positions("mr", "MAIN.RS"), vec![0, 5]);
+        assert_eq!(fuzzy_match("MR", "main.rs"), None);
+        assert_eq!(positions("R", "rust.Rs"), vec![5]);
+    }
+
+    #[test]
+    fn scores_consecutive_matches() {
+        // 32 for the first char at the start, 16 + 8 of its bonus for the next
+        assert_eq!(fuzzy_match("ab", "ab").unwrap().score, 56);
+        assert!(
+            fuzzy_match("abc", "abc_x").unwrap().score > fuzzy_match("abc", "a_b_c").unwrap().score
+        );
+        assert!(
+            fuzzy_match("abc", "xabcx").unwrap().score
+                > fuzzy_match("abc", "xaxbxcx").unwrap().score
+        );
+    }
+
+    #[test]
+    fn prefers_word_starts() {
+        assert_eq!(positions("b", "abc_bar"), vec![4]);
+        assert_eq!(positions("fb", "fooBar"), vec![0, 3]);
+        assert_eq!(fuzzy_match("fb", "fooBar").unwrap().score, 51);
+        assert_eq!(positions("sr", "src/search_query.rs"), vec![0, 1]);
+        assert_eq!(positions("sq", "src/search_query.rs"), vec![4, 11]);
+    }
+
+    #[test]
+    fn ties_go_to_the_earlier_match() {
+        assert_eq!(positions("a", "a_a"), vec![0]);
+    }
+
+    #[test]
+    fn ranks_shallow_and_recent_results_higher() {
+        let now = Utc::now().timestamp_millis();
+        assert_eq!(rank(50, 1, None), 50);
+        assert_eq!(rank(50, 0, None), 50);
+        assert_eq!(rank(50, 3, None), 50 - 2 * PENALTY_DEPTH);
+        assert_eq!(rank(50, 1, Some(now)), 62);
+        assert!(rank(50, 1, Some(now - 3 * 86_400_000)) > rank(50, 1, Some(now - 60 * 86_400_000)));
+        assert_eq!(rank(50, 1, Some(now - 400 * 86_400_000)), 50);
+    }
+}
@@ -1,37 +1,15 @@
-use crate::utils::{dbg_log, DirWalkerEntry};
-use crate::{FDir, SimpleDirInfo};
+use crate::conflicts::{ConflictResolver, ConflictSide, Resolution, SetAside};
+use crate::fdir::{extension_of, FDir, FileType};
+use crate::jobs::JobHandle;
+use crate::utils::{dbg_log, measure_entries, wng_log, DirWalkerEntry};
+use crate::vfs::CloudProvider;
+use crate::SimpleDirInfo;
 use chrono::{DateTime, Utc};
 use drive_v3::objects::{File, UploadType};
 use drive_v3::{Credentials, Drive};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
-
-pub trait CloudProvider {
-    fn new() -> Self;
-
-    fn authenticate(&mut self) -> Result<(), String>;
-
-    fn sign_out(&mut self) -> Result<(), String>;
-
-    fn read_dir(&mut self, path: &PathBuf) -> Result<Vec<FDir>, String>;
-
-    fn download(&mut self, from_path: &str, to_path: &str) -> Result<String, String>;
-
-    fn upload(&mut self, from_path: &str, to_path: &str) -> Result<(), String>;
-
-    fn create_dir(&mut self, from_path: &str, to_path: &str) -> Result<(), String>;
-
-    fn search(&mut self, fname: &str) -> Result<Vec<DirWalkerEntry>, String>;
-
-    fn get_item_size(&self, path: &str) -> Result<SimpleDirInfo, String>;
-
-    fn copy_items(&mut self, arr_items: Vec<FDir>, copy_to_path: &str) -> Result<(), String>;
-
-    fn delete(&mut self, path: &str) -> Result<(), String>;
-
-    fn rename(&mut self, path: &str, new_name: &str) -> Result<(), String>;
-}
+use std::path::{Path, PathBuf};
 
 pub struct GDrive {
     drive: Option<Drive>,
@@ -112,7 +90,11 @@ impl CloudProvider for GDrive {
             .unwrap()
             .files
             .list()
-            .fields("files(name,id,mimeType,size,fileExtension,modifiedTime,parents)")
+            .fields(
+                "files(name,id,mimeType,size,fileExtension,createdTime,modifiedTime,\
+                 viewedByMeTime,parents,owners(displayName),capabilities(canEdit),\
+                 shortcutDetails(targetId))",
+            )
             .q(&format!("'{}' in parents", file_id))
             .execute()
             .map_err(|e| e.to_string())?;
@@ -126,25 +108,51 @@ impl CloudProvider for GDrive {
                     file.clone(),
                 );
 
+                let is_dir = Self::is_dir(&file);
+                let shortcut = file.shortcut_details.as_ref();
+                let extension = match file.file_extension.as_deref() {
+                    Some(ext) if !is_dir && !ext.is_empty() => format!(".{}", ext),
+                    _ => extension_of(&file_name, is_dir),
+                };
+                let owner = file
+                    .owners
+                    .as_ref()
+                    .and_then(|owners| owners.first())
+                    .and_then(|owner| owner.display_name.clone());
                 files_fdir.push(FDir {
-                    name: file_name.clone(),
                     path: path
                         .join(&file_name)
                         .to_str()
                         .unwrap()
                         .to_string()
                         .replace("\\", "/"),
-                    is_dir: Self::is_dir(&file) as i8,
-                    size: file.size.unwrap_or("0".to_string()),
-                    extension: format!(".{}", file.file_extension.unwrap_or("".to_string())),
-                    last_modified: {
-                        file.modified_time
-                            .unwrap()
-                            .parse::<DateTime<Utc>>()
-                            .unwrap()
-                            .format("%Y-%m-%d %H:%M:%S")
-                            .to_string()
+                    file_type: match (is_dir, shortcut) {
+                        (true, _) => FileType::Dir,
+                        (false, Some(_)) => FileType::Symlink,
+                        (false, None) => FileType::File,
                     },
+                    is_dir,
+                    symlink_target: shortcut.and_then(|s| s.target_id.clone()),
+                    extension,
+                    size: file.size.as_deref().unwrap_or("0").parse().unwrap_or(0),
+                    created: file.created_time.as_deref().and_then(Self::parse_millis),
+                    accessed: file
+                        .viewed_by_me_time
+                        .as_deref()
+                        .and_then(Self::parse_millis),
+                    modified: file.modified_time.as_deref().and_then(Self::parse_millis),
+                    permissions: None,
+                    owner,
+                    group: None,
+                    is_hidden: file_name.starts_with('.'),
+                    is_readonly: file
+                        .capabilities
+                        .as_ref()
+                        .and_then(|c| c.can_edit)
+                        .is_some_and(|can_edit| !can_edit),
+                    mime_type: file.mime_type.clone().unwrap_or_default(),
+                    group_key: None,
+                    name: file_name,
                 });
             }
         }
@@ -158,20 +166,9 @@ impl CloudProvider for GDrive {
         }
 
         let cached_file = self.path2file.get(from_path).unwrap();
-        let id = cached_file.id.clone().unwrap();
         let fname = cached_file.name.as_ref().unwrap();
         let saved_path = format!("{}{}", to_path, fname);
-
-        dbg_log(format!("Downloading {} to {}", from_path, saved_path));
-
-        self.drive
-            .as_ref()
-            .unwrap()
-            .files
-            .get_media(&id)
-            .save_to(&saved_path)
-            .execute()
-            .map_err(|e| e.to_string())?;
+        self.save_to(from_path, &saved_path)?;
 
         Ok(saved_path)
     }
@@ -381,9 +378,41 @@ impl CloudProvider for GDrive {
         }
     }
 
-    fn copy_items(&mut self, arr_items: Vec<FDir>, copy_to_path: &str) -> Result<(), String> {
+    fn copy_items(
+        &mut self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        arr_items: Vec<FDir>,
+        copy_to_path: &str,
+    ) -> Result<(), String> {
+        for item in arr_items {
+            self.copy(job, resolver, &item.path, copy_to_path)?;
+        }
+
+        Ok(())
+    }
+
+    fn move_items(
+        &mut self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        arr_items: Vec<FDir>,
+        move_to_path: &str,
+    ) -> Result<(), String> {
+        // The source of an item is only removed once all of it was copied
         for item in arr_items {
-            self.copy(&item.path, copy_to_path)?;
+            if !self.copy(job, resolver, &item.path, move_to_path)? {
+                wng_log(format!(
+                    "{} was kept, conflicts skipped some of it",
+                    item.path
+                ));
+            } else if item.path.starts_with("gdrive:") {
+                self.delete(&item.path)?;
+            } else if item.is_dir {
+                fs::remove_dir_all(&item.path).map_err(|e| e.to_string())?;
+            } else {
+                fs::remove_file(&item.path).map_err(|e| e.to_string())?;
+            }
         }
 
         Ok(())
@@ -452,6 +481,13 @@ impl GDrive {
         root_file
     }
 
+    /// Drive timestamps are RFC 3339, returns epoch millis
+    fn parse_millis(time: &str) -> Option<u64> {
+        time.parse::<DateTime<Utc>>()
+            .ok()
+            .map(|time| time.timestamp_millis() as u64)
+    }
+
     fn is_dir(file: &File) -> bool {
         file.mime_type.as_ref().unwrap() == "application/vnd.google-apps.folder"
     }
@@ -484,10 +520,19 @@ impl GDrive {
         }
     }
 
-    pub fn copy(&mut self, from_path: &str, to_path: &str) -> Result<(), String> {
+    /// Copies an item into the dir `to_path`. Returns false if conflicts
+    /// skipped some of it.
+    pub fn copy(
+        &mut self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        from_path: &str,
+        to_path: &str,
+    ) -> Result<bool, String> {
         if self.drive.is_none() {
             self.authenticate()?;
         }
+        job.checkpoint()?;
 
         let from = &from_path.replace("\\", "/");
         let to = &to_path.replace("\\", "/");
@@ -503,50 +548,129 @@ impl GDrive {
                     self.create_dir(from, to)?;
 
                     let children = self.read_dir(&PathBuf::from(&from))?;
-                    self.copy_items(children, format!("{}/{}", to, item_name).as_str())?;
-                } else {
-                    let metadata = File {
-                        parents: Some(vec![parent_item.id.clone().unwrap()]),
-                        ..Default::default()
-                    };
-
-                    let copied_file = self
-                        .drive
-                        .as_ref()
-                        .unwrap()
-                        .files
-                        .copy(item.id.as_ref().unwrap())
-                        .metadata(&metadata)
-                        .execute()
-                        .map_err(|e| e.to_string())?;
-
-                    self.path2file.insert(
-                        format!("{}/{}", to, &copied_file.name.as_ref().unwrap()),
-                        copied_file,
-                    );
+                    let to = format!("{}/{}", to, item_name);
+                    return self.copy_children(job, resolver, children, &to);
                 }
+                let metadata = File {
+                    parents: Some(vec![parent_item.id.clone().unwrap()]),
+                    ..Default::default()
+                };
+                job.add_total(1, 0);
+
+                let copied_file = self
+                    .drive
+                    .as_ref()
+                    .unwrap()
+                    .files
+                    .copy(item.id.as_ref().unwrap())
+                    .metadata(&metadata)
+                    .execute()
+                    .map_err(|e| e.to_string())?;
+
+                self.path2file.insert(
+                    format!("{}/{}", to, &copied_file.name.as_ref().unwrap()),
+                    copied_file,
+                );
+                job.add_done(1, 0);
             } else {
-                // Local to Gdrive copy
+                // Local to Gdrive copy, files with the same name can exist side by side
+                let (count, size) = measure_entries(from);
+                job.add_total(count, size);
                 self.upload(from, to)?;
+                job.add_done(count, size);
             }
-        } else {
-            // Gdrive to Local copy
-            let item = self.path2file.get(from).ok_or("item not in cache")?;
-            let item_name = item.name.clone().unwrap();
+            return Ok(true);
+        }
 
-            if fs::metadata(to).is_err() {
-                fs::create_dir_all(to).map_err(|e| e.to_string())?;
-            }
+        // Gdrive to Local copy
+        let item = self.path2file.get(from).ok_or("item not in cache")?;
+        let item_name = item.name.clone().unwrap();
 
-            if Self::is_dir(item) {
-                let children = self.read_dir(&PathBuf::from(&from))?;
+        if fs::metadata(to).is_err() {
+            fs::create_dir_all(to).map_err(|e| e.to_string())?;
+        }
 
-                self.copy_items(children, format!("{}/{}", to, item_name).as_str())?;
-            } else {
-                self.download(from, &format!("{}/", to))?;
-            }
+        if Self::is_dir(item) {
+            let children = self.read_dir(&PathBuf::from(&from))?;
+            let to = format!("{}/{}", to, item_name);
+            return self.copy_children(job, resolver, children, &to);
         }
+        self.download_into(job, resolver, from, Path::new(to))
+    }
 
-        Ok(())
+    fn copy_children(
+        &mut self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        children: Vec<FDir>,
+        to: &str,
+    ) -> Result<bool, String> {
+        let mut complete = true;
+        for child in children {
+            complete &= self.copy(job, resolver, &child.path, to)?;
+        }
+        Ok(complete)
+    }
+
+    /// Downloads a file into the local dir `to`, an existing one is handled
+    /// by the resolver. Returns false if it was skipped.
+    fn download_into(
+        &mut self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        from: &str,
+        to: &Path,
+    ) -> Result<bool, String> {
+        let item = self.path2file.get(from).ok_or("item not in cache")?;
+        let name = item.name.clone().unwrap_or_default();
+        let size = item.size.as_deref().unwrap_or("0").parse().unwrap_or(0);
+        let source = ConflictSide {
+            path: from.to_string(),
+            name: name.clone(),
+            is_dir: false,
+            size,
+            last_modified: item
+                .modified_time
+                .as_deref()
+                .and_then(Self::parse_millis)
+                .unwrap_or(0) as u128,
+        };
+        job.add_total(1, size);
+
+        let mut replaced = SetAside::default();
+        let target = match resolver.resolve_source(job, source, &to.join(&name))? {
+            Resolution::CopyTo(target) => target,
+            Resolution::Replace(target) => {
+                replaced.set_aside(&target)?;
+                target
+            }
+            Resolution::Skip => {
+                job.add_done(1, size);
+                return Ok(false);
+            }
+        };
+        job.begin_item(&name);
+        replaced.finish(self.save_to(from, &target.to_string_lossy()))?;
+        job.add_bytes(size);
+        job.finish_item();
+        Ok(true)
+    }
+
+    /// Downloads a file to `saved_path`, replacing what's there
+    fn save_to(&mut self, from_path: &str, saved_path: &str) -> Result<(), String> {
+        let cached_file = self.path2file.get(from_path).ok_or("item not in cache")?;
+        let id = cached_file.id.clone().unwrap();
+
+        dbg_log(format!("Downloading {} to {}", from_path, saved_path));
+
+        self.drive
+            .as_ref()
+            .unwrap()
+            .files
+            .get_media(&id)
+            .save_to(saved_path)
+            .execute()
+            .map(|_| ())
+            .map_err(|e| e.to_string())
     }
 }
@@ -0,0 +1,448 @@
+use crate::conflicts::{ConflictAnswer, ConflictInfo};
+use crate::utils::{calc_transfer_speed, dbg_log, err_log};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{Manager, Window};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Error returned by `JobHandle::checkpoint` once a job got canceled
+pub const JOB_CANCELED: &str = "Job was canceled";
+
+/// How many jobs may run at the same time, the rest stays queued
+const MAX_RUNNING_JOBS: usize = 3;
+
+/// How many finished jobs are kept for `list_jobs`
+const MAX_FINISHED_JOBS: usize = 50;
+
+/// Minimum time between two `job-progress` events of the same job
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Paused,
+    Failed,
+    Canceled,
+    Done,
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Failed | JobStatus::Canceled | JobStatus::Done
+        )
+    }
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct JobProgress {
+    pub items_done: u64,
+    pub items_total: u64,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub current_item: String,
+    /// Transfer speed in MB/s
+    pub speed: f64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct JobInfo {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub path: String,
+    pub status: JobStatus,
+    pub progress: JobProgress,
+    pub error: Option<String>,
+}
+
+/// Payload of the `job-progress` event
+#[derive(Serialize, Clone, Debug)]
+pub struct JobProgressEvent {
+    pub id: String,
+    pub progress: JobProgress,
+}
+
+struct Job {
+    info: Mutex<JobInfo>,
+    canceled: AtomicBool,
+    paused: Mutex<bool>,
+    resumed: Condvar,
+    answer: Mutex<Option<ConflictAnswer>>,
+    answered: Condvar,
+}
+
+impl Job {
+    fn snapshot(&self) -> JobInfo {
+        self.info.lock().unwrap().clone()
+    }
+}
+
+/// Managed state keeping track of every long-running file operation
+pub struct JobManager {
+    jobs: Mutex<HashMap<String, Arc<Job>>>,
+    order: Mutex<Vec<String>>,
+    slots: Arc<Semaphore>,
+}
+
+impl Default for JobManager {
+    fn default() -> Self {
+        JobManager {
+            jobs: Mutex::new(HashMap::new()),
+            order: Mutex::new(vec![]),
+            slots: Arc::new(Semaphore::new(MAX_RUNNING_JOBS)),
+        }
+    }
+}
+
+impl JobManager {
+    /// Registers a new queued job and announces it to the ui
+    pub fn create(
+        &self,
+        app_window: &Window,
+        name: String,
+        description: String,
+        path: &str,
+    ) -> JobHandle {
+        let id = uuid::Uuid::new_v4().to_string();
+        let job = Arc::new(Job {
+            info: Mutex::new(JobInfo {
+                id: id.clone(),
+                name,
+                description,
+                path: path.to_string(),
+                status: JobStatus::Queued,
+                progress: JobProgress::default(),
+                error: None,
+            }),
+            canceled: AtomicBool::new(false),
+            paused: Mutex::new(false),
+            resumed: Condvar::new(),
+            answer: Mutex::new(None),
+            answered: Condvar::new(),
+        });
+        self.prune_finished();
+        self.jobs.lock().unwrap().insert(id.clone(), job.clone());
+        self.order.lock().unwrap().push(id);
+
+        let handle = JobHandle {
+            job,
+            window: Some(app_window.clone()),
+            slots: self.slots.clone(),
+            started_at: Arc::new(Mutex::new(Instant::now())),
+            last_emit: Arc::new(Mutex::new(Instant::now() - PROGRESS_INTERVAL)),
+        };
+        handle.emit_update();
+        handle
+    }
+
+    pub fn list(&self) -> Vec<JobInfo> {
+        let jobs = self.jobs.lock().unwrap();
+        self.order
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|id| jobs.get(id).map(|job| job.snapshot()))
+            .collect()
+    }
+
+    pub fn cancel(&self, id: &str) -> Result<(), String> {
+        let job = self.get(id)?;
+        job.canceled.store(true, Ordering::SeqCst);
+        // Wake up a paused or asking job so it can notice the cancellation
+        *job.paused.lock().unwrap() = false;
+        job.resumed.notify_all();
+        let _answer = job.answer.lock().unwrap();
+        job.answered.notify_all();
+        dbg_log(format!("Canceled job: {}", id));
+        Ok(())
+    }
+
+    pub fn pause(&self, id: &str) -> Result<(), String> {
+        let job = self.get(id)?;
+        if job.snapshot().status.is_finished() {
+            return Err("Job is already finished".into());
+        }
+        *job.paused.lock().unwrap() = true;
+        dbg_log(format!("Paused job: {}", id));
+        Ok(())
+    }
+
+    pub fn resume(&self, id: &str) -> Result<(), String> {
+        let job = self.get(id)?;
+        *job.paused.lock().unwrap() = false;
+        job.resumed.notify_all();
+        dbg_log(format!("Resumed job: {}", id));
+        Ok(())
+    }
+
+    /// Hands the user's answer to a job waiting in `JobHandle::ask_conflict`
+    pub fn answer_conflict(&self, id: &str, answer: ConflictAnswer) -> Result<(), String> {
+        let job = self.get(id)?;
+        if job.snapshot().status != JobStatus::Paused {
+            return Err("Job is not waiting for an answer".into());
+        }
+        *job.answer.lock().unwrap() = Some(answer);
+        job.answered.notify_all();
+        Ok(())
+    }
+
+    fn get(&self, id: &str) -> Result<Arc<Job>, String> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| format!("No job with id {}", id))
+    }
+
+    fn prune_finished(&self) {
+        let mut jobs = self.jobs.lock().unwrap();
+        let mut order = self.order.lock().unwrap();
+        let finished: Vec<String> = order
+            .iter()
+            .filter(|id| {
+                jobs.get(*id)
+                    .map_or(true, |job| job.snapshot().status.is_finished())
+            })
+            .cloned()
+            .collect();
+        if finished.len() <= MAX_FINISHED_JOBS {
+            return;
+        }
+        for id in &finished[..finished.len() - MAX_FINISHED_JOBS] {
+            jobs.remove(id);
+            order.retain(|x| x != id);
+        }
+    }
+}
+
+/// Handed to the code doing the actual work to report progress and react to
+/// pause / cancel requests
+#[derive(Clone)]
+pub struct JobHandle {
+    job: Arc<Job>,
+    /// None for tests, which have no window to report to
+    window: Option<Window>,
+    slots: Arc<Semaphore>,
+    started_at: Arc<Mutex<Instant>>,
+    last_emit: Arc<Mutex<Instant>>,
+}
+
+impl JobHandle {
+    /// A running job that reports to nobody, conflicts it would have to ask
+    /// about fail
+    #[cfg(test)]
+    pub fn detached() -> JobHandle {
+        JobHandle {
+            job: Arc::new(Job {
+                info: Mutex::new(JobInfo {
+                    id: "test".into(),
+                    name: "test".into(),
+                    description: "".into(),
+                    path: "".into(),
+                    status: JobStatus::Running,
+                    progress: JobProgress::default(),
+                    error: None,
+                }),
+                canceled: AtomicBool::new(false),
+                paused: Mutex::new(false),
+                resumed: Condvar::new(),
+                answer: Mutex::new(None),
+                answered: Condvar::new(),
+            }),
+            window: None,
+            slots: Arc::new(Semaphore::new(MAX_RUNNING_JOBS)),
+            started_at: Arc::new(Mutex::new(Instant::now())),
+            last_emit: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    pub fn id(&self) -> String {
+        self.job.info.lock().unwrap().id.clone()
+    }
+
+    pub fn is_canceled(&self) -> bool {
+        self.job.canceled.load(Ordering::SeqCst)
+    }
+
+    /// Waits until a job slot is free and marks the job as running.
+    /// The returned permit has to be kept alive until the job is done.
+    pub async fn start(&self) -> Result<OwnedSemaphorePermit, String> {
+        let permit = self
+            .slots
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| e.to_string())?;
+        if self.is_canceled() {
+            return Err(JOB_CANCELED.into());
+        }
+        *self.started_at.lock().unwrap() = Instant::now();
+        self.set_status(JobStatus::Running);
+        Ok(permit)
+    }
+
+    /// Runs blocking `work` as this job: waits for a free slot, executes it on
+    /// the blocking thread pool and records the outcome
+    pub async fn run_blocking(
+        self,
+        work: impl FnOnce(&JobHandle) -> Result<(), String> + Send + 'static,
+    ) -> Result<(), String> {
+        let result = match self.start().await {
+            Ok(_permit) => {
+                let job = self.clone();
+                tauri::async_runtime::spawn_blocking(move || work(&job))
+                    .await
+                    .map_err(|e| e.to_string())
+                    .and_then(|result| result)
+            }
+            Err(e) => Err(e),
+        };
+        self.finish(&result);
+        result
+    }
+
+    /// Blocks while the job is paused and fails once it got canceled.
+    /// Meant to be called regularly from blocking worker code.
+    pub fn checkpoint(&self) -> Result<(), String> {
+        let mut paused = self.job.paused.lock().unwrap();
+        if *paused && !self.is_canceled() {
+            self.set_status(JobStatus::Paused);
+            while *paused && !self.is_canceled() {
+                paused = self.job.resumed.wait(paused).unwrap();
+            }
+            self.set_status(JobStatus::Running);
+        }
+        drop(paused);
+        if self.is_canceled() {
+            return Err(JOB_CANCELED.into());
+        }
+        Ok(())
+    }
+
+    /// Same as `checkpoint` but without blocking the async runtime
+    pub async fn checkpoint_async(&self) -> Result<(), String> {
+        if *self.job.paused.lock().unwrap() && !self.is_canceled() {
+            self.set_status(JobStatus::Paused);
+            while *self.job.paused.lock().unwrap() && !self.is_canceled() {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            self.set_status(JobStatus::Running);
+        }
+        if self.is_canceled() {
+            return Err(JOB_CANCELED.into());
+        }
+        Ok(())
+    }
+
+    /// Pauses the job, emits a `job-conflict` event and blocks until the user
+    /// answered it through `resolve_conflict` or the job got canceled
+    pub fn ask_conflict(&self, conflict: ConflictInfo) -> Result<ConflictAnswer, String> {
+        let Some(window) = &self.window else {
+            return Err("There's no one to answer the conflict".into());
+        };
+        let mut answer = self.job.answer.lock().unwrap();
+        *answer = None;
+        self.set_status(JobStatus::Paused);
+        let _ = window.emit_all("job-conflict", conflict);
+        while answer.is_none() && !self.is_canceled() {
+            answer = self.job.answered.wait(answer).unwrap();
+        }
+        if self.is_canceled() {
+            return Err(JOB_CANCELED.into());
+        }
+        self.set_status(JobStatus::Running);
+        Ok(answer.take().unwrap())
+    }
+
+    pub fn add_total(&self, items: u64, bytes: u64) {
+        self.update_progress(|progress| {
+            progress.items_total += items;
+            progress.bytes_total += bytes;
+        });
+    }
+
+    /// Marks the start of a new item, e.g. the next file to copy
+    pub fn begin_item(&self, name: &str) {
+        self.update_progress(|progress| progress.current_item = name.to_string());
+    }
+
+    pub fn finish_item(&self) {
+        self.update_progress(|progress| progress.items_done += 1);
+    }
+
+    /// Counts items as done without copying their bytes, e.g. skipped or renamed ones
+    pub fn add_done(&self, items: u64, bytes: u64) {
+        self.update_progress(|progress| {
+            progress.items_done += items;
+            progress.bytes_done += bytes;
+        });
+    }
+
+    pub fn add_bytes(&self, bytes: u64) {
+        let elapsed = self.started_at.lock().unwrap().elapsed().as_millis() as f64 / 1000.0;
+        self.update_progress(|progress| {
+            progress.bytes_done += bytes;
+            let speed = calc_transfer_speed(progress.bytes_done as f64, elapsed);
+            progress.speed = if speed.is_finite() { speed } else { 0.0 };
+        });
+    }
+
+    /// Marks the job as done, failed or canceled depending on the outcome
+    pub fn finish<T>(&self, result: &Result<T, String>) {
+        let status = match result {
+            _ if self.is_canceled() => JobStatus::Canceled,
+            Ok(_) => JobStatus::Done,
+            Err(e) => {
+                err_log(format!("Job {} failed: {}", self.id(), e));
+                self.job.info.lock().unwrap().error = Some(e.clone());
+                JobStatus::Failed
+            }
+        };
+        self.emit_progress();
+        self.set_status(status);
+    }
+
+    fn set_status(&self, status: JobStatus) {
+        self.job.info.lock().unwrap().status = status;
+        self.emit_update();
+    }
+
+    fn update_progress(&self, f: impl FnOnce(&mut JobProgress)) {
+        f(&mut self.job.info.lock().unwrap().progress);
+        let mut last_emit = self.last_emit.lock().unwrap();
+        if last_emit.elapsed() >= PROGRESS_INTERVAL {
+            *last_emit = Instant::now();
+            drop(last_emit);
+            self.emit_progress();
+        }
+    }
+
+    fn emit_progress(&self) {
+        let info = self.job.snapshot();
+        self.emit(
+            "job-progress",
+            JobProgressEvent {
+                id: info.id,
+                progress: info.progress,
+            },
+        );
+    }
+
+    fn emit_update(&self) {
+        self.emit("job-update", self.job.snapshot());
+    }
+
+    fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        if let Some(window) = &self.window {
+            let _ = window.emit_all(event, payload);
+        }
+    }
+}
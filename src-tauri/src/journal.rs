@@ -0,0 +1,404 @@
+use crate::trash;
+use crate::utils::{dbg_log, err_log};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tauri::api::path::config_dir;
+use tauri::async_runtime::Mutex;
+
+const MAX_JOURNAL_ENTRIES: usize = 100;
+
+/// Size and modification time of an item right after an operation, used to
+/// notice if it was changed outside of the app afterwards
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fingerprint {
+    pub is_dir: bool,
+    pub size: u64,
+    /// Epoch millis, not u128 as serde can't read those inside of the
+    /// tagged `Operation`
+    pub modified: u64,
+}
+
+impl Fingerprint {
+    pub fn of(path: &Path) -> Option<Fingerprint> {
+        let metadata = fs::symlink_metadata(path).ok()?;
+        Some(Fingerprint {
+            is_dir: metadata.is_dir(),
+            size: if metadata.is_dir() { 0 } else { metadata.len() },
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_millis() as u64),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PathChange {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// Fingerprint of the item at the place it currently should be
+    pub fingerprint: Option<Fingerprint>,
+}
+
+impl PathChange {
+    /// Records a change that already happened, so the item lives at `to`
+    pub fn new(from: PathBuf, to: PathBuf) -> PathChange {
+        let fingerprint = Fingerprint::of(&to);
+        PathChange {
+            from,
+            to,
+            fingerprint,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Operation {
+    /// Single and multi renames
+    Rename {
+        changes: Vec<PathChange>,
+    },
+    Move {
+        changes: Vec<PathChange>,
+    },
+    Create {
+        path: PathBuf,
+        fingerprint: Option<Fingerprint>,
+    },
+    /// `from` is the original path, `to` the path inside of the trash
+    Trash {
+        changes: Vec<PathChange>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JournalEntry {
+    pub description: String,
+    /// Epoch millis
+    pub timestamp: u128,
+    pub operation: Operation,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct JournalData {
+    undo: Vec<JournalEntry>,
+    redo: Vec<JournalEntry>,
+}
+
+/// Persisted history of file operations that can be undone and redone
+pub struct Journal {
+    data: Mutex<JournalData>,
+    /// Held while an undo or redo runs, so they happen one after another
+    /// without blocking `record`
+    replaying: Mutex<()>,
+    /// Where the journal is persisted
+    path: PathBuf,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Journal::load(journal_path())
+    }
+}
+
+impl Journal {
+    fn load(path: PathBuf) -> Journal {
+        let data = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Journal {
+            data: Mutex::new(data),
+            replaying: Mutex::new(()),
+            path,
+        }
+    }
+
+    pub async fn record(&self, description: String, operation: Operation) {
+        let mut data = self.data.lock().await;
+        data.undo.push(JournalEntry {
+            description,
+            timestamp: std::time::SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis()),
+            operation,
+        });
+        if data.undo.len() > MAX_JOURNAL_ENTRIES {
+            data.undo.remove(0);
+        }
+        data.redo.clear();
+        persist(&self.path, &data);
+    }
+
+    pub async fn undo_last(&self) -> Result<JournalEntry, String> {
+        let _replaying = self.replaying.lock().await;
+        let entry = self.data.lock().await.undo.pop().ok_or("Nothing to undo")?;
+        let (entry, undone) = replay(entry, undo).await?;
+        let mut data = self.data.lock().await;
+        if let Err(e) = undone {
+            data.undo.push(entry);
+            return Err(e);
+        }
+        dbg_log(format!("Undone: {}", entry.description));
+        data.redo.push(entry.clone());
+        persist(&self.path, &data);
+        Ok(entry)
+    }
+
+    pub async fn redo(&self) -> Result<JournalEntry, String> {
+        let _replaying = self.replaying.lock().await;
+        let entry = self.data.lock().await.redo.pop().ok_or("Nothing to redo")?;
+        let (entry, redone) = replay(entry, redo).await?;
+        let mut data = self.data.lock().await;
+        if let Err(e) = redone {
+            data.redo.push(entry);
+            return Err(e);
+        }
+        dbg_log(format!("Redone: {}", entry.description));
+        data.undo.push(entry.clone());
+        persist(&self.path, &data);
+        Ok(entry)
+    }
+}
+
+/// Runs `f` on the operation of the entry on a blocking thread, moving big
+/// dirs across devices can take a while
+async fn replay(
+    mut entry: JournalEntry,
+    f: fn(&mut Operation) -> Result<(), String>,
+) -> Result<(JournalEntry, Result<(), String>), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let result = f(&mut entry.operation);
+        (entry, result)
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+fn undo(operation: &mut Operation) -> Result<(), String> {
+    match operation {
+        Operation::Rename { changes } | Operation::Move { changes } => {
+            check_changes(changes)?;
+            for change in changes.iter_mut().rev() {
+                move_path(&change.to, &change.from)?;
+                change.fingerprint = Fingerprint::of(&change.from);
+            }
+        }
+        Operation::Create { path, fingerprint } => {
+            if Fingerprint::of(path) != *fingerprint {
+                return Err(format!("{:?} was changed since it was created", path));
+            }
+            let removed = if path.is_dir() {
+                // Fails if something was put into the dir in the meantime
+                fs::remove_dir(&path)
+            } else {
+                fs::remove_file(&path)
+            };
+            removed.map_err(|e| format!("Can't remove {:?}: {}", path, e))?;
+        }
+        Operation::Trash { changes } => {
+            check_changes(changes)?;
+            for change in changes.iter_mut().rev() {
+                trash::restore(&change.to)?;
+                change.fingerprint = Fingerprint::of(&change.from);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn redo(operation: &mut Operation) -> Result<(), String> {
+    match operation {
+        Operation::Rename { changes } | Operation::Move { changes } => {
+            check_reverted_changes(changes)?;
+            for change in changes.iter_mut() {
+                move_path(&change.from, &change.to)?;
+                change.fingerprint = Fingerprint::of(&change.to);
+            }
+        }
+        Operation::Create { path, fingerprint } => {
+            if fs::symlink_metadata(&path).is_ok() {
+                return Err(format!("{:?} already exists", path));
+            }
+            let is_dir = fingerprint.is_some_and(|f| f.is_dir);
+            let created = if is_dir {
+                fs::create_dir(&path)
+            } else {
+                fs::File::create(&path).map(|_| ())
+            };
+            created.map_err(|e| e.to_string())?;
+            *fingerprint = Fingerprint::of(path);
+        }
+        Operation::Trash { changes } => {
+            check_reverted_changes(changes)?;
+            for change in changes.iter_mut() {
+                // The item might get a different name inside of the trash
+                let item = trash::move_to_trash(&change.from)?;
+                change.to = PathBuf::from(item.path);
+                change.fingerprint = Fingerprint::of(&change.to);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Makes sure every item is still where (and how) the operation left it
+fn check_changes(changes: &[PathChange]) -> Result<(), String> {
+    for change in changes {
+        if Fingerprint::of(&change.to) != change.fingerprint {
+            return Err(format!("{:?} was changed in the meantime", change.to));
+        }
+        if fs::symlink_metadata(&change.from).is_ok() {
+            return Err(format!("{:?} already exists", change.from));
+        }
+    }
+    Ok(())
+}
+
+fn check_reverted_changes(changes: &[PathChange]) -> Result<(), String> {
+    for change in changes {
+        if Fingerprint::of(&change.from) != change.fingerprint {
+            return Err(format!("{:?} was changed in the meantime", change.from));
+        }
+        if fs::symlink_metadata(&change.to).is_ok() {
+            return Err(format!("{:?} already exists", change.to));
+        }
+    }
+    Ok(())
+}
+
+/// Renames an item and falls back to copy and delete across devices
+fn move_path(from: &Path, to: &Path) -> Result<(), String> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    copy_recursive(from, to).map_err(|e| e.to_string())?;
+    let removed = if from.is_dir() {
+        fs::remove_dir_all(from)
+    } else {
+        fs::remove_file(from)
+    };
+    removed.map_err(|e| e.to_string())
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> std::io::Result<()> {
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+fn journal_path() -> PathBuf {
+    config_dir()
+        .unwrap_or_default()
+        .join("com.codriver.dev")
+        .join("journal.json")
+}
+
+fn persist(path: &Path, data: &JournalData) {
+    let tmp_path = path.with_extension("json.tmp");
+    let written = serde_json::to_string(data)
+        .map_err(|e| e.to_string())
+        .and_then(|content| fs::write(&tmp_path, content).map_err(|e| e.to_string()))
+        .and_then(|_| fs::rename(&tmp_path, path).map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        err_log(format!("Failed to save journal: {}", e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tauri::async_runtime::block_on;
+    use tempfile::TempDir;
+
+    /// Renames the item and returns the operation to record for it
+    fn rename(from: &Path, to: &Path) -> Operation {
+        fs::rename(from, to).unwrap();
+        Operation::Rename {
+            changes: vec![PathChange::new(from.to_path_buf(), to.to_path_buf())],
+        }
+    }
+
+    #[test]
+    fn undo_is_refused_once_an_item_was_changed() {
+        let dir = TempDir::new().unwrap();
+        let journal = Journal::load(dir.path().join("journal.json"));
+        let (from, to) = (dir.path().join("a.txt"), dir.path().join("sub/a.txt"));
+        fs::write(&from, "a").unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::rename(&from, &to).unwrap();
+        let changes = vec![PathChange::new(from.clone(), to.clone())];
+        block_on(journal.record("Move".into(), Operation::Move { changes }));
+
+        fs::write(&to, "changed").unwrap();
+        let refused = block_on(journal.undo_last()).unwrap_err();
+        assert!(
+            refused.contains("was changed in the meantime"),
+            "{}",
+            refused
+        );
+        assert!(!from.exists());
+        assert_eq!(fs::read_to_string(&to).unwrap(), "changed");
+        // The entry stays to be undone later
+        assert_eq!(block_on(journal.redo()).unwrap_err(), "Nothing to redo");
+        assert!(block_on(journal.undo_last()).is_err());
+    }
+
+    #[test]
+    fn undo_is_refused_once_the_old_place_was_taken() {
+        let dir = TempDir::new().unwrap();
+        let journal = Journal::load(dir.path().join("journal.json"));
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        fs::write(&a, "a").unwrap();
+        block_on(journal.record("Rename".into(), rename(&a, &b)));
+
+        fs::write(&a, "new").unwrap();
+        let refused = block_on(journal.undo_last()).unwrap_err();
+        assert!(refused.contains("already exists"), "{}", refused);
+        assert_eq!(fs::read_to_string(&a).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "a");
+    }
+
+    #[test]
+    fn undo_and_redo_go_back_and_forth_in_order() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("journal.json");
+        let journal = Journal::load(path.clone());
+        let [a, b, c, d] = ["a", "b", "c", "d"].map(|name| dir.path().join(name));
+        fs::write(&a, "a").unwrap();
+        block_on(journal.record("First".into(), rename(&a, &b)));
+        block_on(journal.record("Second".into(), rename(&b, &c)));
+
+        assert_eq!(block_on(journal.undo_last()).unwrap().description, "Second");
+        assert!(b.exists());
+        assert_eq!(block_on(journal.undo_last()).unwrap().description, "First");
+        assert!(a.exists());
+        assert_eq!(
+            block_on(journal.undo_last()).unwrap_err(),
+            "Nothing to undo"
+        );
+
+        assert_eq!(block_on(journal.redo()).unwrap().description, "First");
+        assert!(b.exists());
+        // A new operation drops what could have been redone
+        block_on(journal.record("Third".into(), rename(&b, &d)));
+        assert_eq!(block_on(journal.redo()).unwrap_err(), "Nothing to redo");
+
+        // The history survives a restart
+        let journal = Journal::load(path);
+        assert_eq!(block_on(journal.undo_last()).unwrap().description, "Third");
+        assert_eq!(block_on(journal.undo_last()).unwrap().description, "First");
+        assert!(a.exists());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::fdir::{fdir_from_path, FDir};
+use crate::listing_spec::ListingSpec;
+use crate::navigation::NavKey;
+use crate::utils::{dbg_log, wng_log};
+use crate::watcher::DirWatchers;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{Manager, Window};
+
+pub const DEFAULT_CHUNK_SIZE: usize = 500;
+
+/// Returned by `list_dirs_stream` right after the entries were counted
+#[derive(Serialize, Clone, Debug)]
+pub struct ListingInfo {
+    pub listing_id: String,
+    pub path: String,
+    pub total: usize,
+}
+
+/// Payload of the `listing-chunk` event
+#[derive(Serialize, Clone, Debug)]
+pub struct ListingChunk {
+    pub listing_id: String,
+    pub pane_id: String,
+    pub path: String,
+    pub entries: Vec<FDir>,
+    /// Position of the first entry of this chunk in the whole listing
+    pub cursor: usize,
+    /// Where the next page starts, `None` once the end of the directory is reached
+    pub next_cursor: Option<usize>,
+    pub total: usize,
+}
+
+/// Payload of the `listing-done` event
+#[derive(Serialize, Clone, Debug)]
+pub struct ListingDone {
+    pub listing_id: String,
+    pub pane_id: String,
+    pub path: String,
+    pub total: usize,
+    pub cancelled: bool,
+}
+
+/// Where the entries of a listing come from
+pub enum ListingSource {
+    /// Paths of a local directory, their metadata is read while streaming
+    Local(Vec<PathBuf>),
+    /// Entries a cloud provider already returned
+    Loaded(Vec<FDir>),
+}
+
+impl ListingSource {
+    fn len(&self) -> usize {
+        match self {
+            ListingSource::Local(paths) => paths.len(),
+            ListingSource::Loaded(entries) => entries.len(),
+        }
+    }
+}
+
+/// The part of a directory a listing streams
+pub struct Page {
+    pub cursor: usize,
+    /// Streams everything from `cursor` on if not set
+    pub limit: Option<usize>,
+    pub chunk_size: usize,
+}
+
+/// Managed state with the running listing of every pane
+#[derive(Default)]
+pub struct Listings {
+    running: Mutex<HashMap<NavKey, (String, Arc<AtomicBool>)>>,
+}
+
+impl Listings {
+    /// Streams the page of `source` to the window in chunks. A listing still
+    /// running for the same pane gets cancelled.
+    pub fn start(
+        &self,
+        key: NavKey,
+        dir: PathBuf,
+        source: ListingSource,
+        page: Page,
+        app_window: Window,
+    ) -> ListingInfo {
+        let listing_id = uuid::Uuid::new_v4().to_string();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        if let Some((_, previous)) = self
+            .running
+            .lock()
+            .unwrap()
+            .insert(key.clone(), (listing_id.clone(), cancelled.clone()))
+        {
+            previous.store(true, Ordering::Relaxed);
+        }
+
+        let info = ListingInfo {
+            listing_id,
+            path: dir.to_string_lossy().to_string().replace("\\", "/"),
+            total: source.len(),
+        };
+        let stream_info = info.clone();
+        std::thread::spawn(move || {
+            stream(
+                &stream_info,
+                &key,
+                &dir,
+                source,
+                page,
+                &app_window,
+                &cancelled,
+            );
+            let listings = app_window.state::<Listings>();
+            let mut running = listings.running.lock().unwrap();
+            if running
+                .get(&key)
+                .is_some_and(|(id, _)| *id == stream_info.listing_id)
+            {
+                running.remove(&key);
+            }
+        });
+        info
+    }
+
+    /// Returns whether there was a listing to cancel
+    pub fn cancel(&self, key: &NavKey) -> bool {
+        match self.running.lock().unwrap().remove(key) {
+            Some((listing_id, cancelled)) => {
+                cancelled.store(true, Ordering::Relaxed);
+                dbg_log(format!("Cancelled listing {}", listing_id));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn forget_window(&self, window: &str) {
+        self.running.lock().unwrap().retain(|key, (_, cancelled)| {
+            if key.window == window {
+                cancelled.store(true, Ordering::Relaxed);
+            }
+            key.window != window
+        });
+    }
+}
+
+/// Reads the names of a directory's entries without their metadata, filtered
+/// and sorted by the spec the same way `list_dirs` does it, so cursors stay
+/// stable between pages. The spec must not need the metadata.
+pub fn read_entry_paths(dir: &Path, spec: &ListingSpec) -> Result<Vec<PathBuf>, String> {
+    let mut entries = vec![];
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let (path, file_type) = match entry.and_then(|e| Ok((e.path(), e.file_type()?))) {
+            Ok(entry) => entry,
+            Err(e) => {
+                wng_log(format!("Skipping entry of {:?}: {}", dir, e));
+                continue;
+            }
+        };
+        // Symlinks to dirs are listed as dirs
+        let is_dir = match file_type.is_symlink() {
+            true => path.is_dir(),
+            false => file_type.is_dir(),
+        };
+        entries.push((path, is_dir));
+    }
+    spec.apply_to_paths(entries)
+}
+
+fn stream(
+    info: &ListingInfo,
+    key: &NavKey,
+    dir: &Path,
+    source: ListingSource,
+    page: Page,
+    app_window: &Window,
+    cancelled: &AtomicBool,
+) {
+    let total = info.total;
+    let start = page.cursor.min(total);
+    let end = page
+        .limit
+        .map_or(total, |limit| start.saturating_add(limit).min(total));
+    // Only a complete listing can serve as the snapshot of the watcher
+    let is_complete = start == 0 && end == total;
+    let mut listed = vec![];
+
+    let mut cursor = start;
+    while cursor < end && !cancelled.load(Ordering::Relaxed) {
+        let chunk_end = (cursor + page.chunk_size).min(end);
+        let entries: Vec<FDir> = match &source {
+            // Entries that vanished since they were counted are left out
+            ListingSource::Local(paths) => paths[cursor..chunk_end]
+                .par_iter()
+                .filter_map(|path| fdir_from_path(path))
+                .collect(),
+            ListingSource::Loaded(entries) => entries[cursor..chunk_end].to_vec(),
+        };
+        if is_complete {
+            listed.extend(entries.iter().cloned());
+        }
+        let _ = app_window.emit(
+            "listing-chunk",
+            ListingChunk {
+                listing_id: info.listing_id.clone(),
+                pane_id: key.pane.clone(),
+                path: info.path.clone(),
+                entries,
+                cursor,
+                next_cursor: (chunk_end < total).then_some(chunk_end),
+                total,
+            },
+        );
+        cursor = chunk_end;
+    }
+
+    let was_cancelled = cancelled.load(Ordering::Relaxed);
+    if is_complete && !was_cancelled {
+        app_window.state::<DirWatchers>().watch(
+            key.clone(),
+            dir.to_path_buf(),
+            app_window,
+            &listed,
+        );
+    }
+    dbg_log(format!(
+        "Listing {} of {:?} streamed {} of {} entries{}",
+        info.listing_id,
+        dir,
+        cursor - start,
+        total,
+        if was_cancelled { ", cancelled" } else { "" }
+    ));
+    let _ = app_window.emit(
+        "listing-done",
+        ListingDone {
+            listing_id: info.listing_id.clone(),
+            pane_id: key.pane.clone(),
+            path: info.path.clone(),
+            total,
+            cancelled: was_cancelled,
+        },
+    );
+}
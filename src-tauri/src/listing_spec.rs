@@ -0,0 +1,458 @@
+use crate::fdir::FDir;
+use chrono::{DateTime, Datelike, Duration, Local, TimeZone};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    /// Case insensitive, numbers inside of names compare by their value
+    #[default]
+    Name,
+    Size,
+    Modified,
+    Extension,
+    /// The kind of the entry, see `kind_of`
+    Type,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "kind", content = "pattern", rename_all = "snake_case")]
+pub enum NameFilter {
+    /// `*`, `?` and `[...]` wildcards, e.g. `*.rs`
+    Glob(String),
+    Regex(String),
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupBy {
+    Type,
+    /// Today, yesterday, this week, this month, this year and older
+    Date,
+    Extension,
+}
+
+/// How a directory gets listed, persisted per directory in the app config
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct ListingSpec {
+    pub sort_by: SortKey,
+    pub direction: SortDirection,
+    pub dirs_first: bool,
+    pub show_hidden: bool,
+    /// Matched case insensitively against the name
+    pub filter: Option<NameFilter>,
+    pub group_by: Option<GroupBy>,
+}
+
+impl Default for ListingSpec {
+    fn default() -> Self {
+        ListingSpec {
+            sort_by: SortKey::default(),
+            direction: SortDirection::default(),
+            dirs_first: true,
+            show_hidden: true,
+            filter: None,
+            group_by: None,
+        }
+    }
+}
+
+impl ListingSpec {
+    /// Filters and sorts the entries and fills in their `group_key`. Entries of
+    /// a group are next to each other, groups come in a fixed order.
+    pub fn apply(&self, entries: Vec<FDir>) -> Result<Vec<FDir>, String> {
+        let filter = self.name_filter()?;
+        let now = Local::now();
+        let mut entries: Vec<FDir> = entries
+            .into_iter()
+            .filter(|entry| self.show_hidden || !entry.is_hidden)
+            .filter(|entry| filter.as_ref().map_or(true, |f| f.is_match(&entry.name)))
+            .map(|mut entry| {
+                entry.group_key = self
+                    .group_by
+                    .map(|group_by| group_of(&entry, group_by, now));
+                entry
+            })
+            .collect();
+
+        entries.sort_by(|a, b| {
+            let group = match self.group_by {
+                Some(GroupBy::Date) => date_rank(&a.group_key).cmp(&date_rank(&b.group_key)),
+                Some(_) => a.group_key.cmp(&b.group_key),
+                None => Ordering::Equal,
+            };
+            let dirs = if self.dirs_first {
+                b.is_dir.cmp(&a.is_dir)
+            } else {
+                Ordering::Equal
+            };
+            let order = self
+                .compare(a, b)
+                .then_with(|| natural_cmp(&a.name, &b.name));
+            let order = match self.direction {
+                SortDirection::Asc => order,
+                SortDirection::Desc => order.reverse(),
+            };
+            group.then(dirs).then(order)
+        });
+        Ok(entries)
+    }
+
+    /// Whether `apply` needs more than the names of the entries and whether
+    /// they are dirs
+    pub fn needs_metadata(&self) -> bool {
+        self.sort_by != SortKey::Name
+            || self.group_by.is_some()
+            // Hidden files on Windows are marked by an attribute
+            || (cfg!(windows) && !self.show_hidden)
+    }
+
+    /// Filters and sorts entries of which only the path and whether they are
+    /// a dir are known, like `apply` does. Only valid if `needs_metadata` is false.
+    pub fn apply_to_paths(&self, entries: Vec<(PathBuf, bool)>) -> Result<Vec<PathBuf>, String> {
+        let filter = self.name_filter()?;
+        let mut entries: Vec<(String, PathBuf, bool)> = entries
+            .into_iter()
+            .map(|(path, is_dir)| {
+                let name = path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string();
+                (name, path, is_dir)
+            })
+            .filter(|(name, _, _)| self.show_hidden || !name.starts_with('.'))
+            .filter(|(name, _, _)| filter.as_ref().map_or(true, |f| f.is_match(name)))
+            .collect();
+
+        entries.sort_by(|(a, _, a_is_dir), (b, _, b_is_dir)| {
+            let dirs = if self.dirs_first {
+                b_is_dir.cmp(a_is_dir)
+            } else {
+                Ordering::Equal
+            };
+            let order = match self.direction {
+                SortDirection::Asc => natural_cmp(a, b),
+                SortDirection::Desc => natural_cmp(a, b).reverse(),
+            };
+            dirs.then(order)
+        });
+        Ok(entries.into_iter().map(|(_, path, _)| path).collect())
+    }
+
+    fn name_filter(&self) -> Result<Option<Regex>, String> {
+        Ok(match &self.filter {
+            Some(NameFilter::Glob(pattern)) => Some(glob_to_regex(pattern)?),
+            Some(NameFilter::Regex(pattern)) => Some(
+                Regex::new(&format!("(?i){}", pattern))
+                    .map_err(|e| format!("Invalid filter: {}", e))?,
+            ),
+            None => None,
+        })
+    }
+
+    fn compare(&self, a: &FDir, b: &FDir) -> Ordering {
+        match self.sort_by {
+            SortKey::Name => natural_cmp(&a.name, &b.name),
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Modified => a.modified.cmp(&b.modified),
+            SortKey::Extension => a.extension.to_lowercase().cmp(&b.extension.to_lowercase()),
+            SortKey::Type => kind_of(a).cmp(&kind_of(b)),
+        }
+    }
+}
+
+/// "folder" for dirs, otherwise the top level MIME type, e.g. "image"
+pub fn kind_of(entry: &FDir) -> String {
+    if entry.is_dir {
+        return "folder".into();
+    }
+    match entry.mime_type.split('/').next() {
+        Some(kind) if !kind.is_empty() => kind.to_string(),
+        _ => "other".into(),
+    }
+}
+
+const DATE_BUCKETS: [&str; 7] = [
+    "today",
+    "yesterday",
+    "this_week",
+    "this_month",
+    "this_year",
+    "older",
+    "unknown",
+];
+
+fn group_of(entry: &FDir, group_by: GroupBy, now: DateTime<Local>) -> String {
+    match group_by {
+        GroupBy::Type => kind_of(entry),
+        // Entries without an extension share the empty group
+        GroupBy::Extension => entry.extension.to_lowercase(),
+        GroupBy::Date => date_bucket(entry.modified, now).into(),
+    }
+}
+
+fn date_bucket(modified: Option<u64>, now: DateTime<Local>) -> &'static str {
+    let modified = match modified.and_then(|ms| Local.timestamp_millis_opt(ms as i64).single()) {
+        Some(modified) => modified,
+        None => return "unknown",
+    };
+    let today = now.date_naive();
+    let day = modified.date_naive();
+    if day >= today {
+        "today"
+    } else if day == today - Duration::days(1) {
+        "yesterday"
+    } else if day > today - Duration::days(7) {
+        "this_week"
+    } else if day.year() == today.year() && day.month() == today.month() {
+        "this_month"
+    } else if day.year() == today.year() {
+        "this_year"
+    } else {
+        "older"
+    }
+}
+
+fn date_rank(group: &Option<String>) -> usize {
+    group
+        .as_deref()
+        .and_then(|group| DATE_BUCKETS.iter().position(|bucket| *bucket == group))
+        .unwrap_or(DATE_BUCKETS.len())
+}
+
+/// Compares case insensitively, runs of digits by their numeric value
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = take_number(&mut a);
+                let y = take_number(&mut b);
+                // Without leading zeros the longer number is the bigger one
+                let order = x
+                    .trim_start_matches('0')
+                    .len()
+                    .cmp(&y.trim_start_matches('0').len())
+                    .then_with(|| x.trim_start_matches('0').cmp(y.trim_start_matches('0')))
+                    .then_with(|| x.len().cmp(&y.len()));
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let order = x.to_lowercase().cmp(y.to_lowercase());
+                if order != Ordering::Equal {
+                    return order;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn take_number(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut number = String::new();
+    while let Some(c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+        number.push(*c);
+        chars.next();
+    }
+    number
+}
+
+pub(crate) fn glob_to_regex(glob: &str) -> Result<Regex, String> {
+    let mut pattern = String::from("(?i)^");
+    let mut in_class = false;
+    for c in glob.chars() {
+        match c {
+            '*' if !in_class => pattern.push_str(".*"),
+            '?' if !in_class => pattern.push('.'),
+            '[' if !in_class => {
+                in_class = true;
+                pattern.push('[');
+            }
+            ']' if in_class => {
+                in_class = false;
+                pattern.push(']');
+            }
+            '!' if in_class && pattern.ends_with('[') && !pattern.ends_with("\\[") => {
+                pattern.push('^')
+            }
+            // Nested classes and set operations in regex, literal in globs
+            '\\' | '^' | '[' | '&' | '~' if in_class => {
+                pattern.push('\\');
+                pattern.push(c);
+            }
+            c if in_class => pattern.push(c),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    if in_class {
+        return Err(format!("Invalid filter: unclosed [ in {}", glob));
+    }
+    pattern.push('$');
+    Regex::new(&pattern).map_err(|e| format!("Invalid filter: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, is_dir: bool, mime_type: &str, modified: Option<u64>) -> FDir {
+        FDir {
+            name: name.into(),
+            is_dir,
+            mime_type: mime_type.into(),
+            modified,
+            ..FDir::default()
+        }
+    }
+
+    fn names(entries: &[FDir]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.name.as_str()).collect()
+    }
+
+    fn local_millis(y: i32, m: u32, d: u32, h: u32, min: u32, s: u32) -> u64 {
+        let time = Local.with_ymd_and_hms(y, m, d, h, min, s).unwrap();
+        time.timestamp_millis() as u64
+    }
+
+    #[test]
+    fn numbers_compare_by_value() {
+        assert_eq!(natural_cmp("a9", "a10"), Ordering::Less);
+        assert_eq!(natural_cmp("file2.txt", "File10.txt"), Ordering::Less);
+        assert_eq!(natural_cmp("a10b", "a10a"), Ordering::Greater);
+        // Leading zeros only break ties
+        assert_eq!(natural_cmp("a007", "a10"), Ordering::Less);
+        assert_eq!(natural_cmp("a01", "a1"), Ordering::Greater);
+        assert_eq!(natural_cmp("a001", "a01"), Ordering::Greater);
+        assert_eq!(natural_cmp("ABC", "abc"), Ordering::Equal);
+        assert_eq!(natural_cmp("ab", "abc"), Ordering::Less);
+    }
+
+    #[test]
+    fn globs_match_whole_names() {
+        let glob = |pattern: &str| glob_to_regex(pattern).unwrap();
+        assert!(glob("*.rs").is_match("Main.RS"));
+        assert!(!glob("*.rs").is_match("main.rs.bak"));
+        assert!(glob("?.txt").is_match("a.txt"));
+        assert!(!glob("?.txt").is_match("ab.txt"));
+        assert!(glob("img[0-9].png").is_match("img7.png"));
+        assert!(glob("[!a]*").is_match("b"));
+        assert!(!glob("[!a]*").is_match("abc"));
+        // Regex syntax outside of classes is taken literally
+        assert!(glob("a+b (1).txt").is_match("a+b (1).txt"));
+        assert!(!glob("a.b").is_match("axb"));
+    }
+
+    #[test]
+    fn wildcards_in_classes_are_literal() {
+        let glob = |pattern: &str| glob_to_regex(pattern).unwrap();
+        assert!(glob("[*]").is_match("*"));
+        assert!(!glob("[*]").is_match("a"));
+        assert!(glob("[?]").is_match("?"));
+        assert!(!glob("[?]").is_match("a"));
+        assert!(glob("a[[]b").is_match("a[b"));
+        assert!(glob("[[!]").is_match("!"));
+        assert!(glob("[&&x]").is_match("&"));
+        assert!(glob("[\\^]").is_match("^"));
+        assert!(glob_to_regex("a[b").is_err());
+    }
+
+    #[test]
+    fn date_buckets_change_at_midnight() {
+        let now = Local.with_ymd_and_hms(2024, 5, 15, 0, 0, 0).unwrap();
+        let bucket = |millis: u64| date_bucket(Some(millis), now);
+        assert_eq!(bucket(local_millis(2024, 5, 15, 0, 0, 0)), "today");
+        assert_eq!(bucket(local_millis(2024, 5, 14, 23, 59, 59)), "yesterday");
+        assert_eq!(bucket(local_millis(2024, 5, 14, 0, 0, 0)), "yesterday");
+        assert_eq!(bucket(local_millis(2024, 5, 13, 23, 59, 59)), "this_week");
+        assert_eq!(bucket(local_millis(2024, 5, 9, 0, 0, 0)), "this_week");
+        assert_eq!(bucket(local_millis(2024, 5, 8, 23, 59, 59)), "this_month");
+        assert_eq!(bucket(local_millis(2024, 4, 30, 23, 59, 59)), "this_year");
+        assert_eq!(bucket(local_millis(2023, 12, 31, 23, 59, 59)), "older");
+        // Clocks that are ahead still count as today
+        assert_eq!(bucket(local_millis(2024, 6, 1, 0, 0, 0)), "today");
+        assert_eq!(date_bucket(None, now), "unknown");
+    }
+
+    #[test]
+    fn dirs_come_first_in_natural_order() {
+        let entries = vec![
+            entry("b10", false, "text/plain", None),
+            entry("B2", false, "text/plain", None),
+            entry("z", true, "", None),
+            entry("a", true, "", None),
+        ];
+        let spec = ListingSpec::default();
+        assert_eq!(
+            names(&spec.apply(entries.clone()).unwrap()),
+            ["a", "z", "B2", "b10"]
+        );
+
+        let spec = ListingSpec {
+            direction: SortDirection::Desc,
+            ..ListingSpec::default()
+        };
+        assert_eq!(
+            names(&spec.apply(entries.clone()).unwrap()),
+            ["z", "a", "b10", "B2"]
+        );
+
+        let spec = ListingSpec {
+            dirs_first: false,
+            ..ListingSpec::default()
+        };
+        assert_eq!(
+            names(&spec.apply(entries).unwrap()),
+            ["a", "B2", "b10", "z"]
+        );
+    }
+
+    #[test]
+    fn groups_keep_their_entries_together() {
+        let now = Local::now().timestamp_millis() as u64;
+        let entries = vec![
+            entry("old.txt", false, "text/plain", Some(0)),
+            entry("new.png", false, "image/png", Some(now)),
+            entry("dir", true, "", None),
+            entry("new.txt", false, "text/plain", Some(now)),
+        ];
+
+        let spec = ListingSpec {
+            group_by: Some(GroupBy::Date),
+            ..ListingSpec::default()
+        };
+        let listed = spec.apply(entries.clone()).unwrap();
+        assert_eq!(names(&listed), ["new.png", "new.txt", "old.txt", "dir"]);
+        assert_eq!(listed[0].group_key.as_deref(), Some("today"));
+        assert_eq!(listed[3].group_key.as_deref(), Some("unknown"));
+
+        let spec = ListingSpec {
+            group_by: Some(GroupBy::Type),
+            sort_by: SortKey::Modified,
+            direction: SortDirection::Desc,
+            ..ListingSpec::default()
+        };
+        let listed = spec.apply(entries).unwrap();
+        assert_eq!(names(&listed), ["dir", "new.png", "new.txt", "old.txt"]);
+        assert_eq!(listed[1].group_key.as_deref(), Some("image"));
+    }
+}
@@ -0,0 +1,211 @@
+use crate::conflicts::{ConflictResolver, Resolution, SetAside};
+use crate::fdir::{fdir_from_path, FDir};
+use crate::jobs::JobHandle;
+use crate::mover::Mover;
+use crate::utils::{copy_to, dbg_log, measure_entries, DirWalkerEntry};
+use crate::vfs::{CloudProvider, FILE_SCHEME};
+use crate::SimpleDirInfo;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Strips the `file://` scheme from a path of the local disk
+pub fn local_path(path: &str) -> PathBuf {
+    PathBuf::from(path.strip_prefix(FILE_SCHEME).unwrap_or(path))
+}
+
+/// The local disk as a provider. Only moves keep their own handling in the
+/// commands, to record them for undo.
+pub struct LocalDisk;
+
+impl CloudProvider for LocalDisk {
+    fn new() -> Self {
+        LocalDisk
+    }
+
+    fn authenticate(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn sign_out(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn read_dir(&mut self, path: &PathBuf) -> Result<Vec<FDir>, String> {
+        let dir = local_path(&path.to_string_lossy());
+        Ok(fs::read_dir(dir)
+            .map_err(|e| e.to_string())?
+            .filter_map(|entry| entry.ok().and_then(|entry| fdir_from_path(&entry.path())))
+            .collect())
+    }
+
+    fn download(&mut self, from_path: &str, to_path: &str) -> Result<String, String> {
+        let from = local_path(from_path);
+        let saved_path = local_path(to_path).join(from.file_name().unwrap_or_default());
+        fs::copy(&from, &saved_path).map_err(|e| e.to_string())?;
+        Ok(saved_path.to_string_lossy().to_string())
+    }
+
+    /// Copies without a job, so an existing target is never replaced
+    fn upload(&mut self, from_path: &str, to_path: &str) -> Result<(), String> {
+        let (from, to) = (local_path(from_path), local_path(to_path));
+        check_target(&from, &to)?;
+        let target = to.join(from.file_name().unwrap_or_default());
+        if fs::symlink_metadata(&target).is_ok() {
+            return Err(format!("{:?} already exists", target));
+        }
+        copy_into(&from, &to)
+    }
+
+    fn create_dir(&mut self, from_path: &str, to_path: &str) -> Result<(), String> {
+        let dir_name = from_path.replace("\\", "/");
+        let dir_name = dir_name.split('/').next_back().unwrap_or_default();
+        fs::create_dir(local_path(to_path).join(dir_name)).map_err(|e| e.to_string())
+    }
+
+    /// Local searches walk the dir itself and stream their results, see `search_for`
+    fn search(&mut self, _fname: &str) -> Result<Vec<DirWalkerEntry>, String> {
+        Err("Local searches go through search_for".into())
+    }
+
+    fn get_item_size(&self, path: &str) -> Result<SimpleDirInfo, String> {
+        let path = local_path(path);
+        if fs::symlink_metadata(&path).is_err() {
+            return Err(format!("{:?} doesn't exist", path));
+        }
+        let (count_elements, size) = measure_entries(&path.to_string_lossy());
+        Ok(SimpleDirInfo {
+            size,
+            count_elements,
+        })
+    }
+
+    fn copy_items(
+        &mut self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        arr_items: Vec<FDir>,
+        copy_to_path: &str,
+    ) -> Result<(), String> {
+        let to = local_path(copy_to_path);
+        for item in &arr_items {
+            let from = local_path(&item.path);
+            check_target(&from, &to)?;
+            let (count, size) = measure_entries(&from.to_string_lossy());
+            job.add_total(count, size);
+        }
+        for item in arr_items {
+            let from = local_path(&item.path);
+            let target = to.join(from.file_name().unwrap_or_default());
+            copy_item(job, resolver, &item, &from, &target)?;
+        }
+        Ok(())
+    }
+
+    fn move_items(
+        &mut self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        arr_items: Vec<FDir>,
+        move_to_path: &str,
+    ) -> Result<(), String> {
+        let to = local_path(move_to_path);
+        for item in &arr_items {
+            let (count, size) = measure_entries(&local_path(&item.path).to_string_lossy());
+            job.add_total(count, size);
+        }
+        let mut mover = Mover::new(job, resolver);
+        for item in arr_items {
+            let from = local_path(&item.path);
+            let target = to.join(from.file_name().unwrap_or_default());
+            if from == target {
+                continue;
+            }
+            mover.move_item(&from, &target)?;
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, path: &str) -> Result<(), String> {
+        remove(&local_path(path))
+    }
+
+    fn rename(&mut self, path: &str, new_name: &str) -> Result<(), String> {
+        let from = local_path(path);
+        let to = from.with_file_name(new_name);
+        dbg_log(format!("Renaming {:?} to {:?}", from, to));
+        fs::rename(from, to).map_err(|e| e.to_string())
+    }
+}
+
+/// Fails if the dir `to` is `from` or inside of it, copying would never end
+fn check_target(from: &Path, to: &Path) -> Result<(), String> {
+    let from = from.canonicalize().map_err(|e| e.to_string())?;
+    let to = to.canonicalize().unwrap_or_else(|_| to.to_path_buf());
+    match to.starts_with(&from) {
+        true => Err(format!("Can't copy {:?} into itself", from)),
+        false => Ok(()),
+    }
+}
+
+fn copy_item(
+    job: &JobHandle,
+    resolver: &mut ConflictResolver,
+    item: &FDir,
+    from: &Path,
+    target: &Path,
+) -> Result<(), String> {
+    // Dirs and files larger than 5 GB are copied in chunks to report progress
+    if cfg!(target_os = "windows") || item.is_dir || item.size > 5000000000 {
+        return copy_to(
+            job,
+            resolver,
+            target.to_string_lossy().to_string(),
+            from.to_string_lossy().to_string(),
+        );
+    }
+    job.checkpoint()?;
+    let mut replaced = SetAside::default();
+    let target = match resolver.resolve(job, from, target)? {
+        Resolution::CopyTo(target) => target,
+        Resolution::Replace(target) => {
+            replaced.set_aside(&target)?;
+            target
+        }
+        Resolution::Skip => {
+            job.add_done(1, item.size);
+            return Ok(());
+        }
+    };
+    job.begin_item(&item.name);
+    // Copying of files is different on macOS
+    let copied = replaced.finish(fs::copy(from, target).map_err(|e| e.to_string()))?;
+    job.add_bytes(copied);
+    job.finish_item();
+    Ok(())
+}
+
+/// Copies a file or a whole dir into the dir `to`
+fn copy_into(from: &Path, to: &Path) -> Result<(), String> {
+    let target = to.join(from.file_name().unwrap_or_default());
+    if !from.is_dir() {
+        fs::create_dir_all(to).map_err(|e| e.to_string())?;
+        return fs::copy(from, &target)
+            .map(|_| ())
+            .map_err(|e| e.to_string());
+    }
+    fs::create_dir_all(&target).map_err(|e| e.to_string())?;
+    for entry in fs::read_dir(from).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        copy_into(&entry.path(), &target)?;
+    }
+    Ok(())
+}
+
+fn remove(path: &Path) -> Result<(), String> {
+    let metadata = fs::symlink_metadata(path).map_err(|e| e.to_string())?;
+    if metadata.is_dir() {
+        fs::remove_dir_all(path).map_err(|e| e.to_string())
+    } else {
+        fs::remove_file(path).map_err(|e| e.to_string())
+    }
+}
@@ -1,48 +1,38 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
-use chrono::prelude::{DateTime, Utc};
+use chrono::DateTime;
 #[allow(unused)]
 use delete::{delete_file, rapid_delete_dir_all};
-use flate2::read::GzDecoder;
 #[cfg(target_os = "macos")]
 use icns::{IconFamily, IconType};
+#[cfg(target_os = "windows")]
 use remove_dir_all::remove_dir_all;
 use rusty_ytdl::{Video, VideoOptions, VideoQuality, VideoSearchOptions};
 use serde::Serialize;
-use serde_json::Value;
-use std::fs::{self, read_dir, remove_dir, OpenOptions};
+use serde_json::{json, Value};
+use std::fs::{self, read_dir, OpenOptions};
 #[allow(unused)]
 use std::io::Error;
 #[allow(unused)]
 use std::io::{BufReader, BufWriter, Read, Write};
-use std::process::{Command, Stdio};
-use std::sync::LazyLock;
+use std::process::Command;
 use std::{
     env::current_dir,
-    fs::{copy, create_dir, remove_file, File},
-    path::PathBuf,
+    fs::{create_dir, remove_file, File},
+    path::{Path, PathBuf},
+    sync::Arc,
 };
 use stopwatch::Stopwatch;
-use tauri::{
-    api::path::{
-        app_config_dir, audio_dir, config_dir, desktop_dir, document_dir, download_dir, home_dir,
-        picture_dir, video_dir,
-    },
-    Config,
+use tauri::api::path::{
+    audio_dir, config_dir, desktop_dir, document_dir, download_dir, home_dir, picture_dir,
+    video_dir,
 };
 #[allow(unused)]
-use tauri::{Manager, Window, WindowEvent};
-use unrar::Archive;
-use zip::write::FileOptions;
-use zip_extensions::*;
+use tauri::{Manager, State, Window, WindowEvent};
 mod utils;
 use rayon::prelude::*;
 use sysinfo::Disks;
-use utils::{
-    calc_transfer_speed, copy_to, count_entries, create_new_action, dbg_log, err_log, format_bytes,
-    remove_action, unpack_tar, update_progressbar, update_progressbar_2, wng_log, DirWalker,
-    DirWalkerEntry, COPY_COUNTER, TO_COPY_COUNTER,
-};
+use utils::{dbg_log, err_log, format_bytes, measure_entries, wng_log, DirWalker, DirWalkerEntry};
 #[cfg(target_os = "macos")]
 mod window_tauri_ext;
 #[cfg(target_os = "macos")]
@@ -50,22 +40,56 @@ use window_tauri_ext::WindowExt;
 mod applications;
 #[allow(unused)]
 use applications::{get_apps, open_file_with};
-use archiver_rs::Compressed;
 mod rdpfs;
-use crate::gdrive::{CloudProvider, GDrive};
 use substring::Substring;
-use tauri::async_runtime::Mutex;
-use tokio::sync::OnceCell;
 
+mod archive;
+mod archive_fs;
+mod compress;
+mod config;
+mod conflicts;
+mod content_search;
+mod fdir;
+mod fuzzy;
 mod gdrive;
-
-static mut ISCANCELED: bool = false;
-
-static mut PATH_HISTORY: Vec<String> = vec![];
-
-static CURRENT_DIR: LazyLock<Mutex<PathBuf>> = LazyLock::new(|| Mutex::new(current_dir().unwrap()));
-
-static GDRIVE: OnceCell<Mutex<GDrive>> = OnceCell::const_new();
+mod jobs;
+mod journal;
+mod listing;
+mod listing_spec;
+mod local;
+mod mover;
+mod navigation;
+mod s3;
+mod saved_search;
+mod search_index;
+mod search_query;
+mod sftp;
+mod trash;
+mod vfs;
+mod watcher;
+mod webdav;
+use archive::{extract_archive, ArchiveKind, ExtractOptions};
+use compress::CompressOptions;
+use config::{AppConfig, ViewMode};
+use conflicts::{unique_filename, ConflictAnswer, ConflictPolicy, ConflictResolver};
+use content_search::{ContentMatch, ContentOptions, ContentSearcher};
+use fdir::FDir;
+use fuzzy::rank;
+use jobs::{JobHandle, JobInfo, JobManager};
+use journal::{Fingerprint, Journal, JournalEntry, Operation, PathChange};
+use listing::{ListingInfo, ListingSource, Listings, Page, DEFAULT_CHUNK_SIZE};
+use listing_spec::ListingSpec;
+use mover::Mover;
+use navigation::{NavKey, Navigation};
+use s3::{S3Account, MAX_LINK_EXPIRY, S3_PREFIX};
+use saved_search::{SavedSearch, SearchScope, SEARCH_SCHEME};
+use search_index::{IndexRoot, SearchIndex};
+use search_query::{Candidate, Query};
+use sftp::{SftpProfile, SFTP_PREFIX};
+use trash::TrashItem;
+use vfs::{CloudProvider, VfsRegistry};
+use watcher::DirWatchers;
+use webdav::{WebDavAccount, WEBDAV_PREFIX};
 
 // #[cfg(target_os = "windows")]
 // const SLASH: &str = "\\";
@@ -79,6 +103,13 @@ const ASSET_LOCATION: &str = "asset://localhost/";
 
 fn main() {
     tauri::Builder::default()
+        .manage(Navigation::default())
+        .manage(JobManager::default())
+        .manage(Journal::default())
+        .manage(DirWatchers::default())
+        .manage(Listings::default())
+        .manage(VfsRegistry::default())
+        .manage(SearchIndex::default())
         .setup(|app| {
             let win = app.get_window("main").unwrap();
             #[cfg(target_os = "macos")]
@@ -102,19 +133,34 @@ fn main() {
             if let WindowEvent::Resized(..) = e.event() {
                 std::thread::sleep(std::time::Duration::from_millis(1));
             }
+            if let WindowEvent::Destroyed = e.event() {
+                let app = e.window().app_handle();
+                let label = e.window().label().to_string();
+                app.state::<DirWatchers>().forget_window(&label);
+                app.state::<Listings>().forget_window(&label);
+                tauri::async_runtime::spawn(async move {
+                    app.state::<Navigation>().forget_window(&label).await;
+                });
+            }
         })
         .invoke_handler(tauri::generate_handler![
             list_dirs,
+            list_dirs_stream,
+            cancel_listing,
+            get_listing_spec,
+            reset_listing_spec,
             open_dir,
             open_item,
             go_back,
+            go_forward,
             go_home,
             search_for,
             go_to_dir,
             copy_paste,
             delete_item,
+            is_archive,
             extract_item,
-            compress_item,
+            create_archive,
             create_folder,
             switch_view,
             check_app_config,
@@ -124,18 +170,37 @@ fn main() {
             list_disks,
             open_in_terminal,
             rename_element,
-            save_config,
+            update_config,
             switch_to_directory,
-            mount_sshfs,
+            connect_sftp,
+            disconnect_sftp,
+            remove_sftp_profile,
+            add_webdav_account,
+            remove_webdav_account,
+            add_s3_account,
+            remove_s3_account,
+            save_search,
+            remove_saved_search,
+            get_share_link,
             rename_elements_with_format,
             add_favorite,
             arr_copy_paste,
+            move_items,
             arr_delete_items,
-            arr_compress_items,
+            delete_permanently,
+            list_trash,
+            restore_from_trash,
+            empty_trash,
+            undo_last,
+            redo,
             get_installed_apps,
             open_with,
             find_duplicates,
-            cancel_operation,
+            list_jobs,
+            cancel_job,
+            pause_job,
+            resume_job,
+            resolve_conflict,
             get_df_dir,
             download_yt_video,
             get_app_icns,
@@ -143,12 +208,15 @@ fn main() {
             get_simple_dir_info,
             get_themes,
             stop_searching,
+            add_index_root,
+            remove_index_root,
+            get_index_status,
             get_file_content,
+            get_preview_path,
             open_config_location,
             log,
             get_config_location,
-            get_sshfs_mounts,
-            unmount_network_drive,
+            get_sftp_connections,
             is_gdrive_authenticated,
             logout_gdrive,
         ])
@@ -157,16 +225,6 @@ fn main() {
         .expect("error while running tauri application");
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
-struct FDir {
-    name: String,
-    is_dir: i8,
-    path: String,
-    extension: String,
-    size: String,
-    last_modified: String,
-}
-
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 struct Theme {
     name: String,
@@ -182,154 +240,27 @@ struct Theme {
     nav_bar_color: String,
 }
 
-#[derive(serde::Serialize)]
-struct AppConfig {
-    view_mode: String,
-    last_modified: String,
-    configured_path_one: String,
-    configured_path_two: String,
-    configured_path_three: String,
-    is_open_in_terminal: String,
-    is_dual_pane_enabled: String,
-    launch_path: String,
-    is_dual_pane_active: String,
-    search_depth: i32,
-    max_items: i32,
-    is_image_preview: String,
-    is_select_mode: String,
-    arr_favorites: Vec<String>,
-    current_theme: String,
-    gdrive_enabled: String,
-}
-
 #[tauri::command]
 async fn check_app_config() -> AppConfig {
     // Create general config directory for the app
-    create_folder(
-        config_dir()
-            .unwrap()
-            .join("com.codriver.dev")
-            .to_str()
-            .unwrap()
-            .to_string(),
-    )
-    .await;
+    let _ = fs::create_dir_all(config_dir().unwrap().join("com.codriver.dev"));
     // Create directory for saving app thumnails on a mac
-    create_folder(
+    let _ = fs::create_dir_all(
         config_dir()
             .unwrap()
             .join("com.codriver.dev")
-            .join("App-Thumbnails")
-            .to_str()
-            .unwrap()
-            .to_string(),
-    )
-    .await;
+            .join("App-Thumbnails"),
+    );
     // Create a directory for themes
-    create_folder(
+    let _ = fs::create_dir_all(
         config_dir()
             .unwrap()
             .join("com.codriver.dev")
-            .join("Themes")
-            .to_str()
-            .unwrap()
-            .to_string(),
-    )
-    .await;
-
-    // If config doesn't exist, create it
-    if fs::metadata(
-        config_dir()
-            .unwrap()
-            .join("com.codriver.dev/app_config.json"),
-    )
-    .is_err()
-    {
-        let _ = File::create(
-            config_dir()
-                .unwrap()
-                .join("com.codriver.dev/app_config.json"),
-        );
-        let app_config_json = AppConfig {
-            view_mode: "".to_string(),
-            last_modified: chrono::offset::Local::now().to_string(),
-            configured_path_one: "".to_string(),
-            configured_path_two: "".to_string(),
-            configured_path_three: "".to_string(),
-            is_open_in_terminal: "0".to_string(),
-            is_dual_pane_enabled: "0".to_string(),
-            launch_path: "".to_string(),
-            is_dual_pane_active: "0".to_string(),
-            search_depth: 10,
-            max_items: 1000,
-            is_image_preview: "1".to_string(),
-            is_select_mode: "1".to_string(),
-            arr_favorites: vec![],
-            current_theme: "0".to_string(),
-            gdrive_enabled: "0".to_string(),
-        };
-        let _ = serde_json::to_writer_pretty(
-            File::create(
-                config_dir()
-                    .unwrap()
-                    .join("com.codriver.dev/app_config.json")
-                    .to_str()
-                    .unwrap()
-                    .to_string(),
-            )
-            .unwrap(),
-            &app_config_json,
-        );
-    }
+            .join("Themes"),
+    );
 
-    let app_config_file = File::open(
-        config_dir()
-            .unwrap()
-            .join("com.codriver.dev/app_config.json"),
-    )
-    .unwrap();
-    let app_config_reader = BufReader::new(app_config_file);
-    let app_config: Value = serde_json::from_reader(app_config_reader).unwrap();
-
-    let default_vec: Vec<Value> = vec![];
-    return AppConfig {
-        view_mode: app_config["view_mode"].to_string().replace('"', ""),
-        last_modified: app_config["last_modified"].to_string().replace('"', ""),
-        configured_path_one: app_config["configured_path_one"]
-            .to_string()
-            .replace('"', ""),
-        configured_path_two: app_config["configured_path_two"]
-            .to_string()
-            .replace('"', ""),
-        configured_path_three: app_config["configured_path_three"]
-            .to_string()
-            .replace('"', ""),
-        is_open_in_terminal: app_config["is_open_in_terminal"]
-            .to_string()
-            .replace('"', ""),
-        is_dual_pane_enabled: app_config["is_dual_pane_enabled"]
-            .to_string()
-            .replace('"', ""),
-        launch_path: app_config["launch_path"].to_string().replace('"', ""),
-        is_dual_pane_active: app_config["is_dual_pane_active"]
-            .to_string()
-            .replace('"', ""),
-        search_depth: app_config["search_depth"]
-            .to_string()
-            .parse::<i32>()
-            .unwrap(),
-        max_items: app_config["max_items"].to_string().parse::<i32>().unwrap(),
-        is_image_preview: app_config["is_image_preview"].to_string().replace('"', ""),
-        is_select_mode: app_config["is_select_mode"].to_string().replace('"', ""),
-        arr_favorites: app_config["arr_favorites"]
-            .as_array()
-            .unwrap_or_else(|| &default_vec)
-            .iter()
-            .map(|x| x.to_string().replace('"', ""))
-            .collect(),
-        current_theme: app_config["current_theme"].to_string().replace('"', ""),
-        gdrive_enabled: app_config["gdrive_enabled"].to_string().replace('"', ""),
-    };
+    // Creates the config if it doesn't exist and migrates older ones
+    config::load()
 }
 
 #[tauri::command]
@@ -397,268 +328,451 @@ async fn list_disks() -> Vec<DisksInfo> {
         });
     }
 
-    let ls_sshfs_mounts = fs::read_dir("/tmp/codriver-sshfs-mount");
-    if ls_sshfs_mounts.is_ok() {
-        let ls_sshfs_mounts = ls_sshfs_mounts.unwrap();
-        for mount in ls_sshfs_mounts {
-            let mount = mount.unwrap();
-            dbg_log(format!("{:?} | {:?}", mount.file_name(), mount.path()));
-            ls_disks.push(DisksInfo {
-                name: format!("{:?}", mount.file_name())
-                    .split("/")
-                    .last()
-                    .unwrap_or("/")
-                    .to_string()
-                    .replace("\"", ""),
-                dev: format!("{:?}", mount.file_name()),
-                format: "SSHFS Network-Drive".into(),
-                path: format!("{:?}", mount.path()),
-                avail: format!("{:?}", mount.metadata().unwrap().len()),
-                capacity: format!("{:?}", mount.metadata().unwrap().len()),
-                is_removable: true,
-            });
-        }
-    }
     return ls_disks;
 }
 
+/// Open SFTP connections, shown next to the disks
 #[tauri::command]
-async fn get_sshfs_mounts() -> Vec<DisksInfo> {
-    let ls_sshfs_mounts = fs::read_dir("/tmp/codriver-sshfs-mount");
-    if ls_sshfs_mounts.is_ok() {
-        let ls_sshfs_mounts = ls_sshfs_mounts.unwrap();
-        let mut ls_disks: Vec<DisksInfo> = vec![];
-        for mount in ls_sshfs_mounts {
-            let mount = mount.unwrap();
-            ls_disks.push(DisksInfo {
-                name: format!("{:?}", mount.file_name())
-                    .split("/")
-                    .last()
-                    .unwrap_or("/")
-                    .to_string()
-                    .replace("\"", ""),
-                dev: format!("{:?}", mount.file_name()),
-                format: "SSHFS Network-Drive".into(),
-                path: mount.path().as_path().to_string_lossy().to_string(),
-                avail: format!("{:?}", mount.metadata().unwrap().len()),
-                capacity: format!("{:?}", mount.metadata().unwrap().len()),
-                is_removable: true,
-            });
-        }
-        return ls_disks;
-    }
-    return vec![];
+async fn get_sftp_connections(vfs: State<'_, VfsRegistry>) -> Result<Vec<DisksInfo>, String> {
+    let sftp = vfs.sftp();
+    let connections = sftp
+        .lock()
+        .await
+        .connections()
+        .into_iter()
+        .map(|address| DisksInfo {
+            name: address.clone(),
+            dev: address.clone(),
+            format: "SFTP".into(),
+            path: format!("{}{}/", SFTP_PREFIX, address),
+            avail: "".into(),
+            capacity: "".into(),
+            is_removable: true,
+        })
+        .collect();
+    Ok(connections)
 }
 
 #[tauri::command]
-async fn switch_to_directory(current_dir: String) {
+async fn switch_to_directory(
+    current_dir: String,
+    pane_id: Option<String>,
+    app_window: Window,
+    nav: State<'_, Navigation>,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<(), String> {
     dbg_log(format!("Switching to directory: {}", &current_dir));
-    let _ = set_dir(current_dir.into()).await;
+    let _ = set_dir(current_dir, pane_id, app_window, nav, vfs).await?;
+    Ok(())
 }
 #[tauri::command]
-async fn switch_view(view_mode: String) -> Result<Vec<FDir>, String> {
-    let app_config_file = File::open(
-        app_config_dir(&Config::default())
-            .unwrap()
-            .join("com.codriver.dev/app_config.json"),
-    )
-    .unwrap();
-    let app_config_reader = BufReader::new(app_config_file);
-    let mut app_config: Value = serde_json::from_reader(app_config_reader).unwrap();
-    app_config["view_mode"] = Value::from(String::from(&view_mode));
-    let _ = serde_json::to_writer_pretty(
-        File::create(
-            app_config_dir(&Config::default())
-                .unwrap()
-                .join("com.codriver.dev/app_config.json")
-                .to_str()
-                .unwrap()
-                .to_string(),
-        )
-        .unwrap(),
-        &app_config,
-    );
-    dbg_log(format!("View-style switched to: {}", view_mode));
-    return list_dirs().await;
+async fn switch_view(
+    view_mode: ViewMode,
+    pane_id: Option<String>,
+    app_window: Window,
+    nav: State<'_, Navigation>,
+    watchers: State<'_, DirWatchers>,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<Vec<FDir>, String> {
+    update_config(json!({ "view_mode": view_mode }), app_window.clone()).await?;
+    dbg_log(format!("View-style switched to: {:?}", view_mode));
+    return list_dirs(pane_id, None, app_window, nav, watchers, vfs).await;
 }
 
 #[tauri::command]
-async fn get_current_dir() -> String {
-    return CURRENT_DIR
-        .lock()
+async fn get_current_dir(
+    pane_id: Option<String>,
+    app_window: Window,
+    nav: State<'_, Navigation>,
+) -> Result<String, String> {
+    let key = NavKey::new(&app_window, pane_id);
+    Ok(nav
+        .cwd(&key)
         .await
-        .as_path()
-        .to_str()
-        .unwrap()
+        .to_string_lossy()
         .to_string()
-        .replace("\\", "/");
+        .replace("\\", "/"))
 }
 
 #[tauri::command]
-async fn set_dir(current_dir: String) -> bool {
+async fn set_dir(
+    current_dir: String,
+    pane_id: Option<String>,
+    app_window: Window,
+    nav: State<'_, Navigation>,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<bool, String> {
     dbg_log(format!("Current dir: {}", &current_dir));
 
-    if !current_dir.starts_with("gdrive:") {
+    if vfs.is_local(&current_dir) {
         if fs::metadata(&current_dir).is_err() {
-            return false;
+            return Ok(false);
         }
     }
 
-    let mut current_dir_buf = CURRENT_DIR.lock().await;
-    current_dir_buf.clear();
-    current_dir_buf.push(current_dir);
-    return true;
+    let key = NavKey::new(&app_window, pane_id);
+    nav.with(&key, |state| state.cwd = PathBuf::from(current_dir))
+        .await;
+    return Ok(true);
 }
 
+/// Lists the current dir with the given spec, which is then stored for that
+/// dir. Without a spec the stored one is used.
 #[tauri::command]
-async fn list_dirs() -> Result<Vec<FDir>, String> {
-    let current_dir = CURRENT_DIR.lock().await.clone();
+async fn list_dirs(
+    pane_id: Option<String>,
+    spec: Option<ListingSpec>,
+    app_window: Window,
+    nav: State<'_, Navigation>,
+    watchers: State<'_, DirWatchers>,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<Vec<FDir>, String> {
+    let key = NavKey::new(&app_window, pane_id);
+    let current_dir = nav.cwd(&key).await;
+    let spec = match spec {
+        Some(spec) => {
+            if spec != config::listing_spec_for(&current_dir) {
+                let config = config::set_listing_spec(&current_dir, Some(spec.clone()))?;
+                let _ = app_window.emit_all("config-changed", config);
+            }
+            spec
+        }
+        None => config::listing_spec_for(&current_dir),
+    };
+    let dir_list = spec.apply(read_raw_directory(&vfs, current_dir.clone()).await?)?;
+    // Replaces the watcher of the directory the pane was showing before
+    watchers.watch(key, current_dir, &app_window, &dir_list);
+    Ok(dir_list)
+}
 
-    if current_dir.starts_with("gdrive:") {
-        let mut gdrive = get_gdrive().await?.lock().await;
-        return tauri::async_runtime::spawn_blocking(move || gdrive.read_dir(&current_dir))
-            .await
-            .map_err(|e| e.to_string())?;
+#[tauri::command]
+async fn get_listing_spec(
+    pane_id: Option<String>,
+    app_window: Window,
+    nav: State<'_, Navigation>,
+) -> Result<ListingSpec, String> {
+    let current_dir = nav.cwd(&NavKey::new(&app_window, pane_id)).await;
+    Ok(config::listing_spec_for(&current_dir))
+}
+
+/// Goes back to the default spec for the current dir
+#[tauri::command]
+async fn reset_listing_spec(
+    pane_id: Option<String>,
+    app_window: Window,
+    nav: State<'_, Navigation>,
+    watchers: State<'_, DirWatchers>,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<Vec<FDir>, String> {
+    let current_dir = nav.cwd(&NavKey::new(&app_window, pane_id.clone())).await;
+    let config = config::set_listing_spec(&current_dir, None)?;
+    let _ = app_window.emit_all("config-changed", config);
+    list_dirs(pane_id, None, app_window, nav, watchers, vfs).await
+}
+
+/// Counts the entries of the current dir and streams them to the window in
+/// `listing-chunk` events, followed by a `listing-done` event
+#[tauri::command]
+async fn list_dirs_stream(
+    pane_id: Option<String>,
+    cursor: Option<usize>,
+    limit: Option<usize>,
+    chunk_size: Option<usize>,
+    app_window: Window,
+    nav: State<'_, Navigation>,
+    listings: State<'_, Listings>,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<ListingInfo, String> {
+    let key = NavKey::new(&app_window, pane_id);
+    let current_dir = nav.cwd(&key).await;
+    let spec = config::listing_spec_for(&current_dir);
+    // Sorting by metadata needs all of it before the first chunk
+    let source = if !vfs.is_local(&current_dir.to_string_lossy()) || spec.needs_metadata() {
+        ListingSource::Loaded(read_directory(&vfs, current_dir.clone()).await?)
+    } else {
+        let dir = current_dir.clone();
+        let paths =
+            tauri::async_runtime::spawn_blocking(move || listing::read_entry_paths(&dir, &spec))
+                .await
+                .map_err(|e| e.to_string())??;
+        ListingSource::Local(paths)
+    };
+    let page = Page {
+        cursor: cursor.unwrap_or(0),
+        limit,
+        chunk_size: chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE).max(1),
     };
+    Ok(listings.start(key, current_dir, source, page, app_window))
+}
 
-    let mut dir_list: Vec<FDir> = Vec::new();
-    let current_dir = fs::read_dir(CURRENT_DIR.lock().await.as_path()).unwrap();
-    for item in current_dir {
-        let temp_item = item.unwrap();
-        let name = &temp_item.file_name().into_string().unwrap();
-        let path = &temp_item
-            .path()
-            .to_str()
-            .unwrap()
-            .to_string()
-            .replace("\\", "/");
-        let file_ext = ".".to_string().to_owned()
-            + &path
-                .split(".")
-                .nth(&path.split(".").count() - 1)
-                .unwrap_or("");
-        let file_data = fs::metadata(&temp_item.path());
-        let file_date: DateTime<Utc>;
-        let size = temp_item.metadata().unwrap().len();
-        if file_data.is_ok() {
-            file_date = file_data.unwrap().modified().unwrap().clone().into();
-        } else {
-            file_date = Utc::now();
-        }
-        let is_dir_int = match temp_item.path().is_dir() {
-            true => 1,
-            false => 0,
-        };
-        dir_list.push(FDir {
-            name: String::from(name),
-            is_dir: is_dir_int,
-            path: String::from(path),
-            extension: file_ext,
-            size: size.to_string(),
-            last_modified: String::from(file_date.to_string().split(".").nth(0).unwrap()),
-        });
-    }
-    dir_list.sort_by_key(|a| a.name.to_lowercase());
-    return Ok(dir_list);
+#[tauri::command]
+async fn cancel_listing(
+    pane_id: Option<String>,
+    app_window: Window,
+    listings: State<'_, Listings>,
+) -> Result<bool, String> {
+    Ok(listings.cancel(&NavKey::new(&app_window, pane_id)))
+}
+
+/// Lists a dir of any provider with the spec stored for it
+async fn read_directory(vfs: &VfsRegistry, current_dir: PathBuf) -> Result<Vec<FDir>, String> {
+    let spec = config::listing_spec_for(&current_dir);
+    spec.apply(read_raw_directory(vfs, current_dir).await?)
+}
+
+async fn read_raw_directory(vfs: &VfsRegistry, current_dir: PathBuf) -> Result<Vec<FDir>, String> {
+    let path = current_dir.to_string_lossy().to_string();
+    vfs.run(&path, move |provider| provider.read_dir(&current_dir))
+        .await
 }
 
 #[tauri::command]
-async fn open_dir(path: String) -> bool {
+async fn open_dir(
+    path: String,
+    pane_id: Option<String>,
+    app_window: Window,
+    nav: State<'_, Navigation>,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<bool, String> {
     dbg_log(format!("Opening dir: {}", &path));
 
-    if !path.starts_with("gdrive:") {
+    if vfs.is_local(&path) {
         if read_dir(&path).is_err() {
-            return false;
+            return Ok(false);
         }
+    } else if vfs.scheme_of(&path) == SEARCH_SCHEME && saved_search::saved_search(&path).is_err() {
+        return Ok(false);
     }
 
-    let _ = set_dir(path.clone().into()).await;
-    unsafe {
-        PATH_HISTORY.push(path);
-    }
-    return true;
+    let key = NavKey::new(&app_window, pane_id);
+    nav.with(&key, |state| state.navigate(PathBuf::from(path)))
+        .await;
+    return Ok(true);
 }
 
 #[tauri::command]
-async fn go_back(is_dual_pane: bool) {
-    unsafe {
-        if PATH_HISTORY.len() > 1 && !is_dual_pane {
-            let last_path = &PATH_HISTORY[PATH_HISTORY.len() - 2];
-            dbg_log(format!("Went back to: {}", last_path));
-            let _ = set_dir(last_path.into()).await;
-            PATH_HISTORY.pop();
-        } else {
-            let _ = set_dir("./../".into()).await;
-        }
-    }
+async fn go_back(
+    pane_id: Option<String>,
+    app_window: Window,
+    nav: State<'_, Navigation>,
+) -> Result<bool, String> {
+    let key = NavKey::new(&app_window, pane_id);
+    let went_back = nav.with(&key, |state| state.go_back()).await;
+    if went_back {
+        dbg_log(format!("Went back to: {:?}", nav.cwd(&key).await));
+    }
+    Ok(went_back)
 }
 
 #[tauri::command]
-async fn go_to_dir(directory: u8) -> Result<Vec<FDir>, String> {
+async fn go_forward(
+    pane_id: Option<String>,
+    app_window: Window,
+    nav: State<'_, Navigation>,
+) -> Result<bool, String> {
+    let key = NavKey::new(&app_window, pane_id);
+    let went_forward = nav.with(&key, |state| state.go_forward()).await;
+    if went_forward {
+        dbg_log(format!("Went forward to: {:?}", nav.cwd(&key).await));
+    }
+    Ok(went_forward)
+}
+
+#[tauri::command]
+async fn go_to_dir(
+    directory: u8,
+    pane_id: Option<String>,
+    app_window: Window,
+    nav: State<'_, Navigation>,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<Vec<FDir>, String> {
+    let key = NavKey::new(&app_window, pane_id);
     let wanted_directory = match directory {
-        0 => set_dir(desktop_dir().unwrap_or_default().to_str().unwrap().into()).await,
-        1 => set_dir(download_dir().unwrap_or_default().to_str().unwrap().into()).await,
-        2 => set_dir(document_dir().unwrap_or_default().to_str().unwrap().into()).await,
-        3 => set_dir(picture_dir().unwrap_or_default().to_str().unwrap().into()).await,
-        4 => set_dir(video_dir().unwrap_or_default().to_str().unwrap().into()).await,
-        5 => set_dir(audio_dir().unwrap_or_default().to_str().unwrap().into()).await,
-        _ => set_dir(CURRENT_DIR.lock().await.to_str().unwrap().into()).await,
+        0 => desktop_dir(),
+        1 => download_dir(),
+        2 => document_dir(),
+        3 => picture_dir(),
+        4 => video_dir(),
+        5 => audio_dir(),
+        _ => None,
     };
-    if !wanted_directory {
-        err_log("Not a valid directory".into());
-    } else {
-        unsafe {
-            PATH_HISTORY.push(CURRENT_DIR.lock().await.to_string_lossy().to_string());
+    match wanted_directory {
+        Some(path) if fs::metadata(&path).is_ok() => {
+            nav.with(&key, |state| state.navigate(path)).await;
         }
+        _ => err_log("Not a valid directory".into()),
     }
-    return list_dirs().await;
+    read_directory(&vfs, nav.cwd(&key).await).await
 }
 
 // :ftp
+/// Connects to an SFTP server and returns the path of its remote dir
 #[tauri::command]
-async fn mount_sshfs(
-    hostname: String,
-    username: String,
-    password: String,
-    remote_path: String,
-) -> String {
-    let remote_address = format!("{}@{}:{}", username, hostname, remote_path);
-
-    let mount_point = "/tmp/codriver-sshfs-mount/".to_owned() + &username;
-
-    // Ensure the local mount point exists
-    std::fs::create_dir_all(&mount_point).expect("Failed to create mount point directory");
-
-    // Start sshfs process
-    let mut child = Command::new("sshfs")
-        .arg(&remote_address)
-        .arg(&mount_point)
-        .arg("-o")
-        .arg("password_stdin")
-        .stdin(Stdio::piped())
-        .spawn()
-        .expect("Failed to start sshfs process");
+async fn connect_sftp(
+    profile: SftpProfile,
+    password: Option<String>,
+    save_profile: Option<bool>,
+    app_window: Window,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<String, String> {
+    if save_profile.unwrap_or(false) {
+        let config = config::save_sftp_profile(profile.clone())?;
+        vfs.sftp()
+            .lock()
+            .await
+            .set_profiles(config.sftp_profiles.clone());
+        let _ = app_window.emit_all("config-changed", config);
+    }
+    let mut sftp = vfs.sftp().lock_owned().await;
+    tauri::async_runtime::spawn_blocking(move || sftp.connect(&profile, password))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn disconnect_sftp(path: String, vfs: State<'_, VfsRegistry>) -> Result<(), String> {
+    vfs.sftp().lock().await.disconnect(&path)
+}
 
-    // Write the password to stdin of the sshfs process
-    dbg_log(format!("Connecting to {}", remote_address));
-    let stdin = child.stdin.as_mut().expect("Failed to open stdin");
-    stdin
-        .write_all(password.as_bytes())
-        .expect("Failed to write to stdin");
+#[tauri::command]
+async fn remove_sftp_profile(
+    name: String,
+    app_window: Window,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<(), String> {
+    let config = config::remove_sftp_profile(&name)?;
+    vfs.sftp()
+        .lock()
+        .await
+        .set_profiles(config.sftp_profiles.clone());
+    let _ = app_window.emit_all("config-changed", config);
+    Ok(())
+}
 
-    let output = child
-        .wait_with_output()
-        .expect("Failed to read sshfs output");
+/// Checks the credentials against the server, saves the account and returns
+/// the path of its root
+#[tauri::command]
+async fn add_webdav_account(
+    account: WebDavAccount,
+    app_window: Window,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<String, String> {
+    if account.name.is_empty() || account.name.contains('/') {
+        return Err("The name of the account can't be empty or contain a /".into());
+    }
+    let webdav = vfs.webdav().lock_owned().await;
+    let checked = account.clone();
+    tauri::async_runtime::spawn_blocking(move || webdav.check(&checked))
+        .await
+        .map_err(|e| e.to_string())??;
 
-    if output.status.success() {
-        dbg_log(format!("Mounted {} to {}", remote_address, mount_point));
-    } else {
-        wng_log(format!(
-            "Failed to mount: {}",
-            String::from_utf8_lossy(&output.stderr),
-        ));
+    let path = format!("{}{}/", WEBDAV_PREFIX, account.name);
+    let config = config::save_webdav_account(account)?;
+    vfs.webdav()
+        .lock()
+        .await
+        .set_accounts(config.webdav_accounts.clone());
+    let _ = app_window.emit_all("config-changed", config);
+    Ok(path)
+}
+
+#[tauri::command]
+async fn remove_webdav_account(
+    name: String,
+    app_window: Window,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<(), String> {
+    let config = config::remove_webdav_account(&name)?;
+    vfs.webdav()
+        .lock()
+        .await
+        .set_accounts(config.webdav_accounts.clone());
+    let _ = app_window.emit_all("config-changed", config);
+    Ok(())
+}
+
+/// Checks the keys against the service, saves the account and returns the
+/// path of its root
+#[tauri::command]
+async fn add_s3_account(
+    account: S3Account,
+    app_window: Window,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<String, String> {
+    if account.name.is_empty() || account.name.contains('/') {
+        return Err("The name of the account can't be empty or contain a /".into());
+    }
+    let s3 = vfs.s3().lock_owned().await;
+    let checked = account.clone();
+    tauri::async_runtime::spawn_blocking(move || s3.check(&checked))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    let path = format!("{}{}/", S3_PREFIX, account.name);
+    let config = config::save_s3_account(account)?;
+    vfs.s3()
+        .lock()
+        .await
+        .set_accounts(config.s3_accounts.clone());
+    let _ = app_window.emit_all("config-changed", config);
+    Ok(path)
+}
+
+#[tauri::command]
+async fn remove_s3_account(
+    name: String,
+    app_window: Window,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<(), String> {
+    let config = config::remove_s3_account(&name)?;
+    vfs.s3()
+        .lock()
+        .await
+        .set_accounts(config.s3_accounts.clone());
+    let _ = app_window.emit_all("config-changed", config);
+    Ok(())
+}
+
+/// Saves the search or replaces the one with the same name and returns the
+/// path its results are listed at
+#[tauri::command]
+async fn save_search(search: SavedSearch, app_window: Window) -> Result<String, String> {
+    if search.name.is_empty() || search.name.contains(['/', '\\']) {
+        return Err("The name of the search can't be empty or contain a / or \\".into());
     }
-    return mount_point;
+    // Fails for invalid queries and roots
+    SearchScope::of(&search)?;
+
+    let path = format!("{}{}", SEARCH_SCHEME, search.name);
+    let config = config::save_search(search)?;
+    let _ = app_window.emit_all("config-changed", config);
+    Ok(path)
+}
+
+#[tauri::command]
+async fn remove_saved_search(name: String, app_window: Window) -> Result<(), String> {
+    let config = config::remove_saved_search(&name)?;
+    let _ = app_window.emit_all("config-changed", config);
+    Ok(())
+}
+
+/// A link to download the file without credentials, only S3 files have one.
+/// Expires after `expires_in` seconds, at most and by default after 7 days.
+#[tauri::command]
+async fn get_share_link(
+    path: String,
+    expires_in: Option<u64>,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<String, String> {
+    if vfs.scheme_of(&path) != S3_PREFIX {
+        return Err("Links can only be shared for files on S3".into());
+    }
+    let s3 = vfs.s3().lock_owned().await;
+    tauri::async_runtime::spawn_blocking(move || {
+        s3.share_link(&path, expires_in.unwrap_or(MAX_LINK_EXPIRY))
+    })
+    .await
+    .map_err(|e| e.to_string())?
 }
 
 #[tauri::command]
@@ -708,11 +822,15 @@ async fn open_in_terminal(path: String) -> bool {
 }
 
 #[tauri::command]
-async fn go_home() {
-    let _ = set_dir(home_dir().unwrap().to_str().unwrap().into()).await;
-    unsafe {
-        PATH_HISTORY.push(home_dir().unwrap().to_string_lossy().to_string());
-    }
+async fn go_home(
+    pane_id: Option<String>,
+    app_window: Window,
+    nav: State<'_, Navigation>,
+) -> Result<(), String> {
+    let home = home_dir().ok_or("Home directory not found")?;
+    let key = NavKey::new(&app_window, pane_id);
+    nav.with(&key, |state| state.navigate(home)).await;
+    Ok(())
 }
 
 #[tauri::command]
@@ -728,13 +846,37 @@ static mut IS_SEARCHING: bool = false;
 static mut COUNT_CALLED_BACK: i32 = 0;
 #[tauri::command]
 async fn search_for(
-    mut file_name: String,
+    file_name: String,
     max_items: i32,
     search_depth: i32,
     file_content: String,
+    content_options: Option<ContentOptions>,
+    fuzzy: Option<bool>,
     app_window: Window,
     is_quick_search: bool,
+    pane_id: Option<String>,
+    nav: State<'_, Navigation>,
+    vfs: State<'_, VfsRegistry>,
+    index: State<'_, SearchIndex>,
 ) -> Result<(), String> {
+    let fuzzy = fuzzy.unwrap_or(false);
+    let query = match fuzzy {
+        true => Query::parse_fuzzy(&file_name),
+        false => Query::parse(&file_name),
+    }
+    .map_err(|e| e.to_string())?;
+    let content = match file_content.is_empty() {
+        true => None,
+        false => Some(ContentSearcher::new(
+            &file_content,
+            &content_options.unwrap_or_default(),
+        )?),
+    };
+    let current_dir = nav.cwd(&NavKey::new(&app_window, pane_id)).await;
+    let current_dir_str = current_dir.to_string_lossy().to_string();
+    if content.is_some() && !vfs.is_local(&current_dir_str) {
+        return Err("Searching by content only works in local folders".into());
+    }
     unsafe {
         IS_SEARCHING = true;
         COUNT_CALLED_BACK = 0;
@@ -753,71 +895,134 @@ async fn search_for(
         &file_content,
         num_cpus::get()
     ));
-    let temp_file_name = String::from(&file_name);
-    if temp_file_name.split(".").nth(0).unwrap().contains("*") {
-        file_name = temp_file_name.trim().replace("*", "");
-    }
-
-    let file_ext = ".".to_string().to_owned()
-        + file_name
-            .split(".")
-            .nth(file_name.split(".").count() - 1)
-            .unwrap_or("");
-
-    let mut v_exts: Vec<String> = vec![];
-    if file_ext != "" {
-        v_exts.push(file_ext.to_lowercase());
-    }
-
     let sw = Stopwatch::start_new();
 
-    let current_dir = CURRENT_DIR.lock().await.clone();
-    if current_dir.starts_with("gdrive:") {
-        let mut gdrive = get_gdrive().await?.lock().await;
+    let emit = |item: DirWalkerEntry, content_matches: Vec<ContentMatch>| {
+        unsafe {
+            COUNT_CALLED_BACK += 1;
+        }
+        let mut value = serde_json::to_value(&item).unwrap();
+        if !content_matches.is_empty() {
+            value["content_matches"] = json!(content_matches);
+        }
+        // The UI orders fuzzy results by score as they come in
+        if let Some(found) = query.fuzzy_match(&item.name) {
+            value["score"] = json!(rank_of(&item, found.score));
+            value["matched_positions"] = json!(found.positions);
+        }
+        let _ = app_window
+            .emit_all("addSingleItem", value.to_string())
+            .expect("Failed to emit");
+        let _ = app_window.eval(&format!(
+            "$('.file-searching-file-count').html('{} items found')",
+            unsafe { COUNT_CALLED_BACK }
+        ));
+    };
 
-        let search_result = tauri::async_runtime::spawn_blocking(move || gdrive.search(&file_name))
-            .await
-            .map_err(|e| e.to_string())?;
+    // Local folders that are part of an indexed root are answered from the
+    // index, only the content still has to be read
+    let files_only = !is_quick_search && !query.filters_type();
+    let indexed = match vfs.is_local(&current_dir_str) && index.covers(&current_dir) {
+        true => index
+            .search(
+                &current_dir,
+                &query,
+                search_depth as u32,
+                files_only,
+                // Fuzzy results are ranked before the best of them are taken
+                match content.is_some() || fuzzy {
+                    true => usize::MAX,
+                    false => max_items.max(0) as usize,
+                },
+            )
+            .map_err(|e| err_log(format!("Search index failed, walking instead: {}", e)))
+            .ok(),
+        false => None,
+    };
 
-        for item in search_result? {
-            unsafe {
-                COUNT_CALLED_BACK += 1;
-            }
-            let _ = app_window
-                .emit_all(
-                    "addSingleItem",
-                    serde_json::to_string(&item).unwrap().to_string(),
+    if !vfs.is_local(&current_dir_str) {
+        // Providers only search by name, the rest of the query is applied here
+        let name_hint = query.name_hint().unwrap_or_default();
+        let search_result = vfs
+            .run(&current_dir_str, move |provider| {
+                provider.search(&name_hint)
+            })
+            .await;
+
+        for item in search_result?.into_iter().filter(|item| {
+            query.matches(&Candidate {
+                name: &item.name,
+                path: &item.path,
+                is_dir: item.is_dir,
+                size: item.size,
+                modified: DateTime::parse_from_rfc3339(&item.last_modified)
+                    .ok()
+                    .map(|date| date.timestamp_millis()),
+            })
+        }) {
+            emit(item, vec![]);
+        }
+    } else if let Some(mut items) = indexed {
+        dbg_log(format!(
+            "Answering from the search index: {:?}",
+            current_dir
+        ));
+        if fuzzy {
+            items.sort_by_cached_key(|item| {
+                std::cmp::Reverse(
+                    query
+                        .fuzzy_match(&item.name)
+                        .map_or(0, |found| rank_of(item, found.score)),
                 )
-                .expect("Failed to emit");
-            let _ = app_window.eval(&format!(
-                "$('.file-searching-file-count').html('{} items found')",
-                unsafe { COUNT_CALLED_BACK }
-            ));
+            });
         }
-    } else {
-        let _ = DirWalker::new().set_ext(v_exts).search(
-            CURRENT_DIR.lock().await.to_str().unwrap(),
+        for item in items {
+            if unsafe { !IS_SEARCHING || COUNT_CALLED_BACK >= max_items } {
+                break;
+            }
+            let content_matches = match &content {
+                Some(searcher) => match searcher.search_file(Path::new(&item.path)) {
+                    Ok(matches) if !matches.is_empty() => matches,
+                    _ => continue,
+                },
+                None => vec![],
+            };
+            emit(item, content_matches);
+        }
+    } else if fuzzy {
+        // Nothing is emitted while walking, so the walk doesn't stop at
+        // max_items and the best matches of everything are shown
+        let best = std::cell::RefCell::new(BestMatches::new(max_items.max(0) as usize));
+        DirWalker::new().search(
+            current_dir.to_str().unwrap(),
             search_depth as u32,
-            file_name,
+            &query,
             max_items,
             is_quick_search,
-            file_content,
-            &|item: DirWalkerEntry| {
-                unsafe {
-                    COUNT_CALLED_BACK += 1;
-                }
-                let _ = app_window
-                    .emit_all(
-                        "addSingleItem",
-                        serde_json::to_string(&item).unwrap().to_string(),
-                    )
-                    .expect("Failed to emit");
-                let _ = app_window.eval(&format!(
-                    "$('.file-searching-file-count').html('{} items found')",
-                    unsafe { COUNT_CALLED_BACK }
-                ));
+            content.as_ref(),
+            &|item, content_matches| {
+                let rank = query
+                    .fuzzy_match(&item.name)
+                    .map_or(0, |found| rank_of(&item, found.score));
+                best.borrow_mut().push(rank, item, content_matches);
             },
         );
+        for (item, content_matches) in best.into_inner().into_sorted() {
+            if unsafe { !IS_SEARCHING } {
+                break;
+            }
+            emit(item, content_matches);
+        }
+    } else {
+        let _ = DirWalker::new().search(
+            current_dir.to_str().unwrap(),
+            search_depth as u32,
+            &query,
+            max_items,
+            is_quick_search,
+            content.as_ref(),
+            &emit,
+        );
     }
 
     unsafe {
@@ -837,220 +1042,311 @@ async fn search_for(
     Ok(())
 }
 
+/// The `max_items` best ranked search results, the others are dropped while
+/// they come in
+struct BestMatches {
+    max_items: usize,
+    items: Vec<(i64, DirWalkerEntry, Vec<ContentMatch>)>,
+}
+
+impl BestMatches {
+    fn new(max_items: usize) -> Self {
+        BestMatches {
+            max_items,
+            items: vec![],
+        }
+    }
+
+    fn push(&mut self, rank: i64, item: DirWalkerEntry, content_matches: Vec<ContentMatch>) {
+        self.items.push((rank, item, content_matches));
+        if self.items.len() >= self.max_items.max(1) * 2 {
+            self.trim();
+        }
+    }
+
+    /// Keeps the best ones, equal ranks stay in the order they were found
+    fn trim(&mut self) {
+        self.items
+            .sort_by_key(|(rank, _, _)| std::cmp::Reverse(*rank));
+        self.items.truncate(self.max_items);
+    }
+
+    fn into_sorted(mut self) -> Vec<(DirWalkerEntry, Vec<ContentMatch>)> {
+        self.trim();
+        self.items
+            .into_iter()
+            .map(|(_, item, content_matches)| (item, content_matches))
+            .collect()
+    }
+}
+
+/// Fuzzy score of a search result including its depth and age
+fn rank_of(item: &DirWalkerEntry, score: i64) -> i64 {
+    let modified = DateTime::parse_from_rfc3339(&item.last_modified)
+        .ok()
+        .map(|date| date.timestamp_millis());
+    rank(score, item.depth, modified)
+}
+
+#[tauri::command]
+async fn add_index_root(path: String, index: State<'_, SearchIndex>) -> Result<(), String> {
+    index.add_root(Path::new(&path))
+}
+
+#[tauri::command]
+async fn remove_index_root(path: String, index: State<'_, SearchIndex>) -> Result<(), String> {
+    index.remove_root(Path::new(&path))
+}
+
+#[tauri::command]
+async fn get_index_status(index: State<'_, SearchIndex>) -> Result<Vec<IndexRoot>, String> {
+    index.status()
+}
+
 #[tauri::command]
 async fn copy_paste(
     app_window: Window,
     act_file_name: String,
     from_path: String,
-    is_for_dual_pane: String,
     mut copy_to_path: String,
+    conflict_policy: Option<ConflictPolicy>,
+    pane_id: Option<String>,
+    nav: State<'_, Navigation>,
+    jobs: State<'_, JobManager>,
+    vfs: State<'_, VfsRegistry>,
 ) -> Result<(), String> {
     if copy_to_path.clone().len() == 0 {
         wng_log("No destination path provided. Defaulting to current dir".into());
-        copy_to_path = CURRENT_DIR.lock().await.to_string_lossy().to_string();
-    }
-
-    if copy_to_path.starts_with("gdrive:") || from_path.starts_with("gdrive:") {
-        let mut gdrive = get_gdrive().await?.lock().await;
-
-        return tauri::async_runtime::spawn_blocking(move || {
-            gdrive.copy(&from_path, &copy_to_path)
-        })
-        .await
-        .map_err(|e| e.to_string())?;
+        copy_to_path = nav
+            .cwd(&NavKey::new(&app_window, pane_id))
+            .await
+            .to_string_lossy()
+            .to_string();
     }
 
-    unsafe {
-        COPY_COUNTER = 0.0;
-    }
-    let _ = &app_window
-        .eval("document.querySelector('.progress-bar-container-popup').style.display = 'flex'");
     dbg_log(format!("Copying: {} ...", &act_file_name));
-    let final_filename = get_final_filename(
-        act_file_name,
-        from_path.clone(),
-        is_for_dual_pane,
-        copy_to_path,
-    )
-    .await;
-
-    unsafe {
-        TO_COPY_COUNTER = count_entries(&from_path).unwrap();
-        if TO_COPY_COUNTER == 1.0 {
-            let _ =
-                app_window.eval("document.querySelector('.progress-bar-2').style.display = 'none'");
-        } else {
-            let _ = app_window
-                .eval("document.querySelector('.progress-bar-2').style.display = 'block'");
-        }
-    }
+    let job = jobs.create(
+        &app_window,
+        "Copying ...".into(),
+        act_file_name.clone(),
+        &copy_to_path,
+    );
+    let policy = conflict_policy.unwrap_or_default();
     let sw = Stopwatch::start_new();
-
-    // Windows copy pasting
-    // Execute the copy process for either a dir or file
-    #[cfg(target_os = "windows")]
-    let _ = copy_to(&app_window, final_filename, from_path);
-    #[cfg(not(target_os = "windows"))]
-    copy_to(&app_window, final_filename.clone(), from_path.clone());
-
-    // Non windows copy pasting
-    #[cfg(not(target_os = "windows"))]
-    let _ = copy(from_path, final_filename);
-
+    let result = async {
+        let _permit = job.start().await?;
+        vfs.copy_items(&job, policy, vec![vfs.item_at(&from_path)], &copy_to_path)
+            .await
+    }
+    .await;
+    job.finish(&result);
     dbg_log(format!("Copy-Paste time: {:?}", sw.elapsed()));
-    app_window.eval("resetProgressBar()").unwrap();
 
-    Ok(())
+    result
 }
 
 #[tauri::command]
 async fn arr_copy_paste(
     app_window: Window,
     arr_items: Vec<FDir>,
-    is_for_dual_pane: String,
     mut copy_to_path: String,
+    conflict_policy: Option<ConflictPolicy>,
+    pane_id: Option<String>,
+    nav: State<'_, Navigation>,
+    jobs: State<'_, JobManager>,
+    vfs: State<'_, VfsRegistry>,
 ) -> Result<(), String> {
-    let current_path = CURRENT_DIR.lock().await.to_string_lossy().to_string();
+    let current_path = nav
+        .cwd(&NavKey::new(&app_window, pane_id))
+        .await
+        .to_string_lossy()
+        .to_string();
     if &copy_to_path.len() == &0 {
         wng_log("No destination path provided. Defaulting to current dir".into());
         copy_to_path = current_path.clone();
     }
 
-    if copy_to_path.starts_with("gdrive:")
-        || arr_items
-            .iter()
-            .any(|item| item.path.starts_with("gdrive:"))
-    {
-        let mut gdrive = get_gdrive().await?.lock().await;
-        let to_path_clone = copy_to_path.clone();
-
-        return tauri::async_runtime::spawn_blocking(move || {
-            gdrive.copy_items(arr_items, &to_path_clone)
-        })
-        .await
-        .map_err(|e| e.to_string())?;
-    }
-
-    unsafe {
-        COPY_COUNTER = 0.0;
-        TO_COPY_COUNTER = 0.0
-    }
-    let _ = &app_window
-        .eval("document.querySelector('.progress-bar-container-popup').style.display = 'flex'");
-    let _ = app_window.eval("document.querySelector('.progress-bar-2').style.display = 'block'");
-    let mut filename: String;
-    for item in arr_items.clone() {
-        unsafe {
-            TO_COPY_COUNTER += count_entries(&item.path).unwrap();
-        }
-    }
+    let job = jobs.create(
+        &app_window,
+        "Copying ...".into(),
+        format!("{} items", arr_items.len()),
+        &copy_to_path,
+    );
+    let policy = conflict_policy.unwrap_or_default();
     let sw = Stopwatch::start_new();
-    for item in arr_items {
-        let item_path = item.path;
-        filename = item_path
-            .replace("\\", "/")
-            .split("/")
-            .last()
-            .unwrap()
-            .to_string();
-        let final_filename = get_final_filename(
-            filename,
-            item_path.clone(),
-            is_for_dual_pane.clone(),
-            copy_to_path.clone(),
-        )
-        .await;
-        // Execute the copy process for either a dir or file
-        #[cfg(not(target_os = "windows"))]
-        // use copy_to for files larger than 5 gb
-        if item.size.parse::<u64>().unwrap_or(0) > 5000000000 {
-            copy_to(&app_window, final_filename.clone(), item_path.clone());
-        } else {
-            if item.is_dir == 1 {
-                let _ = copy_dir(item_path, final_filename);
-            } else {
-                let _ = copy(item_path, final_filename); // Copying of files is different on macOS
-            }
-        }
-        #[cfg(target_os = "windows")]
-        copy_to(&app_window, final_filename, item_path);
+    let result = async {
+        let _permit = job.start().await?;
+        vfs.copy_items(&job, policy, arr_items, &copy_to_path).await
     }
+    .await;
+    job.finish(&result);
     dbg_log(format!("Copy-Paste time: {:?}", sw.elapsed()));
-    app_window.eval("resetProgressBar()").unwrap();
-    // app_window.eval("listDirectories(true)").unwrap();
 
-    Ok(())
+    result
 }
 
 #[tauri::command]
-async fn get_final_filename(
-    act_file_name: String,
-    from_path: String,
-    is_for_dual_pane: String,
-    copy_to_path: String,
-) -> String {
-    let file = fs::metadata(&from_path);
-    if &file.is_ok() != &true {
-        err_log("File could not be copied".into());
-        return "".into();
-    }
-    let file_name: String;
-    if is_for_dual_pane == "1" {
-        file_name = act_file_name;
-    } else {
-        file_name = PathBuf::from(copy_to_path)
-            .join(&act_file_name)
-            .to_str()
-            .unwrap()
+async fn move_items(
+    app_window: Window,
+    arr_items: Vec<FDir>,
+    mut move_to_path: String,
+    conflict_policy: Option<ConflictPolicy>,
+    pane_id: Option<String>,
+    nav: State<'_, Navigation>,
+    jobs: State<'_, JobManager>,
+    journal: State<'_, Journal>,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<(), String> {
+    if move_to_path.is_empty() {
+        wng_log("No destination path provided. Defaulting to current dir".into());
+        move_to_path = nav
+            .cwd(&NavKey::new(&app_window, pane_id))
+            .await
+            .to_string_lossy()
             .to_string();
     }
-    let temp_file_ext: String;
-    let mut file_ext: String;
-    let mut temp_filename: String = String::new();
 
-    for i in 0..file_name.split(".").count() - 1 {
-        temp_filename += file_name.split(".").nth(i).unwrap();
+    let job = jobs.create(
+        &app_window,
+        "Moving ...".into(),
+        format!("{} items", arr_items.len()),
+        &move_to_path,
+    );
+    // Moves on the local disk are recorded for undo, so they use the Mover here
+    if !vfs.is_local(&move_to_path) || arr_items.iter().any(|item| !vfs.is_local(&item.path)) {
+        let policy = conflict_policy.unwrap_or_default();
+        let result = async {
+            let _permit = job.start().await?;
+            vfs.move_items(&job, policy, arr_items, &move_to_path).await
+        }
+        .await;
+        job.finish(&result);
+        return result;
     }
 
-    temp_file_ext = file_name
-        .split(".")
-        .nth(file_name.split(".").count() - 1)
-        .unwrap()
-        .to_string();
-    file_ext = ".".to_string().to_owned() + &temp_file_ext.as_str();
-
-    if temp_file_ext == file_name {
-        file_ext = "".to_string();
+    for item in &arr_items {
+        let (count, size) = measure_entries(&item.path);
+        job.add_total(count, size);
     }
 
-    temp_filename = file_name
-        .strip_suffix(&file_ext)
-        .unwrap_or(&file_name)
-        .to_string();
-    let mut is_file_existing = fs::metadata(&file_name).is_ok();
-    let mut counter = 1;
-    let mut final_filename: String = format!("{}{}", &temp_filename, file_ext);
+    let description = format!("Move {} items to {}", arr_items.len(), &move_to_path);
+    let moved = Arc::new(std::sync::Mutex::new(vec![]));
+    let moved_clone = moved.clone();
+    let sw = Stopwatch::start_new();
+    let result = job
+        .run_blocking(move |job| {
+            let mut resolver = ConflictResolver::new(conflict_policy.unwrap_or_default());
+            let mut mover = Mover::new(job, &mut resolver);
+            for item in arr_items {
+                let from = PathBuf::from(&item.path);
+                let to = PathBuf::from(&move_to_path).join(from.file_name().unwrap_or_default());
+                if from == to {
+                    continue;
+                }
+                let item_moved = mover.move_item(&from, &to)?;
+                moved_clone.lock().unwrap().extend(item_moved);
+            }
+            Ok(())
+        })
+        .await;
+    dbg_log(format!("Move time: {:?}", sw.elapsed()));
 
-    while is_file_existing {
-        final_filename = format!("{}_{}{}", &temp_filename, counter, file_ext);
-        is_file_existing = fs::metadata(&final_filename).is_ok();
-        counter += 1;
+    // Items moved before a failure still have to be undoable
+    let changes: Vec<PathChange> = moved
+        .lock()
+        .unwrap()
+        .drain(..)
+        .map(|(from, to)| PathChange::new(from, to))
+        .collect();
+    if !changes.is_empty() {
+        journal
+            .record(description, Operation::Move { changes })
+            .await;
     }
-    final_filename = final_filename.replace("\\", "/");
-    return final_filename;
+
+    result
 }
 
 #[tauri::command]
-async fn delete_item(act_file_name: String) -> Result<(), String> {
+async fn delete_item(
+    act_file_name: String,
+    permanently: Option<bool>,
+    journal: State<'_, Journal>,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<(), String> {
+    arr_delete_items(vec![act_file_name], permanently, journal, vfs).await
+}
+
+#[tauri::command]
+async fn arr_delete_items(
+    arr_items: Vec<String>,
+    permanently: Option<bool>,
+    journal: State<'_, Journal>,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<(), String> {
+    let description = match arr_items.len() {
+        1 => format!("Delete {}", &arr_items[0]),
+        count => format!("Delete {} items", count),
+    };
+    let mut changes = vec![];
+    let mut result = Ok(());
+    for path in arr_items {
+        match remove_item(&vfs, path, permanently.unwrap_or(false)).await {
+            Ok(Some(item)) => changes.push(PathChange::new(
+                PathBuf::from(item.original_path),
+                PathBuf::from(item.path),
+            )),
+            Ok(None) => {}
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+    // Only trashed items can be brought back
+    if !changes.is_empty() {
+        journal
+            .record(description, Operation::Trash { changes })
+            .await;
+    }
+
+    result
+}
+
+/// Returns the trash entry if the item was moved to the trash
+async fn remove_item(
+    vfs: &VfsRegistry,
+    act_file_name: String,
+    permanently: bool,
+) -> Result<Option<TrashItem>, String> {
     dbg_log(format!("Deleting: {}", String::from(&act_file_name)));
 
-    if act_file_name.starts_with("gdrive:") {
-        let mut gdrive = get_gdrive().await?.lock().await;
-        let _ = tauri::async_runtime::spawn_blocking(move || gdrive.delete(&act_file_name))
-            .await
-            .map_err(|e| e.to_string())?;
+    if !vfs.is_local(&act_file_name) {
+        if !permanently {
+            return Err(trash::TRASH_UNSUPPORTED.into());
+        }
+        vfs.run(&act_file_name.clone(), move |provider| {
+            provider.delete(&act_file_name)
+        })
+        .await?;
+
+        return Ok(None);
+    }
 
-        return Ok(());
+    // Only the freedesktop.org trash is supported for now
+    if !permanently {
+        if !cfg!(target_os = "linux") {
+            return Err(trash::TRASH_UNSUPPORTED.into());
+        }
+        return tauri::async_runtime::spawn_blocking(move || {
+            trash::move_to_trash(Path::new(&act_file_name)).map(Some)
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+
+    if trash::is_trashed(Path::new(&act_file_name)) {
+        return trash::remove(Path::new(&act_file_name)).map(|_| None);
     }
 
     #[cfg(target_os = "windows")]
@@ -1058,10 +1354,10 @@ async fn delete_item(act_file_name: String) -> Result<(), String> {
         let dir_remove = remove_dir_all(&act_file_name.replace("\\", "/"));
 
         if dir_remove.is_err() {
-            let _ = delete_file(&act_file_name.replace("\\", "/")).expect("Failed to delete file");
+            delete_file(&act_file_name.replace("\\", "/")).map_err(|e| e.to_string())?;
         }
 
-        return Ok(());
+        return Ok(None);
     }
 
     let file = File::open(&act_file_name);
@@ -1070,112 +1366,104 @@ async fn delete_item(act_file_name: String) -> Result<(), String> {
         is_dir = file.unwrap().metadata().unwrap().is_dir();
     }
     if is_dir {
-        let _ = rapid_delete_dir_all(&act_file_name.replace("\\", "/"), None, None)
+        rapid_delete_dir_all(&act_file_name.replace("\\", "/"), None, None)
             .await
-            .expect("Failed to delete dir");
+            .map_err(|e| e.to_string())?;
     } else {
-        let _ = delete_file(&act_file_name.replace("\\", "/")).expect("Failed to delete file");
+        delete_file(&act_file_name.replace("\\", "/")).map_err(|e| e.to_string())?;
     }
 
-    Ok(())
+    Ok(None)
 }
 
 #[tauri::command]
-async fn arr_delete_items(arr_items: Vec<String>) -> Result<(), String> {
-    for path in arr_items {
-        delete_item(path).await?;
-    }
+async fn delete_permanently(
+    arr_items: Vec<String>,
+    journal: State<'_, Journal>,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<(), String> {
+    arr_delete_items(arr_items, Some(true), journal, vfs).await
+}
 
-    Ok(())
+#[tauri::command]
+async fn list_trash() -> Result<Vec<TrashItem>, String> {
+    tauri::async_runtime::spawn_blocking(|| trash::list_trash(&trash::trash_dirs()))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn restore_from_trash(arr_items: Vec<String>) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        for path in arr_items {
+            let restored_path = trash::restore(Path::new(&path))?;
+            dbg_log(format!("Restored: {:?}", restored_path));
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn empty_trash() -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(|| trash::empty(&trash::trash_dirs()))
+        .await
+        .map_err(|e| e.to_string())?
 }
 
+/// Whether the file at `path` is an archive that can be extracted, detected by
+/// its content
 #[tauri::command]
-async fn extract_item(from_path: String, app_window: Window) {
-    let action_id = create_new_action(
+async fn is_archive(path: String, vfs: State<'_, VfsRegistry>) -> Result<bool, String> {
+    if !vfs.is_local(&path) {
+        return Ok(false);
+    }
+    tauri::async_runtime::spawn_blocking(move || ArchiveKind::of(Path::new(&path)).is_some())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn extract_item(
+    from_path: String,
+    options: Option<ExtractOptions>,
+    app_window: Window,
+    jobs: State<'_, JobManager>,
+) -> Result<(), String> {
+    let job = jobs.create(
         &app_window,
         "Extracting ...".into(),
         from_path.clone().split("/").last().unwrap().to_string(),
         &from_path,
     );
-    // Check file extension
-    let file_ext = ".".to_string().to_owned()
-        + from_path
-            .split(".")
-            .nth(from_path.split(".").count() - 1)
-            .unwrap_or("");
-
-    dbg_log(format!("Start unpacking {} - {}", &file_ext, &from_path));
-
-    // zip, 7z or rar unpack
+    let options = options.unwrap_or_default();
     let sw = Stopwatch::start_new();
-    if file_ext == ".zip" {
-        let file = PathBuf::from(&from_path);
-        let _ = create_dir(&from_path.strip_suffix(&file_ext).unwrap());
-        let new_dir = PathBuf::from(&from_path.strip_suffix(&file_ext).unwrap());
-        let extract = zip_extract(&file, &new_dir);
-        if extract.is_err() {
-            let _ = app_window.eval("showToast('Archive couldnt be extracted')");
-            remove_action(app_window, action_id);
-            err_log(extract.unwrap_err().to_string());
-            return;
-        }
-    } else if file_ext == ".rar" {
-        let mut archive = Archive::new(&from_path).open_for_processing().unwrap();
-        while let Some(header) = archive.read_header().unwrap() {
-            dbg_log(format!(
-                "{} bytes: {}",
-                header.entry().unpacked_size,
-                header.entry().filename.to_string_lossy()
-            ));
-            archive = if header.entry().is_file() {
-                header.extract().unwrap()
-            } else {
-                header.skip().unwrap()
-            }
-        }
-    } else if file_ext == ".7z" {
-        let _ =
-            sevenz_rust::decompress_file(&from_path, &from_path.strip_suffix(&file_ext).unwrap());
-    } else if file_ext == ".tar" {
-        unpack_tar(File::open(&from_path).unwrap());
-    } else if file_ext == ".gz" {
-        let file = File::open(&from_path).unwrap();
-        let mut archive = GzDecoder::new(file);
-        let mut buffer = Vec::new();
-        let _ = archive.read_to_end(&mut buffer).unwrap();
-        let _ = File::create(&from_path.strip_suffix(&file_ext).unwrap())
-            .unwrap()
-            .write_all(&buffer);
-        unpack_tar(File::open(&from_path.strip_suffix(&file_ext).unwrap()).unwrap());
-        let _ = remove_file(&from_path.strip_suffix(&file_ext).unwrap());
-    } else if file_ext == ".bz2" {
-        let mut file = archiver_rs::Bzip2::open(&PathBuf::from(&from_path)).unwrap();
-        file.decompress(&PathBuf::from(&from_path.strip_suffix(&file_ext).unwrap()))
-            .unwrap();
-        unpack_tar(File::open(&from_path.strip_suffix(&file_ext).unwrap()).unwrap());
-        let _ = remove_file(&from_path.strip_suffix(&file_ext).unwrap());
-    } else {
-        err_log("Unsupported file type".into());
-        return;
+    let result = job
+        .run_blocking(move |job| extract_archive(job, Path::new(&from_path), &options))
+        .await;
+    if let Err(e) = &result {
+        err_log(format!("Archive couldn't be extracted: {}", e));
     }
-
     dbg_log(format!("Unpack time: {:?}", sw.elapsed()));
-    remove_action(app_window, action_id);
+    result
 }
 
-#[tauri::command]
-async fn open_item(path: String) -> Result<(), String> {
-    dbg_log(format!("Opening: {}", &path));
-    if path.starts_with("gdrive:") {
-        let mut gdrive = get_gdrive().await?.lock().await;
-
-        let temp_path = tauri::async_runtime::spawn_blocking(move || {
-            gdrive.download(&path, std::env::temp_dir().to_str().unwrap())
-        })
-        .await
-        .map_err(|e| e.to_string())?;
-
-        open::that_detached(temp_path?).unwrap();
+#[tauri::command]
+async fn open_item(path: String, vfs: State<'_, VfsRegistry>) -> Result<(), String> {
+    dbg_log(format!("Opening: {}", &path));
+    if !vfs.is_local(&path) {
+        // Remote files are opened from a downloaded copy
+        let temp_path = vfs
+            .run(&path.clone(), move |provider| {
+                provider.download(
+                    &path,
+                    &format!("{}/", std::env::temp_dir().to_string_lossy()),
+                )
+            })
+            .await?;
+
+        open::that_detached(temp_path).map_err(|e| e.to_string())?;
     } else {
         open::that_detached(path).unwrap();
     }
@@ -1183,196 +1471,129 @@ async fn open_item(path: String) -> Result<(), String> {
     Ok(())
 }
 
+/// A local path the file at `path` can be previewed from, files of other
+/// providers are copied into a temp dir first
 #[tauri::command]
-async fn compress_item(
-    from_path: String,
-    compression_level: i32,
-    path_to_zip: String,
+async fn get_preview_path(path: String, vfs: State<'_, VfsRegistry>) -> Result<String, String> {
+    if vfs.is_local(&path) {
+        return Ok(path);
+    }
+    let preview_dir = std::env::temp_dir().join("codriver-preview");
+    fs::create_dir_all(&preview_dir).map_err(|e| e.to_string())?;
+    vfs.run(&path.clone(), move |provider| {
+        provider.download(&path, &preview_dir.to_string_lossy())
+    })
+    .await
+}
+
+#[tauri::command]
+async fn create_archive(
+    arr_items: Vec<String>,
+    archive_name: String,
+    options: Option<CompressOptions>,
     app_window: Window,
-) {
-    let action_id = create_new_action(
+    pane_id: Option<String>,
+    nav: State<'_, Navigation>,
+    jobs: State<'_, JobManager>,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<String, String> {
+    let options = options.unwrap_or_default();
+    let name = archive_name.trim();
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return Err(format!("'{}' isn't a valid archive name", archive_name));
+    }
+    let extension = options.format.extension();
+    let name = match name.to_lowercase().ends_with(extension) {
+        true => name.to_string(),
+        false => format!("{}{}", name, extension),
+    };
+    let cwd = nav.cwd(&NavKey::new(&app_window, pane_id)).await;
+    // Archives are written and read straight on the disk
+    if !vfs.is_local(&cwd.to_string_lossy()) || arr_items.iter().any(|item| !vfs.is_local(item)) {
+        return Err("Archives can only be created from and in local dirs".into());
+    }
+    let archive = unique_filename(&cwd.join(name));
+    let archive_path = archive.to_string_lossy().to_string();
+
+    let job = jobs.create(
         &app_window,
         "Compressing ...".into(),
-        from_path
-            .clone()
-            .replace("'", "")
-            .split("/")
-            .last()
-            .unwrap()
-            .to_string(),
-        &from_path,
-    );
-    let sw = Stopwatch::start_new();
-    dbg_log(format!(
-        "Compression of '{}' started with compression level: {}",
-        &from_path.split("/").last().unwrap(),
-        &compression_level
-    ));
-    let file_ext = ".".to_string().to_owned()
-        + from_path
-            .split("/")
-            .last()
-            .unwrap_or("")
-            .split(".")
-            .last()
-            .unwrap_or("");
-    let created_file = File::create(
-        path_to_zip
-            .strip_suffix(&file_ext)
-            .unwrap_or(&path_to_zip)
-            .to_owned()
-            + ".zip",
-    )
-    .unwrap();
-    dbg_log(format!("Created file: {:?}", created_file));
-    let source: PathBuf;
-    let archive = PathBuf::from(
-        path_to_zip
-            .split("/")
-            .last()
-            .unwrap_or("")
-            .strip_suffix(&file_ext)
-            .unwrap_or(&path_to_zip)
-            .to_owned()
-            + ".zip",
-    );
-    dbg_log(format!("Archive: {:?}", archive));
-    if fs::metadata(&from_path).unwrap().is_dir() {
-        source = PathBuf::from(&from_path);
-    } else {
-        let mut file_name = from_path.clone();
-        file_name = file_name
-            .replace("'", "")
-            .clone()
-            .split("/")
-            .last()
-            .unwrap()
-            .into();
-        let _ = create_dir(
-            config_dir()
-                .unwrap()
-                .join("com.codriver.dev")
-                .join("__compressed_dir"),
-        );
-        let _ = copy(
-            &from_path,
-            config_dir()
-                .unwrap()
-                .join("com.codriver.dev")
-                .join("__compressed_dir")
-                .to_string_lossy()
-                .to_string()
-                + "/"
-                + &file_name,
-        );
-        source = config_dir()
-            .unwrap()
-            .join("com.codriver.dev")
-            .join("__compressed_dir");
-    }
-    let options = FileOptions::default()
-        .compression_method(zip::CompressionMethod::Deflated)
-        .compression_level(Some(compression_level));
-    let _ = zip_create_from_directory_with_options(&archive, &source, options);
-    let _ = remove_dir_all(
-        config_dir()
-            .unwrap()
-            .join("com.codriver.dev")
-            .join("__compressed_dir")
-            .as_path()
+        archive
+            .file_name()
+            .unwrap_or_default()
             .to_string_lossy()
             .to_string(),
+        &archive_path,
     );
-    remove_action(app_window, action_id);
+    let sw = Stopwatch::start_new();
+    let sources = arr_items.iter().map(PathBuf::from).collect::<Vec<_>>();
+    job.run_blocking(move |job| compress::create_archive(job, &sources, &archive, &options))
+        .await?;
     dbg_log(format!("Compression time: {:?}", sw.elapsed()));
+    Ok(archive_path)
 }
 
 #[tauri::command]
-async fn arr_compress_items(arr_items: Vec<String>, compression_level: i32, app_window: Window) {
-    let path_to_zip = CURRENT_DIR
-        .lock()
+async fn create_folder(
+    folder_name: String,
+    pane_id: Option<String>,
+    app_window: Window,
+    nav: State<'_, Navigation>,
+    journal: State<'_, Journal>,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<(), String> {
+    let new_folder_path = nav
+        .cwd(&NavKey::new(&app_window, pane_id))
         .await
-        .join("compressed_items_archive")
+        .join(&folder_name);
+
+    let parent = new_folder_path
+        .parent()
+        .unwrap_or(&new_folder_path)
         .to_string_lossy()
         .to_string();
-    let _ = create_dir(
-        config_dir()
-            .unwrap()
-            .join("com.codriver.dev")
-            .join("compressed_items_archive"),
-    );
-    for item_path in arr_items {
-        let file_name = &item_path.split("/").last().unwrap();
-        if fs::metadata(&item_path).unwrap().is_dir() {
-            copy_to(
-                &app_window,
-                config_dir()
-                    .unwrap()
-                    .join("com.codriver.dev")
-                    .join("compressed_items_archive")
-                    .to_string_lossy()
-                    .to_string()
-                    + "/"
-                    + file_name,
-                item_path.clone(),
-            );
-        } else {
-            let _ = copy(
-                &item_path,
-                config_dir()
-                    .unwrap()
-                    .join("com.codriver.dev")
-                    .join("compressed_items_archive")
-                    .to_string_lossy()
-                    .to_string()
-                    + "/"
-                    + file_name,
-            );
-            app_window.eval("resetProgressBar()").unwrap();
-        }
-    }
-    compress_item(
-        config_dir()
-            .unwrap()
-            .join("com.codriver.dev")
-            .join("compressed_items_archive")
-            .to_string_lossy()
-            .to_string(),
-        compression_level,
-        path_to_zip,
-        app_window.clone(),
-    )
-    .await;
-    let _ = remove_dir_all(
-        config_dir()
-            .unwrap()
-            .join("com.codriver.dev")
-            .join("compressed_items_archive"),
-    );
-}
-
-#[tauri::command]
-async fn create_folder(folder_name: String) -> Result<(), String> {
-    let new_folder_path = CURRENT_DIR.lock().await.join(&folder_name);
-
-    if new_folder_path.starts_with("gdrive:") {
-        let mut gdrive = get_gdrive().await?.lock().await;
-        let _ = tauri::async_runtime::spawn_blocking(move || {
-            gdrive.create_dir(&folder_name, new_folder_path.to_str().unwrap())
+    if !vfs.is_local(&parent) {
+        vfs.run(&parent.clone(), move |provider| {
+            provider.create_dir(&folder_name, &parent)
         })
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
     } else {
-        let _ = fs::create_dir(CURRENT_DIR.lock().await.join(new_folder_path));
+        fs::create_dir(&new_folder_path).map_err(|e| e.to_string())?;
+        journal
+            .record(
+                format!("Create folder {}", folder_name),
+                Operation::Create {
+                    fingerprint: Fingerprint::of(&new_folder_path),
+                    path: new_folder_path,
+                },
+            )
+            .await;
     }
 
     Ok(())
 }
 
 #[tauri::command]
-async fn create_file(file_name: String) {
-    let new_file_path = PathBuf::from(&file_name);
-    let _ = File::create(CURRENT_DIR.lock().await.join(new_file_path));
+async fn create_file(
+    file_name: String,
+    pane_id: Option<String>,
+    app_window: Window,
+    nav: State<'_, Navigation>,
+    journal: State<'_, Journal>,
+) -> Result<(), String> {
+    let current_dir = nav.cwd(&NavKey::new(&app_window, pane_id)).await;
+    let new_file_path = current_dir.join(&file_name);
+    File::create(&new_file_path).map_err(|e| e.to_string())?;
+    journal
+        .record(
+            format!("Create file {}", file_name),
+            Operation::Create {
+                fingerprint: Fingerprint::of(&new_file_path),
+                path: new_file_path,
+            },
+        )
+        .await;
+    Ok(())
 }
 
 #[tauri::command]
@@ -1380,20 +1601,28 @@ async fn rename_element(
     path: String,
     new_name: String,
     app_window: Window,
+    pane_id: Option<String>,
+    nav: State<'_, Navigation>,
+    journal: State<'_, Journal>,
+    vfs: State<'_, VfsRegistry>,
 ) -> Result<Vec<FDir>, String> {
-    if path.starts_with("gdrive:") {
-        let mut gdrive = get_gdrive().await?.lock().await;
-        let _ = tauri::async_runtime::spawn_blocking(move || gdrive.rename(&path, &new_name))
-            .await
-            .map_err(|e| e.to_string())?;
+    let current_dir = nav.cwd(&NavKey::new(&app_window, pane_id)).await;
+    if !vfs.is_local(&path) {
+        vfs.run(&path.clone(), move |provider| {
+            provider.rename(&path, &new_name)
+        })
+        .await?;
 
-        return list_dirs().await;
+        return read_directory(&vfs, current_dir).await;
     }
 
-    let renamed = fs::rename(
-        CURRENT_DIR.lock().await.join(&path.replace("\\", "/")),
-        CURRENT_DIR.lock().await.join(&new_name.replace("\\", "/")),
-    );
+    let from = current_dir.join(&path.replace("\\", "/"));
+    // Results of saved searches aren't inside of the dir that is shown
+    let to = from
+        .parent()
+        .unwrap_or(&current_dir)
+        .join(&new_name.replace("\\", "/"));
+    let renamed = fs::rename(&from, &to);
     if renamed.is_err() {
         err_log("Failed to rename element".into());
         app_window
@@ -1401,61 +1630,25 @@ async fn rename_element(
             .unwrap();
     } else {
         dbg_log(format!("Renamed from {} to {}", path, new_name));
+        journal
+            .record(
+                format!("Rename {} to {}", path, new_name),
+                Operation::Rename {
+                    changes: vec![PathChange::new(from, to)],
+                },
+            )
+            .await;
     }
-    list_dirs().await
+    read_directory(&vfs, current_dir).await
 }
 
 #[tauri::command]
-async fn save_config(
-    configured_path_one: String,
-    configured_path_two: String,
-    configured_path_three: String,
-    is_open_in_terminal: String,
-    is_dual_pane_enabled: String,
-    launch_path: String,
-    is_dual_pane_active: String,
-    search_depth: i32,
-    max_items: i32,
-    is_image_preview: String,
-    is_select_mode: String,
-    arr_favorites: Vec<String>,
-    current_theme: String,
-    is_gdrive_enabled: String,
-) {
-    let app_config_file = File::open(
-        app_config_dir(&Config::default())
-            .unwrap()
-            .join("com.codriver.dev/app_config.json"),
-    )
-    .unwrap();
-    let app_config_reader = BufReader::new(app_config_file);
-    let app_config: Value = serde_json::from_reader(app_config_reader).unwrap();
-    let app_config_json = AppConfig {
-        view_mode: app_config["view_mode"].to_string().replace('"', ""),
-        last_modified: chrono::offset::Local::now().to_string(),
-        configured_path_one: configured_path_one.replace("\\", "/"),
-        configured_path_two: configured_path_two.replace("\\", "/"),
-        configured_path_three: configured_path_three.replace("\\", "/"),
-        is_open_in_terminal: is_open_in_terminal.replace("\\", ""),
-        is_dual_pane_enabled: is_dual_pane_enabled.replace("\\", ""),
-        launch_path: launch_path.replace("\\", "/"),
-        is_dual_pane_active: is_dual_pane_active.replace("\\", ""),
-        search_depth,
-        max_items,
-        is_image_preview: is_image_preview.replace("\\", "/"),
-        is_select_mode: is_select_mode.replace("\\", "/"),
-        arr_favorites,
-        current_theme: current_theme.replace("\\", "/"),
-        gdrive_enabled: is_gdrive_enabled.replace("\\", "/"),
-    };
-    let config_dir = app_config_dir(&Config::default())
-        .unwrap()
-        .join("com.codriver.dev/app_config.json")
-        .to_str()
-        .unwrap()
-        .to_string();
-    let _ = serde_json::to_writer_pretty(File::create(&config_dir).unwrap(), &app_config_json);
-    dbg_log(format!("app_config was saved to {}", config_dir));
+async fn update_config(patch: Value, app_window: Window) -> Result<AppConfig, String> {
+    let config = tauri::async_runtime::spawn_blocking(move || config::apply_patch(patch))
+        .await
+        .map_err(|e| e.to_string())??;
+    let _ = app_window.emit_all("config-changed", &config);
+    Ok(config)
 }
 
 #[tauri::command]
@@ -1466,55 +1659,61 @@ async fn rename_elements_with_format(
     step_by: i32,
     n_digits: usize,
     ext: String,
-) {
+    journal: State<'_, Journal>,
+) -> Result<(), String> {
     let mut counter = start_at;
+    let mut changes = vec![];
+    let mut result = Ok(());
     for element in arr_elements {
         let mut item_ext: String = ext.to_string();
         if element.split(".").last().unwrap().len() > 0 && ext.len() == 0 {
             item_ext = format!("{}", ".".to_string() + element.split(".").last().unwrap());
         }
-        let _ = fs::rename(
-            &element,
-            format!("{}{:0>n_digits$}{}", new_name, counter, item_ext),
-        );
-        dbg_log(format!(
-            "Renamed from {} to {}",
-            element,
-            format!("{}{:0>n_digits$}{}", new_name, counter, item_ext)
-        ));
+        let from = PathBuf::from(&element);
+        // Items are renamed inside of their own directory
+        let to = from
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(format!("{}{:0>n_digits$}{}", new_name, counter, item_ext));
+        if fs::symlink_metadata(&to).is_ok() {
+            result = Err(format!("{:?} already exists", to));
+            break;
+        }
+        if let Err(e) = fs::rename(&from, &to) {
+            result = Err(e.to_string());
+            break;
+        }
+        dbg_log(format!("Renamed from {} to {:?}", element, to));
+        changes.push(PathChange::new(from, to));
         counter += step_by;
     }
+    if !changes.is_empty() {
+        journal
+            .record(
+                format!("Rename {} items to {}", changes.len(), new_name),
+                Operation::Rename { changes },
+            )
+            .await;
+    }
+
+    result
 }
 
-// TODO: impl this stuff
 #[tauri::command]
-async fn add_favorite(arr_favorites: Vec<String>) {
-    let app_config_file = File::open(
-        app_config_dir(&Config::default())
-            .unwrap()
-            .join("com.codriver.dev/app_config.json"),
-    )
-    .unwrap();
-    let app_config_reader = BufReader::new(app_config_file);
-    let mut app_config: Value = serde_json::from_reader(app_config_reader).unwrap();
-    app_config["arr_favorites"] = arr_favorites
-        .clone()
-        .into_iter()
-        .map(|x| Value::String(x))
-        .collect();
-    let _ = serde_json::to_writer_pretty(
-        File::create(
-            app_config_dir(&Config::default())
-                .unwrap()
-                .join("com.codriver.dev/app_config.json")
-                .to_str()
-                .unwrap()
-                .to_string(),
-        )
-        .unwrap(),
-        &app_config,
-    );
+async fn undo_last(journal: State<'_, Journal>) -> Result<JournalEntry, String> {
+    journal.undo_last().await
+}
+
+#[tauri::command]
+async fn redo(journal: State<'_, Journal>) -> Result<JournalEntry, String> {
+    journal.redo().await
+}
+
+#[tauri::command]
+async fn add_favorite(arr_favorites: Vec<String>, app_window: Window) -> Result<(), String> {
+    update_config(json!({ "arr_favorites": &arr_favorites }), app_window).await?;
     dbg_log(format!("Saved favorites: {:?}", arr_favorites));
+    Ok(())
 }
 
 #[tauri::command]
@@ -1534,47 +1733,25 @@ async fn open_with(_file_path: String, _app_path: String) {
 }
 
 #[tauri::command]
-async fn find_duplicates(app_window: Window, path: String, depth: u32) -> Vec<Vec<DirWalkerEntry>> {
-    let files = DirWalker::new()
-        .depth(depth)
-        .run(&path)
-        .ext(vec![
-            "png", "jpg", "jpeg", "txt", "svg", "gif", "mp4", "mp3", "wav", "pdf", "docx", "xlsx",
-            "doc", "zip", "rar", "7z", "dmg", "iso", "exe", "msi", "jar", "deb", "sh", "py", "htm",
-            "html",
-        ])
-        .get_items();
-    let mut seen_items: Vec<DirWalkerEntry> = Vec::new();
-    let mut duplicates: Vec<Vec<DirWalkerEntry>> = Vec::new();
-    for item in files.into_par_iter().collect::<Vec<DirWalkerEntry>>() {
-        let seen_item = seen_items.par_iter().find_any(|x| {
-            x.is_file == true
-                && x.size == item.size
-                && x.size > 0
-                && x.name
-                    .contains(&item.name.substring(0, item.name.len() - 3))
-        });
-        if *&seen_item.is_some() {
-            if duplicates.len() == 0 {
-                duplicates.push(vec![seen_item.unwrap().clone(), item.clone()]);
-            } else {
-                let collection = duplicates.par_iter_mut().find_any(|x| {
-                    x[0].size == seen_item.unwrap().size
-                        && x[0].size > 0
-                        && x[0]
-                            .name
-                            .contains(&item.name.substring(0, item.name.len() - 3))
-                });
-                if *&collection.is_some() {
-                    collection.unwrap().push(item.clone());
-                } else {
-                    duplicates.push(vec![item.clone(), seen_item.unwrap().clone()]);
-                }
-            }
-        } else {
-            seen_items.push(item);
-        }
+async fn find_duplicates(
+    app_window: Window,
+    path: String,
+    depth: u32,
+    jobs: State<'_, JobManager>,
+) -> Result<Vec<Vec<DirWalkerEntry>>, String> {
+    let job = jobs.create(
+        &app_window,
+        "Finding duplicates ...".into(),
+        path.split("/").last().unwrap_or("").to_string(),
+        &path,
+    );
+    let result = async {
+        let _permit = job.start().await?;
+        collect_duplicates(&job, &path, depth).await
     }
+    .await;
+    job.finish(&result);
+    let duplicates = result?;
     for (idx, arr_duplicate) in duplicates.clone().iter().enumerate() {
         let var_idx = &idx.clone().to_string();
         let mut inner_html = String::new();
@@ -1671,14 +1848,97 @@ async fn find_duplicates(app_window: Window, path: String, depth: u32) -> Vec<Ve
         );
         let _ = app_window.eval(&js_query);
     }
-    duplicates
+    Ok(duplicates)
 }
 
-#[tauri::command]
-async fn cancel_operation() {
-    unsafe {
-        ISCANCELED = true;
+async fn collect_duplicates(
+    job: &JobHandle,
+    path: &str,
+    depth: u32,
+) -> Result<Vec<Vec<DirWalkerEntry>>, String> {
+    let files = DirWalker::new()
+        .depth(depth)
+        .run(path)
+        .ext(vec![
+            "png", "jpg", "jpeg", "txt", "svg", "gif", "mp4", "mp3", "wav", "pdf", "docx", "xlsx",
+            "doc", "zip", "rar", "7z", "dmg", "iso", "exe", "msi", "jar", "deb", "sh", "py", "htm",
+            "html",
+        ])
+        .get_items();
+    job.add_total(files.len() as u64, 0);
+    let mut seen_items: Vec<DirWalkerEntry> = Vec::new();
+    let mut duplicates: Vec<Vec<DirWalkerEntry>> = Vec::new();
+    for item in files.into_par_iter().collect::<Vec<DirWalkerEntry>>() {
+        job.checkpoint_async().await?;
+        job.finish_item();
+        let seen_item = seen_items.par_iter().find_any(|x| {
+            x.is_file == true
+                && x.size == item.size
+                && x.size > 0
+                && x.name
+                    .contains(&item.name.substring(0, item.name.len() - 3))
+        });
+        if *&seen_item.is_some() {
+            if duplicates.len() == 0 {
+                duplicates.push(vec![seen_item.unwrap().clone(), item.clone()]);
+            } else {
+                let collection = duplicates.par_iter_mut().find_any(|x| {
+                    x[0].size == seen_item.unwrap().size
+                        && x[0].size > 0
+                        && x[0]
+                            .name
+                            .contains(&item.name.substring(0, item.name.len() - 3))
+                });
+                if *&collection.is_some() {
+                    collection.unwrap().push(item.clone());
+                } else {
+                    duplicates.push(vec![item.clone(), seen_item.unwrap().clone()]);
+                }
+            }
+        } else {
+            seen_items.push(item);
+        }
     }
+    Ok(duplicates)
+}
+
+#[tauri::command]
+async fn list_jobs(jobs: State<'_, JobManager>) -> Result<Vec<JobInfo>, String> {
+    Ok(jobs.list())
+}
+
+#[tauri::command]
+async fn cancel_job(id: String, jobs: State<'_, JobManager>) -> Result<(), String> {
+    jobs.cancel(&id)
+}
+
+#[tauri::command]
+async fn pause_job(id: String, jobs: State<'_, JobManager>) -> Result<(), String> {
+    jobs.pause(&id)
+}
+
+#[tauri::command]
+async fn resume_job(id: String, jobs: State<'_, JobManager>) -> Result<(), String> {
+    jobs.resume(&id)
+}
+
+#[tauri::command]
+async fn resolve_conflict(
+    job_id: String,
+    resolution: ConflictPolicy,
+    apply_to_all: bool,
+    jobs: State<'_, JobManager>,
+) -> Result<(), String> {
+    if resolution == ConflictPolicy::Ask {
+        return Err("A conflict has to be answered with a resolution".into());
+    }
+    jobs.answer_conflict(
+        &job_id,
+        ConflictAnswer {
+            resolution,
+            apply_to_all,
+        },
+    )
 }
 
 #[tauri::command]
@@ -1713,13 +1973,26 @@ async fn get_df_dir(number: u8) -> String {
 }
 
 #[tauri::command]
-async fn download_yt_video(app_window: Window, url: String, quality: String) {
-    let action_id = create_new_action(
-        &app_window,
-        "Downloading ...".into(),
-        url.clone(),
-        &"".into(),
-    );
+async fn download_yt_video(
+    app_window: Window,
+    url: String,
+    quality: String,
+    jobs: State<'_, JobManager>,
+) -> Result<(), String> {
+    let job = jobs.create(&app_window, "Downloading ...".into(), url.clone(), "");
+    let result = async {
+        let _permit = job.start().await?;
+        download_video(&job, url, quality).await
+    }
+    .await;
+    job.finish(&result);
+    if result.is_err() && !job.is_canceled() {
+        let _ = &app_window.eval("alert('Failed to retrieve source')");
+    }
+    result
+}
+
+async fn download_video(job: &JobHandle, url: String, quality: String) -> Result<(), String> {
     dbg_log(format!("Downloading {} as {}", url, quality));
     let chosen_quality = match quality.as_str() {
         "lowestvideo" => VideoQuality::LowestVideo,
@@ -1737,34 +2010,26 @@ async fn download_yt_video(app_window: Window, url: String, quality: String) {
         ..Default::default()
     };
 
-    let video = Video::new_with_options(url, video_options).unwrap();
+    let video = Video::new_with_options(url, video_options).map_err(|e| e.to_string())?;
 
-    let stream = video.stream().await;
-    if stream.is_err() {
-        let _ = &app_window.eval("alert('Failed to retrieve source')");
-        remove_action(app_window, action_id);
-        return;
-    }
-    let stream = stream.unwrap();
-    let video_info = video.get_basic_info().await.unwrap();
-    let mut file = File::create(video_info.video_details.title.to_owned() + ".mp4").unwrap();
-    let total_size = stream.content_length() as f32;
-    let mut downloaded: f64 = 0.0;
-    let sw = Stopwatch::start_new();
+    let stream = video.stream().await.map_err(|e| e.to_string())?;
+    let video_info = video.get_basic_info().await.map_err(|e| e.to_string())?;
+    let file_name = video_info.video_details.title.to_owned() + ".mp4";
+    let mut file = File::create(&file_name).map_err(|e| e.to_string())?;
+    job.add_total(1, stream.content_length() as u64);
+    job.begin_item(&video_info.video_details.title);
 
-    while let Some(chunk) = stream.chunk().await.unwrap() {
-        file.write_all(&chunk).unwrap();
-        downloaded += chunk.len() as f64;
-        let speed = calc_transfer_speed(downloaded, sw.elapsed_ms() as f64 / 1000.0);
-        update_progressbar_2(&app_window, 0.0, &video_info.video_details.title);
-        update_progressbar(
-            &app_window,
-            100.0 / total_size * downloaded as f32,
-            &format_bytes(downloaded as u64),
-            speed,
-        );
+    while let Some(chunk) = stream.chunk().await.map_err(|e| e.to_string())? {
+        if let Err(e) = job.checkpoint_async().await {
+            drop(file);
+            let _ = remove_file(&file_name);
+            return Err(e);
+        }
+        file.write_all(&chunk).map_err(|e| e.to_string())?;
+        job.add_bytes(chunk.len() as u64);
     }
-    remove_action(app_window, action_id);
+    job.finish_item();
+    Ok(())
 }
 
 #[tauri::command]
@@ -1952,13 +2217,12 @@ async fn get_simple_dir_info(
     path: String,
     app_window: Window,
     class_to_fill: String,
+    vfs: State<'_, VfsRegistry>,
 ) -> Result<SimpleDirInfo, String> {
-    if path.starts_with("gdrive:") {
-        let gdrive = get_gdrive().await?.lock().await;
-
-        return tauri::async_runtime::spawn_blocking(move || gdrive.get_item_size(&path))
-            .await
-            .map_err(|e| e.to_string())?;
+    if !vfs.is_local(&path) {
+        return vfs
+            .run(&path.clone(), move |provider| provider.get_item_size(&path))
+            .await;
     }
 
     unsafe {
@@ -2074,46 +2338,13 @@ async fn log(log: String) {
 }
 
 #[tauri::command]
-async fn unmount_network_drive(path: String) {
-    let _ = Command::new("umount").arg(&path).spawn();
-    dbg_log(format!("Unmounted: {}", path));
-    let remove = remove_dir(&path);
-    if remove.is_err() {
-        dbg_log(format!("Failed to remove: {} | Trying again in 0.5s", path));
-        std::thread::sleep(std::time::Duration::from_millis(500));
-        let remove2 = remove_dir(&path);
-        if remove2.is_err() {
-            dbg_log(format!("Failed to remove: {} | Trying again in 1s", path));
-            std::thread::sleep(std::time::Duration::from_millis(1000));
-            let remove3 = remove_dir(&path);
-            if remove3.is_err() {
-                dbg_log(format!(
-                    "Failed to remove: {} | Err: {}",
-                    path,
-                    remove3.err().unwrap()
-                ));
-                return;
-            }
-        }
-    }
-    dbg_log(format!("Removed: {}", path));
-}
-
-#[tauri::command]
-async fn is_gdrive_authenticated() -> bool {
-    GDRIVE.get().is_some()
-        && get_gdrive()
-            .await
-            .unwrap()
-            .lock()
-            .await
-            .is_authenticated()
-            .is_ok()
+async fn is_gdrive_authenticated(vfs: State<'_, VfsRegistry>) -> Result<bool, String> {
+    Ok(vfs.gdrive().lock().await.is_authenticated().is_ok())
 }
 
 #[tauri::command]
-async fn logout_gdrive() -> Result<(), String> {
-    let mut gdrive = get_gdrive().await?.lock().await;
+async fn logout_gdrive(vfs: State<'_, VfsRegistry>) -> Result<(), String> {
+    let mut gdrive = vfs.gdrive().lock_owned().await;
 
     tauri::async_runtime::spawn_blocking(move || gdrive.sign_out())
         .await
@@ -2121,27 +2352,3 @@ async fn logout_gdrive() -> Result<(), String> {
 
     Ok(())
 }
-
-async fn initialize_gdrive() -> Result<(), String> {
-    let gdrive = tauri::async_runtime::spawn_blocking(|| {
-        let mut gdrive_new = GDrive::new();
-        gdrive_new.authenticate()?;
-
-        Ok::<GDrive, String>(gdrive_new)
-    })
-    .await
-    .map_err(|e| e.to_string())??;
-
-    GDRIVE
-        .set(Mutex::new(gdrive))
-        .map_err(|_| "GDrive is already initialized".to_string())
-}
-
-async fn get_gdrive() -> Result<&'static Mutex<GDrive>, String> {
-    if GDRIVE.get().is_none() {
-        initialize_gdrive().await?;
-    }
-    GDRIVE
-        .get()
-        .ok_or_else(|| "Failed to initialize GDrive".to_string())
-}
@@ -0,0 +1,289 @@
+use crate::conflicts::{ConflictResolver, Resolution, SetAside};
+use crate::jobs::JobHandle;
+use crate::utils::{copy_file, dbg_log, measure_entries, wng_log};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A single change made while moving an item, kept to be able to undo it
+enum Step {
+    Renamed {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    Copied {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// Symlink created with the target of the one at `from`
+    Linked {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    CreatedDir(PathBuf),
+    /// Source directory that gets removed once its content was moved
+    EmptiedDir(PathBuf),
+}
+
+/// Moves items by renaming them on the same device or by copy, verify and
+/// delete across devices. A failing item is rolled back completely.
+pub struct Mover<'a> {
+    job: &'a JobHandle,
+    resolver: &'a mut ConflictResolver,
+    steps: Vec<Step>,
+    /// Targets that were overwritten, restored on rollback
+    replaced: SetAside,
+    /// Items that were moved as a whole, merged dirs are listed by their content
+    item_moved: Vec<(PathBuf, PathBuf)>,
+    /// Whether an item can be renamed into a dir instead of being copied
+    can_rename: fn(&Path, &Path) -> bool,
+}
+
+impl<'a> Mover<'a> {
+    pub fn new(job: &'a JobHandle, resolver: &'a mut ConflictResolver) -> Mover<'a> {
+        Mover {
+            job,
+            resolver,
+            steps: vec![],
+            replaced: SetAside::default(),
+            item_moved: vec![],
+            can_rename: is_same_device,
+        }
+    }
+
+    /// Returns the source and final target of everything that was moved.
+    /// Sources that can't be removed afterwards are kept and only warned
+    /// about, the move itself happened.
+    pub fn move_item(&mut self, from: &Path, to: &Path) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+        if to.starts_with(from) {
+            return Err(format!("Can't move {:?} into itself", from));
+        }
+        self.steps.clear();
+        self.item_moved.clear();
+        if let Err(e) = self.transfer(from, to).and_then(|_| self.verify()) {
+            self.rollback();
+            return Err(e);
+        }
+
+        // Everything arrived, the sources can go now
+        for step in &self.steps {
+            match step {
+                // Only the link itself, never what it points to
+                Step::Copied { from, .. } | Step::Linked { from, .. } => {
+                    if let Err(e) = fs::remove_file(from) {
+                        wng_log(format!("Source {:?} was kept: {}", from, e));
+                    }
+                }
+                Step::EmptiedDir(dir) => {
+                    // Skipped conflicts leave files behind, those dirs have to stay
+                    if let Err(e) = fs::remove_dir(dir) {
+                        wng_log(format!("Source dir {:?} was kept: {}", dir, e));
+                    }
+                }
+                _ => {}
+            }
+        }
+        std::mem::take(&mut self.replaced).discard();
+        Ok(std::mem::take(&mut self.item_moved))
+    }
+
+    fn transfer(&mut self, from: &Path, to: &Path) -> Result<(), String> {
+        let item_moved = self.item_moved.len();
+        // Only items merged into an existing dir are listed by their content
+        if let Some(to) = self.transfer_content(from, to)? {
+            self.item_moved.truncate(item_moved);
+            self.item_moved.push((from.to_path_buf(), to));
+        }
+        Ok(())
+    }
+
+    /// Returns where the item ended up if it was moved as a whole
+    fn transfer_content(&mut self, from: &Path, to: &Path) -> Result<Option<PathBuf>, String> {
+        self.job.checkpoint()?;
+        let to = match self.resolver.resolve(self.job, from, to)? {
+            Resolution::CopyTo(target) => target,
+            Resolution::Replace(target) => {
+                self.replaced.set_aside(&target)?;
+                target
+            }
+            Resolution::Skip => {
+                let (count, size) = measure_entries(&from.to_string_lossy());
+                self.job.add_done(count, size);
+                return Ok(None);
+            }
+        };
+        // Symlinks are moved themselves, following them would move and
+        // later delete what they point to
+        let source = fs::symlink_metadata(from).map_err(|e| e.to_string())?;
+        let target = fs::metadata(&to).ok();
+        let is_merge = source.is_dir() && target.as_ref().is_some_and(|t| t.is_dir());
+
+        if !is_merge
+            && to
+                .parent()
+                .is_some_and(|parent| (self.can_rename)(from, parent))
+        {
+            let (count, size) = measure_entries(&from.to_string_lossy());
+            fs::rename(from, &to).map_err(|e| e.to_string())?;
+            self.job.add_done(count, size);
+            self.steps.push(Step::Renamed {
+                from: from.to_path_buf(),
+                to: to.clone(),
+            });
+            return Ok(Some(to));
+        }
+
+        if source.is_symlink() {
+            copy_link(from, &to).map_err(|e| e.to_string())?;
+            self.job.add_done(1, 0);
+            self.steps.push(Step::Linked {
+                from: from.to_path_buf(),
+                to: to.clone(),
+            });
+        } else if source.is_dir() {
+            if !is_merge {
+                fs::create_dir_all(&to).map_err(|e| e.to_string())?;
+                self.steps.push(Step::CreatedDir(to.clone()));
+            }
+            for entry in fs::read_dir(from).map_err(|e| e.to_string())? {
+                let entry = entry.map_err(|e| e.to_string())?;
+                self.transfer(&entry.path(), &to.join(entry.file_name()))?;
+            }
+            self.steps.push(Step::EmptiedDir(from.to_path_buf()));
+        } else {
+            copy_file(self.job, &to.to_string_lossy(), &from.to_string_lossy())?;
+            self.steps.push(Step::Copied {
+                from: from.to_path_buf(),
+                to: to.clone(),
+            });
+        }
+        Ok((!is_merge).then_some(to))
+    }
+
+    /// Compares the size of every copied file with its source
+    fn verify(&self) -> Result<(), String> {
+        for step in &self.steps {
+            if let Step::Copied { from, to } = step {
+                let source = fs::metadata(from).map_err(|e| e.to_string())?;
+                let target = fs::metadata(to).map_err(|e| e.to_string())?;
+                if source.len() != target.len() {
+                    return Err(format!("Verification of {:?} failed", to));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn rollback(&mut self) {
+        dbg_log(format!("Rolling back {} steps", self.steps.len()));
+        for step in self.steps.drain(..).rev() {
+            let undone = match &step {
+                Step::Renamed { from, to } => fs::rename(to, from),
+                Step::Copied { to, .. } | Step::Linked { to, .. } => fs::remove_file(to),
+                // Its content was undone before, so it's empty again
+                Step::CreatedDir(dir) => fs::remove_dir(dir),
+                Step::EmptiedDir(_) => Ok(()),
+            };
+            if let Err(e) = undone {
+                wng_log(format!("Rollback step failed: {}", e));
+            }
+        }
+        // The targets are free again once the copies are gone
+        std::mem::take(&mut self.replaced).restore();
+    }
+}
+
+/// Creates a symlink at `to` pointing where the one at `from` points
+#[cfg(unix)]
+fn copy_link(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_link(from: &Path, to: &Path) -> std::io::Result<()> {
+    let target = fs::read_link(from)?;
+    // Windows tells links to dirs apart from links to files
+    match fs::metadata(from).is_ok_and(|m| m.is_dir()) {
+        true => std::os::windows::fs::symlink_dir(target, to),
+        false => std::os::windows::fs::symlink_file(target, to),
+    }
+}
+
+#[cfg(unix)]
+fn is_same_device(from: &Path, to_dir: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::symlink_metadata(from), fs::metadata(to_dir)) {
+        (Ok(from), Ok(to_dir)) => from.dev() == to_dir.dev(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_same_device(from: &Path, to_dir: &Path) -> bool {
+    // Compare the drive prefix (e.g. "C:") of both paths
+    match (from.canonicalize(), to_dir.canonicalize()) {
+        (Ok(from), Ok(to_dir)) => from.components().next() == to_dir.components().next(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conflicts::ConflictPolicy;
+    use tempfile::TempDir;
+
+    fn across_devices(job: &JobHandle, resolver: &mut ConflictResolver, from: &Path, to: &Path) {
+        let mut mover = Mover::new(job, resolver);
+        mover.can_rename = |_, _| false;
+        mover.move_item(from, to).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_moved_as_links_across_devices() {
+        let dir = TempDir::new().unwrap();
+        let real = dir.path().join("real");
+        fs::create_dir(&real).unwrap();
+        fs::write(real.join("keep.txt"), "data").unwrap();
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+        fs::create_dir(dir.path().join("to")).unwrap();
+
+        let job = JobHandle::detached();
+        let mut resolver = ConflictResolver::new(ConflictPolicy::KeepBoth);
+        let moved = dir.path().join("to/link");
+        across_devices(&job, &mut resolver, &link, &moved);
+
+        assert!(fs::symlink_metadata(&link).is_err());
+        assert_eq!(fs::read_link(&moved).unwrap(), real);
+        assert_eq!(fs::read_to_string(real.join("keep.txt")).unwrap(), "data");
+    }
+
+    #[test]
+    fn dirs_are_copied_and_removed_across_devices() {
+        let dir = TempDir::new().unwrap();
+        let from = dir.path().join("from");
+        fs::create_dir_all(from.join("sub")).unwrap();
+        fs::write(from.join("sub/a.txt"), "a").unwrap();
+        fs::create_dir(dir.path().join("to")).unwrap();
+
+        let job = JobHandle::detached();
+        let mut resolver = ConflictResolver::new(ConflictPolicy::KeepBoth);
+        let to = dir.path().join("to/from");
+        across_devices(&job, &mut resolver, &from, &to);
+
+        assert!(!from.exists());
+        assert_eq!(fs::read_to_string(to.join("sub/a.txt")).unwrap(), "a");
+    }
+
+    #[test]
+    fn items_cant_be_moved_into_themselves() {
+        let dir = TempDir::new().unwrap();
+        let job = JobHandle::detached();
+        let mut resolver = ConflictResolver::new(ConflictPolicy::KeepBoth);
+        let mut mover = Mover::new(&job, &mut resolver);
+        assert!(mover
+            .move_item(dir.path(), &dir.path().join("inner"))
+            .is_err());
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::env::current_dir;
+use std::path::{Path, PathBuf};
+use tauri::async_runtime::Mutex;
+use tauri::Window;
+
+/// Identifies one navigable view: a pane or tab inside a specific window
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NavKey {
+    pub window: String,
+    pub pane: String,
+}
+
+impl NavKey {
+    pub fn new(app_window: &Window, pane_id: Option<String>) -> NavKey {
+        NavKey {
+            window: app_window.label().to_string(),
+            pane: pane_id.unwrap_or_else(|| "main".into()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct NavigationState {
+    pub cwd: PathBuf,
+    pub back: Vec<PathBuf>,
+    pub forward: Vec<PathBuf>,
+}
+
+impl NavigationState {
+    pub fn new() -> NavigationState {
+        NavigationState {
+            cwd: current_dir().unwrap_or_default(),
+            back: vec![],
+            forward: vec![],
+        }
+    }
+
+    /// Moves to `path` and records the previous directory in the back stack
+    pub fn navigate(&mut self, path: PathBuf) {
+        if path == self.cwd {
+            return;
+        }
+        let previous = std::mem::replace(&mut self.cwd, path);
+        self.back.push(previous);
+        self.forward.clear();
+    }
+
+    /// Returns false if there was nothing to go back to
+    pub fn go_back(&mut self) -> bool {
+        let target = match self.back.pop() {
+            Some(path) => path,
+            // Without history we fall back to the real parent directory
+            None => match self.cwd.parent() {
+                Some(parent) if parent != Path::new("") => parent.to_path_buf(),
+                _ => return false,
+            },
+        };
+        let previous = std::mem::replace(&mut self.cwd, target);
+        self.forward.push(previous);
+        true
+    }
+
+    /// Returns false if there was nothing to go forward to
+    pub fn go_forward(&mut self) -> bool {
+        match self.forward.pop() {
+            Some(target) => {
+                let previous = std::mem::replace(&mut self.cwd, target);
+                self.back.push(previous);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Managed state holding the navigation of every open pane/tab
+#[derive(Default)]
+pub struct Navigation {
+    states: Mutex<HashMap<NavKey, NavigationState>>,
+}
+
+impl Navigation {
+    pub async fn cwd(&self, key: &NavKey) -> PathBuf {
+        self.states
+            .lock()
+            .await
+            .get(key)
+            .map(|state| state.cwd.clone())
+            .unwrap_or_else(|| NavigationState::new().cwd)
+    }
+
+    pub async fn with<R>(&self, key: &NavKey, f: impl FnOnce(&mut NavigationState) -> R) -> R {
+        let mut states = self.states.lock().await;
+        f(states
+            .entry(key.clone())
+            .or_insert_with(NavigationState::new))
+    }
+
+    /// Drops the state of all panes that belonged to a closed window
+    pub async fn forget_window(&self, window: &str) {
+        self.states
+            .lock()
+            .await
+            .retain(|key, _| key.window != window);
+    }
+}
@@ -0,0 +1,218 @@
+use crate::config;
+use crate::conflicts::ConflictResolver;
+use crate::fdir::{epoch_millis, fdir_from_path, FDir};
+use crate::jobs::JobHandle;
+use crate::search_query::{Candidate, Query};
+use crate::utils::{dbg_log, DirWalkerEntry};
+use crate::vfs::CloudProvider;
+use crate::SimpleDirInfo;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::api::path::home_dir;
+
+pub const SEARCH_SCHEME: &str = "search:";
+
+const READ_ONLY: &str =
+    "Saved searches only show their results, change them in the dirs they are in";
+
+/// A search saved in the app config, `search:<name>` lists its results
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct SavedSearch {
+    /// Identifies the search inside of `search:` paths
+    pub name: String,
+    /// Dir the search starts in, a leading `~` is the home dir
+    pub root: String,
+    /// Written like the input of the search, e.g. `ext:pdf modified:<7d`
+    pub query: String,
+    /// Levels of dirs below the root that are searched
+    pub depth: u32,
+}
+
+impl Default for SavedSearch {
+    fn default() -> Self {
+        SavedSearch {
+            name: "".into(),
+            root: "".into(),
+            query: "".into(),
+            depth: 10,
+        }
+    }
+}
+
+/// The files a saved search finds, checked against the disk every time
+pub struct SearchScope {
+    pub root: PathBuf,
+    depth: u32,
+    query: Query,
+    // Without a type: term only files are found, like in the search
+    files_only: bool,
+}
+
+impl SearchScope {
+    pub fn of(search: &SavedSearch) -> Result<SearchScope, String> {
+        let query = Query::parse(&search.query).map_err(|e| e.to_string())?;
+        let root = match search.root.strip_prefix('~') {
+            Some(rest) => home_dir()
+                .ok_or("Couldn't find the home dir")?
+                .join(rest.trim_start_matches(['/', '\\'])),
+            None => PathBuf::from(&search.root),
+        };
+        if !root.is_dir() {
+            return Err(format!("{} isn't a directory", search.root));
+        }
+        Ok(SearchScope {
+            root,
+            depth: search.depth,
+            files_only: !query.filters_type(),
+            query,
+        })
+    }
+
+    /// Whether a path is inside of the root and not deeper than the search goes
+    pub fn covers(&self, path: &Path) -> bool {
+        path.strip_prefix(&self.root).is_ok_and(|rel| {
+            let depth = rel.components().count();
+            depth >= 1 && depth <= self.depth as usize
+        })
+    }
+
+    /// Whether the file at `path` is a result, false if it doesn't exist
+    pub fn matches(&self, path: &Path) -> bool {
+        let Ok(metadata) = fs::metadata(path) else {
+            return false;
+        };
+        if self.files_only && !metadata.is_file() {
+            return false;
+        }
+        self.query.matches(&Candidate {
+            name: &path.file_name().unwrap_or_default().to_string_lossy(),
+            path: &path.to_string_lossy().replace("\\", "/"),
+            is_dir: metadata.is_dir(),
+            size: if metadata.is_dir() { 0 } else { metadata.len() },
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(epoch_millis)
+                .map(|ms| ms as i64),
+        })
+    }
+
+    /// The current results in walk order, at most `max_items` of them
+    pub fn results(&self, max_items: usize) -> Vec<FDir> {
+        jwalk::WalkDir::new(&self.root)
+            .sort(true)
+            .min_depth(1)
+            .max_depth(self.depth as usize)
+            .skip_hidden(false)
+            .follow_links(true)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| self.matches(path))
+            .filter_map(|path| fdir_from_path(&path))
+            .take(max_items)
+            .collect()
+    }
+}
+
+/// The name of the saved search a `search:` path belongs to
+pub fn name_of(path: &str) -> Option<&str> {
+    path.strip_prefix(SEARCH_SCHEME)
+        .map(|name| name.trim_end_matches(['/', '\\']))
+        .filter(|name| !name.is_empty())
+}
+
+/// Looks the search of a `search:` path up in the app config
+pub fn saved_search(path: &str) -> Result<SavedSearch, String> {
+    let name = name_of(path).ok_or(format!("{} is not a saved search", path))?;
+    config::load()
+        .saved_searches
+        .into_iter()
+        .find(|search| search.name == name)
+        .ok_or(format!("There's no saved search named {}", name))
+}
+
+/// Saved searches as read-only dirs of their live results. The results keep
+/// their paths on the local disk, so copying, deleting and renaming them
+/// works like in any other listing.
+pub struct SavedSearches;
+
+impl CloudProvider for SavedSearches {
+    fn new() -> Self {
+        SavedSearches
+    }
+
+    fn authenticate(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn sign_out(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Runs the search again every time
+    fn read_dir(&mut self, path: &PathBuf) -> Result<Vec<FDir>, String> {
+        let search = saved_search(&path.to_string_lossy())?;
+        dbg_log(format!(
+            "Running saved search {}: {} in {}",
+            search.name, search.query, search.root
+        ));
+        let max_items = config::load().max_items as usize;
+        Ok(SearchScope::of(&search)?.results(max_items))
+    }
+
+    fn download(&mut self, from_path: &str, _to_path: &str) -> Result<String, String> {
+        Err(format!("{} is a saved search, not a file", from_path))
+    }
+
+    fn upload(&mut self, _from_path: &str, _to_path: &str) -> Result<(), String> {
+        Err(READ_ONLY.into())
+    }
+
+    fn create_dir(&mut self, _from_path: &str, _to_path: &str) -> Result<(), String> {
+        Err(READ_ONLY.into())
+    }
+
+    fn search(&mut self, _fname: &str) -> Result<Vec<DirWalkerEntry>, String> {
+        Err("Search in the root of the saved search instead".into())
+    }
+
+    fn get_item_size(&self, path: &str) -> Result<SimpleDirInfo, String> {
+        let search = saved_search(path)?;
+        let results = SearchScope::of(&search)?.results(config::load().max_items as usize);
+        Ok(SimpleDirInfo {
+            size: results.iter().map(|item| item.size).sum(),
+            count_elements: results.len() as u64,
+        })
+    }
+
+    fn copy_items(
+        &mut self,
+        _job: &JobHandle,
+        _resolver: &mut ConflictResolver,
+        _arr_items: Vec<FDir>,
+        _copy_to_path: &str,
+    ) -> Result<(), String> {
+        Err(READ_ONLY.into())
+    }
+
+    fn move_items(
+        &mut self,
+        _job: &JobHandle,
+        _resolver: &mut ConflictResolver,
+        _arr_items: Vec<FDir>,
+        _move_to_path: &str,
+    ) -> Result<(), String> {
+        Err(READ_ONLY.into())
+    }
+
+    fn delete(&mut self, _path: &str) -> Result<(), String> {
+        Err(READ_ONLY.into())
+    }
+
+    fn rename(&mut self, _path: &str, _new_name: &str) -> Result<(), String> {
+        Err(READ_ONLY.into())
+    }
+}
@@ -0,0 +1,653 @@
+use crate::fdir::{epoch_millis, extension_of};
+use crate::search_query::{Candidate, Query};
+use crate::utils::{dbg_log, err_log, wng_log, DirWalkerEntry};
+use chrono::{DateTime, Utc};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tauri::api::path::config_dir;
+
+/// Entries written to the database in one transaction while scanning
+const BATCH_SIZE: usize = 5000;
+/// Quiet time after the last change before the index gets updated
+const DEBOUNCE: Duration = Duration::from_millis(500);
+/// Upper bound for the delay while a root keeps changing
+const MAX_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RootState {
+    /// The first scan is running, searches still walk the disk
+    Indexing,
+    Ready,
+    /// A scan is running while the previous results are used
+    Updating,
+    Failed,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct IndexRoot {
+    pub path: String,
+    pub state: RootState,
+    pub entries: u64,
+    /// Epoch millis of the last finished scan
+    pub indexed_at: Option<i64>,
+    pub error: Option<String>,
+}
+
+struct Root {
+    state: RootState,
+    indexed_at: Option<i64>,
+    error: Option<String>,
+    /// Without a watcher changes are missed, so searches walk the disk
+    watch_error: Option<String>,
+    // Dropping the watcher also ends its update thread
+    _watcher: Option<RecommendedWatcher>,
+}
+
+/// A row of the entries table
+struct IndexedEntry {
+    path: String,
+    name: String,
+    is_dir: bool,
+    size: u64,
+    /// Epoch millis
+    modified: Option<i64>,
+}
+
+/// Managed state keeping the names, sizes and modification times of
+/// everything below the indexed roots in a database, so searches inside of
+/// them don't have to walk the disk. Roots are scanned again on start and
+/// kept up to date by watchers while the app runs.
+pub struct SearchIndex {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    /// None if the database couldn't be opened
+    db: Mutex<Option<Connection>>,
+    roots: Mutex<HashMap<PathBuf, Root>>,
+}
+
+impl Default for SearchIndex {
+    fn default() -> Self {
+        let db = match open_db() {
+            Ok(db) => Some(db),
+            Err(e) => {
+                err_log(format!("Failed to open the search index: {}", e));
+                None
+            }
+        };
+        let stored: Vec<(PathBuf, Option<i64>)> = db
+            .as_ref()
+            .and_then(|db| {
+                let mut statement = db.prepare("SELECT path, indexed_at FROM roots").ok()?;
+                let rows = statement
+                    .query_map([], |row| {
+                        Ok((PathBuf::from(row.get::<_, String>(0)?), row.get(1)?))
+                    })
+                    .ok()?;
+                Some(rows.filter_map(|row| row.ok()).collect())
+            })
+            .unwrap_or_default();
+
+        let index = SearchIndex {
+            inner: Arc::new(Inner {
+                db: Mutex::new(db),
+                roots: Mutex::new(HashMap::new()),
+            }),
+        };
+        // Catches up with everything that changed while the app was closed
+        for (path, indexed_at) in stored {
+            index.start(path, indexed_at);
+        }
+        index
+    }
+}
+
+impl SearchIndex {
+    pub fn add_root(&self, path: &Path) -> Result<(), String> {
+        if !path.is_dir() {
+            return Err(format!("{:?} isn't a local folder", path));
+        }
+        let path = normalize(path);
+        for root in self.inner.roots.lock().unwrap().keys() {
+            if path.starts_with(root) {
+                return Err(format!(
+                    "{:?} is already indexed as part of {:?}",
+                    path, root
+                ));
+            }
+            if root.starts_with(&path) {
+                return Err(format!(
+                    "{:?} is already indexed, remove it before indexing {:?}",
+                    root, path
+                ));
+            }
+        }
+        self.inner.with_db(|db| {
+            db.execute(
+                "INSERT OR IGNORE INTO roots (path, indexed_at) VALUES (?1, NULL)",
+                params![key_of(&path)],
+            )
+            .map(|_| ())
+        })?;
+        dbg_log(format!("Indexing {:?}", path));
+        self.start(path, None);
+        Ok(())
+    }
+
+    pub fn remove_root(&self, path: &Path) -> Result<(), String> {
+        let path = normalize(path);
+        // A running scan notices it's gone and stops before writing again
+        if self.inner.roots.lock().unwrap().remove(&path).is_none() {
+            return Err(format!("{:?} isn't indexed", path));
+        }
+        dbg_log(format!("Removing {:?} from the search index", path));
+        let root = key_of(&path);
+        self.inner.with_db(|db| {
+            db.execute("DELETE FROM entries WHERE root = ?1", params![root])?;
+            db.execute("DELETE FROM roots WHERE path = ?1", params![root])
+                .map(|_| ())
+        })
+    }
+
+    pub fn status(&self) -> Result<Vec<IndexRoot>, String> {
+        let mut counts: HashMap<String, u64> = HashMap::new();
+        self.inner.with_db(|db| {
+            let mut statement = db.prepare("SELECT root, COUNT(*) FROM entries GROUP BY root")?;
+            let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            for row in rows {
+                let (root, count) = row?;
+                counts.insert(root, count);
+            }
+            Ok(())
+        })?;
+        let mut roots: Vec<IndexRoot> = self
+            .inner
+            .roots
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(path, root)| {
+                let path = key_of(path);
+                IndexRoot {
+                    entries: counts.get(&path).copied().unwrap_or(0),
+                    path,
+                    state: root.state,
+                    indexed_at: root.indexed_at,
+                    error: root.error.clone().or_else(|| {
+                        root.watch_error.as_ref().map(|e| {
+                            format!("Changes aren't tracked, searches walk the disk: {}", e)
+                        })
+                    }),
+                }
+            })
+            .collect();
+        roots.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(roots)
+    }
+
+    /// Whether searches in `dir` can be answered by the index
+    pub fn covers(&self, dir: &Path) -> bool {
+        let dir = normalize(dir);
+        self.inner.roots.lock().unwrap().iter().any(|(path, root)| {
+            dir.starts_with(path)
+                && matches!(root.state, RootState::Ready | RootState::Updating)
+                && root.watch_error.is_none()
+        })
+    }
+
+    /// Everything below `dir` matching the query, at most `depth` levels deep
+    pub fn search(
+        &self,
+        dir: &Path,
+        query: &Query,
+        depth: u32,
+        files_only: bool,
+        max_items: usize,
+    ) -> Result<Vec<DirWalkerEntry>, String> {
+        let (prefix, end) = prefix_range(&normalize(dir));
+        // LIKE only ignores the case of ASCII letters
+        let hint = query
+            .name_hint()
+            .filter(|hint| hint.is_ascii())
+            .map(|hint| format!("%{}%", escape_like(&hint)))
+            .unwrap_or("%".into());
+
+        let mut found = vec![];
+        self.inner.with_db(|db| {
+            let mut statement = db.prepare_cached(
+                "SELECT path, name, is_dir, size, modified FROM entries
+                 WHERE path > ?1 AND path < ?2 AND name LIKE ?3 ESCAPE '\\'
+                 ORDER BY path",
+            )?;
+            let rows = statement.query_map(params![prefix, end, hint], |row| {
+                Ok(IndexedEntry {
+                    path: row.get(0)?,
+                    name: row.get(1)?,
+                    is_dir: row.get(2)?,
+                    size: row.get(3)?,
+                    modified: row.get(4)?,
+                })
+            })?;
+            for row in rows {
+                let entry = row?;
+                let entry_depth =
+                    entry.path[prefix.len()..].matches(MAIN_SEPARATOR).count() as u32 + 1;
+                if entry_depth > depth
+                    || (files_only && entry.is_dir)
+                    || !query.matches(&Candidate {
+                        name: &entry.name,
+                        path: &entry.path.replace('\\', "/"),
+                        is_dir: entry.is_dir,
+                        size: entry.size,
+                        modified: entry.modified,
+                    })
+                {
+                    continue;
+                }
+                found.push(DirWalkerEntry {
+                    extension: extension_of(&entry.name, entry.is_dir).to_lowercase(),
+                    last_modified: entry
+                        .modified
+                        .and_then(DateTime::<Utc>::from_timestamp_millis)
+                        .map_or("".into(), |time| format!("{:?}", time)),
+                    name: entry.name,
+                    path: entry.path,
+                    depth: entry_depth,
+                    is_dir: entry.is_dir,
+                    is_file: !entry.is_dir,
+                    size: entry.size,
+                });
+                if found.len() >= max_items {
+                    break;
+                }
+            }
+            Ok(())
+        })?;
+        Ok(found)
+    }
+
+    /// Watches the root and scans it in the background
+    fn start(&self, path: PathBuf, indexed_at: Option<i64>) {
+        let (watcher, watch_error) = match watch(self.inner.clone(), path.clone()) {
+            Ok(watcher) => (Some(watcher), None),
+            Err(e) => {
+                // The next start scans it again, changes until then are missed
+                wng_log(format!("Failed to watch {:?}: {}", path, e));
+                (None, Some(e))
+            }
+        };
+        self.inner.roots.lock().unwrap().insert(
+            path.clone(),
+            Root {
+                state: match indexed_at {
+                    Some(_) => RootState::Updating,
+                    None => RootState::Indexing,
+                },
+                indexed_at,
+                error: None,
+                watch_error,
+                _watcher: watcher,
+            },
+        );
+
+        let inner = self.inner.clone();
+        std::thread::spawn(move || {
+            let started = Instant::now();
+            let scanned = inner.scan(&path);
+            let mut roots = inner.roots.lock().unwrap();
+            let Some(root) = roots.get_mut(&path) else {
+                return;
+            };
+            match scanned {
+                Ok(count) => {
+                    dbg_log(format!(
+                        "Indexed {} entries of {:?} in {:?}",
+                        count,
+                        path,
+                        started.elapsed()
+                    ));
+                    root.state = RootState::Ready;
+                    root.indexed_at = Some(now_millis());
+                    root.error = None;
+                }
+                Err(e) => {
+                    err_log(format!("Failed to index {:?}: {}", path, e));
+                    root.state = RootState::Failed;
+                    root.error = Some(e);
+                }
+            }
+        });
+    }
+}
+
+impl Inner {
+    fn with_db<T>(
+        &self,
+        run: impl FnOnce(&Connection) -> rusqlite::Result<T>,
+    ) -> Result<T, String> {
+        let db = self.db.lock().unwrap();
+        let db = db.as_ref().ok_or("The search index isn't available")?;
+        run(db).map_err(|e| e.to_string())
+    }
+
+    fn has_root(&self, path: &Path) -> bool {
+        self.roots.lock().unwrap().contains_key(path)
+    }
+
+    /// Writes everything below `path` with the current time as scan id and
+    /// drops the entries older scans left behind, changes the watcher
+    /// writes in the meantime are newer and stay
+    fn scan(&self, path: &Path) -> Result<u64, String> {
+        let root = key_of(path);
+        let scan = now_millis();
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        let mut count = 0;
+        for entry in jwalk::WalkDir::new(path)
+            .skip_hidden(false)
+            .follow_links(false)
+            .min_depth(1)
+        {
+            let Some(entry) = entry.ok().and_then(|entry| entry_of(&entry.path())) else {
+                continue;
+            };
+            batch.push(entry);
+            if batch.len() >= BATCH_SIZE {
+                count += batch.len() as u64;
+                self.write(path, &root, scan, &mut batch)?;
+            }
+        }
+        count += batch.len() as u64;
+        self.write(path, &root, scan, &mut batch)?;
+
+        self.with_db(|db| {
+            db.execute(
+                "DELETE FROM entries WHERE root = ?1 AND scan < ?2",
+                params![root, scan],
+            )?;
+            db.execute(
+                "UPDATE roots SET indexed_at = ?2 WHERE path = ?1",
+                params![root, now_millis()],
+            )
+            .map(|_| ())
+        })?;
+        Ok(count)
+    }
+
+    /// Stores the entries unless the root was removed in the meantime
+    fn write(
+        &self,
+        path: &Path,
+        root: &str,
+        scan: i64,
+        batch: &mut Vec<IndexedEntry>,
+    ) -> Result<(), String> {
+        let mut db = self.db.lock().unwrap();
+        let db = db.as_mut().ok_or("The search index isn't available")?;
+        // Checked while holding the database, removing a root deletes its
+        // entries only after it's gone from the roots
+        if !self.has_root(path) {
+            return Err("The folder isn't indexed anymore".into());
+        }
+        let transaction = db.transaction().map_err(|e| e.to_string())?;
+        {
+            let mut statement = transaction
+                .prepare_cached(
+                    "INSERT OR REPLACE INTO entries (path, root, name, is_dir, size, modified, scan)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                )
+                .map_err(|e| e.to_string())?;
+            for entry in batch.drain(..) {
+                statement
+                    .execute(params![
+                        entry.path,
+                        root,
+                        entry.name,
+                        entry.is_dir,
+                        entry.size,
+                        entry.modified,
+                        scan
+                    ])
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        transaction.commit().map_err(|e| e.to_string())
+    }
+
+    /// Updates the entries of changed paths, new dirs are scanned completely
+    fn apply_changes(&self, path: &Path, changed: HashSet<PathBuf>) -> Result<(), String> {
+        let root = key_of(path);
+        let scan = now_millis();
+        let mut batch = vec![];
+        let mut removed = vec![];
+        for changed in changed {
+            if !changed.starts_with(path) || changed == path {
+                continue;
+            }
+            match entry_of(&changed) {
+                Some(entry) => {
+                    let is_dir = entry.is_dir;
+                    batch.push(entry);
+                    if is_dir {
+                        // Dirs moved in only show up as a single change
+                        batch.extend(
+                            jwalk::WalkDir::new(&changed)
+                                .skip_hidden(false)
+                                .follow_links(false)
+                                .min_depth(1)
+                                .into_iter()
+                                .filter_map(|entry| entry_of(&entry.ok()?.path())),
+                        );
+                    }
+                }
+                None => removed.push(key_of(&changed)),
+            }
+        }
+
+        if !removed.is_empty() {
+            self.with_db(|db| {
+                let mut statement = db.prepare_cached(
+                    "DELETE FROM entries WHERE path = ?1 OR (path > ?2 AND path < ?3)",
+                )?;
+                for removed in &removed {
+                    statement.execute(params![
+                        removed,
+                        format!("{}{}", removed, MAIN_SEPARATOR),
+                        format!("{}{}", removed, (MAIN_SEPARATOR as u8 + 1) as char)
+                    ])?;
+                }
+                Ok(())
+            })?;
+        }
+        if !batch.is_empty() {
+            self.write(path, &root, scan, &mut batch)?;
+        }
+        Ok(())
+    }
+}
+
+fn watch(inner: Arc<Inner>, path: PathBuf) -> Result<RecommendedWatcher, String> {
+    let (tx, rx) = channel();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+        if let Ok(event) = res {
+            let _ = tx.send(event.paths);
+        }
+    })
+    .map_err(|e| e.to_string())?;
+    watcher
+        .watch(&path, RecursiveMode::Recursive)
+        .map_err(|e| e.to_string())?;
+    std::thread::spawn(move || update(rx, inner, path));
+    Ok(watcher)
+}
+
+fn update(rx: Receiver<Vec<PathBuf>>, inner: Arc<Inner>, path: PathBuf) {
+    // Ends once the watcher got dropped
+    while let Ok(paths) = rx.recv() {
+        let mut changed: HashSet<PathBuf> = paths.into_iter().collect();
+        let started = Instant::now();
+        while started.elapsed() < MAX_DELAY {
+            match rx.recv_timeout(DEBOUNCE) {
+                Ok(paths) => changed.extend(paths),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+        if !inner.has_root(&path) {
+            return;
+        }
+        if let Err(e) = inner.apply_changes(&path, changed) {
+            err_log(format!("Failed to update the index of {:?}: {}", path, e));
+        }
+    }
+}
+
+fn open_db() -> Result<Connection, String> {
+    let dir = config_dir().unwrap_or_default().join("com.codriver.dev");
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let db = Connection::open(dir.join("search_index.sqlite")).map_err(|e| e.to_string())?;
+    db.execute_batch(
+        "PRAGMA journal_mode = WAL;
+         PRAGMA synchronous = NORMAL;
+         CREATE TABLE IF NOT EXISTS roots (
+             path TEXT PRIMARY KEY,
+             indexed_at INTEGER
+         );
+         CREATE TABLE IF NOT EXISTS entries (
+             path TEXT PRIMARY KEY,
+             root TEXT NOT NULL,
+             name TEXT NOT NULL,
+             is_dir INTEGER NOT NULL,
+             size INTEGER NOT NULL,
+             modified INTEGER,
+             scan INTEGER NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS entries_root ON entries (root, scan);",
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(db)
+}
+
+/// Links are indexed as themselves, not what they point to
+fn entry_of(path: &Path) -> Option<IndexedEntry> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    Some(IndexedEntry {
+        path: key_of(path),
+        name: path.file_name()?.to_string_lossy().to_string(),
+        is_dir: metadata.is_dir(),
+        size: if metadata.is_dir() { 0 } else { metadata.len() },
+        modified: metadata
+            .modified()
+            .ok()
+            .and_then(epoch_millis)
+            .map(|millis| millis as i64),
+    })
+}
+
+/// Without trailing separators and `.` parts, links stay as they are
+fn normalize(path: &Path) -> PathBuf {
+    path.components().collect()
+}
+
+fn key_of(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+/// The keys of everything below `dir` are between these, they all start with
+/// the first one
+fn prefix_range(dir: &Path) -> (String, String) {
+    let mut prefix = key_of(dir);
+    if !prefix.ends_with(MAIN_SEPARATOR) {
+        prefix.push(MAIN_SEPARATOR);
+    }
+    // Everything starting with the prefix sorts before this
+    let end = format!(
+        "{}{}",
+        &prefix[..prefix.len() - 1],
+        (MAIN_SEPARATOR as u8 + 1) as char
+    );
+    (prefix, end)
+}
+
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+fn now_millis() -> i64 {
+    epoch_millis(SystemTime::now()).unwrap_or(0) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(parts: &[&str]) -> String {
+        parts.join(std::path::MAIN_SEPARATOR_STR)
+    }
+
+    fn is_below(dir: &str, path: &str) -> bool {
+        let (prefix, end) = prefix_range(Path::new(dir));
+        path > prefix.as_str() && path < end.as_str()
+    }
+
+    #[test]
+    fn prefix_range_ends_after_the_separator() {
+        let dir = key(&["", "home", "user"]);
+        let (prefix, end) = prefix_range(Path::new(&dir));
+        assert_eq!(prefix, format!("{}{}", dir, MAIN_SEPARATOR));
+        assert_eq!(
+            end,
+            format!("{}{}", dir, (MAIN_SEPARATOR as u8 + 1) as char)
+        );
+    }
+
+    #[test]
+    fn prefix_range_covers_everything_below_the_dir() {
+        let dir = key(&["", "home", "user"]);
+        assert!(is_below(&dir, &key(&[&dir, "a"])));
+        assert!(is_below(&dir, &key(&[&dir, "a", "b", "c.txt"])));
+        assert!(is_below(&dir, &key(&[&dir, "~zz"])));
+        assert!(is_below(&dir, &key(&[&dir, "\u{10FFFF}"])));
+    }
+
+    #[test]
+    fn prefix_range_leaves_out_the_dir_and_its_siblings() {
+        let dir = key(&["", "home", "user"]);
+        assert!(!is_below(&dir, &dir));
+        assert!(!is_below(&dir, &format!("{}-old", dir)));
+        assert!(!is_below(&dir, &format!("{}.bak", dir)));
+        assert!(!is_below(&dir, &format!("{}0", dir)));
+        assert!(!is_below(&dir, &format!("{}s", dir)));
+        assert!(!is_below(&dir, &key(&[&format!("{}s", dir), "a"])));
+        assert!(!is_below(&dir, &key(&["", "home", "other", "a"])));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn prefix_range_of_the_root_covers_all_paths() {
+        let (prefix, end) = prefix_range(Path::new("/"));
+        assert_eq!((prefix.as_str(), end.as_str()), ("/", "0"));
+        assert!(is_below("/", "/home"));
+        assert!(is_below("/", "/home/user/a"));
+    }
+
+    #[test]
+    fn normalize_drops_trailing_separators() {
+        let dir = key(&["", "home", "user"]);
+        let with_separator = format!("{}{}", dir, MAIN_SEPARATOR);
+        assert_eq!(
+            prefix_range(&normalize(Path::new(&with_separator))),
+            prefix_range(Path::new(&dir))
+        );
+    }
+}
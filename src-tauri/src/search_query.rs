@@ -0,0 +1,1114 @@
+use crate::fuzzy::{fuzzy_match, FuzzyMatch};
+use crate::listing_spec::glob_to_regex;
+use chrono::{Local, NaiveDate, TimeZone};
+use regex::Regex;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A parsed search query like `*.rs size:>10KB modified:<7d NOT path:target`.
+///
+/// Terms next to each other have to match all, `OR`, `AND`, `NOT` (or a
+/// leading `-`) and parentheses combine them. The operators are only
+/// recognized in upper case, so `or` is still a name to look for.
+///
+/// - `foo` matches names containing `foo`, `*.rs` and `file?.[ch]` are globs
+///   matching the whole name, `/^\d+\.log$/` is a regex
+/// - `"two words"` matches names containing the quoted text
+/// - `name:` is the same as a plain term, `path:` matches the whole path
+///   with text or a glob
+/// - `ext:pdf` or `ext:jpg,png` matches the extension
+/// - `size:>10MB`, `size:<=1.5GB`, `size:0` with B, KB, MB, GB and TB
+/// - `modified:<7d` for the last 7 days, `modified:>1y` for older than a year
+///   with s, min, h, d, w, mo and y. `modified:>=2024-01-31` for dates.
+/// - `type:file` or `type:dir`
+///
+/// Matching is case insensitive. An empty query matches everything. In
+/// fuzzy mode plain words match names like fzf does, `mr` finds `main.rs`.
+#[derive(Debug, Clone)]
+pub struct Query {
+    expr: Expr,
+}
+
+/// Why a query couldn't be parsed, `position` is the char offset in the query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryError {
+    UnknownField {
+        field: String,
+        position: usize,
+    },
+    MissingValue {
+        field: String,
+        position: usize,
+    },
+    InvalidValue {
+        field: String,
+        value: String,
+        expected: &'static str,
+    },
+    InvalidRegex {
+        pattern: String,
+        reason: String,
+    },
+    InvalidGlob {
+        pattern: String,
+    },
+    UnclosedQuote {
+        position: usize,
+    },
+    UnclosedRegex {
+        position: usize,
+    },
+    UnclosedParenthesis {
+        position: usize,
+    },
+    UnexpectedParenthesis {
+        position: usize,
+    },
+    /// `AND`, `OR` or `NOT` without something to apply it to
+    MissingOperand {
+        operator: String,
+        position: usize,
+    },
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QueryError::UnknownField { field, position } => write!(
+                f,
+                "Unknown search field '{}:' at {}, use name, path, ext, size, modified or type",
+                field, position
+            ),
+            QueryError::MissingValue { field, position } => {
+                write!(f, "'{}:' at {} needs a value", field, position)
+            }
+            QueryError::InvalidValue {
+                field,
+                value,
+                expected,
+            } => write!(
+                f,
+                "'{}' isn't valid for '{}:', expected {}",
+                value, field, expected
+            ),
+            QueryError::InvalidRegex { pattern, reason } => {
+                write!(f, "Invalid regex /{}/: {}", pattern, reason)
+            }
+            QueryError::InvalidGlob { pattern } => write!(f, "Invalid glob pattern {}", pattern),
+            QueryError::UnclosedQuote { position } => {
+                write!(f, "The quote at {} is never closed", position)
+            }
+            QueryError::UnclosedRegex { position } => {
+                write!(f, "The regex at {} is missing its closing /", position)
+            }
+            QueryError::UnclosedParenthesis { position } => {
+                write!(f, "The parenthesis at {} is never closed", position)
+            }
+            QueryError::UnexpectedParenthesis { position } => {
+                write!(f, "Unexpected ) at {}", position)
+            }
+            QueryError::MissingOperand { operator, position } => {
+                write!(
+                    f,
+                    "{} at {} is missing something to combine",
+                    operator, position
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// An entry a query gets evaluated against
+pub struct Candidate<'a> {
+    pub name: &'a str,
+    pub path: &'a str,
+    pub is_dir: bool,
+    pub size: u64,
+    /// Epoch millis
+    pub modified: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    All,
+    Term(Predicate),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+#[derive(Debug, Clone)]
+enum Predicate {
+    Name(TextMatch),
+    Path(TextMatch),
+    /// Lower case, without the dot
+    Ext(Vec<String>),
+    Size(Bounds),
+    /// Epoch millis
+    Modified(Bounds),
+    IsDir(bool),
+}
+
+#[derive(Debug, Clone)]
+enum TextMatch {
+    /// Lower case
+    Contains(String),
+    Pattern(Regex),
+    Fuzzy(String),
+}
+
+/// Inclusive range
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+    min: Option<i64>,
+    max: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Term(RawTerm),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    kind: TokenKind,
+    position: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct RawTerm {
+    field: Option<String>,
+    value: RawValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum RawValue {
+    Plain(String),
+    Quoted(String),
+    Regex(String),
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Result<Query, QueryError> {
+        Query::parse_with(query, false)
+    }
+
+    pub fn parse_fuzzy(query: &str) -> Result<Query, QueryError> {
+        Query::parse_with(query, true)
+    }
+
+    fn parse_with(query: &str, fuzzy: bool) -> Result<Query, QueryError> {
+        let tokens = tokenize(query)?;
+        let mut parser = Parser {
+            tokens: tokens.into_iter().peekable(),
+            fuzzy,
+        };
+        let expr = match parser.tokens.peek() {
+            None => Expr::All,
+            Some(_) => parser.parse_or()?,
+        };
+        if let Some(token) = parser.tokens.next() {
+            return Err(QueryError::UnexpectedParenthesis {
+                position: token.position,
+            });
+        }
+        Ok(Query { expr })
+    }
+
+    pub fn matches(&self, candidate: &Candidate) -> bool {
+        self.expr.matches(candidate)
+    }
+
+    /// Whether the query says anything about files vs dirs
+    pub fn filters_type(&self) -> bool {
+        self.expr
+            .any_term(&|predicate| matches!(predicate, Predicate::IsDir(_)))
+    }
+
+    /// Literal text every match has in its name if there is one, for
+    /// providers that can only search by a part of the name
+    pub fn name_hint(&self) -> Option<String> {
+        self.expr.name_hint()
+    }
+
+    /// How well a name matches the fuzzy terms, None without fuzzy terms
+    /// or if the name doesn't match them
+    pub fn fuzzy_match(&self, name: &str) -> Option<FuzzyMatch> {
+        let mut found = vec![];
+        self.expr.fuzzy_matches(name, &mut found);
+        let mut found = found.into_iter();
+        let mut combined = found.next()?;
+        for other in found {
+            combined.score += other.score;
+            combined.positions.extend(other.positions);
+        }
+        combined.positions.sort_unstable();
+        combined.positions.dedup();
+        Some(combined)
+    }
+}
+
+impl Expr {
+    fn matches(&self, candidate: &Candidate) -> bool {
+        match self {
+            Expr::All => true,
+            Expr::Term(predicate) => predicate.matches(candidate),
+            Expr::Not(expr) => !expr.matches(candidate),
+            Expr::And(exprs) => exprs.iter().all(|expr| expr.matches(candidate)),
+            Expr::Or(exprs) => exprs.iter().any(|expr| expr.matches(candidate)),
+        }
+    }
+
+    fn any_term(&self, check: &impl Fn(&Predicate) -> bool) -> bool {
+        match self {
+            Expr::All => false,
+            Expr::Term(predicate) => check(predicate),
+            Expr::Not(expr) => expr.any_term(check),
+            Expr::And(exprs) | Expr::Or(exprs) => exprs.iter().any(|expr| expr.any_term(check)),
+        }
+    }
+
+    /// Negated terms don't count
+    fn fuzzy_matches(&self, name: &str, found: &mut Vec<FuzzyMatch>) {
+        match self {
+            Expr::Term(Predicate::Name(TextMatch::Fuzzy(pattern))) => {
+                found.extend(fuzzy_match(pattern, name))
+            }
+            Expr::And(exprs) | Expr::Or(exprs) => {
+                for expr in exprs {
+                    expr.fuzzy_matches(name, found);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn name_hint(&self) -> Option<String> {
+        match self {
+            Expr::Term(Predicate::Name(TextMatch::Contains(text))) => Some(text.clone()),
+            Expr::And(exprs) => exprs.iter().find_map(|expr| expr.name_hint()),
+            _ => None,
+        }
+    }
+}
+
+impl Predicate {
+    fn matches(&self, candidate: &Candidate) -> bool {
+        match self {
+            Predicate::Name(text) => text.matches(candidate.name),
+            Predicate::Path(text) => text.matches(candidate.path),
+            Predicate::Ext(exts) => candidate
+                .name
+                .rsplit_once('.')
+                .is_some_and(|(_, ext)| exts.iter().any(|e| e.eq_ignore_ascii_case(ext))),
+            Predicate::Size(bounds) => !candidate.is_dir && bounds.contains(candidate.size as i64),
+            Predicate::Modified(bounds) => candidate
+                .modified
+                .is_some_and(|modified| bounds.contains(modified)),
+            Predicate::IsDir(is_dir) => candidate.is_dir == *is_dir,
+        }
+    }
+}
+
+impl TextMatch {
+    fn matches(&self, text: &str) -> bool {
+        match self {
+            TextMatch::Contains(part) => text.to_lowercase().contains(part),
+            TextMatch::Pattern(regex) => regex.is_match(text),
+            TextMatch::Fuzzy(pattern) => fuzzy_match(pattern, text).is_some(),
+        }
+    }
+}
+
+impl Bounds {
+    fn contains(&self, value: i64) -> bool {
+        self.min.map_or(true, |min| value >= min) && self.max.map_or(true, |max| value <= max)
+    }
+
+    /// `comparison` against the range `from..until` a value like `2024-01-31`
+    /// or `10MB` stands for, `until` is exclusive
+    fn of(comparison: Comparison, from: i64, until: i64) -> Bounds {
+        let (min, max) = match comparison {
+            Comparison::Less => (None, Some(from - 1)),
+            Comparison::LessOrEqual => (None, Some(until - 1)),
+            Comparison::Greater => (Some(until), None),
+            Comparison::GreaterOrEqual => (Some(from), None),
+            Comparison::Equal => (Some(from), Some(until - 1)),
+        };
+        Bounds { min, max }
+    }
+}
+
+struct Parser {
+    tokens: Peekable<std::vec::IntoIter<Token>>,
+    /// Plain words match names fuzzy
+    fuzzy: bool,
+}
+
+impl Parser {
+    fn parse_or(&mut self) -> Result<Expr, QueryError> {
+        let mut exprs = vec![self.parse_and()?];
+        while let Some(token) = self.tokens.next_if(|token| token.kind == TokenKind::Or) {
+            exprs.push(self.operand("OR", token.position, Parser::parse_and)?);
+        }
+        Ok(match exprs.len() {
+            1 => exprs.remove(0),
+            _ => Expr::Or(exprs),
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, QueryError> {
+        let mut exprs = vec![self.parse_not()?];
+        loop {
+            match self.tokens.peek().map(|token| &token.kind) {
+                Some(TokenKind::And) => {
+                    let position = self.tokens.next().map_or(0, |token| token.position);
+                    exprs.push(self.operand("AND", position, Parser::parse_not)?);
+                }
+                // Terms next to each other have to match all
+                Some(TokenKind::Term(_)) | Some(TokenKind::Not) | Some(TokenKind::Open) => {
+                    exprs.push(self.parse_not()?)
+                }
+                _ => break,
+            }
+        }
+        Ok(match exprs.len() {
+            1 => exprs.remove(0),
+            _ => Expr::And(exprs),
+        })
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, QueryError> {
+        match self.tokens.next_if(|token| token.kind == TokenKind::Not) {
+            Some(token) => Ok(Expr::Not(Box::new(self.operand(
+                "NOT",
+                token.position,
+                Parser::parse_not,
+            )?))),
+            None => self.parse_primary(),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, QueryError> {
+        let Some(token) = self.tokens.next() else {
+            return Err(QueryError::MissingOperand {
+                operator: "The query".into(),
+                position: 0,
+            });
+        };
+        match token.kind {
+            TokenKind::Term(term) => {
+                Ok(Expr::Term(predicate_of(term, token.position, self.fuzzy)?))
+            }
+            TokenKind::Open => {
+                let expr = self.operand("(", token.position, Parser::parse_or)?;
+                match self.tokens.next() {
+                    Some(Token {
+                        kind: TokenKind::Close,
+                        ..
+                    }) => Ok(expr),
+                    _ => Err(QueryError::UnclosedParenthesis {
+                        position: token.position,
+                    }),
+                }
+            }
+            TokenKind::Close => Err(QueryError::UnexpectedParenthesis {
+                position: token.position,
+            }),
+            TokenKind::And | TokenKind::Or => Err(QueryError::MissingOperand {
+                operator: if token.kind == TokenKind::And {
+                    "AND"
+                } else {
+                    "OR"
+                }
+                .into(),
+                position: token.position,
+            }),
+            TokenKind::Not => unreachable!("NOT is handled by parse_not"),
+        }
+    }
+
+    /// Parses what an operator applies to, failing if there's nothing left
+    fn operand(
+        &mut self,
+        operator: &str,
+        position: usize,
+        parse: fn(&mut Parser) -> Result<Expr, QueryError>,
+    ) -> Result<Expr, QueryError> {
+        let missing = match self.tokens.peek().map(|token| &token.kind) {
+            None | Some(TokenKind::Close) | Some(TokenKind::And) | Some(TokenKind::Or) => true,
+            Some(_) => false,
+        };
+        if missing {
+            return Err(QueryError::MissingOperand {
+                operator: operator.to_string(),
+                position,
+            });
+        }
+        parse(self)
+    }
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, QueryError> {
+    let mut tokens = vec![];
+    let mut chars = query.chars().peekable();
+    let mut position = 0;
+    while let Some(&c) = chars.peek() {
+        let start = position;
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                position += 1;
+                continue;
+            }
+            '(' | ')' => {
+                chars.next();
+                position += 1;
+                tokens.push(Token {
+                    kind: if c == '(' {
+                        TokenKind::Open
+                    } else {
+                        TokenKind::Close
+                    },
+                    position: start,
+                });
+                continue;
+            }
+            _ => {}
+        }
+
+        // A leading - negates the term
+        if c == '-' {
+            chars.next();
+            position += 1;
+            if chars
+                .peek()
+                .is_some_and(|c| !c.is_whitespace() && *c != ')')
+            {
+                tokens.push(Token {
+                    kind: TokenKind::Not,
+                    position: start,
+                });
+                continue;
+            }
+            tokens.push(Token {
+                kind: TokenKind::Term(RawTerm {
+                    field: None,
+                    value: RawValue::Plain("-".into()),
+                }),
+                position: start,
+            });
+            continue;
+        }
+
+        let term = read_term(&mut chars, &mut position)?;
+        let kind = match &term {
+            RawTerm {
+                field: None,
+                value: RawValue::Plain(word),
+            } if word == "AND" || word == "OR" || word == "NOT" => match word.as_str() {
+                "AND" => TokenKind::And,
+                "OR" => TokenKind::Or,
+                _ => TokenKind::Not,
+            },
+            _ => TokenKind::Term(term),
+        };
+        tokens.push(Token {
+            kind,
+            position: start,
+        });
+    }
+    Ok(tokens)
+}
+
+/// Reads `word`, `field:word`, `"quoted text"`, `field:"quoted text"`,
+/// `/regex/` or `name:/regex/`
+fn read_term(chars: &mut Peekable<Chars>, position: &mut usize) -> Result<RawTerm, QueryError> {
+    let start = *position;
+    let mut field = None;
+    let mut word = String::new();
+    loop {
+        match chars.peek() {
+            Some('"') if word.is_empty() => {
+                chars.next();
+                *position += 1;
+                let text = read_until(chars, position, '"')
+                    .ok_or(QueryError::UnclosedQuote { position: start })?;
+                return Ok(RawTerm {
+                    field,
+                    value: RawValue::Quoted(text),
+                });
+            }
+            // Paths start with a / themselves, only names can be regexes
+            Some('/') if word.is_empty() && field.as_deref().map_or(true, |f| f == "name") => {
+                chars.next();
+                *position += 1;
+                let pattern = read_until(chars, position, '/')
+                    .ok_or(QueryError::UnclosedRegex { position: start })?;
+                return Ok(RawTerm {
+                    field,
+                    value: RawValue::Regex(pattern),
+                });
+            }
+            Some(':')
+                if field.is_none() && !word.is_empty() && word.chars().all(char::is_alphabetic) =>
+            {
+                chars.next();
+                *position += 1;
+                field = Some(std::mem::take(&mut word).to_lowercase());
+            }
+            Some(c) if !c.is_whitespace() && *c != '(' && *c != ')' => {
+                word.push(*c);
+                chars.next();
+                *position += 1;
+            }
+            _ => break,
+        }
+    }
+    Ok(RawTerm {
+        field,
+        value: RawValue::Plain(word),
+    })
+}
+
+/// The text up to the unescaped `end`, `\` escapes `end` and itself
+fn read_until(chars: &mut Peekable<Chars>, position: &mut usize, end: char) -> Option<String> {
+    let mut text = String::new();
+    while let Some(c) = chars.next() {
+        *position += 1;
+        match c {
+            '\\' if chars.peek() == Some(&end) => {
+                text.push(end);
+                chars.next();
+                *position += 1;
+            }
+            c if c == end => return Some(text),
+            c => text.push(c),
+        }
+    }
+    None
+}
+
+fn predicate_of(term: RawTerm, position: usize, fuzzy: bool) -> Result<Predicate, QueryError> {
+    let field = term.field.unwrap_or_else(|| "name".into());
+    let text = match &term.value {
+        RawValue::Plain(text) | RawValue::Quoted(text) | RawValue::Regex(text) => text.clone(),
+    };
+    if text.is_empty() && !matches!(term.value, RawValue::Quoted(_)) {
+        return Err(QueryError::MissingValue { field, position });
+    }
+    let invalid = |expected| QueryError::InvalidValue {
+        field: field.clone(),
+        value: text.clone(),
+        expected,
+    };
+
+    match field.as_str() {
+        "name" => Ok(Predicate::Name(text_match(term.value, fuzzy)?)),
+        "path" => Ok(Predicate::Path(text_match(term.value, false)?)),
+        "ext" => Ok(Predicate::Ext(
+            text.split(',')
+                .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+                .filter(|ext| !ext.is_empty())
+                .collect(),
+        )),
+        "size" => {
+            let (comparison, value) = split_comparison(&text);
+            let (from, until) = parse_size(value).ok_or(invalid("a size like >10MB or <=512KB"))?;
+            Ok(Predicate::Size(Bounds::of(comparison, from, until)))
+        }
+        "modified" => {
+            let (comparison, value) = split_comparison(&text);
+            Ok(Predicate::Modified(
+                modified_bounds(comparison, value)
+                    .ok_or(invalid("an age like <7d or a date like >=2024-01-31"))?,
+            ))
+        }
+        "type" => match text.to_lowercase().as_str() {
+            "dir" | "folder" | "directory" => Ok(Predicate::IsDir(true)),
+            "file" => Ok(Predicate::IsDir(false)),
+            _ => Err(invalid("file or dir")),
+        },
+        _ => Err(QueryError::UnknownField { field, position }),
+    }
+}
+
+fn text_match(value: RawValue, fuzzy: bool) -> Result<TextMatch, QueryError> {
+    match value {
+        RawValue::Quoted(text) => Ok(TextMatch::Contains(text.to_lowercase())),
+        RawValue::Regex(pattern) => Regex::new(&format!("(?i){}", pattern))
+            .map(TextMatch::Pattern)
+            .map_err(|e| QueryError::InvalidRegex {
+                pattern,
+                // The first lines repeat the pattern with a marker
+                reason: e
+                    .to_string()
+                    .lines()
+                    .last()
+                    .unwrap_or_default()
+                    .trim_start_matches("error: ")
+                    .to_string(),
+            }),
+        RawValue::Plain(text) if text.contains(['*', '?', '[']) => glob_to_regex(&text)
+            .map(TextMatch::Pattern)
+            .map_err(|_| QueryError::InvalidGlob { pattern: text }),
+        RawValue::Plain(text) if fuzzy => Ok(TextMatch::Fuzzy(text)),
+        RawValue::Plain(text) => Ok(TextMatch::Contains(text.to_lowercase())),
+    }
+}
+
+fn split_comparison(text: &str) -> (Comparison, &str) {
+    for (prefix, comparison) in [
+        (">=", Comparison::GreaterOrEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">", Comparison::Greater),
+        ("<", Comparison::Less),
+        ("=", Comparison::Equal),
+    ] {
+        if let Some(rest) = text.strip_prefix(prefix) {
+            return (comparison, rest.trim());
+        }
+    }
+    (Comparison::Equal, text.trim())
+}
+
+/// The byte range a size like `1.5GB` stands for, in steps of its unit
+fn parse_size(text: &str) -> Option<(i64, i64)> {
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let unit: i64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1024,
+        "m" | "mb" => 1024 * 1024,
+        "g" | "gb" => 1024 * 1024 * 1024,
+        "t" | "tb" => 1024 * 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    let from = (number * unit as f64) as i64;
+    // `10MB` is everything that's shown as 10 MB, `10.5MB` is more precise
+    let step = match number.fract() == 0.0 {
+        true => unit,
+        false => (unit / 10).max(1),
+    };
+    Some((from, from + step))
+}
+
+/// Ages like `7d` compare how long ago something was modified, `<7d` means
+/// within the last 7 days. Dates compare by the day.
+fn modified_bounds(comparison: Comparison, text: &str) -> Option<Bounds> {
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        let from = Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+            .earliest()?
+            .timestamp_millis();
+        let until = Local
+            .from_local_datetime(&date.succ_opt()?.and_hms_opt(0, 0, 0)?)
+            .earliest()?
+            .timestamp_millis();
+        return Some(Bounds::of(comparison, from, until));
+    }
+
+    let split = text.find(|c: char| !c.is_ascii_digit())?;
+    let (number, unit) = text.split_at(split);
+    let number: i64 = number.parse().ok()?;
+    let unit: i64 = match unit.to_lowercase().as_str() {
+        "s" => 1000,
+        "min" => 60 * 1000,
+        "h" => 60 * 60 * 1000,
+        "d" => 24 * 60 * 60 * 1000,
+        "w" => 7 * 24 * 60 * 60 * 1000,
+        "mo" => 30 * 24 * 60 * 60 * 1000,
+        "y" => 365 * 24 * 60 * 60 * 1000,
+        _ => return None,
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_millis() as i64;
+    let at = now.checked_sub(number.checked_mul(unit)?)?;
+    // Younger than the age means modified after that point in time
+    Some(match comparison {
+        Comparison::Less | Comparison::LessOrEqual | Comparison::Equal => Bounds {
+            min: Some(at),
+            max: None,
+        },
+        Comparison::Greater | Comparison::GreaterOrEqual => Bounds {
+            min: None,
+            max: Some(at),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KB: i64 = 1024;
+    const DAY: i64 = 24 * 60 * 60 * 1000;
+
+    fn file(name: &str, size: u64) -> Candidate<'_> {
+        Candidate {
+            name,
+            path: name,
+            is_dir: false,
+            size,
+            modified: None,
+        }
+    }
+
+    fn matches(query: &str, candidate: &Candidate) -> bool {
+        Query::parse(query).unwrap().matches(candidate)
+    }
+
+    fn term(field: Option<&str>, value: RawValue, position: usize) -> Token {
+        Token {
+            kind: TokenKind::Term(RawTerm {
+                field: field.map(String::from),
+                value,
+            }),
+            position,
+        }
+    }
+
+    fn now() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64
+    }
+
+    #[test]
+    fn tokenizes_fields_and_quotes() {
+        assert_eq!(
+            tokenize(r#"name:"two words" foo"#).unwrap(),
+            vec![
+                term(Some("name"), RawValue::Quoted("two words".into()), 0),
+                term(None, RawValue::Plain("foo".into()), 17),
+            ]
+        );
+        assert_eq!(
+            tokenize(r#""say \"hi\"""#).unwrap(),
+            vec![term(None, RawValue::Quoted(r#"say "hi""#.into()), 0)]
+        );
+        assert_eq!(
+            tokenize("EXT:pdf").unwrap(),
+            vec![term(Some("ext"), RawValue::Plain("pdf".into()), 0)]
+        );
+    }
+
+    #[test]
+    fn tokenizes_regexes_only_for_names() {
+        assert_eq!(
+            tokenize(r"/^\d+\.log$/").unwrap(),
+            vec![term(None, RawValue::Regex(r"^\d+\.log$".into()), 0)]
+        );
+        assert_eq!(
+            tokenize(r"name:/a\/b/").unwrap(),
+            vec![term(Some("name"), RawValue::Regex("a/b".into()), 0)]
+        );
+        assert_eq!(
+            tokenize("path:/home/user").unwrap(),
+            vec![term(Some("path"), RawValue::Plain("/home/user".into()), 0)]
+        );
+    }
+
+    #[test]
+    fn tokenizes_operators_and_negation() {
+        let kinds = |query| {
+            tokenize(query)
+                .unwrap()
+                .into_iter()
+                .map(|token| token.kind)
+                .collect::<Vec<_>>()
+        };
+        let plain = |text: &str| {
+            TokenKind::Term(RawTerm {
+                field: None,
+                value: RawValue::Plain(text.into()),
+            })
+        };
+        assert_eq!(
+            kinds("(a OR b) AND NOT c"),
+            vec![
+                TokenKind::Open,
+                plain("a"),
+                TokenKind::Or,
+                plain("b"),
+                TokenKind::Close,
+                TokenKind::And,
+                TokenKind::Not,
+                plain("c"),
+            ]
+        );
+        assert_eq!(kinds("-foo"), vec![TokenKind::Not, plain("foo")]);
+        // Lower case operators and a lone - are names to look for
+        assert_eq!(kinds("a or b"), vec![plain("a"), plain("or"), plain("b")]);
+        assert_eq!(kinds("a - b"), vec![plain("a"), plain("-"), plain("b")]);
+    }
+
+    #[test]
+    fn matches_names_globs_and_regexes() {
+        assert!(matches("main", &file("Main.rs", 0)));
+        assert!(!matches("main", &file("lib.rs", 0)));
+        assert!(matches("*.rs", &file("main.RS", 0)));
+        assert!(!matches("*.rs", &file("main.rs.bak", 0)));
+        assert!(matches("file?.[ch]", &file("file1.c", 0)));
+        assert!(matches(r"/^\d+\.log$/", &file("2024.log", 0)));
+        assert!(!matches(r"/^\d+\.log$/", &file("app.log", 0)));
+        assert!(matches(r#""two words""#, &file("Two Words.txt", 0)));
+        assert!(!matches(r#""two words""#, &file("two_words.txt", 0)));
+    }
+
+    #[test]
+    fn matches_fields() {
+        let mut candidate = file("report.PDF", 20 * KB as u64);
+        candidate.path = "/home/user/docs/report.PDF";
+        assert!(matches("ext:pdf", &candidate));
+        assert!(matches("ext:jpg,.pdf", &candidate));
+        assert!(!matches("ext:jpg", &candidate));
+        assert!(matches("path:docs", &candidate));
+        assert!(matches("path:/home/*/docs/*", &candidate));
+        assert!(matches("type:file", &candidate));
+        assert!(!matches("type:dir", &candidate));
+        assert!(matches("size:>10KB", &candidate));
+        assert!(!matches("size:<10KB", &candidate));
+
+        // Dirs have no size to compare
+        candidate.is_dir = true;
+        assert!(matches("type:folder", &candidate));
+        assert!(!matches("size:>0", &candidate));
+    }
+
+    #[test]
+    fn combines_terms() {
+        let candidate = file("main.rs", 0);
+        assert!(matches("main ext:rs", &candidate));
+        assert!(!matches("main ext:py", &candidate));
+        assert!(matches("lib OR main", &candidate));
+        assert!(matches("(lib OR main) AND ext:rs", &candidate));
+        assert!(!matches("(lib OR main) NOT ext:rs", &candidate));
+        assert!(!matches("-main", &candidate));
+        assert!(matches("-(lib OR test)", &candidate));
+        assert!(matches("", &candidate));
+    }
+
+    #[test]
+    fn or_binds_weaker_than_and() {
+        // a OR (b AND c)
+        assert!(matches("main OR lib test", &file("main.rs", 0)));
+        assert!(!matches("(main OR lib) test", &file("main.rs", 0)));
+    }
+
+    #[test]
+    fn reports_errors_with_positions() {
+        let error = |query| Query::parse(query).unwrap_err();
+        assert_eq!(
+            error("foo bar:x"),
+            QueryError::UnknownField {
+                field: "bar".into(),
+                position: 4
+            }
+        );
+        assert_eq!(
+            error("size:"),
+            QueryError::MissingValue {
+                field: "size".into(),
+                position: 0
+            }
+        );
+        assert!(matches!(
+            error("size:big"),
+            QueryError::InvalidValue { field, .. } if field == "size"
+        ));
+        assert!(matches!(
+            error("modified:soon"),
+            QueryError::InvalidValue { field, .. } if field == "modified"
+        ));
+        assert!(matches!(
+            error("type:link"),
+            QueryError::InvalidValue { field, .. } if field == "type"
+        ));
+        assert!(matches!(error("/(/"), QueryError::InvalidRegex { .. }));
+        assert_eq!(error("a \"b"), QueryError::UnclosedQuote { position: 2 });
+        assert_eq!(error("a /b"), QueryError::UnclosedRegex { position: 2 });
+        assert_eq!(error("(a"), QueryError::UnclosedParenthesis { position: 0 });
+        assert_eq!(
+            error("a)"),
+            QueryError::UnexpectedParenthesis { position: 1 }
+        );
+        assert_eq!(
+            error("a OR"),
+            QueryError::MissingOperand {
+                operator: "OR".into(),
+                position: 2
+            }
+        );
+        assert_eq!(
+            error("AND a"),
+            QueryError::MissingOperand {
+                operator: "AND".into(),
+                position: 0
+            }
+        );
+        assert_eq!(
+            error("a NOT"),
+            QueryError::MissingOperand {
+                operator: "NOT".into(),
+                position: 2
+            }
+        );
+        assert!(matches!(error("()"), QueryError::MissingOperand { .. }));
+    }
+
+    #[test]
+    fn parses_sizes_in_steps_of_their_unit() {
+        assert_eq!(parse_size("0"), Some((0, 1)));
+        assert_eq!(parse_size("512b"), Some((512, 513)));
+        assert_eq!(parse_size("10KB"), Some((10 * KB, 11 * KB)));
+        assert_eq!(parse_size("10 mb"), Some((10 * KB * KB, 11 * KB * KB)));
+        assert_eq!(
+            parse_size("1.5GB"),
+            Some((
+                3 * KB * KB * KB / 2,
+                3 * KB * KB * KB / 2 + KB * KB * KB / 10
+            ))
+        );
+        assert_eq!(parse_size("2t"), Some((2 * KB.pow(4), 3 * KB.pow(4))));
+        assert_eq!(parse_size("10XB"), None);
+        assert_eq!(parse_size("KB"), None);
+        assert_eq!(parse_size(""), None);
+    }
+
+    #[test]
+    fn compares_against_the_whole_range_of_a_size() {
+        let (from, until) = parse_size("10KB").unwrap();
+        let bounds = |comparison| {
+            let bounds = Bounds::of(comparison, from, until);
+            (bounds.min, bounds.max)
+        };
+        assert_eq!(bounds(Comparison::Less), (None, Some(10 * KB - 1)));
+        assert_eq!(bounds(Comparison::LessOrEqual), (None, Some(11 * KB - 1)));
+        assert_eq!(bounds(Comparison::Greater), (Some(11 * KB), None));
+        assert_eq!(bounds(Comparison::GreaterOrEqual), (Some(10 * KB), None));
+        assert_eq!(
+            bounds(Comparison::Equal),
+            (Some(10 * KB), Some(11 * KB - 1))
+        );
+        assert_eq!(
+            split_comparison(">= 10KB"),
+            (Comparison::GreaterOrEqual, "10KB")
+        );
+        assert_eq!(split_comparison("10KB"), (Comparison::Equal, "10KB"));
+    }
+
+    #[test]
+    fn ages_compare_how_long_ago_something_was_modified() {
+        let before = now();
+        let within = modified_bounds(Comparison::Less, "7d").unwrap();
+        let after = now();
+        assert_eq!(within.max, None);
+        let min = within.min.unwrap();
+        assert!(min >= before - 7 * DAY && min <= after - 7 * DAY);
+
+        let older = modified_bounds(Comparison::Greater, "1y").unwrap();
+        assert_eq!(older.min, None);
+        assert!(older.max.unwrap() <= now() - 365 * DAY);
+
+        let hours = modified_bounds(Comparison::Equal, "36h").unwrap();
+        assert!(hours.contains(now() - DAY));
+        assert!(!hours.contains(now() - 2 * DAY));
+
+        assert!(modified_bounds(Comparison::Less, "7x").is_none());
+        assert!(modified_bounds(Comparison::Less, "d").is_none());
+        assert!(modified_bounds(Comparison::Less, "7").is_none());
+    }
+
+    #[test]
+    fn dates_compare_by_the_day() {
+        let day = modified_bounds(Comparison::Equal, "2024-01-31").unwrap();
+        let (min, max) = (day.min.unwrap(), day.max.unwrap());
+        let noon = min + DAY / 2;
+        assert!(max - min + 1 >= 23 * 60 * 60 * 1000 && max - min < 25 * 60 * 60 * 1000);
+        assert!(day.contains(noon));
+
+        let before = modified_bounds(Comparison::Less, "2024-01-31").unwrap();
+        assert_eq!((before.min, before.max), (None, Some(min - 1)));
+        let after = modified_bounds(Comparison::Greater, "2024-01-31").unwrap();
+        assert_eq!((after.min, after.max), (Some(max + 1), None));
+        assert!(modified_bounds(Comparison::Equal, "2024-02-30").is_none());
+    }
+
+    #[test]
+    fn matches_modified_times() {
+        let mut candidate = file("a.txt", 0);
+        assert!(!matches("modified:<7d", &candidate));
+        candidate.modified = Some(now() - DAY);
+        assert!(matches("modified:<7d", &candidate));
+        assert!(!matches("modified:>7d", &candidate));
+    }
+
+    #[test]
+    fn fuzzy_terms_match_like_fzf() {
+        let query = Query::parse_fuzzy("mr").unwrap();
+        assert!(query.matches(&file("main.rs", 0)));
+        assert!(!query.matches(&file("lib.rs", 0)));
+        assert_eq!(query.fuzzy_match("main.rs").unwrap().positions, vec![0, 5]);
+
+        // Globs and quotes stay exact, negated terms don't count for ranking
+        let query = Query::parse_fuzzy(r#"mr "rs" NOT lib"#).unwrap();
+        assert!(query.matches(&file("main.rs", 0)));
+        assert_eq!(query.fuzzy_match("main.rs").unwrap().positions, vec![0, 5]);
+        assert!(Query::parse_fuzzy("*.rs")
+            .unwrap()
+            .fuzzy_match("main.rs")
+            .is_none());
+        assert!(Query::parse("mr").unwrap().fuzzy_match("main.rs").is_none());
+    }
+
+    #[test]
+    fn combines_fuzzy_matches_of_several_terms() {
+        let query = Query::parse_fuzzy("ma rs").unwrap();
+        let found = query.fuzzy_match("main.rs").unwrap();
+        assert_eq!(found.positions, vec![0, 1, 5, 6]);
+        assert_eq!(
+            found.score,
+            fuzzy_match("ma", "main.rs").unwrap().score
+                + fuzzy_match("rs", "main.rs").unwrap().score
+        );
+    }
+
+    #[test]
+    fn hints_at_literal_names() {
+        let hint = |query| Query::parse(query).unwrap().name_hint();
+        assert_eq!(hint("Main ext:rs"), Some("main".into()));
+        assert_eq!(hint("ext:rs Main"), Some("main".into()));
+        assert_eq!(hint("*.rs"), None);
+        assert_eq!(hint("main OR lib"), None);
+        assert_eq!(hint("NOT main"), None);
+
+        assert!(Query::parse("a type:dir").unwrap().filters_type());
+        assert!(Query::parse("NOT type:file").unwrap().filters_type());
+        assert!(!Query::parse("a ext:rs").unwrap().filters_type());
+    }
+}
@@ -0,0 +1,1051 @@
+use crate::conflicts::{unique_name, ConflictResolver, ConflictSide, Resolution, SetAside};
+use crate::fdir::{extension_of, FDir, FileType};
+use crate::jobs::JobHandle;
+use crate::utils::{dbg_log, err_log, measure_entries, wng_log, DirWalkerEntry};
+use crate::vfs::CloudProvider;
+use crate::SimpleDirInfo;
+use serde::{Deserialize, Serialize};
+use ssh2::{CheckResult, KnownHostFileKind, Session};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::api::path::home_dir;
+
+pub const SFTP_PREFIX: &str = "sftp://";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Reads of the SFTP subsystem are answered in chunks of up to 256 KB
+const TRANSFER_BUFFER: usize = 256 * 1024;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum SftpAuth {
+    /// Keys of the running ssh-agent
+    Agent,
+    /// An unencrypted private key, encrypted ones have to go through the agent
+    Key { private_key: PathBuf },
+    /// The password is only passed when connecting and never saved
+    Password,
+}
+
+/// Connection settings, saved ones are kept in the app config
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct SftpProfile {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub auth: SftpAuth,
+    /// Opened after connecting, the home dir of the user if empty
+    pub remote_path: String,
+    /// Defaults to `~/.ssh/known_hosts`
+    pub known_hosts: Option<PathBuf>,
+    /// Adds the key of an unknown host to known_hosts instead of refusing
+    /// the connection, like `StrictHostKeyChecking=accept-new`
+    pub accept_new_host: bool,
+}
+
+impl Default for SftpProfile {
+    fn default() -> Self {
+        SftpProfile {
+            name: "".into(),
+            host: "".into(),
+            port: 22,
+            username: "".into(),
+            auth: SftpAuth::Agent,
+            remote_path: "".into(),
+            known_hosts: None,
+            accept_new_host: false,
+        }
+    }
+}
+
+impl SftpProfile {
+    /// `user@host:port`, identifies the connection inside of `sftp://` paths
+    pub fn address(&self) -> String {
+        format!("{}@{}:{}", self.username, self.host, self.port)
+    }
+}
+
+struct Connection {
+    // Kept alive as long as the SFTP channel is used
+    _session: Session,
+    sftp: ssh2::Sftp,
+}
+
+/// A local path or a path on one of the connected servers
+#[derive(PartialEq, Eq, Debug)]
+enum Location {
+    Local(PathBuf),
+    Remote { address: String, path: PathBuf },
+}
+
+impl Location {
+    fn parse(path: &str) -> Result<Location, String> {
+        let path = path.replace("\\", "/");
+        match path.strip_prefix(SFTP_PREFIX) {
+            Some(rest) => {
+                let (address, remote) = match rest.find('/') {
+                    Some(i) => (&rest[..i], &rest[i..]),
+                    None => (rest, "/"),
+                };
+                // Paths without a port use the default one
+                let address = match address.rsplit_once(':') {
+                    Some(_) => address.to_string(),
+                    None => format!("{}:22", address),
+                };
+                Ok(Location::Remote {
+                    address,
+                    path: PathBuf::from(remote),
+                })
+            }
+            None => Ok(Location::Local(PathBuf::from(path))),
+        }
+    }
+
+    fn path(&self) -> &Path {
+        match self {
+            Location::Local(path) => path,
+            Location::Remote { path, .. } => path,
+        }
+    }
+
+    /// Another path on the same server
+    fn with_path(&self, path: PathBuf) -> Location {
+        match self {
+            Location::Local(_) => Location::Local(path),
+            Location::Remote { address, .. } => Location::Remote {
+                address: address.clone(),
+                path,
+            },
+        }
+    }
+
+    fn join(&self, name: &str) -> Location {
+        match self {
+            Location::Local(path) => Location::Local(path.join(name)),
+            Location::Remote { address, path } => Location::Remote {
+                address: address.clone(),
+                path: path.join(name),
+            },
+        }
+    }
+
+    fn name(&self) -> String {
+        self.path()
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Location::Local(path) => write!(f, "{}", path.to_string_lossy()),
+            Location::Remote { address, path } => {
+                write!(f, "{}{}{}", SFTP_PREFIX, address, path.to_string_lossy())
+            }
+        }
+    }
+}
+
+/// In-process SFTP client, one connection per `user@host:port`
+pub struct Sftp {
+    connections: HashMap<String, Connection>,
+    /// Saved profiles, used to reconnect to paths of earlier sessions
+    profiles: Vec<SftpProfile>,
+}
+
+impl CloudProvider for Sftp {
+    fn new() -> Self {
+        Sftp {
+            connections: HashMap::new(),
+            profiles: vec![],
+        }
+    }
+
+    /// Every server authenticates on its own in `connect`
+    fn authenticate(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn sign_out(&mut self) -> Result<(), String> {
+        self.connections.clear();
+        Ok(())
+    }
+
+    fn read_dir(&mut self, path: &PathBuf) -> Result<Vec<FDir>, String> {
+        let (address, remote_path) = self.remote(&path.to_string_lossy())?;
+        let sftp = &self.connections[&address].sftp;
+        let entries = sftp.readdir(&remote_path).map_err(|e| e.to_string())?;
+        Ok(entries
+            .into_iter()
+            .map(|(entry_path, stat)| fdir_from_stat(sftp, &address, &entry_path, &stat))
+            .collect())
+    }
+
+    fn download(&mut self, from_path: &str, to_path: &str) -> Result<String, String> {
+        let from = Location::parse(from_path)?;
+        self.ensure_connected(&from)?;
+        let saved_path = PathBuf::from(to_path).join(from.name());
+        self.copy_stream(&from, &Location::Local(saved_path.clone()), &mut |_| Ok(()))?;
+        Ok(saved_path.to_string_lossy().to_string())
+    }
+
+    /// Copies without a job, so an existing target is never replaced
+    fn upload(&mut self, from_path: &str, to_path: &str) -> Result<(), String> {
+        let to = Location::parse(to_path)?;
+        self.ensure_connected(&to)?;
+        let from = Location::Local(PathBuf::from(from_path));
+        let target = to.join(&from.name());
+        if self.exists(&target) {
+            return Err(format!("{} already exists", target));
+        }
+        if !self.is_dir(&from)? {
+            return self.copy_stream(&from, &target, &mut |_| Ok(()));
+        }
+        self.make_dir(&target)?;
+        for name in self.child_names(&from)? {
+            self.upload(&from.join(&name).to_string(), &target.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn create_dir(&mut self, from_path: &str, to_path: &str) -> Result<(), String> {
+        let dir_name = from_path.replace("\\", "/");
+        let dir_name = dir_name.split('/').next_back().unwrap_or_default();
+        let (address, remote_path) = self.remote(to_path)?;
+        dbg_log(format!("Creating directory {}/{}", to_path, dir_name));
+        self.connections[&address]
+            .sftp
+            .mkdir(&remote_path.join(dir_name), 0o755)
+            .map_err(|e| e.to_string())
+    }
+
+    fn search(&mut self, _fname: &str) -> Result<Vec<DirWalkerEntry>, String> {
+        Err("Searching isn't supported on SFTP connections".into())
+    }
+
+    fn get_item_size(&self, path: &str) -> Result<SimpleDirInfo, String> {
+        let location = Location::parse(path)?;
+        let (address, remote_path) = match &location {
+            Location::Remote { address, path } => (address, path),
+            Location::Local(_) => return Err(format!("{} is not an SFTP path", path)),
+        };
+        let sftp = self.sftp(address)?;
+        let mut info = SimpleDirInfo {
+            size: 0,
+            count_elements: 0,
+        };
+        measure(sftp, remote_path, &mut info)?;
+        Ok(info)
+    }
+
+    fn copy_items(
+        &mut self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        arr_items: Vec<FDir>,
+        copy_to_path: &str,
+    ) -> Result<(), String> {
+        let to = Location::parse(copy_to_path)?;
+        self.ensure_connected(&to)?;
+        for item in arr_items {
+            let from = Location::parse(&item.path)?;
+            self.ensure_connected(&from)?;
+            self.check_target(&from, &to)?;
+            let (count, size) = self.measure_files(&from)?;
+            job.add_total(count, size);
+            self.copy_into(job, resolver, &from, &to)?;
+        }
+        Ok(())
+    }
+
+    fn move_items(
+        &mut self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        arr_items: Vec<FDir>,
+        move_to_path: &str,
+    ) -> Result<(), String> {
+        let to = Location::parse(move_to_path)?;
+        self.ensure_connected(&to)?;
+        for item in arr_items {
+            let from = Location::parse(&item.path)?;
+            self.ensure_connected(&from)?;
+            let target = to.join(&from.name());
+            if self.canonical(&from) == self.canonical(&target) {
+                continue;
+            }
+            self.check_target(&from, &to)?;
+            let (count, size) = self.measure_files(&from)?;
+            job.add_total(count, size);
+            if self.rename_on_server(job, resolver, &from, &target)? {
+                job.add_done(count, size);
+                continue;
+            }
+            // The source of an item is only removed once all of it was copied
+            if !self.copy_into(job, resolver, &from, &to)? {
+                wng_log(format!("{} was kept, conflicts skipped some of it", from));
+                continue;
+            }
+            match &from {
+                Location::Remote { .. } => self.delete(&item.path)?,
+                Location::Local(path) if item.is_dir => {
+                    fs::remove_dir_all(path).map_err(|e| e.to_string())?
+                }
+                Location::Local(path) => fs::remove_file(path).map_err(|e| e.to_string())?,
+            }
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, path: &str) -> Result<(), String> {
+        let (address, remote_path) = self.remote(path)?;
+        let sftp = &self.connections[&address].sftp;
+        remove_recursive(sftp, &remote_path).map_err(|e| e.to_string())
+    }
+
+    fn rename(&mut self, path: &str, new_name: &str) -> Result<(), String> {
+        let (address, remote_path) = self.remote(path)?;
+        let target = remote_path.with_file_name(new_name);
+        self.connections[&address]
+            .sftp
+            .rename(&remote_path, &target, None)
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl Sftp {
+    pub fn set_profiles(&mut self, profiles: Vec<SftpProfile>) {
+        self.profiles = profiles;
+    }
+
+    /// Connects and authenticates, returns the `sftp://` path of the remote dir
+    pub fn connect(
+        &mut self,
+        profile: &SftpProfile,
+        password: Option<String>,
+    ) -> Result<String, String> {
+        let address = profile.address();
+        let addr = (profile.host.as_str(), profile.port)
+            .to_socket_addrs()
+            .map_err(|e| e.to_string())?
+            .next()
+            .ok_or(format!("Couldn't resolve {}", profile.host))?;
+        let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+            .map_err(|e| format!("Couldn't connect to {}: {}", address, e))?;
+        let mut session = Session::new().map_err(|e| e.to_string())?;
+        session.set_tcp_stream(stream);
+        session.handshake().map_err(|e| e.to_string())?;
+        verify_host_key(&session, profile)?;
+
+        let authenticated = match &profile.auth {
+            SftpAuth::Agent => session.userauth_agent(&profile.username),
+            SftpAuth::Key { private_key } => {
+                session.userauth_pubkey_file(&profile.username, None, private_key, None)
+            }
+            SftpAuth::Password => session.userauth_password(
+                &profile.username,
+                password.as_deref().ok_or("A password is required")?,
+            ),
+        };
+        authenticated.map_err(|e| format!("Authentication failed: {}", e))?;
+        if !session.authenticated() {
+            return Err("Authentication failed".into());
+        }
+
+        let sftp = session.sftp().map_err(|e| e.to_string())?;
+        let remote_path = match profile.remote_path.trim() {
+            "" => sftp
+                .realpath(Path::new("."))
+                .map_err(|e| e.to_string())?
+                .to_string_lossy()
+                .to_string(),
+            remote_path => remote_path.to_string(),
+        };
+        dbg_log(format!("Connected to {}", address));
+        self.connections.insert(
+            address.clone(),
+            Connection {
+                _session: session,
+                sftp,
+            },
+        );
+        Ok(format!("{}{}{}", SFTP_PREFIX, address, remote_path))
+    }
+
+    pub fn disconnect(&mut self, path: &str) -> Result<(), String> {
+        match Location::parse(path)? {
+            Location::Remote { address, .. } => {
+                self.connections.remove(&address);
+                dbg_log(format!("Disconnected from {}", address));
+                Ok(())
+            }
+            Location::Local(_) => Err(format!("{} is not an SFTP path", path)),
+        }
+    }
+
+    /// Addresses of the open connections
+    pub fn connections(&self) -> Vec<String> {
+        self.connections.keys().cloned().collect()
+    }
+
+    /// Splits an `sftp://` path and makes sure its server is connected
+    fn remote(&mut self, path: &str) -> Result<(String, PathBuf), String> {
+        let location = Location::parse(path)?;
+        self.ensure_connected(&location)?;
+        match location {
+            Location::Remote { address, path } => Ok((address, path)),
+            Location::Local(_) => Err(format!("{} is not an SFTP path", path)),
+        }
+    }
+
+    /// Reconnects with a saved profile, e.g. for paths of a previous session
+    fn ensure_connected(&mut self, location: &Location) -> Result<(), String> {
+        let address = match location {
+            Location::Remote { address, .. } => address,
+            Location::Local(_) => return Ok(()),
+        };
+        if self.connections.contains_key(address) {
+            return Ok(());
+        }
+        let profile = self
+            .profiles
+            .iter()
+            .find(|profile| profile.address() == *address && profile.auth != SftpAuth::Password)
+            .cloned()
+            .ok_or(format!("Not connected to {}", address))?;
+        self.connect(&profile, None).map(|_| ())
+    }
+
+    fn sftp(&self, address: &str) -> Result<&ssh2::Sftp, String> {
+        self.connections
+            .get(address)
+            .map(|connection| &connection.sftp)
+            .ok_or(format!("Not connected to {}", address))
+    }
+
+    fn is_dir(&self, location: &Location) -> Result<bool, String> {
+        match location {
+            Location::Local(path) => Ok(path.is_dir()),
+            Location::Remote { address, path } => Ok(self
+                .sftp(address)?
+                .stat(path)
+                .map_err(|e| e.to_string())?
+                .is_dir()),
+        }
+    }
+
+    fn child_names(&self, location: &Location) -> Result<Vec<String>, String> {
+        let paths = match location {
+            Location::Local(path) => fs::read_dir(path)
+                .map_err(|e| e.to_string())?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect::<Vec<_>>(),
+            Location::Remote { address, path } => self
+                .sftp(address)?
+                .readdir(path)
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .map(|(path, _)| path)
+                .collect(),
+        };
+        Ok(paths
+            .iter()
+            .filter_map(|path| path.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .collect())
+    }
+
+    fn exists(&self, location: &Location) -> bool {
+        match location {
+            Location::Local(path) => fs::symlink_metadata(path).is_ok(),
+            Location::Remote { address, path } => self
+                .sftp(address)
+                .is_ok_and(|sftp| sftp.lstat(path).is_ok()),
+        }
+    }
+
+    /// The location with symlinks resolved, unchanged if it doesn't exist
+    fn canonical(&self, location: &Location) -> Location {
+        let path = match location {
+            Location::Local(path) => path.canonicalize().ok(),
+            Location::Remote { address, path } => self
+                .sftp(address)
+                .ok()
+                .and_then(|sftp| sftp.realpath(path).ok()),
+        };
+        location.with_path(path.unwrap_or_else(|| location.path().to_path_buf()))
+    }
+
+    /// Fails if the dir `to` is `from` or inside of it, copying would never end
+    fn check_target(&self, from: &Location, to: &Location) -> Result<(), String> {
+        let inside = match (self.canonical(from), self.canonical(to)) {
+            (Location::Local(from), Location::Local(to)) => to.starts_with(from),
+            (
+                Location::Remote { address, path },
+                Location::Remote {
+                    address: to_address,
+                    path: to_path,
+                },
+            ) => address == to_address && to_path.starts_with(path),
+            _ => false,
+        };
+        match inside {
+            true => Err(format!("Can't copy {} into itself", from)),
+            false => Ok(()),
+        }
+    }
+
+    fn conflict_side(&self, location: &Location) -> Option<ConflictSide> {
+        let (address, path) = match location {
+            Location::Local(path) => {
+                return fs::symlink_metadata(path)
+                    .ok()
+                    .map(|_| ConflictSide::from_path(path))
+            }
+            Location::Remote { address, path } => (address, path),
+        };
+        let sftp = self.sftp(address).ok()?;
+        sftp.lstat(path).ok()?;
+        let stat = sftp.stat(path).ok();
+        let is_dir = stat.as_ref().is_some_and(|stat| stat.is_dir());
+        Some(ConflictSide {
+            path: location.to_string(),
+            name: location.name(),
+            is_dir,
+            size: stat.as_ref().and_then(|stat| stat.size).unwrap_or(0),
+            last_modified: stat
+                .and_then(|stat| stat.mtime)
+                .map_or(0, |secs| secs as u128 * 1000),
+        })
+    }
+
+    /// Asks the resolver what to do with `target`, locally or on a server
+    fn resolve(
+        &self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        from: &Location,
+        target: &Location,
+    ) -> Result<Resolution, String> {
+        let source = self
+            .conflict_side(from)
+            .ok_or(format!("{} doesn't exist", from))?;
+        let existing = self.conflict_side(target);
+        // Overwriting an item with itself would truncate it, copying it into
+        // its own dir makes a copy instead
+        if existing.is_some() && self.canonical(from) == self.canonical(target) {
+            let path = unique_name(target.path(), |path| {
+                self.exists(&target.with_path(path.into()))
+            });
+            return Ok(Resolution::CopyTo(path));
+        }
+        resolver.resolve_target(job, source, existing, target.path(), |path| {
+            self.exists(&target.with_path(path.to_path_buf()))
+        })
+    }
+
+    /// Moves an item by renaming it if it stays on the same server. Returns
+    /// false if it has to be copied instead.
+    fn rename_on_server(
+        &self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        from: &Location,
+        target: &Location,
+    ) -> Result<bool, String> {
+        let (address, path) = match (from, target) {
+            (
+                Location::Remote { address, path },
+                Location::Remote {
+                    address: to_address,
+                    ..
+                },
+            ) if address == to_address => (address, path),
+            _ => return Ok(false),
+        };
+        job.checkpoint()?;
+        let sftp = self.sftp(address)?;
+        let rename = |to: &Path| sftp.rename(path, to, None).map_err(|e| e.to_string());
+        match self.resolve(job, resolver, from, target)? {
+            // Merging into an existing dir goes through a copy
+            Resolution::CopyTo(to) if self.exists(&target.with_path(to.clone())) => Ok(false),
+            Resolution::CopyTo(to) => rename(&to).map(|_| true),
+            Resolution::Replace(to) => {
+                self.replace(&target.with_path(to.clone()), || rename(&to))?;
+                Ok(true)
+            }
+            Resolution::Skip => Ok(true),
+        }
+    }
+
+    /// Replaces `target` with what `write` creates there. The old one is only
+    /// removed once that succeeded and put back otherwise.
+    fn replace<T>(
+        &self,
+        target: &Location,
+        write: impl FnOnce() -> Result<T, String>,
+    ) -> Result<T, String> {
+        let (address, path) = match target {
+            Location::Local(path) => {
+                let mut replaced = SetAside::default();
+                replaced.set_aside(path)?;
+                return replaced.finish(write());
+            }
+            Location::Remote { address, path } => (address, path),
+        };
+        let sftp = self.sftp(address)?;
+        let backup = unique_name(
+            &path.with_file_name(format!(".{}.codriver-old", target.name())),
+            |path| sftp.lstat(path).is_ok(),
+        );
+        sftp.rename(path, &backup, None)
+            .map_err(|e| e.to_string())?;
+        let result = write();
+        match &result {
+            Ok(_) => {
+                if let Err(e) = remove_recursive(sftp, &backup) {
+                    wng_log(format!("Failed to remove the replaced {:?}: {}", backup, e));
+                }
+            }
+            Err(_) => {
+                let _ = remove_recursive(sftp, path);
+                if let Err(e) = sftp.rename(&backup, path, None) {
+                    err_log(format!(
+                        "Failed to restore {}, it was kept as {:?}: {}",
+                        target, backup, e
+                    ));
+                }
+            }
+        }
+        result
+    }
+
+    /// Number of files below a location and their total size
+    fn measure_files(&self, location: &Location) -> Result<(u64, u64), String> {
+        match location {
+            Location::Local(path) => Ok(measure_entries(&path.to_string_lossy())),
+            Location::Remote { address, path } => count_files(self.sftp(address)?, path),
+        }
+    }
+
+    fn make_dir(&self, location: &Location) -> Result<(), String> {
+        match location {
+            Location::Local(path) => fs::create_dir_all(path).map_err(|e| e.to_string()),
+            Location::Remote { address, path } => {
+                let sftp = self.sftp(address)?;
+                if sftp.stat(path).is_err() {
+                    sftp.mkdir(path, 0o755).map_err(|e| e.to_string())?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Copies a file or a whole dir into the dir `to`. Returns false if
+    /// conflicts skipped some of it.
+    fn copy_into(
+        &self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        from: &Location,
+        to: &Location,
+    ) -> Result<bool, String> {
+        job.checkpoint()?;
+        let target = to.join(&from.name());
+        let target = match self.resolve(job, resolver, from, &target)? {
+            Resolution::CopyTo(path) => to.with_path(path),
+            Resolution::Replace(path) => {
+                let target = to.with_path(path);
+                return self.replace(&target, || self.copy_resolved(job, resolver, from, &target));
+            }
+            Resolution::Skip => {
+                let (count, size) = self.measure_files(from)?;
+                job.add_done(count, size);
+                return Ok(false);
+            }
+        };
+        self.copy_resolved(job, resolver, from, &target)
+    }
+
+    fn copy_resolved(
+        &self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        from: &Location,
+        target: &Location,
+    ) -> Result<bool, String> {
+        if !self.is_dir(from)? {
+            self.copy_file(job, from, target)?;
+            return Ok(true);
+        }
+        self.make_dir(target)?;
+        let mut complete = true;
+        for name in self.child_names(from)? {
+            complete &= self.copy_into(job, resolver, &from.join(&name), target)?;
+        }
+        Ok(complete)
+    }
+
+    /// Copies a single file, a partially written file is removed on error
+    fn copy_file(&self, job: &JobHandle, from: &Location, to: &Location) -> Result<(), String> {
+        job.begin_item(&from.name());
+        let copied = self.copy_stream(from, to, &mut |bytes| {
+            job.add_bytes(bytes);
+            job.checkpoint()
+        });
+        if copied.is_err() {
+            let _ = match to {
+                Location::Local(path) => fs::remove_file(path).map_err(|e| e.to_string()),
+                Location::Remote { address, path } => self
+                    .sftp(address)
+                    .and_then(|sftp| sftp.unlink(path).map_err(|e| e.to_string())),
+            };
+            return copied;
+        }
+        job.finish_item();
+        Ok(())
+    }
+
+    /// Writes the file `from` to `to`, calling `on_chunk` with the size of
+    /// every chunk that was written
+    fn copy_stream(
+        &self,
+        from: &Location,
+        to: &Location,
+        on_chunk: &mut dyn FnMut(u64) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let mut reader: Box<dyn Read> = match from {
+            Location::Local(path) => Box::new(fs::File::open(path).map_err(|e| e.to_string())?),
+            Location::Remote { address, path } => {
+                Box::new(self.sftp(address)?.open(path).map_err(|e| e.to_string())?)
+            }
+        };
+        let mut writer: Box<dyn Write> = match to {
+            Location::Local(path) => Box::new(fs::File::create(path).map_err(|e| e.to_string())?),
+            Location::Remote { address, path } => Box::new(
+                self.sftp(address)?
+                    .create(path)
+                    .map_err(|e| e.to_string())?,
+            ),
+        };
+        let mut buf = vec![0; TRANSFER_BUFFER];
+        loop {
+            let read = reader.read(&mut buf).map_err(|e| e.to_string())?;
+            if read == 0 {
+                break;
+            }
+            writer.write_all(&buf[..read]).map_err(|e| e.to_string())?;
+            on_chunk(read as u64)?;
+        }
+        writer.flush().map_err(|e| e.to_string())
+    }
+}
+
+fn verify_host_key(session: &Session, profile: &SftpProfile) -> Result<(), String> {
+    let (key, key_type) = session.host_key().ok_or("The server sent no host key")?;
+    let mut known_hosts = session.known_hosts().map_err(|e| e.to_string())?;
+    let file = match &profile.known_hosts {
+        Some(file) => file.clone(),
+        None => home_dir()
+            .ok_or("Couldn't find the home dir")?
+            .join(".ssh")
+            .join("known_hosts"),
+    };
+    if file.exists() {
+        known_hosts
+            .read_file(&file, KnownHostFileKind::OpenSSH)
+            .map_err(|e| format!("Couldn't read {:?}: {}", file, e))?;
+    }
+
+    match known_hosts.check_port(&profile.host, profile.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::NotFound if profile.accept_new_host => {
+            // known_hosts writes non-default ports as [host]:port
+            let host = match profile.port {
+                22 => profile.host.clone(),
+                port => format!("[{}]:{}", profile.host, port),
+            };
+            known_hosts
+                .add(&host, key, "added by CoDriver", key_type.into())
+                .map_err(|e| e.to_string())?;
+            if let Some(parent) = file.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            known_hosts
+                .write_file(&file, KnownHostFileKind::OpenSSH)
+                .map_err(|e| e.to_string())?;
+            dbg_log(format!("Added host key of {} to {:?}", host, file));
+            Ok(())
+        }
+        CheckResult::NotFound => Err(format!(
+            "The host key of {} is unknown, verify it with ssh first",
+            profile.host
+        )),
+        CheckResult::Mismatch => Err(format!(
+            "The host key of {} doesn't match the known one, refusing to connect",
+            profile.host
+        )),
+        CheckResult::Failure => Err(format!("Couldn't verify the host key of {}", profile.host)),
+    }
+}
+
+fn fdir_from_stat(
+    sftp: &ssh2::Sftp,
+    address: &str,
+    path: &Path,
+    link_stat: &ssh2::FileStat,
+) -> FDir {
+    let name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let kind = link_stat.file_type();
+    let file_type = if kind.is_symlink() {
+        FileType::Symlink
+    } else if kind.is_dir() {
+        FileType::Dir
+    } else if kind.is_file() {
+        FileType::File
+    } else {
+        FileType::Other
+    };
+    let (stat, symlink_target) = match file_type {
+        FileType::Symlink => (
+            sftp.stat(path).unwrap_or_else(|_| link_stat.clone()),
+            sftp.readlink(path)
+                .ok()
+                .map(|target| target.to_string_lossy().to_string()),
+        ),
+        _ => (link_stat.clone(), None),
+    };
+    let is_dir = stat.is_dir();
+    let mime_type = if is_dir {
+        "inode/directory".to_string()
+    } else {
+        mime_guess::from_path(&name)
+            .first_or_octet_stream()
+            .essence_str()
+            .to_string()
+    };
+    let permissions = stat.perm.map(|perm| perm & 0o7777);
+
+    FDir {
+        path: format!("{}{}{}", SFTP_PREFIX, address, path.to_string_lossy()),
+        file_type,
+        is_dir,
+        symlink_target,
+        extension: extension_of(&name, is_dir),
+        size: if is_dir { 0 } else { stat.size.unwrap_or(0) },
+        // SFTP v3 has no creation time
+        created: None,
+        accessed: stat.atime.map(|secs| secs * 1000),
+        modified: stat.mtime.map(|secs| secs * 1000),
+        permissions,
+        owner: stat.uid.map(|uid| uid.to_string()),
+        group: stat.gid.map(|gid| gid.to_string()),
+        is_hidden: name.starts_with('.'),
+        is_readonly: permissions.is_some_and(|perm| perm & 0o222 == 0),
+        mime_type,
+        group_key: None,
+        name,
+    }
+}
+
+fn measure(sftp: &ssh2::Sftp, path: &Path, info: &mut SimpleDirInfo) -> Result<(), String> {
+    let stat = sftp.lstat(path).map_err(|e| e.to_string())?;
+    info.count_elements += 1;
+    if !stat.is_dir() {
+        info.size += stat.size.unwrap_or(0);
+        return Ok(());
+    }
+    for (child, _) in sftp.readdir(path).map_err(|e| e.to_string())? {
+        measure(sftp, &child, info)?;
+    }
+    Ok(())
+}
+
+/// Number of files below `path` and their total size, like `measure_entries`
+fn count_files(sftp: &ssh2::Sftp, path: &Path) -> Result<(u64, u64), String> {
+    let stat = sftp.stat(path).map_err(|e| e.to_string())?;
+    if !stat.is_dir() {
+        return Ok((1, stat.size.unwrap_or(0)));
+    }
+    let mut total = (0, 0);
+    for (child, _) in sftp.readdir(path).map_err(|e| e.to_string())? {
+        let (count, size) = count_files(sftp, &child)?;
+        total = (total.0 + count, total.1 + size);
+    }
+    Ok(total)
+}
+
+fn remove_recursive(sftp: &ssh2::Sftp, path: &Path) -> Result<(), ssh2::Error> {
+    // Symlinks to dirs are removed as links
+    if sftp.lstat(path)?.is_dir() {
+        for (child, _) in sftp.readdir(path)? {
+            remove_recursive(sftp, &child)?;
+        }
+        sftp.rmdir(path)
+    } else {
+        sftp.unlink(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn remote(address: &str, path: &str) -> Location {
+        Location::Remote {
+            address: address.into(),
+            path: PathBuf::from(path),
+        }
+    }
+
+    #[test]
+    fn paths_split_into_address_and_remote_path() {
+        let location = Location::parse("sftp://me@example.com:2222/home/me/a.txt").unwrap();
+        assert_eq!(location, remote("me@example.com:2222", "/home/me/a.txt"));
+        assert_eq!(location.name(), "a.txt");
+        assert_eq!(
+            location.to_string(),
+            "sftp://me@example.com:2222/home/me/a.txt"
+        );
+        // Without a port the default one is used, without a path the root
+        assert_eq!(
+            Location::parse("sftp://me@example.com").unwrap(),
+            remote("me@example.com:22", "/")
+        );
+        assert_eq!(
+            Location::parse("sftp:\\\\me@host:22\\dir").unwrap(),
+            remote("me@host:22", "/dir")
+        );
+        assert_eq!(
+            Location::parse("/home/me").unwrap(),
+            Location::Local(PathBuf::from("/home/me"))
+        );
+    }
+
+    #[test]
+    fn locations_stay_on_their_server() {
+        let location = remote("me@host:22", "/a");
+        assert_eq!(location.join("b"), remote("me@host:22", "/a/b"));
+        assert_eq!(
+            location.with_path(PathBuf::from("/c")),
+            remote("me@host:22", "/c")
+        );
+        assert_eq!(
+            SftpProfile {
+                host: "host".into(),
+                username: "me".into(),
+                ..SftpProfile::default()
+            }
+            .address(),
+            "me@host:22"
+        );
+    }
+
+    #[test]
+    fn auth_methods_are_read_by_their_tag() {
+        let profile: SftpProfile = serde_json::from_str(
+            r#"{"host": "host", "auth": {"method": "key", "private_key": "/keys/id"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            profile.auth,
+            SftpAuth::Key {
+                private_key: PathBuf::from("/keys/id")
+            }
+        );
+        assert_eq!(profile.port, 22);
+        let profile: SftpProfile = serde_json::from_str(r#"{"host": "host"}"#).unwrap();
+        assert_eq!(profile.auth, SftpAuth::Agent);
+    }
+
+    #[test]
+    fn only_profiles_without_a_password_reconnect() {
+        let mut sftp = Sftp::new();
+        let profile = |auth| SftpProfile {
+            name: "local".into(),
+            // Nothing listens on port 1, so connecting fails right away
+            host: "127.0.0.1".into(),
+            port: 1,
+            username: "me".into(),
+            auth,
+            ..SftpProfile::default()
+        };
+        let path = "sftp://me@127.0.0.1:1/home";
+
+        sftp.set_profiles(vec![profile(SftpAuth::Password)]);
+        assert_eq!(
+            sftp.read_dir(&PathBuf::from(path)).unwrap_err(),
+            "Not connected to me@127.0.0.1:1"
+        );
+        sftp.set_profiles(vec![profile(SftpAuth::Agent)]);
+        let error = sftp.read_dir(&PathBuf::from(path)).unwrap_err();
+        assert!(error.starts_with("Couldn't connect to"), "{}", error);
+    }
+
+    /// Runs against the server in `CODRIVER_SFTP_TEST_URL`, e.g.
+    /// `sftp://me@localhost:2222/upload`, authenticated by the ssh-agent or by
+    /// `CODRIVER_SFTP_TEST_PASSWORD`. The host key has to be known already.
+    #[test]
+    #[ignore]
+    fn round_trip_against_a_server() {
+        let url = std::env::var("CODRIVER_SFTP_TEST_URL").unwrap();
+        let Location::Remote { address, path } = Location::parse(&url).unwrap() else {
+            panic!("{} is not an SFTP url", url);
+        };
+        let (username, host) = address.split_once('@').unwrap();
+        let (host, port) = host.rsplit_once(':').unwrap();
+        let password = std::env::var("CODRIVER_SFTP_TEST_PASSWORD").ok();
+        let profile = SftpProfile {
+            name: "test".into(),
+            host: host.into(),
+            port: port.parse().unwrap(),
+            username: username.into(),
+            auth: match password {
+                Some(_) => SftpAuth::Password,
+                None => SftpAuth::Agent,
+            },
+            remote_path: path.to_string_lossy().to_string(),
+            ..SftpProfile::default()
+        };
+        let mut sftp = Sftp::new();
+        let root = sftp.connect(&profile, password).unwrap();
+
+        let local = TempDir::new().unwrap();
+        let file = local.path().join("codriver-test.txt");
+        fs::write(&file, "round trip").unwrap();
+        sftp.upload(&file.to_string_lossy(), &root).unwrap();
+        let uploaded = format!("{}/codriver-test.txt", root);
+        let listed = sftp.read_dir(&PathBuf::from(&root)).unwrap();
+        assert!(listed
+            .iter()
+            .any(|entry| entry.path == uploaded && entry.size == 10));
+
+        sftp.rename(&uploaded, "codriver-renamed.txt").unwrap();
+        let renamed = format!("{}/codriver-renamed.txt", root);
+        let downloads = local.path().join("downloads");
+        fs::create_dir(&downloads).unwrap();
+        let saved = sftp
+            .download(&renamed, &downloads.to_string_lossy())
+            .unwrap();
+        assert_eq!(fs::read_to_string(saved).unwrap(), "round trip");
+
+        sftp.delete(&renamed).unwrap();
+        let listed = sftp.read_dir(&PathBuf::from(&root)).unwrap();
+        assert!(!listed.iter().any(|entry| entry.path == renamed));
+    }
+}
@@ -0,0 +1,454 @@
+use crate::utils::{dbg_log, measure_entries, wng_log};
+use chrono::Local;
+use serde::Serialize;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use tauri::api::path::data_dir;
+
+const TRASH_INFO_EXT: &str = ".trashinfo";
+/// Error for items that can't go to the trash, the UI offers to delete them
+/// permanently instead
+pub const TRASH_UNSUPPORTED: &str = "Moving to the trash isn't supported here, delete permanently?";
+
+/// An entry of the freedesktop.org trash
+#[derive(Serialize, Clone, Debug)]
+pub struct TrashItem {
+    /// Where the item currently lives, `<trash dir>/files/<name>`
+    pub path: String,
+    pub name: String,
+    pub original_path: String,
+    /// As written in the trashinfo, e.g. 2024-05-01T13:37:00
+    pub deleted_at: String,
+    pub is_dir: bool,
+    pub size: u64,
+}
+
+/// The home trash, `$XDG_DATA_HOME/Trash`
+pub fn home_trash() -> PathBuf {
+    data_dir().unwrap_or_default().join("Trash")
+}
+
+/// Moves an item into the trash and writes its `.trashinfo` next to it
+pub fn move_to_trash(path: &Path) -> Result<TrashItem, String> {
+    // Symlinks are trashed themselves, not what they point to
+    let name = path
+        .file_name()
+        .ok_or("Can't move a root directory to the trash")?
+        .to_string_lossy()
+        .to_string();
+    let path = path
+        .parent()
+        .ok_or("Can't move a root directory to the trash")?
+        .canonicalize()
+        .map_err(|e| e.to_string())?
+        .join(&name);
+    let metadata = fs::symlink_metadata(&path).map_err(|e| e.to_string())?;
+    let trash_dir = trash_dir_for(&path)?;
+    create_trash_dir(&trash_dir)?;
+
+    let deleted_at = Local::now().format("%Y-%m-%dT%H:%M:%S").to_string();
+    let (id, info_path) = reserve_id(
+        &trash_dir,
+        &name,
+        &format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            encode_path(&path.to_string_lossy()),
+            deleted_at
+        ),
+    )?;
+    let target = trash_dir.join("files").join(&id);
+
+    let size = match metadata.is_symlink() {
+        true => metadata.len(),
+        false => measure_entries(&path.to_string_lossy()).1,
+    };
+    let is_dir = metadata.is_dir();
+    if let Err(e) = fs::rename(&path, &target) {
+        let _ = fs::remove_file(&info_path);
+        return Err(format!("Couldn't move {:?} to the trash: {}", path, e));
+    }
+    dbg_log(format!("Trashed {:?} as {:?}", path, target));
+
+    Ok(TrashItem {
+        path: target.to_string_lossy().to_string(),
+        name,
+        original_path: path.to_string_lossy().to_string(),
+        deleted_at,
+        is_dir,
+        size,
+    })
+}
+
+/// Lists the items of the home trash and of the trash dirs of mounted devices
+pub fn list_trash(trash_dirs: &[PathBuf]) -> Vec<TrashItem> {
+    let mut items = vec![];
+    for trash_dir in trash_dirs {
+        let entries = match fs::read_dir(trash_dir.join("info")) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let id = match file_name.strip_suffix(TRASH_INFO_EXT) {
+                Some(id) => id.to_string(),
+                None => continue,
+            };
+            match read_trash_item(trash_dir, &id) {
+                Ok(item) => items.push(item),
+                Err(e) => wng_log(format!("Skipping trash entry {}: {}", id, e)),
+            }
+        }
+    }
+    items.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at));
+    items
+}
+
+/// Moves a trashed item back to where it was deleted from
+pub fn restore(trashed_path: &Path) -> Result<PathBuf, String> {
+    let (trash_dir, id) = split_trashed_path(trashed_path)?;
+    let item = read_trash_item(&trash_dir, &id)?;
+    let original_path = PathBuf::from(&item.original_path);
+    if fs::symlink_metadata(&original_path).is_ok() {
+        return Err(format!("{} already exists", item.original_path));
+    }
+    if let Some(parent) = original_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::rename(trashed_path, &original_path).map_err(|e| e.to_string())?;
+    let _ = fs::remove_file(info_path(&trash_dir, &id));
+    Ok(original_path)
+}
+
+/// Whether the path points at an item inside of one of the trash dirs
+pub fn is_trashed(path: &Path) -> bool {
+    match split_trashed_path(path) {
+        Ok((trash_dir, _)) => trash_dirs().contains(&trash_dir),
+        Err(_) => false,
+    }
+}
+
+/// Removes a single trashed item for good
+pub fn remove(trashed_path: &Path) -> Result<(), String> {
+    let (trash_dir, id) = split_trashed_path(trashed_path)?;
+    let removed = match fs::symlink_metadata(trashed_path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(trashed_path),
+        Ok(_) => fs::remove_file(trashed_path),
+        Err(_) => Ok(()),
+    };
+    removed.map_err(|e| e.to_string())?;
+    let _ = fs::remove_file(info_path(&trash_dir, &id));
+    Ok(())
+}
+
+/// Removes every item of the given trash dirs
+pub fn empty(trash_dirs: &[PathBuf]) -> Result<(), String> {
+    for item in list_trash(trash_dirs) {
+        remove(Path::new(&item.path))?;
+    }
+    for trash_dir in trash_dirs {
+        let _ = fs::remove_file(trash_dir.join("directorysizes"));
+    }
+    Ok(())
+}
+
+/// All trash dirs that currently exist: the home trash and `.Trash-$uid`
+/// in the top directory of mounted devices
+pub fn trash_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![home_trash()];
+    #[cfg(unix)]
+    {
+        use sysinfo::Disks;
+        let uid = current_uid();
+        for disk in Disks::new_with_refreshed_list().list() {
+            let trash_dir = disk.mount_point().join(format!(".Trash-{}", uid));
+            if trash_dir.is_dir() && !dirs.contains(&trash_dir) {
+                dirs.push(trash_dir);
+            }
+        }
+    }
+    dirs
+}
+
+fn read_trash_item(trash_dir: &Path, id: &str) -> Result<TrashItem, String> {
+    let info = fs::read_to_string(info_path(trash_dir, id)).map_err(|e| e.to_string())?;
+    let mut original_path = None;
+    let mut deleted_at = String::new();
+    for line in info.lines() {
+        if let Some(path) = line.strip_prefix("Path=") {
+            original_path = Some(decode_path(path));
+        } else if let Some(date) = line.strip_prefix("DeletionDate=") {
+            deleted_at = date.to_string();
+        }
+    }
+    let original_path = original_path.ok_or("trashinfo has no Path")?;
+    // Relative paths are relative to the top directory of the device
+    let original_path = match trash_dir.parent() {
+        Some(top_dir) if !original_path.starts_with('/') && trash_dir != home_trash() => {
+            top_dir.join(&original_path).to_string_lossy().to_string()
+        }
+        _ => original_path,
+    };
+    let file = trash_dir.join("files").join(id);
+    let (_, size) = measure_entries(&file.to_string_lossy());
+
+    Ok(TrashItem {
+        path: file.to_string_lossy().to_string(),
+        name: Path::new(&original_path)
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+        original_path,
+        deleted_at,
+        is_dir: fs::symlink_metadata(&file).is_ok_and(|m| m.is_dir()),
+        size,
+    })
+}
+
+/// Splits `<trash dir>/files/<id>` into the trash dir and the id
+fn split_trashed_path(trashed_path: &Path) -> Result<(PathBuf, String), String> {
+    let files_dir = trashed_path.parent().filter(|dir| dir.ends_with("files"));
+    match (
+        files_dir.and_then(|dir| dir.parent()),
+        trashed_path.file_name(),
+    ) {
+        (Some(trash_dir), Some(id)) => {
+            Ok((trash_dir.to_path_buf(), id.to_string_lossy().to_string()))
+        }
+        _ => Err(format!("{:?} is not inside of a trash", trashed_path)),
+    }
+}
+
+/// Creates the trash dir with its `files` and `info` dirs, which only the
+/// user may access
+fn create_trash_dir(trash_dir: &Path) -> Result<(), String> {
+    if let Some(parent) = trash_dir.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    for dir in [
+        trash_dir.to_path_buf(),
+        trash_dir.join("files"),
+        trash_dir.join("info"),
+    ] {
+        let mut builder = fs::DirBuilder::new();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(0o700);
+        }
+        match builder.create(&dir) {
+            Err(e) if e.kind() != ErrorKind::AlreadyExists => return Err(e.to_string()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Picks an unused id for an item named `name` and writes its trashinfo.
+/// Creating the trashinfo fails if another delete took the id first.
+fn reserve_id(trash_dir: &Path, name: &str, info: &str) -> Result<(String, PathBuf), String> {
+    let stem = Path::new(name)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let ext = Path::new(name)
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    let mut counter = 0;
+    loop {
+        let id = match counter {
+            0 => name.to_string(),
+            _ => format!("{}_{}{}", stem, counter, ext),
+        };
+        counter += 1;
+        if fs::symlink_metadata(trash_dir.join("files").join(&id)).is_ok() {
+            continue;
+        }
+        let info_path = info_path(trash_dir, &id);
+        let mut file = match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_path)
+        {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.to_string()),
+        };
+        if let Err(e) = file.write_all(info.as_bytes()) {
+            let _ = fs::remove_file(&info_path);
+            return Err(e.to_string());
+        }
+        return Ok((id, info_path));
+    }
+}
+
+fn info_path(trash_dir: &Path, id: &str) -> PathBuf {
+    trash_dir
+        .join("info")
+        .join(format!("{}{}", id, TRASH_INFO_EXT))
+}
+
+/// Items have to be renamed into a trash on the same device, so items on
+/// other devices go into `$topdir/.Trash-$uid`
+#[cfg(unix)]
+fn trash_dir_for(path: &Path) -> Result<PathBuf, String> {
+    use std::os::unix::fs::MetadataExt;
+    let home_trash = home_trash();
+    let device = |path: &Path| fs::metadata(path).map(|m| m.dev()).ok();
+    let item_device = device(path.parent().unwrap_or(path));
+    let home_device = home_trash.ancestors().find_map(device);
+    if item_device.is_none() || item_device == home_device {
+        return Ok(home_trash);
+    }
+
+    let mut top_dir = path;
+    while let Some(parent) = top_dir.parent() {
+        if device(parent) != item_device {
+            break;
+        }
+        top_dir = parent;
+    }
+    Ok(top_dir.join(format!(".Trash-{}", current_uid())))
+}
+
+#[cfg(not(unix))]
+fn trash_dir_for(_path: &Path) -> Result<PathBuf, String> {
+    Ok(home_trash())
+}
+
+#[cfg(unix)]
+fn current_uid() -> u32 {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata("/proc/self").map_or(0, |m| m.uid())
+}
+
+/// Percent-encodes a path the way trashinfo files expect it
+fn encode_path(path: &str) -> String {
+    let mut encoded = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn decode_path(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn paths_round_trip_through_percent_encoding() {
+        let path = "/home/me/My Files/ä%.txt";
+        let encoded = encode_path(path);
+        assert_eq!(encoded, "/home/me/My%20Files/%C3%A4%25.txt");
+        assert_eq!(decode_path(&encoded), path);
+        // Broken escapes are kept as they are
+        assert_eq!(decode_path("/a%zz/b%4"), "/a%zz/b%4");
+    }
+
+    #[test]
+    fn ids_count_up_while_taken() {
+        let dir = TempDir::new().unwrap();
+        create_trash_dir(dir.path()).unwrap();
+
+        let (id, info) = reserve_id(dir.path(), "a.txt", "first").unwrap();
+        assert_eq!(id, "a.txt");
+        assert_eq!(fs::read_to_string(info).unwrap(), "first");
+        // Taken by the trashinfo of the first item
+        let (id, _) = reserve_id(dir.path(), "a.txt", "second").unwrap();
+        assert_eq!(id, "a_1.txt");
+        // Taken by an item without trashinfo
+        fs::write(dir.path().join("files/a_2.txt"), "").unwrap();
+        let (id, _) = reserve_id(dir.path(), "a.txt", "third").unwrap();
+        assert_eq!(id, "a_3.txt");
+    }
+
+    #[test]
+    fn trashed_items_go_to_the_home_trash_and_back() {
+        let dir = TempDir::new().unwrap();
+        // The only test that touches the home trash
+        std::env::set_var("XDG_DATA_HOME", dir.path().join("data"));
+        let docs = dir.path().canonicalize().unwrap().join("docs");
+        fs::create_dir(&docs).unwrap();
+        let path = docs.join("my notes.txt");
+        fs::write(&path, "notes").unwrap();
+        assert_eq!(trash_dir_for(&path).unwrap(), home_trash());
+
+        let item = move_to_trash(&path).unwrap();
+        assert!(!path.exists());
+        assert_eq!(
+            Path::new(&item.path),
+            home_trash().join("files/my notes.txt")
+        );
+        let info = fs::read_to_string(info_path(&home_trash(), "my notes.txt")).unwrap();
+        assert_eq!(
+            info,
+            format!(
+                "[Trash Info]\nPath={}/my%20notes.txt\nDeletionDate={}\n",
+                docs.to_string_lossy(),
+                item.deleted_at
+            )
+        );
+
+        let listed = list_trash(&[home_trash()]);
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].original_path, path.to_string_lossy());
+        assert_eq!(listed[0].size, 5);
+
+        assert_eq!(restore(Path::new(&item.path)).unwrap(), path);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "notes");
+        assert!(list_trash(&[home_trash()]).is_empty());
+    }
+
+    #[test]
+    fn topdir_trashes_resolve_relative_paths() {
+        let dir = TempDir::new().unwrap();
+        let trash_dir = dir.path().join(".Trash-1000");
+        create_trash_dir(&trash_dir).unwrap();
+        fs::write(trash_dir.join("files/a b.txt"), "").unwrap();
+        fs::write(
+            info_path(&trash_dir, "a b.txt"),
+            "[Trash Info]\nPath=docs/a%20b.txt\nDeletionDate=2024-05-01T13:37:00\n",
+        )
+        .unwrap();
+        fs::write(trash_dir.join("files/c"), "").unwrap();
+        fs::write(info_path(&trash_dir, "c"), "[Trash Info]\nPath=/mnt/c\n").unwrap();
+
+        let item = read_trash_item(&trash_dir, "a b.txt").unwrap();
+        assert_eq!(
+            Path::new(&item.original_path),
+            dir.path().join("docs/a b.txt")
+        );
+        assert_eq!(item.name, "a b.txt");
+        assert_eq!(item.deleted_at, "2024-05-01T13:37:00");
+        let item = read_trash_item(&trash_dir, "c").unwrap();
+        assert_eq!(item.original_path, "/mnt/c");
+    }
+}
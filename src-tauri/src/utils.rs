@@ -1,25 +1,21 @@
 use chrono::prelude::*;
 use color_print::cprintln;
-use regex::Regex;
 use serde::Serialize;
 use std::{
     ffi::OsStr,
     fmt::Debug,
     fs::{self, File},
     io::{BufReader, BufWriter, Read, Write},
+    path::Path,
 };
-use stopwatch::Stopwatch;
 use sysinfo::System;
-use tar::Archive as TarArchive;
-use tauri::Window;
 
-#[allow(unused_imports)]
-use crate::ISCANCELED;
+use crate::conflicts::{ConflictResolver, Resolution, SetAside};
+use crate::content_search::{ContentMatch, ContentSearcher};
+use crate::jobs::JobHandle;
+use crate::search_query::{Candidate, Query};
 use crate::{COUNT_CALLED_BACK, IS_SEARCHING};
 
-pub static mut COPY_COUNTER: f32 = 0.0;
-pub static mut TO_COPY_COUNTER: f32 = 0.0;
-
 pub fn dbg_log(msg: String) {
     cprintln!(
         "[<white>{:?}</white> DBG] {}",
@@ -42,145 +38,116 @@ pub fn err_log(msg: String) {
     );
 }
 
-pub fn copy_to(app_window: &Window, final_filename: String, from_path: String) {
-    let file = fs::metadata(&from_path).unwrap();
-    if file.is_file() {
-        // Kopieren der Datei
-        let mut fr = BufReader::new(File::open(&from_path).unwrap());
-        let mut buf = vec![0; 10_000_000];
-        let new_file = File::create(&final_filename).unwrap();
-        let file_size = fs::metadata(&from_path).unwrap().len() as f32;
-        let mut fw = BufWriter::new(new_file);
-        let mut s = 0;
-        let mut speed: f64;
-        let sw = Stopwatch::start_new();
-        let mut progress: f32;
-        unsafe {
-            update_progressbar_2(
-                app_window,
-                (100.0 / TO_COPY_COUNTER) * COPY_COUNTER,
-                final_filename.split("/").last().unwrap(),
-            );
-            COPY_COUNTER += 1.0;
+/// Copies a file or a whole directory while reporting progress to the job.
+/// Existing targets are handled by the resolver, directories get merged.
+/// A partially written file is removed again if the job gets canceled.
+pub fn copy_to(
+    job: &JobHandle,
+    resolver: &mut ConflictResolver,
+    final_filename: String,
+    from_path: String,
+) -> Result<(), String> {
+    job.checkpoint()?;
+    let resolution = resolver.resolve(job, Path::new(&from_path), Path::new(&final_filename))?;
+    let mut replaced = SetAside::default();
+    let final_filename = match resolution {
+        Resolution::CopyTo(target) => target,
+        Resolution::Replace(target) => {
+            replaced.set_aside(&target)?;
+            target
         }
-        loop {
-            match fr.read(&mut buf) {
-                Ok(ds) => {
-                    s += ds as u64;
-                    if ds == 0 {
-                        break;
-                    }
-                    fw.write_all(&buf[..ds]).unwrap();
-                    // Calculate transfer speed and progres
-                    speed = calc_transfer_speed(s as f64, sw.elapsed_ms() as f64 / 1000.0);
-                    if speed.is_infinite() {
-                        speed = 0.0
-                    }
-                    progress = (100.0 / file_size) * s as f32;
-                    unsafe {
-                        update_progressbar(
-                            app_window,
-                            progress,
-                            format!("{}/{}", COPY_COUNTER, TO_COPY_COUNTER).as_str(),
-                            speed,
-                        );
-                    };
-                }
-                Err(e) => {
-                    err_log(format!("Error copying: {}", e));
-                    break;
-                }
-            }
+        Resolution::Skip => {
+            let (count, size) = measure_entries(&from_path);
+            job.add_done(count, size);
+            return Ok(());
         }
+    };
+    let final_filename = final_filename
+        .to_string_lossy()
+        .to_string()
+        .replace("\\", "/");
+    replaced.finish(copy_resolved(job, resolver, final_filename, from_path))
+}
+
+fn copy_resolved(
+    job: &JobHandle,
+    resolver: &mut ConflictResolver,
+    final_filename: String,
+    from_path: String,
+) -> Result<(), String> {
+    let file = fs::metadata(&from_path).map_err(|e| e.to_string())?;
+    if file.is_file() {
+        copy_file(job, &final_filename, &from_path)?;
     } else if file.is_dir() {
         // Recursive copying of the directory
-        fs::create_dir_all(&final_filename).unwrap();
-        for entry in fs::read_dir(&from_path).unwrap() {
-            let entry = entry.unwrap();
+        fs::create_dir_all(&final_filename).map_err(|e| e.to_string())?;
+        for entry in fs::read_dir(&from_path).map_err(|e| e.to_string())? {
+            let entry = entry.map_err(|e| e.to_string())?;
             let path = entry.path();
             let relative_path = path.strip_prefix(&from_path).unwrap();
             let dest_file = final_filename.clone() + "/" + relative_path.to_str().unwrap();
-            copy_to(app_window, dest_file, path.to_str().unwrap().to_string());
+            copy_to(job, resolver, dest_file, path.to_str().unwrap().to_string())?;
         }
     } else {
         wng_log(format!("Unsupported file type: {}", from_path));
     }
+    Ok(())
 }
 
-pub fn count_entries(path: &str) -> Result<f32, std::io::Error> {
-    let mut count: f32 = 0.0;
-
-    if fs::metadata(path).is_err() {
-        return Ok(0.0);
-    }
-    if !fs::metadata(path).unwrap().is_dir() {
-        return Ok(1.0);
+/// Copies a single file, a partially written file is removed on error
+pub fn copy_file(job: &JobHandle, final_filename: &str, from_path: &str) -> Result<(), String> {
+    job.begin_item(final_filename.split("/").last().unwrap_or(""));
+    let copied = copy_file_chunked(job, final_filename, from_path);
+    if copied.is_err() {
+        let _ = fs::remove_file(final_filename);
+        return copied;
     }
+    job.finish_item();
+    Ok(())
+}
 
-    for entry in fs::read_dir(path).unwrap() {
-        let entry = entry.unwrap();
-        let path = entry.path();
-
-        if path.is_dir() {
-            count += count_entries(&path.to_str().unwrap()).unwrap();
-        } else {
-            count += 1.0;
+fn copy_file_chunked(job: &JobHandle, final_filename: &str, from_path: &str) -> Result<(), String> {
+    let mut fr = BufReader::new(File::open(from_path).map_err(|e| e.to_string())?);
+    let mut buf = vec![0; 10_000_000];
+    let new_file = File::create(final_filename).map_err(|e| e.to_string())?;
+    let mut fw = BufWriter::new(new_file);
+    loop {
+        job.checkpoint()?;
+        match fr.read(&mut buf) {
+            Ok(0) => break,
+            Ok(ds) => {
+                fw.write_all(&buf[..ds]).map_err(|e| e.to_string())?;
+                job.add_bytes(ds as u64);
+            }
+            Err(e) => {
+                err_log(format!("Error copying: {}", e));
+                return Err(e.to_string());
+            }
         }
     }
-    Ok(count)
+    fw.flush().map_err(|e| e.to_string())
 }
 
-pub fn show_progressbar(app_window: &Window) {
-    let _ = &app_window
-        .eval("document.querySelector('.progress-bar-container-popup').style.display = 'flex'");
-    let _ = app_window.eval("document.querySelector('.progress-bar-2').style.display = 'block'");
-}
-
-pub fn update_progressbar(
-    app_window: &Window,
-    progress: f32,
-    items_count_text: &str,
-    mb_per_sec: f64,
-) {
-    show_progressbar(app_window);
-    let _ = app_window.eval(
-        format!(
-            "document.querySelector('.progress-bar-fill').style.width = '{}%'",
-            &progress
-        )
-        .as_str(),
-    );
-    let _ = app_window.eval(
-        format!(
-            "document.querySelector('.progress-bar-text').innerText = '{:.2} %'",
-            progress
-        )
-        .as_str(),
-    );
-    let _ = app_window.eval(
-        format!(
-            "document.querySelector('.progress-bar-text-2').innerText = '{:.2} MB/s | {}'",
-            mb_per_sec, items_count_text
-        )
-        .as_str(),
-    );
-}
+/// Returns the number of files and their total size below `path`
+pub fn measure_entries(path: &str) -> (u64, u64) {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return (0, 0),
+    };
+    if !metadata.is_dir() {
+        return (1, metadata.len());
+    }
 
-pub fn update_progressbar_2(app_window: &Window, progress: f32, file_name: &str) {
-    let _ = app_window.eval(
-        format!(
-            "document.querySelector('.progress-bar-2-fill').style.width = '{}%'",
-            progress
-        )
-        .as_str(),
-    );
-    let _ = app_window.eval(
-        format!(
-            "document.querySelector('.progress-bar-item-text').innerText = '{}'",
-            file_name
-        )
-        .as_str(),
-    );
+    let mut count = 0;
+    let mut size = 0;
+    if let Ok(entries) = fs::read_dir(path) {
+        for entry in entries.flatten() {
+            let (entry_count, entry_size) = measure_entries(&entry.path().to_string_lossy());
+            count += entry_count;
+            size += entry_size;
+        }
+    }
+    (count, size)
 }
 
 pub fn calc_transfer_speed(file_size: f64, time: f64) -> f64 {
@@ -283,19 +250,15 @@ impl DirWalker {
         &mut self,
         path: &str,
         depth: u32,
-        file_name: String,
+        query: &Query,
         max_items: i32,
         is_quick_search: bool,
-        file_content: String,
-        callback: &impl Fn(DirWalkerEntry),
+        content: Option<&ContentSearcher>,
+        callback: &impl Fn(DirWalkerEntry, Vec<ContentMatch>),
     ) {
-        let reg_exp: Regex;
-
-        if !self.exts.is_empty() {
-            reg_exp = Regex::new(format!("(?i){}", file_name).as_str()).unwrap();
-        } else {
-            reg_exp = Regex::new(format!("(?i){}.*", file_name).as_str()).unwrap();
-        }
+        // Without a type: term only files are found, the quick search also
+        // jumps to dirs
+        let files_only = !is_quick_search && !query.filters_type();
 
         for entry in jwalk::WalkDir::new(path)
             .parallelism(jwalk::Parallelism::RayonNewPool(
@@ -323,62 +286,63 @@ impl DirWalker {
 
             let entry = entry.unwrap();
 
-            let name = entry.file_name().to_str().unwrap().to_string();
+            let name = entry.file_name().to_string_lossy().to_string();
             let path = entry.path();
-            let item_path = entry.file_name().to_str().unwrap().to_lowercase();
-            if item_path.contains("onedrive") {
+            if name.to_lowercase().contains("onedrive") {
                 continue;
             }
-            let item_ext = ".".to_owned()
-                + &item_path
-                    .split(".")
-                    .last()
-                    .unwrap()
-                    .to_string()
-                    .to_lowercase();
 
-            let file_metadata = fs::metadata(&path);
-            if file_metadata.is_err() {
-                return;
+            let Ok(file_metadata) = fs::metadata(&path) else {
+                continue;
+            };
+            if files_only && !file_metadata.is_file() {
+                continue;
+            }
+            let last_mod: Option<DateTime<Utc>> =
+                file_metadata.modified().ok().map(|time| time.into());
+            let path_str = path.to_string_lossy().replace("\\", "/");
+            let size = if file_metadata.is_dir() {
+                0
+            } else {
+                file_metadata.len()
+            };
+
+            if !query.matches(&Candidate {
+                name: &name,
+                path: &path_str,
+                is_dir: file_metadata.is_dir(),
+                size,
+                modified: last_mod.map(|time| time.timestamp_millis()),
+            }) {
+                continue;
             }
 
-            let last_mod: DateTime<Utc> = file_metadata.unwrap().modified().unwrap().into();
-
-            if reg_exp.is_match(&name)
-                && (self.exts.len() > 0 && self.exts.contains(&item_ext)
-                    || path.is_file() && self.exts.len() == 0
-                    || is_quick_search)
-            {
-                // Search for file content
-                if !file_content.is_empty() {
-                    let content = fs::read_to_string(&path).unwrap();
-                    // Extend with line number later on
-                    if content.contains(&file_content) {
-                        callback(DirWalkerEntry {
-                            name,
-                            path: path.to_string_lossy().to_string(),
-                            depth,
-                            is_dir: path.is_dir(),
-                            is_file: path.is_file(),
-                            extension: item_ext,
-                            last_modified: format!("{:?}", last_mod),
-                            size: fs::metadata(&path).unwrap().len(),
-                        });
-                        return;
-                    }
-                }
-                // Search w/o file content
-                callback(DirWalkerEntry {
+            // Only files have content, unreadable ones are skipped
+            let content_matches = match content {
+                Some(_) if !file_metadata.is_file() => continue,
+                Some(searcher) => match searcher.search_file(&path) {
+                    Ok(matches) if !matches.is_empty() => matches,
+                    _ => continue,
+                },
+                None => vec![],
+            };
+
+            callback(
+                DirWalkerEntry {
+                    extension: match name.rsplit_once('.') {
+                        Some((_, ext)) => format!(".{}", ext.to_lowercase()),
+                        None => "".into(),
+                    },
                     name,
                     path: path.to_string_lossy().to_string(),
-                    depth,
-                    is_dir: path.is_dir(),
-                    is_file: path.is_file(),
-                    extension: item_ext,
-                    last_modified: format!("{:?}", last_mod),
-                    size: fs::metadata(&path).unwrap().len(),
-                });
-            }
+                    depth: entry.depth as u32,
+                    is_dir: file_metadata.is_dir(),
+                    is_file: file_metadata.is_file(),
+                    last_modified: last_mod.map_or("".into(), |time| format!("{:?}", time)),
+                    size,
+                },
+                content_matches,
+            );
         }
     }
 
@@ -387,11 +351,6 @@ impl DirWalker {
         self
     }
 
-    pub fn set_ext(&mut self, exts: Vec<String>) -> &mut Self {
-        self.exts = exts;
-        self
-    }
-
     pub fn ext(&mut self, extensions: Vec<&str>) -> &mut Self {
         self.items = self
             .items
@@ -429,39 +388,3 @@ pub fn format_bytes(size: u64) -> String {
     // Format with 2 decimal places
     format!("{:.2} {}", size, UNITS[unit_index])
 }
-
-pub fn unpack_tar(file: File) {
-    let mut archive = TarArchive::new(file);
-    let _ = fs::create_dir("Unpacked_Archive");
-
-    for file in archive.entries().unwrap() {
-        // Make sure there wasn't an I/O error
-        if file.is_err() {
-            continue;
-        }
-        // Unwrap the file
-        let mut file = file.unwrap();
-        let _ = file.unpack_in("Unpacked_Archive").unwrap_or_default();
-    }
-}
-
-pub fn create_new_action(
-    app_window: &Window,
-    action_name: String,
-    action_desc: String,
-    path: &String,
-) -> String {
-    let id = uuid::Uuid::new_v4().to_string();
-    let _ = app_window.eval(
-        format!(
-            "createNewAction('{}', '{}', '{}', '{}')",
-            id, action_name, action_desc, path
-        )
-        .as_str(),
-    );
-    return id;
-}
-
-pub fn remove_action(app_window: Window, action_id: String) {
-    let _ = app_window.eval(format!("removeAction('{}')", action_id).as_str());
-}
@@ -0,0 +1,339 @@
+use crate::archive_fs::{ArchiveFs, ARCHIVE_SCHEME};
+use crate::conflicts::{ConflictPolicy, ConflictResolver};
+use crate::fdir::{fdir_from_path, FDir};
+use crate::gdrive::GDrive;
+use crate::jobs::JobHandle;
+use crate::local::{local_path, LocalDisk};
+use crate::s3::{S3, S3_PREFIX};
+use crate::saved_search::{SavedSearches, SEARCH_SCHEME};
+use crate::sftp::{Sftp, SFTP_PREFIX};
+use crate::utils::{dbg_log, DirWalkerEntry};
+use crate::webdav::{WebDav, WEBDAV_PREFIX};
+use crate::{config, SimpleDirInfo};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{self, Arc};
+use tauri::async_runtime::Mutex;
+
+pub const FILE_SCHEME: &str = "file://";
+pub const GDRIVE_SCHEME: &str = "gdrive:";
+
+/// A file system the commands work on. Besides their own paths,
+/// `copy_items`, `move_items` and `upload` accept items on the local disk and
+/// `download` saves to it, transfers between two providers go through that.
+pub trait CloudProvider: Send {
+    fn new() -> Self
+    where
+        Self: Sized;
+
+    fn authenticate(&mut self) -> Result<(), String>;
+
+    fn sign_out(&mut self) -> Result<(), String>;
+
+    fn read_dir(&mut self, path: &PathBuf) -> Result<Vec<FDir>, String>;
+
+    /// Returns the path the file was saved to inside of the dir `to_path`
+    fn download(&mut self, from_path: &str, to_path: &str) -> Result<String, String>;
+
+    fn upload(&mut self, from_path: &str, to_path: &str) -> Result<(), String>;
+
+    /// Creates a dir named like the last part of `from_path` inside of `to_path`
+    fn create_dir(&mut self, from_path: &str, to_path: &str) -> Result<(), String>;
+
+    fn search(&mut self, fname: &str) -> Result<Vec<DirWalkerEntry>, String>;
+
+    fn get_item_size(&self, path: &str) -> Result<SimpleDirInfo, String>;
+
+    /// Reports its progress to `job` and asks `resolver` what to do with
+    /// targets that already exist
+    fn copy_items(
+        &mut self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        arr_items: Vec<FDir>,
+        copy_to_path: &str,
+    ) -> Result<(), String>;
+
+    fn move_items(
+        &mut self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        arr_items: Vec<FDir>,
+        move_to_path: &str,
+    ) -> Result<(), String>;
+
+    fn delete(&mut self, path: &str) -> Result<(), String>;
+
+    fn rename(&mut self, path: &str, new_name: &str) -> Result<(), String>;
+}
+
+pub type SharedProvider = Arc<Mutex<dyn CloudProvider>>;
+
+/// Managed state mapping path schemes to the providers handling them. Paths
+/// without a registered scheme belong to the local disk.
+pub struct VfsRegistry {
+    providers: Vec<(&'static str, SharedProvider)>,
+    local: SharedProvider,
+    // Kept typed for the commands only one provider has
+    gdrive: Arc<Mutex<GDrive>>,
+    sftp: Arc<Mutex<Sftp>>,
+    webdav: Arc<Mutex<WebDav>>,
+    s3: Arc<Mutex<S3>>,
+}
+
+impl Default for VfsRegistry {
+    fn default() -> Self {
+        let local: SharedProvider = Arc::new(Mutex::new(LocalDisk::new()));
+        let gdrive = Arc::new(Mutex::new(GDrive::new()));
+        let mut sftp = Sftp::new();
+        sftp.set_profiles(config::load().sftp_profiles);
+        let sftp = Arc::new(Mutex::new(sftp));
+        let mut webdav = WebDav::new();
+        webdav.set_accounts(config::load().webdav_accounts);
+        let webdav = Arc::new(Mutex::new(webdav));
+        let mut s3 = S3::new();
+        s3.set_accounts(config::load().s3_accounts);
+        let s3 = Arc::new(Mutex::new(s3));
+
+        let mut registry = VfsRegistry {
+            providers: vec![],
+            local: local.clone(),
+            gdrive: gdrive.clone(),
+            sftp: sftp.clone(),
+            webdav: webdav.clone(),
+            s3: s3.clone(),
+        };
+        registry.register(FILE_SCHEME, local);
+        registry.register(GDRIVE_SCHEME, gdrive);
+        registry.register(SFTP_PREFIX, sftp);
+        registry.register(WEBDAV_PREFIX, webdav);
+        registry.register(S3_PREFIX, s3);
+        registry.register(ARCHIVE_SCHEME, Arc::new(Mutex::new(ArchiveFs::new())));
+        registry.register(SEARCH_SCHEME, Arc::new(Mutex::new(SavedSearches::new())));
+        registry
+    }
+}
+
+impl VfsRegistry {
+    /// Replaces the provider of a scheme that is already registered
+    pub fn register(&mut self, scheme: &'static str, provider: SharedProvider) {
+        self.providers
+            .retain(|(registered, _)| *registered != scheme);
+        self.providers.push((scheme, provider));
+    }
+
+    /// The registered scheme of a path, the longest one if several match
+    pub fn scheme_of(&self, path: &str) -> &'static str {
+        let path = path.replace("\\", "/");
+        self.providers
+            .iter()
+            .map(|(scheme, _)| *scheme)
+            .filter(|scheme| path.starts_with(scheme))
+            .max_by_key(|scheme| scheme.len())
+            .unwrap_or(FILE_SCHEME)
+    }
+
+    pub fn is_local(&self, path: &str) -> bool {
+        self.scheme_of(path) == FILE_SCHEME
+    }
+
+    pub fn provider(&self, path: &str) -> SharedProvider {
+        let scheme = self.scheme_of(path);
+        self.providers
+            .iter()
+            .find(|(registered, _)| *registered == scheme)
+            .map(|(_, provider)| provider.clone())
+            .unwrap_or_else(|| self.local.clone())
+    }
+
+    pub fn gdrive(&self) -> Arc<Mutex<GDrive>> {
+        self.gdrive.clone()
+    }
+
+    pub fn sftp(&self) -> Arc<Mutex<Sftp>> {
+        self.sftp.clone()
+    }
+
+    pub fn webdav(&self) -> Arc<Mutex<WebDav>> {
+        self.webdav.clone()
+    }
+
+    pub fn s3(&self) -> Arc<Mutex<S3>> {
+        self.s3.clone()
+    }
+
+    /// Runs `f` on a blocking thread with the provider of `path` locked
+    pub async fn run<R, F>(&self, path: &str, f: F) -> Result<R, String>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut dyn CloudProvider) -> Result<R, String> + Send + 'static,
+    {
+        run_on(self.provider(path), f).await
+    }
+
+    /// Copies items from any providers into the dir `to_path` as part of a
+    /// started job
+    pub async fn copy_items(
+        &self,
+        job: &JobHandle,
+        policy: ConflictPolicy,
+        arr_items: Vec<FDir>,
+        to_path: &str,
+    ) -> Result<(), String> {
+        self.transfer(job, policy, arr_items, to_path, false).await
+    }
+
+    /// Moves items from any providers into the dir `to_path`, sources are
+    /// only removed once their copy succeeded
+    pub async fn move_items(
+        &self,
+        job: &JobHandle,
+        policy: ConflictPolicy,
+        arr_items: Vec<FDir>,
+        to_path: &str,
+    ) -> Result<(), String> {
+        self.transfer(job, policy, arr_items, to_path, true).await
+    }
+
+    /// The item at `path`, with full metadata if it's on the local disk
+    pub fn item_at(&self, path: &str) -> FDir {
+        if self.is_local(path) {
+            if let Some(item) = fdir_from_path(&local_path(path)) {
+                return item;
+            }
+        }
+        let path = path.replace("\\", "/");
+        FDir {
+            name: path
+                .trim_end_matches('/')
+                .rsplit('/')
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            path,
+            ..Default::default()
+        }
+    }
+
+    async fn transfer(
+        &self,
+        job: &JobHandle,
+        policy: ConflictPolicy,
+        arr_items: Vec<FDir>,
+        to_path: &str,
+        is_move: bool,
+    ) -> Result<(), String> {
+        // Shared by all groups, so "apply to all" answers stick
+        let resolver = Arc::new(sync::Mutex::new(ConflictResolver::new(policy)));
+        let to_scheme = self.scheme_of(to_path);
+        // Items keep their order, consecutive items of a provider go together
+        let mut groups: Vec<(&'static str, Vec<FDir>)> = vec![];
+        for item in arr_items {
+            let scheme = self.scheme_of(&item.path);
+            match groups.last_mut() {
+                Some((last, items)) if *last == scheme => items.push(item),
+                _ => groups.push((scheme, vec![item])),
+            }
+        }
+
+        for (scheme, items) in groups {
+            job.checkpoint_async().await?;
+            if scheme == to_scheme || scheme == FILE_SCHEME {
+                // The target provider reads local items itself
+                let provider = self.provider(to_path);
+                run_transfer(provider, job, &resolver, items, to_path, is_move).await?;
+            } else if to_scheme == FILE_SCHEME {
+                let provider = self.provider(&items[0].path);
+                run_transfer(provider, job, &resolver, items, to_path, is_move).await?;
+            } else {
+                self.transfer_staged(job, &resolver, items, to_path, is_move)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Transfers between two remote providers through a local temp dir
+    async fn transfer_staged(
+        &self,
+        job: &JobHandle,
+        resolver: &Arc<sync::Mutex<ConflictResolver>>,
+        items: Vec<FDir>,
+        to_path: &str,
+        is_move: bool,
+    ) -> Result<(), String> {
+        let staging_dir =
+            std::env::temp_dir().join(format!("codriver-transfer-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&staging_dir).map_err(|e| e.to_string())?;
+        dbg_log(format!(
+            "Staging {} items for {} in {:?}",
+            items.len(),
+            to_path,
+            staging_dir
+        ));
+
+        let source = self.provider(&items[0].path);
+        let staging = staging_dir.to_string_lossy().to_string();
+        // Nothing is in the staging dir yet, so nothing conflicts there
+        let mut result = run_transfer(
+            source.clone(),
+            job,
+            resolver,
+            items.clone(),
+            &staging,
+            false,
+        )
+        .await;
+        if result.is_ok() {
+            let staged: Vec<FDir> = fs::read_dir(&staging_dir)
+                .map_err(|e| e.to_string())?
+                .filter_map(|entry| entry.ok().and_then(|entry| fdir_from_path(&entry.path())))
+                .collect();
+            let target = self.provider(to_path);
+            result = run_transfer(target, job, resolver, staged, to_path, false).await;
+        }
+        let _ = fs::remove_dir_all(&staging_dir);
+
+        if result.is_ok() && is_move {
+            result = run_on(source, move |provider| {
+                items
+                    .iter()
+                    .try_for_each(|item| provider.delete(&item.path))
+            })
+            .await;
+        }
+        result
+    }
+}
+
+async fn run_transfer(
+    provider: SharedProvider,
+    job: &JobHandle,
+    resolver: &Arc<sync::Mutex<ConflictResolver>>,
+    items: Vec<FDir>,
+    to_path: &str,
+    is_move: bool,
+) -> Result<(), String> {
+    let job = job.clone();
+    let resolver = resolver.clone();
+    let to = to_path.to_string();
+    run_on(provider, move |provider| {
+        let mut resolver = resolver.lock().map_err(|e| e.to_string())?;
+        match is_move {
+            true => provider.move_items(&job, &mut resolver, items, &to),
+            false => provider.copy_items(&job, &mut resolver, items, &to),
+        }
+    })
+    .await
+}
+
+async fn run_on<R, F>(provider: SharedProvider, f: F) -> Result<R, String>
+where
+    R: Send + 'static,
+    F: FnOnce(&mut dyn CloudProvider) -> Result<R, String> + Send + 'static,
+{
+    let mut provider = provider.lock_owned().await;
+    tauri::async_runtime::spawn_blocking(move || f(&mut *provider))
+        .await
+        .map_err(|e| e.to_string())?
+}
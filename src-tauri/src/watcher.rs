@@ -0,0 +1,180 @@
+use crate::fdir::{fdir_from_path, FDir};
+use crate::navigation::NavKey;
+use crate::saved_search::{saved_search, SearchScope, SEARCH_SCHEME};
+use crate::utils::{dbg_log, err_log};
+use crate::vfs::VfsRegistry;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{Manager, Window};
+
+/// Quiet time after the last change before an event gets emitted
+const DEBOUNCE: Duration = Duration::from_millis(200);
+/// Upper bound for the delay while a directory keeps changing
+const MAX_DELAY: Duration = Duration::from_secs(1);
+
+/// Payload of the `dir-changed` event
+#[derive(Serialize, Clone, Debug)]
+pub struct DirChangedEvent {
+    pub pane_id: String,
+    pub path: String,
+    pub added: Vec<FDir>,
+    pub removed: Vec<FDir>,
+    pub modified: Vec<FDir>,
+}
+
+struct PaneWatcher {
+    path: PathBuf,
+    // Dropping the watcher also ends its debounce thread
+    _watcher: RecommendedWatcher,
+}
+
+/// Managed state holding one watcher per pane for the directory it shows
+#[derive(Default)]
+pub struct DirWatchers {
+    watchers: Mutex<HashMap<NavKey, PaneWatcher>>,
+}
+
+impl DirWatchers {
+    /// Watches `path` for the pane, `entries` is the listing the UI currently shows
+    pub fn watch(&self, key: NavKey, path: PathBuf, app_window: &Window, entries: &[FDir]) {
+        let mut watchers = self.watchers.lock().unwrap();
+        if watchers.get(&key).is_some_and(|w| w.path == path) {
+            return;
+        }
+        watchers.remove(&key);
+        let is_search = path.to_string_lossy().starts_with(SEARCH_SCHEME);
+        // Remote providers have nothing to watch
+        if !is_search
+            && !app_window
+                .state::<VfsRegistry>()
+                .is_local(&path.to_string_lossy())
+        {
+            return;
+        }
+        // Saved searches watch everything below their root for new results
+        let scope = match is_search {
+            true => {
+                let scope = saved_search(&path.to_string_lossy())
+                    .and_then(|search| SearchScope::of(&search));
+                match scope {
+                    Ok(scope) => Some(scope),
+                    Err(e) => {
+                        err_log(format!("Failed to watch {:?}: {}", path, e));
+                        return;
+                    }
+                }
+            }
+            false => None,
+        };
+        let (watched, mode) = match &scope {
+            Some(scope) => (scope.root.clone(), RecursiveMode::Recursive),
+            None => (path.clone(), RecursiveMode::NonRecursive),
+        };
+
+        let (tx, rx) = channel();
+        let watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+            if let Ok(event) = res {
+                let _ = tx.send(event.paths);
+            }
+        });
+        let mut watcher = match watcher {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                err_log(format!("Failed to create watcher: {}", e));
+                return;
+            }
+        };
+        if let Err(e) = watcher.watch(&watched, mode) {
+            err_log(format!("Failed to watch {:?}: {}", watched, e));
+            return;
+        }
+
+        let snapshot = entries
+            .iter()
+            .map(|entry| (entry.path.clone(), entry.clone()))
+            .collect();
+        let app_window = app_window.clone();
+        let pane_id = key.pane.clone();
+        let dir = path.clone();
+        std::thread::spawn(move || debounce(rx, app_window, pane_id, dir, scope, snapshot));
+
+        dbg_log(format!("Watching {:?} for {:?}", path, key));
+        watchers.insert(
+            key,
+            PaneWatcher {
+                path,
+                _watcher: watcher,
+            },
+        );
+    }
+
+    pub fn forget_window(&self, window: &str) {
+        self.watchers
+            .lock()
+            .unwrap()
+            .retain(|key, _| key.window != window);
+    }
+}
+
+fn debounce(
+    rx: Receiver<Vec<PathBuf>>,
+    app_window: Window,
+    pane_id: String,
+    dir: PathBuf,
+    scope: Option<SearchScope>,
+    mut snapshot: HashMap<String, FDir>,
+) {
+    // Ends once the watcher got dropped
+    while let Ok(paths) = rx.recv() {
+        let mut changed: HashSet<PathBuf> = paths.into_iter().collect();
+        let started = Instant::now();
+        while started.elapsed() < MAX_DELAY {
+            match rx.recv_timeout(DEBOUNCE) {
+                Ok(paths) => changed.extend(paths),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+
+        let mut event = DirChangedEvent {
+            pane_id: pane_id.clone(),
+            path: dir.to_string_lossy().to_string().replace("\\", "/"),
+            added: vec![],
+            removed: vec![],
+            modified: vec![],
+        };
+        for path in changed {
+            // Only direct children or results are part of the listing
+            let is_listed = match &scope {
+                Some(scope) => scope.covers(&path),
+                None => path.parent() == Some(dir.as_path()),
+            };
+            if !is_listed {
+                continue;
+            }
+            let key = path.to_string_lossy().to_string().replace("\\", "/");
+            let entry = fdir_from_path(&path)
+                .filter(|_| scope.as_ref().map_or(true, |scope| scope.matches(&path)));
+            match entry {
+                Some(entry) => match snapshot.insert(key, entry.clone()) {
+                    Some(_) => event.modified.push(entry),
+                    None => event.added.push(entry),
+                },
+                None => {
+                    if let Some(entry) = snapshot.remove(&key) {
+                        event.removed.push(entry);
+                    }
+                }
+            }
+        }
+
+        if !event.added.is_empty() || !event.removed.is_empty() || !event.modified.is_empty() {
+            let _ = app_window.emit("dir-changed", event);
+        }
+    }
+}
@@ -0,0 +1,1249 @@
+use crate::conflicts::{unique_name, ConflictResolver, ConflictSide, Resolution, SetAside};
+use crate::fdir::{extension_of, FDir, FileType};
+use crate::jobs::JobHandle;
+use crate::utils::{dbg_log, err_log, measure_entries, wng_log, DirWalkerEntry};
+use crate::vfs::CloudProvider;
+use crate::SimpleDirInfo;
+use chrono::DateTime;
+use percent_encoding::percent_decode_str;
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use reqwest::blocking::{Body, Client, RequestBuilder, Response};
+use reqwest::{Method, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+pub const WEBDAV_PREFIX: &str = "webdav://";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Bigger files are uploaded in chunks of this size if the server supports
+/// Nextcloud's chunked upload, otherwise they are streamed in a single PUT
+const CHUNK_SIZE: u64 = 10 * 1024 * 1024;
+const NEXTCLOUD_FILES: &str = "/remote.php/dav/files/";
+/// Levels of dirs below the root that searches without `SEARCH` walk into
+const SEARCH_DEPTH: usize = 3;
+/// Searches without `SEARCH` stop after this many matches
+const SEARCH_LIMIT: usize = 500;
+
+const PROPS: &str = "<d:resourcetype/><d:getcontentlength/><d:getcontenttype/>\
+    <d:getlastmodified/><d:creationdate/><oc:permissions/><oc:owner-display-name/>";
+
+/// A WebDAV server, saved together with its credentials in the app config
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct WebDavAccount {
+    /// Identifies the account inside of `webdav://` paths
+    pub name: String,
+    /// Root of the account, e.g. `https://cloud.example.com/remote.php/dav/files/alice`
+    pub url: String,
+    pub username: String,
+    pub password: String,
+}
+
+/// Properties of one `<d:response>` of a multistatus answer
+#[derive(Default, Debug)]
+struct DavEntry {
+    href: String,
+    is_collection: bool,
+    size: Option<u64>,
+    content_type: Option<String>,
+    modified: Option<String>,
+    created: Option<String>,
+    permissions: Option<String>,
+    owner: Option<String>,
+}
+
+/// WebDAV client for Nextcloud, ownCloud and plain WebDAV servers. Paths look
+/// like `webdav://<account name>/dir/file`.
+pub struct WebDav {
+    // Created on first use, blocking clients can't be set up inside of the async runtime
+    client: OnceLock<Client>,
+    accounts: Vec<WebDavAccount>,
+}
+
+impl CloudProvider for WebDav {
+    fn new() -> Self {
+        WebDav {
+            client: OnceLock::new(),
+            accounts: vec![],
+        }
+    }
+
+    /// Accounts are checked when they are added in `check`
+    fn authenticate(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn sign_out(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn read_dir(&mut self, path: &PathBuf) -> Result<Vec<FDir>, String> {
+        let (account, dir) = self.remote(&path.to_string_lossy())?;
+        Ok(self
+            .propfind(account, &dir, "1", true)?
+            .into_iter()
+            // The dir itself is part of the answer
+            .filter(|(rel, _)| *rel != dir)
+            .map(|(_, entry)| entry)
+            .collect())
+    }
+
+    fn download(&mut self, from_path: &str, to_path: &str) -> Result<String, String> {
+        let (account, file) = self.remote(from_path)?;
+        let saved_path = PathBuf::from(to_path).join(name_of(&file));
+        self.get_file(account, &file, &saved_path)?;
+        Ok(saved_path.to_string_lossy().to_string())
+    }
+
+    /// Uploads without a job, so an existing target is never replaced
+    fn upload(&mut self, from_path: &str, to_path: &str) -> Result<(), String> {
+        let (account, dir) = self.remote(to_path)?;
+        let from = Path::new(from_path);
+        let target = join(
+            &dir,
+            &from.file_name().unwrap_or_default().to_string_lossy(),
+        );
+        if self.stat(account, &target).is_ok() {
+            return Err(format!("{} already exists", target));
+        }
+        self.put_tree(account, from, &target)
+    }
+
+    fn create_dir(&mut self, from_path: &str, to_path: &str) -> Result<(), String> {
+        let dir_name = from_path.replace("\\", "/");
+        let dir_name = dir_name.split('/').next_back().unwrap_or_default();
+        let (account, parent) = self.remote(to_path)?;
+        dbg_log(format!("Creating directory {}/{}", to_path, dir_name));
+        self.mkcol(account, &join(&parent, dir_name))
+    }
+
+    /// Uses DASL `SEARCH` where the server supports it and matches the names
+    /// in the first levels of dirs everywhere else
+    fn search(&mut self, fname: &str) -> Result<Vec<DirWalkerEntry>, String> {
+        let mut results = vec![];
+        let mut is_searched = false;
+        for account in &self.accounts {
+            let found = self.search_account(account, fname).or_else(|e| {
+                dbg_log(format!(
+                    "SEARCH on {} failed, matching names instead: {}",
+                    account.name, e
+                ));
+                self.walk_search(account, fname)
+            });
+            match found {
+                Ok(found) => {
+                    is_searched = true;
+                    results.extend(found);
+                }
+                Err(e) => wng_log(format!("Search on {} failed: {}", account.name, e)),
+            }
+        }
+        if !is_searched && !self.accounts.is_empty() {
+            return Err("None of the WebDAV servers could be searched".into());
+        }
+        Ok(results
+            .into_iter()
+            .map(|item| DirWalkerEntry {
+                last_modified: item
+                    .modified
+                    .and_then(|ms| DateTime::from_timestamp_millis(ms as i64))
+                    .map(|date| date.to_rfc3339())
+                    .unwrap_or_default(),
+                name: item.name,
+                path: item.path,
+                depth: 0,
+                is_dir: item.is_dir,
+                is_file: !item.is_dir,
+                size: item.size,
+                extension: item.extension,
+            })
+            .collect())
+    }
+
+    fn get_item_size(&self, path: &str) -> Result<SimpleDirInfo, String> {
+        let (account, item) = self.remote(path)?;
+        let mut info = SimpleDirInfo {
+            size: 0,
+            count_elements: 0,
+        };
+        self.measure(account, &item, &mut info)?;
+        Ok(info)
+    }
+
+    fn copy_items(
+        &mut self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        arr_items: Vec<FDir>,
+        copy_to_path: &str,
+    ) -> Result<(), String> {
+        for item in arr_items {
+            self.transfer(job, resolver, &item, copy_to_path, false)?;
+        }
+        Ok(())
+    }
+
+    fn move_items(
+        &mut self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        arr_items: Vec<FDir>,
+        move_to_path: &str,
+    ) -> Result<(), String> {
+        for item in arr_items {
+            self.transfer(job, resolver, &item, move_to_path, true)?;
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, path: &str) -> Result<(), String> {
+        let (account, item) = self.remote(path)?;
+        let url = item_url(account, &item, false)?;
+        self.send(self.request("DELETE", account, url), "DELETE")
+            .map(|_| ())
+    }
+
+    fn rename(&mut self, path: &str, new_name: &str) -> Result<(), String> {
+        let (account, item) = self.remote(path)?;
+        let target = join(parent_of(&item), new_name);
+        self.move_or_copy("MOVE", account, &item, &target)
+    }
+}
+
+impl WebDav {
+    pub fn set_accounts(&mut self, accounts: Vec<WebDavAccount>) {
+        self.accounts = accounts;
+    }
+
+    /// Makes sure the server answers with the credentials of the account
+    pub fn check(&self, account: &WebDavAccount) -> Result<(), String> {
+        self.propfind(account, "/", "0", true).map(|_| ())
+    }
+
+    fn client(&self) -> &Client {
+        self.client.get_or_init(|| {
+            Client::builder()
+                .connect_timeout(CONNECT_TIMEOUT)
+                .build()
+                .unwrap_or_default()
+        })
+    }
+
+    /// Splits a `webdav://` path into its account and the path inside of it
+    fn remote(&self, path: &str) -> Result<(&WebDavAccount, String), String> {
+        let path = path.replace("\\", "/");
+        let rest = path
+            .strip_prefix(WEBDAV_PREFIX)
+            .ok_or(format!("{} is not a WebDAV path", path))?;
+        let (name, inner) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let account = self
+            .accounts
+            .iter()
+            .find(|account| account.name == name)
+            .ok_or(format!("There's no WebDAV account named {}", name))?;
+        Ok((account, normalize(inner)))
+    }
+
+    fn request(&self, method: &str, account: &WebDavAccount, url: Url) -> RequestBuilder {
+        // Only fails for invalid tokens, all methods used here are valid
+        let method = Method::from_bytes(method.as_bytes()).unwrap_or(Method::GET);
+        self.client()
+            .request(method, url)
+            .basic_auth(&account.username, Some(&account.password))
+    }
+
+    fn send(&self, request: RequestBuilder, what: &str) -> Result<Response, String> {
+        let response = request.send().map_err(|e| e.to_string())?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("{} failed: {}", what, status));
+        }
+        Ok(response)
+    }
+
+    /// Entries of a `PROPFIND`, each with its path inside of the account
+    fn propfind(
+        &self,
+        account: &WebDavAccount,
+        path: &str,
+        depth: &str,
+        is_dir: bool,
+    ) -> Result<Vec<(String, FDir)>, String> {
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?><d:propfind xmlns:d="DAV:" xmlns:oc="http://owncloud.org/ns"><d:prop>{}</d:prop></d:propfind>"#,
+            PROPS
+        );
+        let request = self
+            .request("PROPFIND", account, item_url(account, path, is_dir)?)
+            .header("Depth", depth)
+            .header("Content-Type", "application/xml; charset=utf-8")
+            .body(body);
+        let xml = self
+            .send(request, "PROPFIND")?
+            .text()
+            .map_err(|e| e.to_string())?;
+        parse_multistatus(&xml)?
+            .into_iter()
+            .map(|entry| fdir_of(account, entry))
+            .collect()
+    }
+
+    fn stat(&self, account: &WebDavAccount, path: &str) -> Result<FDir, String> {
+        self.propfind(account, path, "0", false)?
+            .into_iter()
+            .next()
+            .map(|(_, entry)| entry)
+            .ok_or(format!("{} doesn't exist", path))
+    }
+
+    fn mkcol(&self, account: &WebDavAccount, path: &str) -> Result<(), String> {
+        let url = item_url(account, path, true)?;
+        self.send(self.request("MKCOL", account, url), "MKCOL")
+            .map(|_| ())
+    }
+
+    fn move_or_copy(
+        &self,
+        method: &str,
+        account: &WebDavAccount,
+        from: &str,
+        to: &str,
+    ) -> Result<(), String> {
+        let request = self
+            .request(method, account, item_url(account, from, false)?)
+            .header("Destination", item_url(account, to, false)?.as_str())
+            // Existing items are never replaced
+            .header("Overwrite", "F")
+            .header("Depth", "infinity");
+        self.send(request, method).map(|_| ())
+    }
+
+    fn get_file(&self, account: &WebDavAccount, path: &str, to: &Path) -> Result<(), String> {
+        dbg_log(format!("Downloading {} to {:?}", path, to));
+        let mut response = self.send(
+            self.request("GET", account, item_url(account, path, false)?),
+            "GET",
+        )?;
+        let mut file = File::create(to).map_err(|e| e.to_string())?;
+        if let Err(e) = response.copy_to(&mut file) {
+            let _ = fs::remove_file(to);
+            return Err(e.to_string());
+        }
+        Ok(())
+    }
+
+    fn put_file(&self, account: &WebDavAccount, from: &Path, path: &str) -> Result<(), String> {
+        let size = fs::metadata(from).map_err(|e| e.to_string())?.len();
+        if size > CHUNK_SIZE {
+            if let Some(uploads) = uploads_url(account) {
+                return self.put_chunked(account, from, path, size, uploads);
+            }
+        }
+        dbg_log(format!("Uploading {:?} to {}", from, path));
+        let file = File::open(from).map_err(|e| e.to_string())?;
+        let request = self
+            .request("PUT", account, item_url(account, path, false)?)
+            .body(Body::sized(file, size));
+        self.send(request, "PUT").map(|_| ())
+    }
+
+    /// Nextcloud's chunked upload: the chunks go into an upload dir which is
+    /// then moved to the target
+    fn put_chunked(
+        &self,
+        account: &WebDavAccount,
+        from: &Path,
+        path: &str,
+        size: u64,
+        uploads: Url,
+    ) -> Result<(), String> {
+        dbg_log(format!("Uploading {:?} to {} in chunks", from, path));
+        let destination = item_url(account, path, false)?.to_string();
+        let chunk_url = |name: &str| uploads.join(name).map_err(|e| e.to_string());
+        self.send(
+            self.request("MKCOL", account, uploads.clone())
+                .header("Destination", &destination),
+            "MKCOL",
+        )?;
+
+        let upload = || {
+            let mut file = File::open(from).map_err(|e| e.to_string())?;
+            let mut number = 1;
+            loop {
+                let mut chunk = Vec::with_capacity(CHUNK_SIZE as usize);
+                (&mut file)
+                    .take(CHUNK_SIZE)
+                    .read_to_end(&mut chunk)
+                    .map_err(|e| e.to_string())?;
+                if chunk.is_empty() {
+                    break;
+                }
+                let request = self
+                    .request("PUT", account, chunk_url(&format!("{:05}", number))?)
+                    .header("Destination", &destination)
+                    .header("OC-Total-Length", size)
+                    .body(chunk);
+                self.send(request, "PUT")?;
+                number += 1;
+            }
+            let request = self
+                .request("MOVE", account, chunk_url(".file")?)
+                .header("Destination", &destination)
+                .header("OC-Total-Length", size);
+            self.send(request, "MOVE").map(|_| ())
+        };
+        let result = upload();
+        if result.is_err() {
+            let _ = self.request("DELETE", account, uploads).send();
+        }
+        result
+    }
+
+    fn delete_item(&self, account: &WebDavAccount, path: &str) -> Result<(), String> {
+        let url = item_url(account, path, false)?;
+        self.send(self.request("DELETE", account, url), "DELETE")
+            .map(|_| ())
+    }
+
+    /// Asks the resolver what to do with `target` on the server
+    fn resolve(
+        &self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        source: ConflictSide,
+        account: &WebDavAccount,
+        target: &str,
+    ) -> Result<Resolution, String> {
+        let existing = self
+            .stat(account, target)
+            .ok()
+            .map(|item| ConflictSide::from_item(&item));
+        let path_of = |path: &Path| normalize(&path.to_string_lossy().replace("\\", "/"));
+        let exists = |path: &Path| self.stat(account, &path_of(path)).is_ok();
+        Ok(
+            match resolver.resolve_target(job, source, existing, Path::new(target), exists)? {
+                Resolution::CopyTo(path) => Resolution::CopyTo(path_of(&path).into()),
+                Resolution::Replace(path) => Resolution::Replace(path_of(&path).into()),
+                Resolution::Skip => Resolution::Skip,
+            },
+        )
+    }
+
+    /// Replaces `target` with what `write` creates there. The old one is only
+    /// removed once that succeeded and put back otherwise.
+    fn replace<T>(
+        &self,
+        account: &WebDavAccount,
+        target: &str,
+        write: impl FnOnce() -> Result<T, String>,
+    ) -> Result<T, String> {
+        let backup = unique_name(
+            Path::new(&join(
+                parent_of(target),
+                &format!(".{}.codriver-old", name_of(target)),
+            )),
+            |path| self.stat(account, &path.to_string_lossy()).is_ok(),
+        );
+        let backup = backup.to_string_lossy().replace("\\", "/");
+        self.move_or_copy("MOVE", account, target, &backup)?;
+        let result = write();
+        match &result {
+            Ok(_) => {
+                if let Err(e) = self.delete_item(account, &backup) {
+                    wng_log(format!("Failed to remove the replaced {}: {}", backup, e));
+                }
+            }
+            Err(_) => {
+                let _ = self.delete_item(account, target);
+                if let Err(e) = self.move_or_copy("MOVE", account, &backup, target) {
+                    err_log(format!(
+                        "Failed to restore {}, it was kept as {}: {}",
+                        target, backup, e
+                    ));
+                }
+            }
+        }
+        result
+    }
+
+    /// Uploads a local file or a whole dir into the dir `to`. Returns false
+    /// if conflicts skipped some of it.
+    fn upload_into(
+        &self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        account: &WebDavAccount,
+        from: &Path,
+        to: &str,
+    ) -> Result<bool, String> {
+        job.checkpoint()?;
+        let target = join(to, &from.file_name().unwrap_or_default().to_string_lossy());
+        let source = ConflictSide::from_path(from);
+        let (count, size) = match source.is_dir {
+            true => (0, 0),
+            false => (1, source.size),
+        };
+        let target = match self.resolve(job, resolver, source, account, &target)? {
+            Resolution::CopyTo(target) => target.to_string_lossy().to_string(),
+            Resolution::Replace(target) => {
+                let target = target.to_string_lossy().to_string();
+                return self.replace(account, &target, || {
+                    self.upload_resolved(job, resolver, account, from, &target)
+                });
+            }
+            Resolution::Skip => {
+                let (count, size) = measure_entries(&from.to_string_lossy());
+                job.add_done(count, size);
+                return Ok(false);
+            }
+        };
+        let complete = self.upload_resolved(job, resolver, account, from, &target)?;
+        job.add_done(count, size);
+        Ok(complete)
+    }
+
+    fn upload_resolved(
+        &self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        account: &WebDavAccount,
+        from: &Path,
+        target: &str,
+    ) -> Result<bool, String> {
+        if !from.is_dir() {
+            job.begin_item(&name_of(target));
+            self.put_file(account, from, target)?;
+            return Ok(true);
+        }
+        // Existing dirs are merged
+        if self.stat(account, target).is_err() {
+            self.mkcol(account, target)?;
+        }
+        let mut complete = true;
+        for entry in fs::read_dir(from).map_err(|e| e.to_string())? {
+            let entry = entry.map_err(|e| e.to_string())?;
+            complete &= self.upload_into(job, resolver, account, &entry.path(), target)?;
+        }
+        Ok(complete)
+    }
+
+    /// Uploads a local file or a whole dir to `to`, replacing what's there
+    fn put_tree(&self, account: &WebDavAccount, from: &Path, to: &str) -> Result<(), String> {
+        if !from.is_dir() {
+            return self.put_file(account, from, to);
+        }
+        self.mkcol(account, to)?;
+        for entry in fs::read_dir(from).map_err(|e| e.to_string())? {
+            let entry = entry.map_err(|e| e.to_string())?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            self.put_tree(account, &entry.path(), &join(to, &name))?;
+        }
+        Ok(())
+    }
+
+    /// Downloads a file or a whole dir into the local dir `to`. Returns false
+    /// if conflicts skipped some of it.
+    fn fetch_into(
+        &self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        account: &WebDavAccount,
+        from: &str,
+        item: &FDir,
+        to: &Path,
+    ) -> Result<bool, String> {
+        job.checkpoint()?;
+        let target = to.join(name_of(from));
+        if item.is_dir {
+            fs::create_dir_all(&target).map_err(|e| e.to_string())?;
+            let mut complete = true;
+            for (child, entry) in self
+                .propfind(account, from, "1", true)?
+                .into_iter()
+                .filter(|(child, _)| child != from)
+            {
+                complete &= self.fetch_into(job, resolver, account, &child, &entry, &target)?;
+            }
+            return Ok(complete);
+        }
+
+        job.add_total(1, item.size);
+        let mut replaced = SetAside::default();
+        let target = match resolver.resolve_source(job, ConflictSide::from_item(item), &target)? {
+            Resolution::CopyTo(target) => target,
+            Resolution::Replace(target) => {
+                replaced.set_aside(&target)?;
+                target
+            }
+            Resolution::Skip => {
+                job.add_done(1, item.size);
+                return Ok(false);
+            }
+        };
+        job.begin_item(&item.name);
+        replaced.finish(self.get_file(account, from, &target))?;
+        job.add_bytes(item.size);
+        job.finish_item();
+        Ok(true)
+    }
+
+    /// `COPY` or `MOVE` on the server into the dir `to`, existing dirs are
+    /// merged item by item. Returns false if conflicts skipped some of it.
+    #[allow(clippy::too_many_arguments)]
+    fn transfer_on_server(
+        &self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        account: &WebDavAccount,
+        from: &str,
+        item: &FDir,
+        to: &str,
+        is_move: bool,
+    ) -> Result<bool, String> {
+        job.checkpoint()?;
+        let method = if is_move { "MOVE" } else { "COPY" };
+        let target = join(to, &name_of(from));
+        match self.resolve(
+            job,
+            resolver,
+            ConflictSide::from_item(item),
+            account,
+            &target,
+        )? {
+            Resolution::CopyTo(target) if self.stat(account, &target.to_string_lossy()).is_ok() => {
+                let target = target.to_string_lossy().to_string();
+                let mut complete = true;
+                for (child, entry) in self
+                    .propfind(account, from, "1", true)?
+                    .into_iter()
+                    .filter(|(child, _)| child != from)
+                {
+                    complete &= self.transfer_on_server(
+                        job, resolver, account, &child, &entry, &target, is_move,
+                    )?;
+                }
+                // The emptied source dir goes, too
+                if is_move && complete {
+                    self.delete_item(account, from)?;
+                }
+                Ok(complete)
+            }
+            Resolution::CopyTo(target) => {
+                job.add_total(1, item.size);
+                self.move_or_copy(method, account, from, &target.to_string_lossy())?;
+                job.add_done(1, item.size);
+                Ok(true)
+            }
+            Resolution::Replace(target) => {
+                let target = target.to_string_lossy().to_string();
+                job.add_total(1, item.size);
+                self.replace(account, &target, || {
+                    self.move_or_copy(method, account, from, &target)
+                })?;
+                job.add_done(1, item.size);
+                Ok(true)
+            }
+            Resolution::Skip => Ok(false),
+        }
+    }
+
+    fn transfer(
+        &self,
+        job: &JobHandle,
+        resolver: &mut ConflictResolver,
+        item: &FDir,
+        to_path: &str,
+        is_move: bool,
+    ) -> Result<(), String> {
+        let from = item.path.replace("\\", "/");
+        let complete = match (
+            from.starts_with(WEBDAV_PREFIX),
+            to_path.starts_with(WEBDAV_PREFIX),
+        ) {
+            (true, true) => {
+                let (account, from) = self.remote(&from)?;
+                let (to_account, to) = self.remote(to_path)?;
+                let source = self.stat(account, &from)?;
+                if account.name == to_account.name {
+                    self.transfer_on_server(job, resolver, account, &from, &source, &to, is_move)?;
+                    return Ok(());
+                }
+                // Different servers can't copy between each other
+                let staging_dir =
+                    std::env::temp_dir().join(format!("codriver-webdav-{}", uuid::Uuid::new_v4()));
+                fs::create_dir_all(&staging_dir).map_err(|e| e.to_string())?;
+                let staged = staging_dir.join(name_of(&from));
+                let result = self
+                    .fetch_into(job, resolver, account, &from, &source, &staging_dir)
+                    .and_then(|_| {
+                        let (count, size) = measure_entries(&staged.to_string_lossy());
+                        job.add_total(count, size);
+                        self.upload_into(job, resolver, to_account, &staged, &to)
+                    });
+                let _ = fs::remove_dir_all(&staging_dir);
+                result?
+            }
+            (true, false) => {
+                let (account, from) = self.remote(&from)?;
+                let source = self.stat(account, &from)?;
+                fs::create_dir_all(to_path).map_err(|e| e.to_string())?;
+                self.fetch_into(job, resolver, account, &from, &source, Path::new(to_path))?
+            }
+            (false, true) => {
+                let (account, to) = self.remote(to_path)?;
+                let (count, size) = measure_entries(&from);
+                job.add_total(count, size);
+                self.upload_into(job, resolver, account, Path::new(&from), &to)?
+            }
+            (false, false) => return Err("Neither side of the transfer is on WebDAV".into()),
+        };
+
+        // The source of an item is only removed once all of it was copied
+        if is_move && !complete {
+            wng_log(format!("{} was kept, conflicts skipped some of it", from));
+        } else if is_move {
+            if from.starts_with(WEBDAV_PREFIX) {
+                let (account, from) = self.remote(&from)?;
+                self.delete_item(account, &from)?;
+            } else if item.is_dir {
+                fs::remove_dir_all(&from).map_err(|e| e.to_string())?;
+            } else {
+                fs::remove_file(&from).map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn measure(
+        &self,
+        account: &WebDavAccount,
+        path: &str,
+        info: &mut SimpleDirInfo,
+    ) -> Result<(), String> {
+        let item = self.stat(account, path)?;
+        if !item.is_dir {
+            info.size += item.size;
+            info.count_elements += 1;
+            return Ok(());
+        }
+        // Depth "infinity" is disabled on most servers, dirs are walked one by one
+        for (child, entry) in self.propfind(account, path, "1", true)? {
+            if child == path {
+                continue;
+            }
+            if entry.is_dir {
+                self.measure(account, &child, info)?;
+            } else {
+                info.size += entry.size;
+                info.count_elements += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn search_account(&self, account: &WebDavAccount, fname: &str) -> Result<Vec<FDir>, String> {
+        let base = Url::parse(&account.url).map_err(|e| e.to_string())?;
+        // Nextcloud answers searches on its DAV root, scoped to the files of the user
+        let (arbiter, scope) = match base.path().find(NEXTCLOUD_FILES) {
+            Some(i) => {
+                let mut arbiter = base.clone();
+                arbiter.set_path(&format!("{}/remote.php/dav/", &base.path()[..i]));
+                let scope = format!("/files/{}", &base.path()[i + NEXTCLOUD_FILES.len()..]);
+                (arbiter, scope)
+            }
+            None => (base.clone(), base.path().to_string()),
+        };
+        let body = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<d:searchrequest xmlns:d="DAV:" xmlns:oc="http://owncloud.org/ns">
+  <d:basicsearch>
+    <d:select><d:prop>{}</d:prop></d:select>
+    <d:from><d:scope><d:href>{}</d:href><d:depth>infinity</d:depth></d:scope></d:from>
+    <d:where><d:like><d:prop><d:displayname/></d:prop><d:literal>%{}%</d:literal></d:like></d:where>
+  </d:basicsearch>
+</d:searchrequest>"#,
+            PROPS,
+            escape(scope.trim_end_matches('/')),
+            escape(fname)
+        );
+        let request = self
+            .request("SEARCH", account, arbiter)
+            .header("Content-Type", "text/xml; charset=utf-8")
+            .body(body);
+        let response = request.send().map_err(|e| e.to_string())?;
+        if response.status() != StatusCode::MULTI_STATUS {
+            return Err(format!("SEARCH failed: {}", response.status()));
+        }
+        let xml = response.text().map_err(|e| e.to_string())?;
+        parse_multistatus(&xml)?
+            .into_iter()
+            .map(|entry| fdir_of(account, entry).map(|(_, item)| item))
+            .collect()
+    }
+    /// Matches names dir by dir, for servers without `SEARCH`
+    fn walk_search(&self, account: &WebDavAccount, fname: &str) -> Result<Vec<FDir>, String> {
+        let needle = fname.to_lowercase();
+        let mut results = vec![];
+        let mut dirs = vec!["/".to_string()];
+        for _ in 0..=SEARCH_DEPTH {
+            let mut subdirs = vec![];
+            for dir in dirs {
+                let entries = match self.propfind(account, &dir, "1", true) {
+                    Ok(entries) => entries,
+                    Err(e) if dir == "/" => return Err(e),
+                    Err(e) => {
+                        dbg_log(format!("Skipping {} in the search: {}", dir, e));
+                        continue;
+                    }
+                };
+                for (path, item) in entries.into_iter().filter(|(path, _)| *path != dir) {
+                    if item.is_dir {
+                        subdirs.push(path);
+                    }
+                    if item.name.to_lowercase().contains(&needle) {
+                        results.push(item);
+                        if results.len() >= SEARCH_LIMIT {
+                            return Ok(results);
+                        }
+                    }
+                }
+            }
+            dirs = subdirs;
+        }
+        Ok(results)
+    }
+}
+
+/// URL of a path inside of the account, dirs get a trailing slash
+fn item_url(account: &WebDavAccount, path: &str, is_dir: bool) -> Result<Url, String> {
+    let mut url = Url::parse(&account.url).map_err(|e| e.to_string())?;
+    {
+        let mut segments = url
+            .path_segments_mut()
+            .map_err(|_| format!("{} can't be used as WebDAV root", account.url))?;
+        segments.pop_if_empty();
+        segments.extend(path.split('/').filter(|segment| !segment.is_empty()));
+        if is_dir {
+            segments.push("");
+        }
+    }
+    Ok(url)
+}
+
+/// Where the chunks of an upload go, only Nextcloud has that
+fn uploads_url(account: &WebDavAccount) -> Option<Url> {
+    let mut url = Url::parse(&account.url).ok()?;
+    let path = url.path().to_string();
+    let i = path.find(NEXTCLOUD_FILES)?;
+    let user = path[i + NEXTCLOUD_FILES.len()..].split('/').next()?;
+    url.set_path(&format!(
+        "{}/remote.php/dav/uploads/{}/codriver-{}/",
+        &path[..i],
+        user,
+        uuid::Uuid::new_v4()
+    ));
+    Some(url)
+}
+
+fn fdir_of(account: &WebDavAccount, entry: DavEntry) -> Result<(String, FDir), String> {
+    let base = Url::parse(&account.url).map_err(|e| e.to_string())?;
+    let href = base.join(&entry.href).map_err(|e| e.to_string())?;
+    let base_path = percent_decode_str(base.path()).decode_utf8_lossy();
+    let href_path = percent_decode_str(href.path()).decode_utf8_lossy();
+    let rel = normalize(
+        href_path
+            .strip_prefix(base_path.trim_end_matches('/'))
+            .unwrap_or(&href_path),
+    );
+
+    let name = name_of(&rel);
+    let is_dir = entry.is_collection;
+    let mime_type = match (is_dir, entry.content_type) {
+        (true, _) => "inode/directory".to_string(),
+        (false, Some(content_type)) => content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+        (false, None) => mime_guess::from_path(&name)
+            .first_or_octet_stream()
+            .essence_str()
+            .to_string(),
+    };
+    let item = FDir {
+        path: format!("{}{}{}", WEBDAV_PREFIX, account.name, rel),
+        file_type: if is_dir {
+            FileType::Dir
+        } else {
+            FileType::File
+        },
+        is_dir,
+        extension: extension_of(&name, is_dir),
+        size: if is_dir { 0 } else { entry.size.unwrap_or(0) },
+        modified: entry
+            .modified
+            .and_then(|date| DateTime::parse_from_rfc2822(&date).ok())
+            .map(|date| date.timestamp_millis() as u64),
+        created: entry
+            .created
+            .and_then(|date| DateTime::parse_from_rfc3339(&date).ok())
+            .map(|date| date.timestamp_millis() as u64),
+        owner: entry.owner,
+        is_hidden: name.starts_with('.'),
+        // ownCloud permissions: W writes files, C and K create inside of dirs
+        is_readonly: entry
+            .permissions
+            .is_some_and(|permissions| !permissions.contains(['W', 'C', 'K'])),
+        mime_type,
+        name,
+        ..Default::default()
+    };
+    Ok((rel, item))
+}
+
+fn parse_multistatus(xml: &str) -> Result<Vec<DavEntry>, String> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+    let mut entries = vec![];
+    let mut entry: Option<DavEntry> = None;
+    let mut tag = String::new();
+    loop {
+        match reader.read_event().map_err(|e| e.to_string())? {
+            Event::Start(start) => {
+                tag = String::from_utf8_lossy(start.local_name().as_ref()).to_string();
+                match tag.as_str() {
+                    "response" => entry = Some(DavEntry::default()),
+                    "collection" => {
+                        if let Some(entry) = entry.as_mut() {
+                            entry.is_collection = true;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::Empty(empty) if empty.local_name().as_ref() == b"collection" => {
+                if let Some(entry) = entry.as_mut() {
+                    entry.is_collection = true;
+                }
+            }
+            Event::Text(text) => {
+                let Some(entry) = entry.as_mut() else {
+                    continue;
+                };
+                let text = text.unescape().map_err(|e| e.to_string())?.to_string();
+                match tag.as_str() {
+                    "href" => entry.href = text,
+                    "getcontentlength" => entry.size = text.parse().ok(),
+                    "getcontenttype" => entry.content_type = Some(text),
+                    "getlastmodified" => entry.modified = Some(text),
+                    "creationdate" => entry.created = Some(text),
+                    "permissions" => entry.permissions = Some(text),
+                    "owner-display-name" => entry.owner = Some(text),
+                    _ => {}
+                }
+            }
+            Event::End(end) => {
+                if end.local_name().as_ref() == b"response" {
+                    entries.extend(entry.take());
+                }
+                tag.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(entries)
+}
+
+/// `/a/b` without duplicate or trailing slashes, `/` for the root
+fn normalize(path: &str) -> String {
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    format!("/{}", segments.join("/"))
+}
+
+fn join(dir: &str, name: &str) -> String {
+    normalize(&format!("{}/{}", dir, name))
+}
+
+fn name_of(path: &str) -> String {
+    path.rsplit('/').next().unwrap_or_default().to_string()
+}
+
+fn parent_of(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(0) | None => "/",
+        Some(i) => &path[..i],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+    use tempfile::TempDir;
+
+    /// A Depth 1 answer of Nextcloud for `/Photos`, props it doesn't have
+    /// for an item are listed with a 404 status
+    const PHOTOS: &str = r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:" xmlns:s="http://sabredav.org/ns" xmlns:oc="http://owncloud.org/ns">
+ <d:response>
+  <d:href>/remote.php/dav/files/alice/Photos/</d:href>
+  <d:propstat>
+   <d:prop>
+    <d:resourcetype><d:collection/></d:resourcetype>
+    <d:getlastmodified>Tue, 14 May 2024 09:30:00 GMT</d:getlastmodified>
+    <oc:permissions>RGDNVCK</oc:permissions>
+    <oc:owner-display-name>Alice</oc:owner-display-name>
+   </d:prop>
+   <d:status>HTTP/1.1 200 OK</d:status>
+  </d:propstat>
+  <d:propstat>
+   <d:prop><d:getcontentlength/><d:getcontenttype/><d:creationdate/></d:prop>
+   <d:status>HTTP/1.1 404 Not Found</d:status>
+  </d:propstat>
+ </d:response>
+ <d:response>
+  <d:href>/remote.php/dav/files/alice/Photos/My%20Trip/</d:href>
+  <d:propstat>
+   <d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop>
+   <d:status>HTTP/1.1 200 OK</d:status>
+  </d:propstat>
+ </d:response>
+ <d:response>
+  <d:href>/remote.php/dav/files/alice/Photos/caf%C3%A9%20%26%20more.jpg</d:href>
+  <d:propstat>
+   <d:prop>
+    <d:resourcetype/>
+    <d:getcontentlength>2048</d:getcontentlength>
+    <d:getcontenttype>image/jpeg; charset=binary</d:getcontenttype>
+    <d:getlastmodified>Wed, 15 May 2024 10:00:00 GMT</d:getlastmodified>
+    <d:creationdate>2024-05-01T08:00:00Z</d:creationdate>
+    <oc:permissions>RGDNV</oc:permissions>
+   </d:prop>
+   <d:status>HTTP/1.1 200 OK</d:status>
+  </d:propstat>
+ </d:response>
+ <d:response>
+  <d:href>http://cloud.example.com/remote.php/dav/files/alice/Photos/a&amp;b.txt</d:href>
+  <d:propstat>
+   <d:prop><d:resourcetype/><d:getcontentlength>7</d:getcontentlength></d:prop>
+   <d:status>HTTP/1.1 200 OK</d:status>
+  </d:propstat>
+ </d:response>
+</d:multistatus>"#;
+
+    /// Depth 0 and 1 answers for `/Photos/My Trip`
+    const TRIP: &str = r#"<d:multistatus xmlns:d="DAV:"><d:response>
+  <d:href>/remote.php/dav/files/alice/Photos/My%20Trip/</d:href>
+  <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat>
+</d:response></d:multistatus>"#;
+    const TRIP_LISTING: &str = r#"<d:multistatus xmlns:d="DAV:"><d:response>
+  <d:href>/remote.php/dav/files/alice/Photos/My%20Trip/</d:href>
+  <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat>
+</d:response><d:response>
+  <d:href>/remote.php/dav/files/alice/Photos/My%20Trip/beach.png</d:href>
+  <d:propstat><d:prop><d:getcontentlength>100</d:getcontentlength></d:prop></d:propstat>
+</d:response></d:multistatus>"#;
+
+    fn account(url: &str) -> WebDavAccount {
+        WebDavAccount {
+            name: "cloud".into(),
+            url: url.into(),
+            username: "alice".into(),
+            password: "secret".into(),
+        }
+    }
+
+    /// Answers one request per answer with a multistatus, returns the
+    /// method, path and Depth header of every request
+    fn serve(answers: Vec<&'static str>) -> (String, JoinHandle<Vec<[String; 3]>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!(
+            "http://{}/remote.php/dav/files/alice",
+            listener.local_addr().unwrap()
+        );
+        let server = thread::spawn(move || {
+            let mut requests = vec![];
+            for answer in answers {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let mut parts = line.split_whitespace().map(String::from);
+                let (method, path) = (parts.next().unwrap(), parts.next().unwrap());
+                let (mut depth, mut length) = (String::new(), 0);
+                loop {
+                    line.clear();
+                    reader.read_line(&mut line).unwrap();
+                    let Some((name, value)) = line.trim_end().split_once(": ") else {
+                        break;
+                    };
+                    match name.to_lowercase().as_str() {
+                        "depth" => depth = value.to_string(),
+                        "content-length" => length = value.parse().unwrap(),
+                        _ => {}
+                    }
+                }
+                reader.read_exact(&mut vec![0; length]).unwrap();
+                write!(
+                    stream,
+                    "HTTP/1.1 207 Multi-Status\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    answer.len(),
+                    answer
+                )
+                .unwrap();
+                requests.push([method, path, depth]);
+            }
+            requests
+        });
+        (url, server)
+    }
+
+    #[test]
+    fn multistatus_responses_become_entries() {
+        let entries = parse_multistatus(PHOTOS).unwrap();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].href, "/remote.php/dav/files/alice/Photos/");
+        assert!(entries[0].is_collection);
+        // Empty props of the 404 propstat don't count
+        assert_eq!(entries[0].size, None);
+        assert_eq!(entries[0].owner.as_deref(), Some("Alice"));
+        assert!(entries[1].is_collection);
+        assert!(!entries[2].is_collection);
+        assert_eq!(entries[2].size, Some(2048));
+        assert_eq!(
+            entries[3].href,
+            "http://cloud.example.com/remote.php/dav/files/alice/Photos/a&b.txt"
+        );
+        assert!(parse_multistatus("<d:multistatus></d:response>").is_err());
+    }
+
+    #[test]
+    fn hrefs_are_decoded_relative_to_the_account() {
+        let account = account("http://cloud.example.com/remote.php/dav/files/alice/");
+        let entries: Vec<(String, FDir)> = parse_multistatus(PHOTOS)
+            .unwrap()
+            .into_iter()
+            .map(|entry| fdir_of(&account, entry).unwrap())
+            .collect();
+        let paths: Vec<&str> = entries.iter().map(|(rel, _)| rel.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "/Photos",
+                "/Photos/My Trip",
+                "/Photos/café & more.jpg",
+                "/Photos/a&b.txt"
+            ]
+        );
+
+        let (_, dir) = &entries[1];
+        assert_eq!(dir.path, "webdav://cloud/Photos/My Trip");
+        assert_eq!(dir.name, "My Trip");
+        assert!(dir.is_dir);
+        assert_eq!(dir.mime_type, "inode/directory");
+        let (_, image) = &entries[2];
+        assert_eq!(image.name, "café & more.jpg");
+        assert_eq!(image.extension, ".jpg");
+        assert_eq!(image.size, 2048);
+        assert_eq!(image.mime_type, "image/jpeg");
+        assert_eq!(image.modified, Some(1_715_767_200_000));
+        assert_eq!(image.created, Some(1_714_550_400_000));
+        assert!(image.is_readonly);
+        assert!(!entries[0].1.is_readonly);
+        assert_eq!(entries[3].1.mime_type, "text/plain");
+    }
+
+    #[test]
+    fn listings_ask_for_one_level_and_drop_the_dir_itself() {
+        let (url, server) = serve(vec![PHOTOS, TRIP, TRIP_LISTING]);
+        let mut webdav = WebDav::new();
+        webdav.set_accounts(vec![account(&url)]);
+
+        let listed = webdav
+            .read_dir(&PathBuf::from("webdav://cloud/Photos"))
+            .unwrap();
+        let names: Vec<&str> = listed.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, ["My Trip", "café & more.jpg", "a&b.txt"]);
+        // Sizes are measured level by level
+        let info = webdav
+            .get_item_size("webdav://cloud/Photos/My Trip")
+            .unwrap();
+        assert_eq!((info.count_elements, info.size), (1, 100));
+
+        let requests = server.join().unwrap();
+        let dir = "/remote.php/dav/files/alice/Photos/";
+        let expected = [
+            ["PROPFIND", dir, "1"],
+            [
+                "PROPFIND",
+                "/remote.php/dav/files/alice/Photos/My%20Trip",
+                "0",
+            ],
+            [
+                "PROPFIND",
+                "/remote.php/dav/files/alice/Photos/My%20Trip/",
+                "1",
+            ],
+        ];
+        assert_eq!(requests, expected.map(|request| request.map(String::from)));
+    }
+
+    /// Runs against the account in `CODRIVER_WEBDAV_TEST_URL`, e.g.
+    /// `http://localhost:8080/remote.php/dav/files/alice`, with the
+    /// credentials in `CODRIVER_WEBDAV_TEST_USER` and `CODRIVER_WEBDAV_TEST_PASSWORD`
+    #[test]
+    #[ignore]
+    fn round_trip_against_a_server() {
+        let env = |name: &str| std::env::var(name).unwrap();
+        let mut webdav = WebDav::new();
+        let account = WebDavAccount {
+            name: "test".into(),
+            url: env("CODRIVER_WEBDAV_TEST_URL"),
+            username: env("CODRIVER_WEBDAV_TEST_USER"),
+            password: env("CODRIVER_WEBDAV_TEST_PASSWORD"),
+        };
+        webdav.check(&account).unwrap();
+        webdav.set_accounts(vec![account]);
+
+        let local = TempDir::new().unwrap();
+        let file = local.path().join("codriver test.txt");
+        fs::write(&file, "round trip").unwrap();
+        webdav
+            .upload(&file.to_string_lossy(), "webdav://test/")
+            .unwrap();
+        let listed = webdav.read_dir(&PathBuf::from("webdav://test/")).unwrap();
+        assert!(listed
+            .iter()
+            .any(|item| item.name == "codriver test.txt" && item.size == 10));
+
+        webdav
+            .rename("webdav://test/codriver test.txt", "codriver renamed.txt")
+            .unwrap();
+        let saved = webdav
+            .download(
+                "webdav://test/codriver renamed.txt",
+                &local.path().to_string_lossy(),
+            )
+            .unwrap();
+        assert_eq!(fs::read_to_string(saved).unwrap(), "round trip");
+
+        webdav.delete("webdav://test/codriver renamed.txt").unwrap();
+        let listed = webdav.read_dir(&PathBuf::from("webdav://test/")).unwrap();
+        assert!(!listed
+            .iter()
+            .any(|item| item.name == "codriver renamed.txt"));
+    }
+}
@@ -50,6 +50,8 @@ ds.subscribe('DS:unselect', async (payload) => {
 });
 
 /* region Global Variables */
+// Must match trash::TRASH_UNSUPPORTED
+const TrashUnsupported = "Moving to the trash isn't supported here, delete permanently?";
 let ViewMode = "wrap";
 let OrgViewMode = "wrap";
 
@@ -109,15 +111,13 @@ let ArrCopyItems = [];
 let IsImagePreview = false;
 let IsGdriveEnabled = false;
 let CurrentFtpPath = "";
+let SftpProfiles = [];
 let IsCopyToCut = false;
 let Platform = "";
 let IsSelectMode = true;
 let IsItemPreviewOpen = false;
 let IsInputFocused = false;
 let ArrFavorites = [];
-let IsFilteredBySize = false;
-let IsFilteredByDate = false;
-let IsFilteredByName = false;
 let SelectedItemToOpen = null;
 let DefaultFileIcon = "";
 let DefaultFolderIcon = "";
@@ -187,7 +187,33 @@ async function startFullSearch() {
     let fileContent = document.querySelector(
         ".full-dualpane-search-file-content-input",
     ).value;
-    await searchFor(fileName, maxItems, searchDepth, false, fileContent);
+    let contentOptions = {
+        regex: $(".full-search-content-regex-checkbox").is(":checked"),
+        case_sensitive: $(".full-search-content-case-checkbox").is(":checked"),
+        whole_word: $(".full-search-content-word-checkbox").is(":checked"),
+    };
+    let isFuzzy = $(".full-search-fuzzy-checkbox").is(":checked");
+    await searchFor(fileName, maxItems, searchDepth, false, fileContent, contentOptions, isFuzzy);
+}
+
+/* Saves the search of the popup, its results are then listed like a folder */
+async function saveFullSearch() {
+    let searchDepth = parseInt(
+        document.querySelector(".full-search-search-depth-input").value,
+    );
+    let search = {
+        name: document.querySelector(".full-search-save-name-input").value.trim(),
+        root: await getCurrentDir(),
+        query: document.querySelector(".full-dualpane-search-input").value,
+        depth: searchDepth >= 1 ? searchDepth : 9999999,
+    };
+    await invoke("save_search", {search})
+        .then(async (path) => {
+            closeFullSearchContainer();
+            await insertSiteNavButtons();
+            await openDirAndSwitch(path);
+        })
+        .catch((e) => showToast(e, ToastType.ERROR, 5000));
 }
 
 document.addEventListener("keydown", async (e) => {
@@ -267,9 +293,12 @@ async function resetEverything() {
     closeSettings();
     closeFullSearchContainer();
     closeFtpConfig();
+    closeWebDavConfig();
+    closeS3Config();
     closeItemPreview();
     closeMultiRenamePopup();
     closeCompressPopup();
+    closeExtractPopup();
     closeYtDownloadPopup();
     closeInfoProperties();
     resetProgressBar();
@@ -572,7 +601,8 @@ document.onkeydown = async (e) => {
         if (IsPopUpOpen == false) {
             // check if del is pressed
             if (IsInputFocused == false && (e.keyCode == 46 || (e.metaKey && e.keyCode == 8))) {
-                await deleteItems();
+                // Shift + Del skips the trash
+                await deleteItems(e.shiftKey);
                 closeLoadingPopup();
                 await listDirectories();
                 goUp();
@@ -653,6 +683,26 @@ document.onkeydown = async (e) => {
                 e.preventDefault();
                 e.stopPropagation();
             }
+            // check if cmd / ctrl + z is pressed | Undo, with shift | Redo
+            if (
+                ((e.ctrlKey && Platform != "darwin") || e.metaKey) &&
+                e.key.toLowerCase() == "z" &&
+                IsInputFocused == false
+            ) {
+                e.preventDefault();
+                e.stopPropagation();
+                if (e.shiftKey) {
+                    await redoOperation();
+                } else {
+                    await undoOperation();
+                }
+            }
+            // check if cmd / ctrl + y is pressed | Redo
+            if (((e.ctrlKey && Platform != "darwin") || e.metaKey) && e.key == "y" && IsInputFocused == false) {
+                e.preventDefault();
+                e.stopPropagation();
+                await redoOperation();
+            }
             // check if cmd / ctrl + g is pressed | Path input
             if (((e.ctrlKey && Platform != "darwin") || e.metaKey) && e.key == "g") {
                 showInputPopup("Input path to jump to");
@@ -830,12 +880,16 @@ async function showItems(items, dualPaneSide = "", millerCol = 1) {
         itemLink.setAttribute("itempath", item.path);
         itemLink.setAttribute("itemindex", counter++);
         itemLink.setAttribute("itempaneside", dualPaneSide);
-        itemLink.setAttribute("itemisdir", item.is_dir);
+        itemLink.setAttribute("itemisdir", item.is_dir ? 1 : 0);
         itemLink.setAttribute("itemext", item.extension);
         itemLink.setAttribute("itemname", item.name);
         itemLink.setAttribute("itemsize", formatBytes(item.size));
         itemLink.setAttribute("itemrawsize", item.size);
-        itemLink.setAttribute("itemmodified", item.last_modified);
+        itemLink.setAttribute("itemmodified", item.modified ?? "");
+        itemLink.setAttribute("itemfiletype", item.file_type ?? "");
+        itemLink.setAttribute("itemmime", item.mime_type ?? "");
+        itemLink.setAttribute("itemowner", item.owner ?? "");
+        itemLink.setAttribute("itempermissions", item.permissions ?? "");
         itemLink.setAttribute("draggable", true);
         itemLink.setAttribute("id", "item-link");
         itemLink.setAttribute("itemformillercol", parseInt(millerCol) + 1);
@@ -1061,7 +1115,7 @@ async function showItems(items, dualPaneSide = "", millerCol = 1) {
 					<p class="item-button-list-text" style="text-align: left; overflow: hidden; text-overflow: ellipsis;">${item.name}</p>
 				</span>
 				<span class="item-button-list-info-span" style="display: flex; gap: 10px; align-items: center; width: 50%; justify-content: flex-end; padding-right: 5px;">
-					<p class="item-button-list-text" style="width: auto; text-align: right;">${item.last_modified}</p>
+					<p class="item-button-list-text" style="width: auto; text-align: right;">${formatTimestamp(item.modified)}</p>
 					<p class="item-button-list-text" style="width: 75px; text-align: right;">${formatBytes(parseInt(item.size), 2)}</p>
 				</span>
 			`;
@@ -1186,6 +1240,19 @@ listen("addSingleItem", async (item) => {
     }, 10);
 });
 
+/* Lines of content search matches with their context, like grep -n */
+function formatContentMatches(matches) {
+    return matches
+        .map((match) =>
+            [
+                ...match.before.map((line, i) => match.line_number - match.before.length + i + "-  " + line),
+                match.line_number + ":" + match.column + ": " + match.line,
+                ...match.after.map((line, i) => match.line_number + i + 1 + "-  " + line),
+            ].join("\n"),
+        )
+        .join("\n--\n");
+}
+
 async function addSingleItem(item, dualPaneSide = "", millerCol = 1, itemIndex = 0) {
     if (IsShowHiddenFiles === false) {
         if (item.name.startsWith(".") == true || item.name.toLowerCase().includes("desktop.ini")) {
@@ -1228,6 +1295,9 @@ async function addSingleItem(item, dualPaneSide = "", millerCol = 1, itemIndex =
     itemLink.setAttribute("draggable", true);
     itemLink.setAttribute("id", "item-link");
     itemLink.setAttribute("itemformillercol", parseInt(millerCol) + 1);
+    if (item.content_matches?.length > 0) {
+        itemLink.setAttribute("title", formatContentMatches(item.content_matches));
+    }
 
     let newRow = document.createElement("div");
     newRow.className = "directory-item-entry";
@@ -1513,24 +1583,70 @@ async function addSingleItem(item, dualPaneSide = "", millerCol = 1, itemIndex =
         setupItemContextMenu(itemLink, e);
     });
 
+    if (item.matched_positions != null) {
+        let nameText = newRow.querySelector(".item-button-text, .item-button-list-text");
+        if (nameText != null) {
+            nameText.innerHTML = highlightPositions(item.name, item.matched_positions);
+        }
+    }
+
     if (IsDualPaneEnabled === true) {
         if (dualPaneSide === "left") {
-            $(".dual-pane-left").append(itemLink);
+            appendByScore(document.querySelector(".dual-pane-left"), itemLink, item.score);
             LeftPaneItemCollection = document.querySelector(".dual-pane-left");
             goUp(false, true);
         } else if (dualPaneSide === "right") {
-            $(".dual-pane-right").append(itemLink);
+            appendByScore(document.querySelector(".dual-pane-right"), itemLink, item.score);
             RightPaneItemCollection = document.querySelector(".dual-pane-right");
             goUp(false, true);
         }
     } else {
-        $(".directory-list").append(itemLink);
+        appendByScore(document.querySelector(".directory-list"), itemLink, item.score);
     }
     ArrDirectoryItems.push(itemLink);
 }
 
+/* Keeps fuzzy search results ordered by their score while they stream in */
+function appendByScore(container, itemLink, score) {
+    if (score == null) {
+        container.append(itemLink);
+        return;
+    }
+    itemLink.setAttribute("itemscore", score);
+    let children = Array.from(container.children);
+    let index = children.findIndex((child) => parseInt(child.getAttribute("itemscore")) < score);
+    if (index == -1) {
+        container.append(itemLink);
+        return;
+    }
+    container.insertBefore(itemLink, children[index]);
+    // The keyboard navigation uses the index as the position in the list
+    Array.from(container.children)
+        .slice(index)
+        .forEach((child, i) => child.setAttribute("itemindex", index + i));
+}
+
+/* Wraps the chars of a fuzzy match, positions are char indices */
+function highlightPositions(name, positions) {
+    let matched = new Set(positions);
+    return Array.from(name)
+        .map((char, i) => {
+            let escaped = $("<div>").text(char).html();
+            return matched.has(i) ? `<span class="fuzzy-match">${escaped}</span>` : escaped;
+        })
+        .join("");
+}
+
+// Every pane / tab has its own navigation state in the backend
+function getPaneId() {
+    if (IsDualPaneEnabled === true && SelectedItemPaneSide != "") {
+        return SelectedItemPaneSide;
+    }
+    return "tab-" + CurrentActiveTab;
+}
+
 async function getCurrentDir() {
-    return await invoke("get_current_dir");
+    return await invoke("get_current_dir", {paneId: getPaneId()});
 }
 
 async function setCurrentDir(currentDir = "", dualPaneSide = "") {
@@ -1540,7 +1656,7 @@ async function setCurrentDir(currentDir = "", dualPaneSide = "") {
         SelectedItemPaneSide = dualPaneSide;
     }
 
-    await invoke("set_dir", {currentDir}).then(async (isSuccess) => {
+    await invoke("set_dir", {currentDir, paneId: getPaneId()}).then(async (isSuccess) => {
         if (isSuccess === false) {
             alert("Switching directory failed. Probably no permissions.");
             return;
@@ -1605,9 +1721,11 @@ async function setCurrentDir(currentDir = "", dualPaneSide = "") {
     }
 }
 
-async function deleteItems() {
+async function deleteItems(permanently = false) {
     ContextMenu.style.display = "none";
-    let msg = "Do you really want to delete:<br/><br/>";
+    let msg = permanently
+        ? "Do you really want to permanently delete:<br/><br/>"
+        : "Do you really want to move to the trash:<br/><br/>";
     for (let i = 0; i < ArrSelectedItems.length; i++) {
         if (i == 0) {
             msg += "<span class='confirm-popup-item'>" + ArrSelectedItems[i].getAttribute("itemname") + "</span>";
@@ -1620,9 +1738,22 @@ async function deleteItems() {
     if (isConfirm == true) {
         let actionId = new Date().getMilliseconds();
         createNewAction(actionId, "Deleting", "Delete Items", "Delete Items");
+        // Asked once for all items that can't go to the trash
+        let deleteUntrashable = null;
         for (let i = 0; i < arr.length; i++) {
             let actFileName = arr[i];
-            await invoke("delete_item", {actFileName}).catch((e) => showToast(e, ToastType.ERROR, 5000));
+            await invoke("delete_item", {actFileName, permanently}).catch(async (e) => {
+                if (e != TrashUnsupported) {
+                    showToast(e, ToastType.ERROR, 5000);
+                    return;
+                }
+                if (deleteUntrashable == null) {
+                    deleteUntrashable = await confirmPopup(e, PopupType.DELETE);
+                }
+                if (deleteUntrashable == true) {
+                    await invoke("delete_item", {actFileName, permanently: true}).catch((e) => showToast(e, ToastType.ERROR, 5000));
+                }
+            });
         }
         IsCopyToCut = false;
         await listDirectories();
@@ -1632,6 +1763,93 @@ async function deleteItems() {
     }
 }
 
+async function undoOperation() {
+    await invoke("undo_last")
+        .then((entry) => showToast("Undone: " + entry.description, ToastType.INFO))
+        .catch((e) => showToast(e, ToastType.ERROR, 5000));
+    await listDirectories();
+}
+
+async function redoOperation() {
+    await invoke("redo")
+        .then((entry) => showToast("Redone: " + entry.description, ToastType.INFO))
+        .catch((e) => showToast(e, ToastType.ERROR, 5000));
+    await listDirectories();
+}
+
+async function showTrash() {
+    let items = await invoke("list_trash").catch((e) => {
+        showToast(e, ToastType.ERROR, 5000);
+        return null;
+    });
+    if (items == null) return;
+    closeTrash();
+    let rows = items
+        .map(
+            (item) => `
+			<div class="popup-body-row-section trash-item" style="justify-content: space-between;">
+			<div class="popup-body-col-section">
+			<h5>${item.is_dir ? `<i class="fa-solid fa-folder"></i>` : `<i class="fa-solid fa-file"></i>`} ${item.name}</h5>
+			<p class="text-2">${item.original_path} | ${formatBytes(item.size, 2)} | ${item.deleted_at.replace("T", " ")}</p>
+			</div>
+			<div style="display: flex; gap: 5px;">
+			<button class="icon-button" onclick="restoreFromTrash('${item.path}')"><i class="fa-solid fa-rotate-left"></i></button>
+			<button class="icon-button" onclick="deleteFromTrash('${item.path}')"><i class="fa-solid fa-trash-can"></i></button>
+			</div>
+			</div>`,
+        )
+        .join("");
+    let popup = document.createElement("div");
+    popup.innerHTML = `
+			<div class="popup-header">
+			<i class="fa-solid fa-trash-can"></i>
+			<h3>Trash (${items.length} items)</h3>
+			</div>
+			<div class="popup-body" style="max-height: 60vh; overflow-y: auto;">
+			${rows == "" ? `<p class="text-2">The trash is empty</p>` : rows}
+			</div>
+			<div class="popup-controls">
+			<button class="icon-button" onclick="closeTrash()">
+			<div class="button-icon"><i class="fa-solid fa-xmark"></i></div>
+			Close
+			</button>
+			<button class="icon-button" onclick="emptyTrash()">
+			<div class="button-icon"><i class="fa-solid fa-dumpster"></i></div>
+			Empty trash
+			</button>
+			</div>
+			`;
+    popup.className = "uni-popup trash-popup";
+    document.querySelector("body").append(popup);
+    IsPopUpOpen = true;
+}
+
+function closeTrash() {
+    $(".trash-popup").remove();
+    IsPopUpOpen = false;
+}
+
+async function restoreFromTrash(path) {
+    await invoke("restore_from_trash", {arrItems: [path]})
+        .then(() => showToast("Item restored", ToastType.SUCCESS))
+        .catch((e) => showToast(e, ToastType.ERROR, 5000));
+    await showTrash();
+    await listDirectories();
+}
+
+async function deleteFromTrash(path) {
+    await invoke("delete_permanently", {arrItems: [path]}).catch((e) => showToast(e, ToastType.ERROR, 5000));
+    await showTrash();
+}
+
+async function emptyTrash() {
+    let isConfirm = await confirmPopup("Do you really want to permanently delete all items in the trash?", PopupType.DELETE);
+    if (isConfirm == true) {
+        await invoke("empty_trash").catch((e) => showToast(e, ToastType.ERROR, 5000));
+        await showTrash();
+    }
+}
+
 async function copyItem(item, toCut = false, fromInternal = false) {
     if (item == null) {
         return;
@@ -1667,21 +1885,86 @@ async function copyItem(item, toCut = false, fromInternal = false) {
 }
 
 async function extractItem(item) {
-    let compressFilePath = item.getAttribute("itempath");
-    let compressFileName = compressFilePath.split("/")[compressFilePath.split("/").length - 1].replace("'", "");
+    IsPopUpOpen = true;
     ContextMenu.style.display = "none";
-    let isExtracting = await confirmPopup("Do you want to extract " + compressFileName + "?", PopupType.EXTRACT);
-    if (isExtracting == true) {
-        ContextMenu.style.display = "none";
-        let extractFilePath = item.getAttribute("itempath");
-        let extractFileName = item.getAttribute("itemname");
-        if (extractFileName != "") {
-            let fromPath = extractFilePath.toString();
-            await invoke("extract_item", {fromPath, appWindow});
-            showToast("Extraction done", ToastType.SUCCESS);
+    let fromPath = item.getAttribute("itempath");
+    let extractFileName = item.getAttribute("itemname");
+    let targetDir = fromPath.substring(0, fromPath.lastIndexOf("/")) || "/";
+    let popup = document.createElement("div");
+    popup.innerHTML = `
+		<div class="popup-header">
+		<i class="fa-solid fa-maximize"></i>
+		<h3>Extraction options</h3>
+		</div>
+		<div style="padding: 10px; border-bottom: 1px solid var(--tertiaryColor);">
+		<p class="text-2">Selected archive</p>
+		<h5>${extractFileName}</h5>
+		</div>
+		<div class="popup-body">
+		<div class="popup-body-row-section">
+		<div class="popup-body-col-section" style="width: 100%;">
+		<p class="text-2">Destination</p>
+		<input class="text-input extraction-popup-input extraction-popup-target-input" type="text" style="width: 100%;" />
+		<label class="text-2"><input type="checkbox" class="extraction-popup-subfolder-checkbox" checked /> Extract into a new folder</label>
+		</div>
+		</div>
+		<div class="popup-body-row-section">
+		<div class="popup-body-col-section" style="width: 100%;">
+		<p class="text-2">Existing files</p>
+		<select class="text-input select extraction-popup-policy-input" style="width: 100%;">
+		<option value="ask">Ask</option>
+		<option value="keep_both">Keep both</option>
+		<option value="overwrite">Overwrite</option>
+		<option value="overwrite_if_newer">Overwrite if newer</option>
+		<option value="skip">Skip</option>
+		</select>
+		</div>
+		</div>
+		<div class="popup-body-row-section">
+		<div class="popup-body-col-section" style="width: 100%;">
+		<p class="text-2">Password</p>
+		<input class="text-input extraction-popup-input extraction-popup-password-input" type="password" style="width: 100%;" placeholder="Only for encrypted archives" />
+		</div>
+		</div>
+		</div>
+		<div class="popup-controls">
+		<button class="icon-button" onclick="closeExtractPopup()">
+		<div class="button-icon"><i class="fa-solid fa-xmark"></i></div>
+		Close
+		</button>
+		<button class="icon-button extract-item-button">
+		<div class="button-icon"><i class="fa-solid fa-maximize"></i></div>
+		Extract
+		</button>
+		</div>
+		`;
+    popup.className = "uni-popup extraction-popup";
+    document.querySelector("body").append(popup);
+    $(".extraction-popup-target-input").val(targetDir);
+    $(".extraction-popup-input").on("focus", () => (IsInputFocused = true));
+    $(".extraction-popup-input").on("blur", () => (IsInputFocused = false));
+    document
+        .querySelector(".extract-item-button")
+        .addEventListener("click", async () => {
+            let password = $(".extraction-popup-password-input").val();
+            let options = {
+                target_dir: $(".extraction-popup-target-input").val(),
+                create_subfolder: $(".extraction-popup-subfolder-checkbox").is(":checked"),
+                conflict_policy: $(".extraction-popup-policy-input").val(),
+                password: password == "" ? null : password,
+            };
+            closeExtractPopup();
+            await invoke("extract_item", {fromPath, options, appWindow})
+                .then(() => showToast("Extraction done", ToastType.SUCCESS))
+                .catch((e) => showToast(e, ToastType.ERROR, 5000));
             await listDirectories();
-        }
-    }
+        });
+}
+
+function closeExtractPopup() {
+    $(".extraction-popup").remove();
+    IsPopUpOpen = false;
+    IsInputFocused = false;
 }
 
 async function showCompressPopup(item) {
@@ -1702,6 +1985,13 @@ async function showCompressPopup(item) {
         compressFileName = item.getAttribute("itemname");
     }
     if (compressFileName != "") {
+        let archiveName = "Archive";
+        if (arrCompressItems.length == 1) {
+            archiveName = item.getAttribute("itemname");
+            if (item.getAttribute("itemisdir") != "1" && archiveName.lastIndexOf(".") > 0) {
+                archiveName = archiveName.substring(0, archiveName.lastIndexOf("."));
+            }
+        }
         let popup = document.createElement("div");
         popup.innerHTML = `
 			<div class="popup-header">
@@ -1715,8 +2005,30 @@ async function showCompressPopup(item) {
 			<div class="popup-body">
 			<div class="popup-body-row-section">
 			<div class="popup-body-col-section" style="width: 100%;">
+			<p class="text-2">Archive name</p>
+			<input class="text-input compression-popup-input compression-popup-name-input" type="text" style="width: 100%;" />
+			</div>
+			</div>
+			<div class="popup-body-row-section">
+			<div class="popup-body-col-section">
+			<p class="text-2">Format</p>
+			<select class="text-input select compression-popup-format-input" style="width: 100%;">
+			<option value="zip">.zip</option>
+			<option value="7z">.7z</option>
+			<option value="tar_gz">.tar.gz</option>
+			<option value="tar_xz">.tar.xz</option>
+			<option value="tar_zst">.tar.zst</option>
+			</select>
+			</div>
+			<div class="popup-body-col-section">
 			<p class="text-2">Level (0 - 9)</p>
-			<input class="text-input compression-popup-level-input" type="number" value="6" placeholder="0 - 9" />
+			<input class="text-input compression-popup-input compression-popup-level-input" type="number" value="6" placeholder="0 - 9" />
+			</div>
+			</div>
+			<div class="popup-body-row-section">
+			<div class="popup-body-col-section" style="width: 100%;">
+			<p class="text-2">Password (zip only, AES-256)</p>
+			<input class="text-input compression-popup-input compression-popup-password-input" type="password" style="width: 100%;" placeholder="Leave empty for no encryption" />
 			</div>
 			</div>
 			</div>
@@ -1733,23 +2045,38 @@ async function showCompressPopup(item) {
 			`;
         popup.className = "uni-popup compression-popup";
         document.querySelector("body").append(popup);
+        $(".compression-popup-name-input").val(archiveName);
+        $(".compression-popup-format-input").on("change", () => {
+            let isZip = $(".compression-popup-format-input").val() == "zip";
+            $(".compression-popup-password-input").prop("disabled", !isZip);
+            if (!isZip) {
+                $(".compression-popup-password-input").val("");
+            }
+        });
         document
             .querySelector(".compress-item-button")
             .addEventListener("click", async () => {
+                let password = $(".compression-popup-password-input").val();
+                let level = parseInt($(".compression-popup-level-input").val());
                 await compressItem(
                     arrCompressItems,
-                    $(".compression-popup-level-input").val(),
+                    $(".compression-popup-name-input").val(),
+                    {
+                        format: $(".compression-popup-format-input").val(),
+                        level: isNaN(level) ? 6 : Math.min(Math.max(level, 0), 9),
+                        password: password == "" ? null : password,
+                    },
                 );
             });
-        $(".compression-popup-level-input").on(
+        $(".compression-popup-input").on(
             "focus",
             () => (IsInputFocused = true),
         );
-        $(".compression-popup-level-input").on(
+        $(".compression-popup-input").on(
             "blur",
             () => (IsInputFocused = false),
         );
-        $(".compression-popup-level-input").on("keyup", (e) => {
+        $(".compression-popup-input").on("keyup", (e) => {
             if (
                 ((e.ctrlKey && Platform != "darwin") || e.metaKey) &&
                 e.key == "Enter"
@@ -1760,35 +2087,20 @@ async function showCompressPopup(item) {
     }
 }
 
-async function compressItem(arrItems, compressionLevel = 3) {
+async function compressItem(arrItems, archiveName, options) {
     closeCompressPopup();
-    if (arrItems.length > 1) {
-        ContextMenu.style.display = "none";
-        await invoke("arr_compress_items", {
-            arrItems: arrItems.map((item) => item.getAttribute("itempath")),
-            compressionLevel: parseInt(compressionLevel),
-            appWindow
-        });
-        await listDirectories();
-        showToast("Compressing done", ToastType.INFO);
-    } else {
-        let item = arrItems[0];
-        let compressFilePath = item.getAttribute("itempath");
-        let compressFileName = item.getAttribute("itemname");
-        if (compressFileName != "") {
-            // open compressing... popup
-            ContextMenu.style.display = "none";
-            SelectedItemPaneSide = item.getAttribute("itempaneside");
-            await invoke("compress_item", {
-                fromPath: compressFilePath,
-                compressionLevel: parseInt(compressionLevel),
-                pathToZip: compressFilePath,
-                appWindow
-            });
-            await listDirectories();
-            showToast("Compressing done", ToastType.INFO);
-        }
-    }
+    ContextMenu.style.display = "none";
+    SelectedItemPaneSide = arrItems[0].getAttribute("itempaneside");
+    await invoke("create_archive", {
+        arrItems: arrItems.map((item) => item.getAttribute("itempath")),
+        archiveName,
+        options,
+        appWindow,
+        paneId: getPaneId()
+    })
+        .then(() => showToast("Compressing done", ToastType.INFO))
+        .catch((e) => showToast(e, ToastType.ERROR, 5000));
+    await listDirectories();
 }
 
 async function closeCompressPopup() {
@@ -1823,7 +2135,7 @@ function showInputPopup(msg) {
     popup.className = "input-popup input-dialog";
     popup.children[1].addEventListener("keyup", async (e) => {
         if (e.keyCode == 13) {
-            await invoke("open_dir", {path: popup.children[1].value});
+            await invoke("open_dir", {path: popup.children[1].value, paneId: getPaneId()});
             await listDirectories();
             closeInputPopup();
         }
@@ -1861,24 +2173,36 @@ async function itemMoveTo(isForDualPane = false) {
         }
     }
     if (selectedPath != "" && selectedPath != null) {
-        await invoke("arr_copy_paste", {
+        await invoke("move_items", {
             appWindow,
-            arrItems: ArrSelectedItems.map((item) => item.getAttribute("itempath")),
-            isForDualPane: isForDualPane ? "1" : "0",
-            copyToPath: selectedPath,
+            arrItems: ArrSelectedItems.map((item) => itemToFDir(item)),
+            moveToPath: selectedPath,
+            conflictPolicy: "ask",
         }).then(async () => {
-            await invoke("arr_delete_items", {
-                arrItems: ArrSelectedItems.map((item) => item.getAttribute("itempath")),
-            }).catch((e) => showToast(e, ToastType.ERROR, 5000));
             if (isForDualPane) {
                 refreshBothViews(SelectedItemPaneSide);
             } else {
                 refreshView();
             }
-        });
+        }).catch((e) => showToast(e, ToastType.ERROR, 5000));
     }
 }
 
+function itemToFDir(item) {
+    return {
+        name: item.getAttribute("itemname") ?? "",
+        path: item.getAttribute("itempath") ?? "",
+        is_dir: item.getAttribute("itemisdir") == "1",
+        file_type: item.getAttribute("itemfiletype") || undefined,
+        size: parseInt(item.getAttribute("itemrawsize")) || 0,
+        modified: parseInt(item.getAttribute("itemmodified")) || null,
+        extension: item.getAttribute("itemext") ?? "",
+        mime_type: item.getAttribute("itemmime") ?? "",
+        owner: item.getAttribute("itemowner") || null,
+        permissions: parseInt(item.getAttribute("itempermissions")) || null,
+    };
+}
+
 async function pasteItem(copyToPath = "") {
     let arr = [];
     if (IsDualPaneEnabled == true) {
@@ -1887,47 +2211,44 @@ async function pasteItem(copyToPath = "") {
         arr = ArrCopyItems;
     }
 
-    arr = arr.map((item) => ({
-        name: item.getAttribute("itemname") ?? "",
-        path: item.getAttribute("itempath") ?? "",
-        is_dir: parseInt(item.getAttribute("itemisdir") ?? 0) ?? 0,
-        size: item.getAttribute("itemrawsize") ?? "",
-        last_modified: item.getAttribute("itemmodified") ?? "",
-        extension: item.getAttribute("itemext") ?? "",
-    }));
+    arr = arr.map((item) => itemToFDir(item));
+
+    // Cut items are moved instead of copied and deleted afterwards
+    let command = IsCopyToCut == true ? "move_items" : "arr_copy_paste";
+    let pathArg = IsCopyToCut == true ? "moveToPath" : "copyToPath";
 
     ContextMenu.style.display = "none";
     if (IsDualPaneEnabled == true) {
         if (SelectedItemPaneSide == "left") {
-            await invoke("set_dir", {currentDir: RightDualPanePath});
-            await invoke("arr_copy_paste", {
+            await invoke("set_dir", {currentDir: RightDualPanePath, paneId: "right"});
+            await invoke(command, {
                 appWindow,
                 arrItems: arr,
-                isForDualPane: "1",
-                copyToPath,
-            });
+                [pathArg]: copyToPath,
+                conflictPolicy: "ask",
+                paneId: "right",
+            }).catch((e) => showToast(e, ToastType.ERROR, 5000));
         } else if (SelectedItemPaneSide == "right") {
-            await invoke("set_dir", {currentDir: LeftDualPanePath});
-            await invoke("arr_copy_paste", {
+            await invoke("set_dir", {currentDir: LeftDualPanePath, paneId: "left"});
+            await invoke(command, {
                 appWindow,
                 arrItems: arr,
-                isForDualPane: "1",
-                copyToPath,
-            });
+                [pathArg]: copyToPath,
+                conflictPolicy: "ask",
+                paneId: "left",
+            }).catch((e) => showToast(e, ToastType.ERROR, 5000));
         }
     } else {
-        await invoke("arr_copy_paste", {
+        await invoke(command, {
             appWindow,
             arrItems: arr,
-            isForDualPane: "0",
-            copyToPath,
-        });
+            [pathArg]: copyToPath,
+            conflictPolicy: "ask",
+            paneId: getPaneId(),
+        }).catch((e) => showToast(e, ToastType.ERROR, 5000));
         ContextMenu.style.display = "none";
     }
     if (IsCopyToCut == true) {
-        await invoke("arr_delete_items", {
-            arrItems: arr.map((element) => element.path),
-        }).catch((e) => showToast(e, ToastType.ERROR, 5000));
         ArrCopyItems = [];
         if (IsDualPaneEnabled === true) {
             refreshBothViews(SelectedItemPaneSide);
@@ -2059,17 +2380,17 @@ function renameElementInputPrompt(item) {
 
 async function createFolder(folderName) {
     let isDualPaneEnabled = IsDualPaneEnabled;
-    await invoke("create_folder", {folderName, isDualPaneEnabled}).catch((e) => showToast(e, ToastType.ERROR, 5000));
+    await invoke("create_folder", {folderName, isDualPaneEnabled, paneId: getPaneId()}).catch((e) => showToast(e, ToastType.ERROR, 5000));
     listDirectories();
 }
 
 async function createFile(fileName) {
-    await invoke("create_file", {fileName});
+    await invoke("create_file", {fileName, paneId: getPaneId()});
     listDirectories();
 }
 
 async function renameElement(path, newName) {
-    await invoke("rename_element", {path, newName, appWindow}).catch((e) => showToast(e, ToastType.ERROR, 5000));
+    await invoke("rename_element", {path, newName, appWindow, paneId: getPaneId()}).catch((e) => showToast(e, ToastType.ERROR, 5000));
     IsInputFocused = false;
     await listDirectories();
 }
@@ -2086,7 +2407,7 @@ async function showAppInfo() {
 async function checkAppConfig() {
     await applyPlatformFeatures();
     await invoke("check_app_config").then(async (appConfig) => {
-        let viewMode = appConfig.view_mode.replaceAll('"', "");
+        let viewMode = appConfig.view_mode;
         switch (viewMode) {
             case "wrap":
                 ViewMode = "miller";
@@ -2101,94 +2422,13 @@ async function checkAppConfig() {
 
         await switchView();
 
-        // document.querySelector(".context-open-in-terminal").style.display = "none";
-
-        if (appConfig.is_dual_pane_enabled.includes("1")) {
-            document.querySelector(".show-dual-pane-checkbox").checked = true;
-            document.querySelector(".switch-dualpane-view-button").style.display =
-                "block";
-        } else {
-            document.querySelector(".show-dual-pane-checkbox").checked = false;
-            document.querySelector(".switch-dualpane-view-button").style.display =
-                "none";
-        }
-        if (appConfig.is_select_mode.includes("1")) {
-            document.querySelector("#choose-interaction-mode").checked = true;
-            IsSelectMode = true;
-        } else {
-            document.querySelector("#choose-interaction-mode").checked = false;
-            IsSelectMode = false;
-        }
-
-        if (appConfig.is_image_preview.includes("1")) {
-            document.querySelector(".image-preview-checkbox").checked =
-                IsImagePreview = true;
-        } else {
-            document.querySelector(".image-preview-checkbox").checked = false;
-        }
-
-        if (appConfig.gdrive_enabled.includes("1")) {
-            document.querySelector(".gdrive_enabled_checkbox").checked = true;
-            IsGDriveEnabled = true;
-            if (!document.querySelector(".gdrive-nav-button")) {
-                insertGdriveButton();
-            }
-        } else {
-            document.querySelector(".gdrive_enabled_checkbox").checked = false;
-            IsGDriveEnabled = false;
-            document.querySelector(".gdrive-nav-button").remove();
-        }
-
-        // Theme options
-        CurrentTheme = appConfig.current_theme;
-        appConfig.themes = await invoke("get_themes");
-        // Fallback when there's no theme installed
-        if (appConfig.themes.length == 0) {
-            appConfig.themes = [
-                {
-                    "name": "Default",
-                    "primary_color": "#3f4352",
-                    "secondary_color": "rgba(56, 59, 71, 1)",
-                    "tertiary_color": "#474b5c",
-                    "text_color": "rgba(255, 255, 255, 0.8)",
-                    "text_color2": "rgba(255, 255, 255, 0.6)",
-                    "text_color3": "rgb(255, 255, 255)",
-                    "transparent_color": "rgba(0, 0, 0, 0.15)",
-                    "transparent_color_active": "rgba(0, 0, 0, 0.25)",
-                    "site_bar_color": "rgb(45, 47, 57)",
-                    "nav_bar_color": "rgba(30, 30, 40, 0.5)"
-                }
-            ];
-        }
-        let themeSelect = document.querySelector(".theme-select");
-        themeSelect.innerHTML = "";
-        let themeCounter = 0;
-        appConfig.themes.forEach((theme) => {
-            let themeOption = document.createElement("option");
-            themeOption.value = themeCounter;
-            themeOption.textContent = theme.name;
-            themeSelect.appendChild(themeOption);
-            themeCounter++;
-        });
+        await applyAppConfig(appConfig);
 
-        // Set current theme
-        themeSelect.value = CurrentTheme;
-
-        checkColorMode(appConfig);
-
-        // General configurations
-        document.querySelector(".configured-path-one-input").value = ConfiguredPathOne = appConfig.configured_path_one;
-        document.querySelector(".configured-path-two-input").value = ConfiguredPathTwo = appConfig.configured_path_two;
-        document.querySelector(".configured-path-three-input").value = ConfiguredPathThree = appConfig.configured_path_three;
-        document.querySelector(".launch-path-input").value = appConfig.launch_path;
-        document.querySelector(".search-depth-input").value = SettingsSearchDepth = parseInt(appConfig.search_depth);
-        document.querySelector(".max-items-input").value = SettingsMaxItems = parseInt(appConfig.max_items);
-
-        if (appConfig.is_dual_pane_active.includes("1")) {
+        if (appConfig.is_dual_pane_active === true) {
             await switchToDualPane();
             if (appConfig.launch_path.length >= 1) {
                 let path = appConfig.launch_path;
-                let isSwitched = await invoke("open_dir", {path});
+                let isSwitched = await invoke("open_dir", {path, paneId: getPaneId()});
                 if (isSwitched === true) {
                     await setCurrentDir(path, "left");
                     await listDirectories();
@@ -2199,7 +2439,7 @@ async function checkAppConfig() {
             }
         } else if (appConfig.launch_path.length >= 1 && IsFirstRun == true) {
             let path = appConfig.launch_path;
-            let isSwitched = await invoke("open_dir", {path});
+            let isSwitched = await invoke("open_dir", {path, paneId: getPaneId()});
             if (isSwitched === true) {
                 await setCurrentDir(path, "left");
                 await listDirectories();
@@ -2216,6 +2456,95 @@ async function checkAppConfig() {
     IsFirstRun = false;
 }
 
+/* Applies the settings that don't depend on navigation, also used for live updates */
+async function applyAppConfig(appConfig) {
+    if (appConfig.is_dual_pane_enabled === true) {
+        document.querySelector(".show-dual-pane-checkbox").checked = true;
+        document.querySelector(".switch-dualpane-view-button").style.display =
+            "block";
+    } else {
+        document.querySelector(".show-dual-pane-checkbox").checked = false;
+        document.querySelector(".switch-dualpane-view-button").style.display =
+            "none";
+    }
+    if (appConfig.is_select_mode === true) {
+        document.querySelector("#choose-interaction-mode").checked = true;
+        IsSelectMode = true;
+    } else {
+        document.querySelector("#choose-interaction-mode").checked = false;
+        IsSelectMode = false;
+    }
+
+    if (appConfig.is_image_preview === true) {
+        document.querySelector(".image-preview-checkbox").checked =
+            IsImagePreview = true;
+    } else {
+        document.querySelector(".image-preview-checkbox").checked = false;
+    }
+
+    if (appConfig.gdrive_enabled === true) {
+        document.querySelector(".gdrive_enabled_checkbox").checked = true;
+        IsGDriveEnabled = true;
+        if (!document.querySelector(".gdrive-nav-button")) {
+            insertGdriveButton();
+        }
+    } else {
+        document.querySelector(".gdrive_enabled_checkbox").checked = false;
+        IsGDriveEnabled = false;
+        document.querySelector(".gdrive-nav-button")?.remove();
+    }
+
+    // Theme options
+    CurrentTheme = appConfig.current_theme;
+    appConfig.themes = await invoke("get_themes");
+    // Fallback when there's no theme installed
+    if (appConfig.themes.length == 0) {
+        appConfig.themes = [
+            {
+                "name": "Default",
+                "primary_color": "#3f4352",
+                "secondary_color": "rgba(56, 59, 71, 1)",
+                "tertiary_color": "#474b5c",
+                "text_color": "rgba(255, 255, 255, 0.8)",
+                "text_color2": "rgba(255, 255, 255, 0.6)",
+                "text_color3": "rgb(255, 255, 255)",
+                "transparent_color": "rgba(0, 0, 0, 0.15)",
+                "transparent_color_active": "rgba(0, 0, 0, 0.25)",
+                "site_bar_color": "rgb(45, 47, 57)",
+                "nav_bar_color": "rgba(30, 30, 40, 0.5)"
+            }
+        ];
+    }
+    let themeSelect = document.querySelector(".theme-select");
+    themeSelect.innerHTML = "";
+    let themeCounter = 0;
+    appConfig.themes.forEach((theme) => {
+        let themeOption = document.createElement("option");
+        themeOption.value = themeCounter;
+        themeOption.textContent = theme.name;
+        themeSelect.appendChild(themeOption);
+        themeCounter++;
+    });
+
+    // Set current theme
+    themeSelect.value = CurrentTheme;
+
+    checkColorMode(appConfig);
+
+    // General configurations
+    document.querySelector(".configured-path-one-input").value = ConfiguredPathOne = appConfig.configured_path_one;
+    document.querySelector(".configured-path-two-input").value = ConfiguredPathTwo = appConfig.configured_path_two;
+    document.querySelector(".configured-path-three-input").value = ConfiguredPathThree = appConfig.configured_path_three;
+    document.querySelector(".launch-path-input").value = appConfig.launch_path;
+    document.querySelector(".search-depth-input").value = SettingsSearchDepth = appConfig.search_depth;
+    document.querySelector(".max-items-input").value = SettingsMaxItems = appConfig.max_items;
+}
+
+/* Another window (or this one) changed settings */
+listen("config-changed", async (event) => {
+    await applyAppConfig(event.payload);
+});
+
 async function applyPlatformFeatures() {
     Platform = await platform();
     // Check for macOS and position titlebar buttons on the left
@@ -2322,7 +2651,7 @@ async function listDisks() {
 }
 
 async function listDirectories(fromDualPaneCopy = false) {
-    let lsItems = await invoke("list_dirs").catch((e) => showToast(e, ToastType.ERROR, 5000));
+    let lsItems = await invoke("list_dirs", {paneId: getPaneId()}).catch((e) => showToast(e, ToastType.ERROR, 5000));
     if (IsDualPaneEnabled == true) {
         ViewMode = "column";
         if (fromDualPaneCopy == true) {
@@ -2354,6 +2683,21 @@ async function refreshView() {
     await listDirectories();
 }
 
+/* The backend watches the directory of every pane and reports changes made by other programs */
+listen("dir-changed", async (event) => {
+    let change = event.payload;
+    // Don't pull the list away while the user is renaming or in a dialog
+    if (IsPopUpOpen == true || IsInputFocused == true || IsShowDisks == true) return;
+    if (IsDualPaneEnabled == true && (change.pane_id == "left" || change.pane_id == "right")) {
+        let items = await invoke("list_dirs", {paneId: change.pane_id}).catch(() => null);
+        if (items != null) {
+            await showItems(items, change.pane_id);
+        }
+    } else if (change.pane_id == getPaneId()) {
+        await listDirectories();
+    }
+});
+
 async function refreshBothViews(dualPaneSide = "") {
     switch (dualPaneSide) {
         case "left":
@@ -2466,6 +2810,11 @@ async function openItem(element, dualPaneSide, shortcutDirPath = null) {
     let path = element != null ? element.getAttribute("itempath") : shortcutDirPath;
     let millerCol = element != null ? element.getAttribute("itemformillercol") : null;
     let ext = element != null ? element.getAttribute("itemext") : null;
+    // Archives on the local disk are browsed like directories
+    if (isDir == 0 && isBrowsableArchive(path)) {
+        path = `archive://${path}`;
+        isDir = 1;
+    }
     if (IsPopUpOpen == false || IsQuickSearchOpen === true) {
         if (
             IsItemPreviewOpen == false &&
@@ -2473,7 +2822,7 @@ async function openItem(element, dualPaneSide, shortcutDirPath = null) {
             ext != ".app"
         ) {
             // Open directory
-            let isSwitched = await invoke("open_dir", {path});
+            let isSwitched = await invoke("open_dir", {path, paneId: getPaneId()});
             if (isSwitched == true) {
                 if (IsDualPaneEnabled === false) {
                     if (ViewMode == "miller") {
@@ -2513,6 +2862,17 @@ async function openItem(element, dualPaneSide, shortcutDirPath = null) {
     }
 }
 
+function isBrowsableArchive(path) {
+    if (path == null || path.includes("://") || path.startsWith("gdrive:")) {
+        return false;
+    }
+    let name = path.toLowerCase();
+    return [
+        ".zip", ".7z", ".rar", ".tar", ".tar.gz", ".tgz", ".tar.bz2", ".tbz2", ".tbz",
+        ".tar.xz", ".txz", ".tar.zst", ".tzst", ".tar.lz4",
+    ].some((ext) => name.endsWith(ext));
+}
+
 function selectItem(element, dualPaneSide = "", isNotReset = false) {
     let path = element?.getAttribute("itempath");
     let index = element?.getAttribute("itemindex");
@@ -2623,7 +2983,7 @@ async function unSelectAllItems() {
 }
 
 async function goHome() {
-    await invoke("go_home");
+    await invoke("go_home", {paneId: getPaneId()});
     await listDirectories();
     await setCurrentDir(await getCurrentDir());
 }
@@ -2637,12 +2997,18 @@ async function goBack() {
         }
     }
     if (IsMetaDown == false) {
-        await invoke("go_back", {isDualPane: IsDualPaneEnabled});
+        await invoke("go_back", {paneId: getPaneId()});
         await listDirectories();
     }
     await setCurrentDir(await getCurrentDir());
 }
 
+async function goForward() {
+    await invoke("go_forward", {paneId: getPaneId()});
+    await listDirectories();
+    await setCurrentDir(await getCurrentDir());
+}
+
 function goUp(isSwitched = false, toFirst = false) {
     if (IsDualPaneEnabled === true) {
         let element = null;
@@ -2939,7 +3305,7 @@ async function openSelectedItem() {
 }
 
 async function goToDir(directory) {
-    await invoke("go_to_dir", {directory}).then(async (items) => {
+    await invoke("go_to_dir", {directory, paneId: getPaneId()}).then(async (items) => {
         if (IsDualPaneEnabled == true) {
             await showItems(items, SelectedItemPaneSide);
         } else {
@@ -2967,9 +3333,11 @@ async function searchFor(
     searchDepth = SettingsSearchDepth,
     isQuickSearch = false,
     fileContent = "",
+    contentOptions = null,
+    fuzzy = false,
 ) {
     if (IsSearching === true) return;
-    if (fileName.length > 1 || isQuickSearch == true) {
+    if (fileName.length > 1 || isQuickSearch == true || fileContent.length > 0) {
         $(".is-file-searching").css("display", "block");
         document.querySelector(".cancel-search-button").style.display = "block";
         if (IsDualPaneEnabled === true) {
@@ -2988,8 +3356,11 @@ async function searchFor(
             maxItems,
             searchDepth,
             fileContent,
+            contentOptions,
+            fuzzy,
             appWindow,
-            isQuickSearch
+            isQuickSearch,
+            paneId: getPaneId()
         }).catch((e) => showToast(e, ToastType.ERROR, 5000));
         setTimeout(() => {
             ds.setSettings({
@@ -3118,7 +3489,7 @@ async function switchView() {
             $(".file-searchbar").css("pointer-events", "all");
             ViewMode = "wrap";
         }
-        await invoke("switch_view", {viewMode: ViewMode});
+        await invoke("switch_view", {viewMode: ViewMode, paneId: getPaneId()});
     }
     if (IsShowDisks === false) {
         await listDirectories();
@@ -3137,7 +3508,7 @@ async function switchToDualPane() {
         document.querySelectorAll(".item-button").forEach((item) => (item.style.display = "none"));
         document.querySelectorAll(".item-button-list").forEach((item) => (item.style.display = "flex"));
         document.querySelector(".switch-dualpane-view-button").innerHTML = `<i class="fa-regular fa-rectangle-xmark"></i>`;
-        await invoke("list_dirs").then(async (items) => {
+        await invoke("list_dirs", {paneId: getPaneId()}).then(async (items) => {
             await showItems(items, "left");
             await showItems(items, "right");
             goUp(false, true);
@@ -3239,6 +3610,7 @@ function openSettings() {
         });
         IsDisableShortcuts = true;
         IsPopUpOpen = true;
+        renderIndexStatus();
     }
 }
 
@@ -3269,56 +3641,26 @@ async function saveConfig(isToReload = true, isVerbose = true) {
         ":checked",
     ));
     let isGdriveEnabled = (IsGdriveEnabled = document.querySelector(".gdrive_enabled_checkbox").checked);
-    let currentTheme = $(".theme-select").val();
-
-    if (isOpenInTerminal == true) {
-        isOpenInTerminal = "1";
-    } else {
-        isOpenInTerminal = "0";
-    }
-    if (isDualPaneEnabled == true) {
-        isDualPaneEnabled = "1";
-    } else {
-        isDualPaneEnabled = "0";
-    }
-    if (isDualPaneActive == true) {
-        isDualPaneActive = "1";
-    } else {
-        isDualPaneActive = "0";
-    }
-    if (isImagePreview == true) {
-        isImagePreview = "1";
-    } else {
-        isImagePreview = "0";
-    }
-    if (isSelectMode == true) {
-        isSelectMode = "1";
-    } else {
-        isSelectMode = "0";
-    }
-    if (isGdriveEnabled == true) {
-        isGdriveEnabled = "1";
-    } else {
-        isGdriveEnabled = "0";
-    }
-
-    await invoke("save_config", {
-        configuredPathOne,
-        configuredPathTwo,
-        configuredPathThree,
-        isOpenInTerminal,
-        isDualPaneEnabled,
-        launchPath,
-        isDualPaneEnabled,
-        isDualPaneActive,
-        searchDepth,
-        maxItems,
-        isImagePreview,
-        isSelectMode,
-        currentTheme,
-        arrFavorites: ArrFavorites,
-        isGdriveEnabled,
-    });
+    let currentTheme = parseInt($(".theme-select").val() ?? 0);
+
+    await invoke("update_config", {
+        patch: {
+            configured_path_one: configuredPathOne,
+            configured_path_two: configuredPathTwo,
+            configured_path_three: configuredPathThree,
+            is_open_in_terminal: isOpenInTerminal,
+            is_dual_pane_enabled: isDualPaneEnabled,
+            launch_path: launchPath,
+            is_dual_pane_active: isDualPaneActive,
+            search_depth: searchDepth,
+            max_items: maxItems,
+            is_image_preview: isImagePreview,
+            is_select_mode: isSelectMode,
+            current_theme: currentTheme,
+            arr_favorites: ArrFavorites,
+            gdrive_enabled: isGdriveEnabled,
+        },
+    }).catch((e) => showToast(e, ToastType.ERROR, 5000));
     if (isVerbose === true) {
         showToast("Settings have been saved", ToastType.INFO);
     }
@@ -3351,7 +3693,10 @@ async function showProperties(item) {
         let path = item.getAttribute("itempath");
         let ext = item.getAttribute("itemext");
         let extension_description = getExtDescription(ext); // undefined if it's unknown or a directory
-        let modifiedAt = item.getAttribute("itemmodified");
+        let modifiedAt = formatTimestamp(item.getAttribute("itemmodified"));
+        let mimeType = item.getAttribute("itemmime");
+        let owner = item.getAttribute("itemowner");
+        let permissions = item.getAttribute("itempermissions");
         ContextMenu.style.display = "none";
         let popup = document.createElement("div");
         popup.className = "uni-popup item-properties-popup";
@@ -3364,6 +3709,9 @@ async function showProperties(item) {
 			${extension_description ? `<br/><p>Type: ${extension_description}</p>` : ''}
 			<br/>
 			<p>Modified: ${modifiedAt}</p>
+			${mimeType ? `<br/><p>MIME type: ${mimeType}</p>` : ''}
+			${owner ? `<br/><p>Owner: ${owner}</p>` : ''}
+			${permissions ? `<br/><p>Permissions: ${parseInt(permissions).toString(8).padStart(4, "0")}</p>` : ''}
 			<br/>
 			<div style="display: flex; gap: 5px;">
 				<div>Size:</div><div class="properties-item-size"><div class="preloader-small-invert"></div></div>
@@ -3397,7 +3745,14 @@ async function showItemPreview(item, isOverride = false) {
     }
     let name = item.getAttribute("itemname");
     let ext = item.getAttribute("itemext");
+    // Files of other providers are previewed from a local copy
     let path = item.getAttribute("itempath");
+    let previewPath = async () =>
+        await invoke("get_preview_path", {path}).catch((e) => {
+            showToast(e, ToastType.ERROR, 5000);
+            IsItemPreviewOpen = false;
+            throw e;
+        });
     let popup = document.createElement("div");
     popup.className = "item-preview-popup";
     IsItemPreviewOpen = true;
@@ -3415,13 +3770,13 @@ async function showItemPreview(item, isOverride = false) {
         case ".avif":
             module = `
 				<div class="module-container">
-					<img decoding="async" src="${convertFileSrc(path)}"/>
+					<img decoding="async" src="${convertFileSrc(await previewPath())}"/>
 				</div>
 			`;
             break;
         case ".pdf":
         case ".html":
-            module = `<iframe src="${convertFileSrc(path)}" />`;
+            module = `<iframe src="${convertFileSrc(await previewPath())}" />`;
             break;
         case ".mp4":
         case ".mkv":
@@ -3440,7 +3795,7 @@ async function showItemPreview(item, isOverride = false) {
         case ".wmv":
             module = `
 				<div class="module-container">
-					<video src="${convertFileSrc(path)}" autoplay></video>
+					<video src="${convertFileSrc(await previewPath())}" autoplay></video>
 				</div>
 			`;
             break;
@@ -3474,7 +3829,7 @@ async function showItemPreview(item, isOverride = false) {
         case ".gitignore":
             popup.style.maxWidth = "50%";
             module = `
-				<div class="module-container"><pre class="item-preview-file-content" style="padding: 20px;">${await invoke("get_file_content", {path})}</pre></div>
+				<div class="module-container"><pre class="item-preview-file-content" style="padding: 20px;">${await invoke("get_file_content", {path: await previewPath()})}</pre></div>
 			`;
             break;
         default:
@@ -3616,10 +3971,15 @@ async function renameItemsWithFormat(
         stepBy,
         nDigits,
         ext,
-    }).then(async () => {
-        closeMultiRenamePopup();
-        await listDirectories();
-    });
+    })
+        .then(async () => {
+            closeMultiRenamePopup();
+            await listDirectories();
+        })
+        .catch(async (e) => {
+            showToast(e, ToastType.ERROR, 5000);
+            await listDirectories();
+        });
 }
 
 function closeMultiRenamePopup() {
@@ -3671,9 +4031,32 @@ async function showFtpConfig() {
                     }
                 }),
         );
+        let appConfig = await invoke("check_app_config");
+        SftpProfiles = appConfig.sftp_profiles ?? [];
+        let select = document.querySelector(".ftp-profile-select");
+        select.innerHTML = `<option value="">New connection</option>`;
+        SftpProfiles.forEach((profile) => {
+            let option = document.createElement("option");
+            option.value = profile.name;
+            option.textContent = `${profile.name} (${profile.username}@${profile.host})`;
+            select.append(option);
+        });
+        $(".ftp-profiles-section").css("display", SftpProfiles.length > 0 ? "flex" : "none");
     }
 }
 
+function fillFtpProfile(name) {
+    let profile = SftpProfiles.find((profile) => profile.name == name);
+    $(".ftp-hostname-input").val(profile?.host ?? "");
+    $(".ftp-port-input").val(profile?.port ?? "");
+    $(".ftp-username-input").val(profile?.username ?? "");
+    $(".ftp-path-input").val(profile?.remote_path ?? "");
+    $(".ftp-key-input").val(profile?.auth.private_key ?? "");
+    $(".ftp-password-input").val("");
+    $(".ftp-accept-new-host-checkbox").prop("checked", profile?.accept_new_host ?? false);
+    $(".ftp-save-profile-checkbox").prop("checked", false);
+}
+
 function closeFtpConfig() {
     $(".ftp-connect-container").css("display", "none");
     $(".ftp-loader").css("display", "none");
@@ -3684,89 +4067,155 @@ async function connectToFtp() {
     let hostname = $(".ftp-hostname-input").val();
     let username = $(".ftp-username-input").val();
     let password = $(".ftp-password-input").val();
-    let remotePath = $(".ftp-path-input").val();
+    let privateKey = $(".ftp-key-input").val();
+    let auth = privateKey != ""
+        ? {method: "key", private_key: privateKey}
+        : {method: password != "" ? "password" : "agent"};
+    let profile = {
+        name: $(".ftp-profile-select").val() || `${username}@${hostname}`,
+        host: hostname,
+        port: parseInt($(".ftp-port-input").val()) || 22,
+        username,
+        auth,
+        remote_path: $(".ftp-path-input").val(),
+        accept_new_host: $(".ftp-accept-new-host-checkbox").is(":checked"),
+    };
     $(".ftp-loader").css("display", "flex");
-    openFTP(hostname, username, password, remotePath);
+    openFTP(profile, password, $(".ftp-save-profile-checkbox").is(":checked"));
 }
 
-async function openFTP(
-    hostname,
-    username,
-    password,
-    remotePath = "/",
-) {
-    await invoke("mount_sshfs", {
-        hostname,
-        username,
-        password,
-        remotePath,
-    }).then(async (mountedPath) => {
-        await openDirAndSwitch(mountedPath);
+async function openFTP(profile, password = "", saveProfile = false) {
+    await invoke("connect_sftp", {
+        profile,
+        password: password != "" ? password : null,
+        saveProfile,
+    })
+        .then(async (remotePath) => {
+            await openDirAndSwitch(remotePath);
+            insertSiteNavButtons();
+        })
+        .catch((e) => showToast(e, ToastType.ERROR, 5000));
+    closeFtpConfig();
+}
+
+async function showWebDavConfig() {
+    if (IsPopUpOpen == false) {
+        document.querySelector(".webdav-connect-container").style.display = "block";
+        IsPopUpOpen = true;
+        document.querySelectorAll(".webdav-popup-input").forEach(
+            (input) =>
+                (input.onkeyup = (e) => {
+                    if (e.key === "Enter") {
+                        connectToWebDav();
+                    }
+                }),
+        );
+    }
+}
+
+function closeWebDavConfig() {
+    $(".webdav-connect-container").css("display", "none");
+    $(".webdav-loader").css("display", "none");
+    IsPopUpOpen = false;
+}
+
+async function connectToWebDav() {
+    let account = {
+        name: $(".webdav-name-input").val(),
+        url: $(".webdav-url-input").val(),
+        username: $(".webdav-username-input").val(),
+        password: $(".webdav-password-input").val(),
+    };
+    $(".webdav-loader").css("display", "flex");
+    await invoke("add_webdav_account", {account})
+        .then(async (rootPath) => {
+            await openDirAndSwitch(rootPath);
+            insertSiteNavButtons();
+        })
+        .catch((e) => showToast(e, ToastType.ERROR, 5000));
+    closeWebDavConfig();
+}
+
+function removeWebDavAccount(account) {
+    invoke("remove_webdav_account", {name: account.name}).then(() => {
         insertSiteNavButtons();
     });
-    closeFtpConfig();
 }
 
-async function sortItems(sortMethod) {
-    if (IsShowDisks == false) {
-        let arr = [...DirectoryList.children];
-        arr = getFDirObjectListFromDirectoryList(arr);
-        if (sortMethod == "size") {
-            if (IsFilteredBySize == true) {
-                arr.sort((a, b) => {
-                    return parseInt(b.size) - parseInt(a.size);
-                });
-                IsFilteredBySize = false;
-            } else {
-                arr.sort((a, b) => {
-                    return parseInt(a.size) - parseInt(b.size);
-                });
-                IsFilteredBySize = true;
-            }
-        }
-        if (sortMethod == "name") {
-            if (IsFilteredByName == true) {
-                arr.sort((a, b) => {
-                    return a.name.localeCompare(b.name);
-                });
-                IsFilteredByName = false;
-            } else {
-                arr.sort((a, b) => {
-                    return b.name.localeCompare(a.name);
-                });
-                IsFilteredByName = true;
-            }
-        }
-        if (sortMethod == "date") {
-            if (IsFilteredByDate == true) {
-                arr.sort((a, b) => {
-                    return new Date(b.last_modified) - new Date(a.last_modified);
-                });
-                IsFilteredByDate = false;
-            } else {
-                arr.sort((a, b) => {
-                    return new Date(a.last_modified) - new Date(b.last_modified);
-                });
-                IsFilteredByDate = true;
-            }
-        }
-        await showItems(arr);
+async function showS3Config() {
+    if (IsPopUpOpen == false) {
+        document.querySelector(".s3-connect-container").style.display = "block";
+        IsPopUpOpen = true;
+        document.querySelectorAll(".s3-popup-input").forEach(
+            (input) =>
+                (input.onkeyup = (e) => {
+                    if (e.key === "Enter") {
+                        connectToS3();
+                    }
+                }),
+        );
     }
 }
 
-function getFDirObjectListFromDirectoryList(arrElements) {
-    return arrElements.map((item) => {
-        return {
-            name: item.getAttribute("itemname"),
-            size: item.getAttribute("itemrawsize"),
-            path: item.getAttribute("itempath"),
-            extension: item.getAttribute("itemext"),
-            last_modified: item.getAttribute("itemmodified"),
-            is_dir: item.getAttribute("itemisdir"),
-        };
+function closeS3Config() {
+    $(".s3-connect-container").css("display", "none");
+    $(".s3-loader").css("display", "none");
+    IsPopUpOpen = false;
+}
+
+async function connectToS3() {
+    let account = {
+        name: $(".s3-name-input").val(),
+        endpoint: $(".s3-endpoint-input").val(),
+        region: $(".s3-region-input").val(),
+        access_key: $(".s3-access-key-input").val(),
+        secret_key: $(".s3-secret-key-input").val(),
+        path_style: $(".s3-path-style-checkbox").is(":checked"),
+    };
+    $(".s3-loader").css("display", "flex");
+    await invoke("add_s3_account", {account})
+        .then(async (rootPath) => {
+            await openDirAndSwitch(rootPath);
+            insertSiteNavButtons();
+        })
+        .catch((e) => showToast(e, ToastType.ERROR, 5000));
+    closeS3Config();
+}
+
+function removeS3Account(account) {
+    invoke("remove_s3_account", {name: account.name}).then(() => {
+        insertSiteNavButtons();
+    });
+}
+
+function removeSavedSearch(search) {
+    invoke("remove_saved_search", {name: search.name}).then(() => {
+        insertSiteNavButtons();
     });
 }
 
+async function copyShareLink(item) {
+    await invoke("get_share_link", {path: item.getAttribute("itempath")})
+        .then(async (link) => {
+            await writeText(link);
+            showToast("Copied link to clipboard, it expires in 7 days", ToastType.INFO);
+        })
+        .catch((e) => showToast(e, ToastType.ERROR, 5000));
+}
+
+async function sortItems(sortMethod) {
+    if (IsShowDisks == false) {
+        let sortBy = {name: "name", date: "modified", size: "size"}[sortMethod];
+        let spec = await invoke("get_listing_spec", {paneId: getPaneId()});
+        // Clicking the active column again flips the direction
+        spec.direction = spec.sort_by == sortBy && spec.direction == "asc" ? "desc" : "asc";
+        spec.sort_by = sortBy;
+        await invoke("list_dirs", {paneId: getPaneId(), spec})
+            .then(async (items) => await showItems(items))
+            .catch((e) => showToast(e, ToastType.ERROR, 5000));
+    }
+}
+
 function checkColorMode(appConfig) {
     var r = document.querySelector(":root");
     let themeId = parseInt(CurrentTheme);
@@ -3869,7 +4318,6 @@ function showFindDuplicates(item) {
 
 function closeFindDuplicatesPopup() {
     IsPopUpOpen = false;
-    cancelOperation();
     document.querySelector(".find-duplicates-popup").remove();
 }
 
@@ -3954,14 +4402,9 @@ async function startYtDownload(
 
 async function closeYtDownloadPopup() {
     IsPopUpOpen = false;
-    cancelOperation();
     document.querySelector(".yt-download-popup")?.remove();
 }
 
-async function cancelOperation() {
-    await invoke("cancel_operation");
-}
-
 async function showExtraContextMenu(e, item) {
     $(".extra-c-menu")?.remove();
     let contextMenu = document.createElement("div");
@@ -4039,13 +4482,24 @@ async function getDir(number) {
     return dirPath;
 }
 
+/* Names come from the user or a server, so they are only ever set as text */
+function setNavButtonLabel(button, iconClass, name) {
+    let icon = document.createElement("i");
+    icon.className = "fa-solid " + iconClass;
+    button.replaceChildren(icon, document.createTextNode(" " + name));
+}
+
 async function insertSiteNavButtons() {
     for (let children of document.querySelector(".site-nav-bar").children) {
         if (!children.classList.contains("active-actions-container")) {
             children.remove();
         }
     }
-    let sshfsMounts = await invoke("get_sshfs_mounts");
+    let sftpConnections = await invoke("get_sftp_connections");
+    let appConfig = await invoke("check_app_config");
+    let webDavAccounts = appConfig.webdav_accounts ?? [];
+    let s3Accounts = appConfig.s3_accounts ?? [];
+    let savedSearches = appConfig.saved_searches ?? [];
     let siteNavButtons = [
         [
             "Desktop",
@@ -4083,7 +4537,9 @@ async function insertSiteNavButtons() {
             "fa-solid fa-music",
             async () => await goToDir(5),
         ],
-        Platform.includes("win") && Platform != "darwin" ? [] : ["FTP", "", "fa-solid fa-circle-nodes", showFtpConfig],
+        ["SFTP", "", "fa-solid fa-circle-nodes", showFtpConfig],
+        ["WebDAV", "", "fa-solid fa-cloud", showWebDavConfig],
+        ["S3", "", "fa-solid fa-bucket", showS3Config],
     ];
 
     for (let i = 0; i < siteNavButtons.length; i++) {
@@ -4119,27 +4575,100 @@ async function insertSiteNavButtons() {
 
     insertGdriveButton();
 
-    if (sshfsMounts.length > 0) {
+    if (sftpConnections.length > 0) {
         let seperator2 = document.createElement("div");
         seperator2.className = "horizontal-seperator";
         document.querySelector(".site-nav-bar").append(seperator2);
 
-        sshfsMounts.forEach((mount) => {
-            let sshfsButton = document.createElement("button");
-            sshfsButton.className = "site-nav-bar-button sshfs-mount-button";
-            sshfsButton.setAttribute("onclick", `openDirAndSwitch('${mount.path}')`);
-            sshfsButton.innerHTML = `<i class="fa-solid fa-network-wired"></i> ${mount.name}`;
-            sshfsButton.addEventListener("contextmenu", (e) => {
+        sftpConnections.forEach((connection) => {
+            let sftpButton = document.createElement("button");
+            sftpButton.className = "site-nav-bar-button sftp-connection-button";
+            sftpButton.onclick = () => openDirAndSwitch(connection.path);
+            setNavButtonLabel(sftpButton, "fa-network-wired", connection.name);
+            sftpButton.addEventListener("contextmenu", (e) => {
+                e.preventDefault();
+                e.stopPropagation();
+                showCustomContextMenu(e, [
+                    {
+                        name: "Disconnect",
+                        onclick: () => disconnectSftp(connection)
+                    },
+                ]);
+            });
+            document.querySelector(".site-nav-bar").append(sftpButton);
+        });
+    }
+
+    if (webDavAccounts.length > 0) {
+        let seperator3 = document.createElement("div");
+        seperator3.className = "horizontal-seperator";
+        document.querySelector(".site-nav-bar").append(seperator3);
+
+        webDavAccounts.forEach((account) => {
+            let webDavButton = document.createElement("button");
+            webDavButton.className = "site-nav-bar-button webdav-account-button";
+            webDavButton.onclick = () => openDirAndSwitch(`webdav://${account.name}/`);
+            setNavButtonLabel(webDavButton, "fa-cloud", account.name);
+            webDavButton.addEventListener("contextmenu", (e) => {
+                e.preventDefault();
+                e.stopPropagation();
+                showCustomContextMenu(e, [
+                    {
+                        name: "Remove account",
+                        onclick: () => removeWebDavAccount(account)
+                    },
+                ]);
+            });
+            document.querySelector(".site-nav-bar").append(webDavButton);
+        });
+    }
+
+    if (s3Accounts.length > 0) {
+        let seperator4 = document.createElement("div");
+        seperator4.className = "horizontal-seperator";
+        document.querySelector(".site-nav-bar").append(seperator4);
+
+        s3Accounts.forEach((account) => {
+            let s3Button = document.createElement("button");
+            s3Button.className = "site-nav-bar-button s3-account-button";
+            s3Button.onclick = () => openDirAndSwitch(`s3://${account.name}/`);
+            setNavButtonLabel(s3Button, "fa-bucket", account.name);
+            s3Button.addEventListener("contextmenu", (e) => {
                 e.preventDefault();
                 e.stopPropagation();
                 showCustomContextMenu(e, [
                     {
-                        name: "Unmont",
-                        onclick: () => unmountNetworkDrive(mount)
+                        name: "Remove account",
+                        onclick: () => removeS3Account(account)
                     },
                 ]);
             });
-            document.querySelector(".site-nav-bar").append(sshfsButton);
+            document.querySelector(".site-nav-bar").append(s3Button);
+        });
+    }
+
+    if (savedSearches.length > 0) {
+        let seperator5 = document.createElement("div");
+        seperator5.className = "horizontal-seperator";
+        document.querySelector(".site-nav-bar").append(seperator5);
+
+        savedSearches.forEach((search) => {
+            let searchButton = document.createElement("button");
+            searchButton.className = "site-nav-bar-button saved-search-button";
+            searchButton.onclick = () => openDirAndSwitch(`search:${search.name}`);
+            setNavButtonLabel(searchButton, "fa-magnifying-glass", search.name);
+            searchButton.title = `${search.query} in ${search.root}`;
+            searchButton.addEventListener("contextmenu", (e) => {
+                e.preventDefault();
+                e.stopPropagation();
+                showCustomContextMenu(e, [
+                    {
+                        name: "Remove search",
+                        onclick: () => removeSavedSearch(search)
+                    },
+                ]);
+            });
+            document.querySelector(".site-nav-bar").append(searchButton);
         });
     }
 }
@@ -4199,8 +4728,162 @@ function removeAction(actionId) {
     }, 300);
 }
 
+/* Long running file operations are reported by the backend as jobs */
+listen("job-update", async (event) => {
+    let job = event.payload;
+    let isFinished = ["done", "failed", "canceled"].includes(job.status);
+    let action = ArrActiveActions.find((action) => action.id === job.id);
+    if (isFinished) {
+        if (action != null) {
+            removeAction(job.id);
+        }
+        $(`.conflict-popup-${job.id}`).remove();
+        if (ArrActiveActions.length == 0) {
+            resetProgressBar();
+        }
+        if (job.status == "failed") {
+            showToast(job.error, ToastType.ERROR, 5000);
+        }
+        return;
+    }
+    if (action == null) {
+        createNewAction(job.id, job.name, job.description, job.path);
+    }
+    $(`.active-action-${job.id} .active-action-pause`)
+        .html(job.status == "paused" ? `<i class="fa-solid fa-play"></i>` : `<i class="fa-solid fa-pause"></i>`);
+});
+
+listen("job-progress", async (event) => {
+    let progress = event.payload.progress;
+    let bytesPercent = progress.bytes_total > 0 ? (100 / progress.bytes_total) * progress.bytes_done : 0;
+    let itemsPercent = progress.items_total > 0 ? (100 / progress.items_total) * progress.items_done : 0;
+    document.querySelector(".progress-bar-container-popup").style.display = "flex";
+    document.querySelector(".progress-bar-2").style.display = progress.items_total > 1 ? "block" : "none";
+    document.querySelector(".progress-bar-fill").style.width = bytesPercent + "%";
+    document.querySelector(".progress-bar-text").innerText = bytesPercent.toFixed(2) + " %";
+    document.querySelector(".progress-bar-text-2").innerText =
+        progress.speed.toFixed(2) + " MB/s | " + progress.items_done + "/" + progress.items_total;
+    document.querySelector(".progress-bar-2-fill").style.width = itemsPercent + "%";
+    document.querySelector(".progress-bar-item-text").innerText = progress.current_item;
+});
+
+/* A job hit an existing target while copying and waits for an answer */
+listen("job-conflict", async (event) => {
+    let conflict = event.payload;
+    let describe = (side) => `
+			<div class="popup-body-col-section" style="width: 100%;">
+			<p class="text-2">${side.path}</p>
+			<h5>${side.is_dir ? "Folder" : formatBytes(side.size, 2)} | ${new Date(side.last_modified).toLocaleString()}</h5>
+			</div>`;
+    let popup = document.createElement("div");
+    popup.innerHTML = `
+			<div class="popup-header">
+			<i class="fa-solid fa-code-merge"></i>
+			<h3>"${conflict.target.name}" already exists</h3>
+			</div>
+			<div class="popup-body">
+			<div class="popup-body-row-section">
+			<div class="popup-body-col-section" style="width: 100%;">
+			<p class="text-2">Source</p>
+			</div>
+			${describe(conflict.source)}
+			</div>
+			<div class="popup-body-row-section">
+			<div class="popup-body-col-section" style="width: 100%;">
+			<p class="text-2">Target</p>
+			</div>
+			${describe(conflict.target)}
+			</div>
+			<div class="popup-body-row-section">
+			<label class="text-2"><input type="checkbox" class="conflict-popup-apply-all" /> Apply to all remaining conflicts</label>
+			</div>
+			</div>
+			<div class="popup-controls">
+			<button class="icon-button" onclick="resolveConflict('${conflict.job_id}', 'skip')">
+			<div class="button-icon"><i class="fa-solid fa-forward"></i></div>
+			Skip
+			</button>
+			<button class="icon-button" onclick="resolveConflict('${conflict.job_id}', 'overwrite')">
+			<div class="button-icon"><i class="fa-solid fa-file-pen"></i></div>
+			Overwrite
+			</button>
+			<button class="icon-button" onclick="resolveConflict('${conflict.job_id}', 'overwrite_if_newer')">
+			<div class="button-icon"><i class="fa-solid fa-clock-rotate-left"></i></div>
+			Overwrite if newer
+			</button>
+			<button class="icon-button" onclick="resolveConflict('${conflict.job_id}', 'keep_both')">
+			<div class="button-icon"><i class="fa-solid fa-clone"></i></div>
+			Keep both
+			</button>
+			</div>
+			`;
+    popup.className = `uni-popup conflict-popup conflict-popup-${conflict.job_id}`;
+    document.querySelector("body").append(popup);
+    IsPopUpOpen = true;
+});
+
+async function resolveConflict(jobId, resolution) {
+    let applyToAll = $(`.conflict-popup-${jobId} .conflict-popup-apply-all`).is(":checked");
+    $(`.conflict-popup-${jobId}`).remove();
+    IsPopUpOpen = false;
+    await invoke("resolve_conflict", {jobId, resolution, applyToAll})
+        .catch((e) => showToast(e, ToastType.ERROR, 5000));
+}
+
+async function cancelJob(jobId) {
+    await invoke("cancel_job", {id: jobId}).catch((e) => showToast(e, ToastType.ERROR, 5000));
+}
+
+async function togglePauseJob(jobId) {
+    let jobs = await invoke("list_jobs");
+    let job = jobs.find((job) => job.id === jobId);
+    if (job == null) return;
+    let command = job.status == "paused" ? "resume_job" : "pause_job";
+    await invoke(command, {id: jobId}).catch((e) => showToast(e, ToastType.ERROR, 5000));
+}
+
+async function renderIndexStatus() {
+    let roots = await invoke("get_index_status").catch((e) => {
+        showToast(e, ToastType.ERROR, 5000);
+        return [];
+    });
+    let container = $(".search-index-roots").html("");
+    for (const root of roots) {
+        let info = root.state == "failed"
+            ? "Failed: " + root.error
+            : root.state + ", " + root.entries + " items";
+        // Roots that can't be watched are still searched, but by walking the disk
+        if (root.state != "failed" && root.error != null) {
+            info += ". " + root.error;
+        }
+        let row = $(`
+            <span style="display: flex; gap: 10px; align-items: center; margin-top: 5px;">
+                <p class="search-index-root-path"></p>
+                <p class="text-2 search-index-root-info"></p>
+                <button class="icon-button"><span class="button-icon"><i class="fa-solid fa-xmark"></i></span>Remove</button>
+            </span>
+        `);
+        row.find(".search-index-root-path").text(root.path);
+        row.find(".search-index-root-info").text(info);
+        row.find("button").on("click", () => removeIndexRoot(root.path));
+        container.append(row);
+    }
+}
+
+async function addIndexRoot() {
+    await invoke("add_index_root", {path: CurrentDir})
+        .then(() => showToast("Indexing " + CurrentDir, ToastType.INFO))
+        .catch((e) => showToast(e, ToastType.ERROR, 5000));
+    await renderIndexStatus();
+}
+
+async function removeIndexRoot(path) {
+    await invoke("remove_index_root", {path}).catch((e) => showToast(e, ToastType.ERROR, 5000));
+    await renderIndexStatus();
+}
+
 async function openDirAndSwitch(path) {
-    await invoke("open_dir", {path});
+    await invoke("open_dir", {path, paneId: getPaneId()});
     await setCurrentDir(path);
     await listDirectories();
     await unSelectAllItems();
@@ -4350,17 +5033,10 @@ async function setupItemContextMenu(item, e) {
         });
 
 
-        // Check if item is an supported archive
-        let extension = item.getAttribute("itemext");
-        if (
-            extension != ".zip" &&
-            extension != ".rar" &&
-            extension != ".7z" &&
-            extension != ".tar" &&
-            extension != ".gz" &&
-            extension != ".br" &&
-            extension != ".bz2"
-        ) {
+        // Check if item is an supported archive, the backend looks at its content
+        let isArchive = item.getAttribute("itemisdir") != "1"
+            && await invoke("is_archive", {path: item.getAttribute("itempath")}).catch(() => false);
+        if (!isArchive) {
             document.querySelector(".c-item-extract").setAttribute("disabled", "true");
             document.querySelector(".c-item-extract").classList.add("c-item-disabled");
         } else {
@@ -4371,6 +5047,15 @@ async function setupItemContextMenu(item, e) {
             document.querySelector(".c-item-compress").classList.add("c-item-disabled");
         }
 
+        // Only files on S3 can be shared with a presigned link
+        if (item.getAttribute("itempath").startsWith("s3://") && item.getAttribute("itemisdir") != "1") {
+            document.querySelector(".c-item-copylink").removeAttribute("disabled");
+            document.querySelector(".c-item-copylink").classList.remove("c-item-disabled");
+        } else {
+            document.querySelector(".c-item-copylink").setAttribute("disabled", "true");
+            document.querySelector(".c-item-copylink").classList.add("c-item-disabled");
+        }
+
         // Check if item can be searched through for duplicates
         if (item.getAttribute("itemisdir") == "1") {
             document.querySelector(".c-item-duplicates").removeAttribute("disabled");
@@ -4408,6 +5093,13 @@ async function setupItemContextMenu(item, e) {
             },
             {once: true},
         );
+        document.querySelector(".c-item-copylink").addEventListener(
+            "click",
+            async () => {
+                await copyShareLink(item);
+            },
+            {once: true},
+        );
         document.querySelector(".c-item-moveto").addEventListener(
             "click",
             async () => {
@@ -4480,8 +5172,8 @@ function closeCustomContextMenu() {
     $(".custom-context-menu").remove();
 }
 
-function unmountNetworkDrive(networkDrive) {
-    invoke("unmount_network_drive", {path: networkDrive.path}).then(() => {
+function disconnectSftp(connection) {
+    invoke("disconnect_sftp", {path: connection.path}).then(() => {
         insertSiteNavButtons();
     });
 }
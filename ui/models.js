@@ -21,6 +21,10 @@ class ActiveAction {
                 </div>
                 <div class="horizontal-seperator"></div>
                 <p class="action-description">${this.description}</p>
+                <div class="active-action-controls">
+                    <button class="icon-button active-action-pause" onclick="event.stopPropagation(); togglePauseJob('${this.id}')"><i class="fa-solid fa-pause"></i></button>
+                    <button class="icon-button" onclick="event.stopPropagation(); cancelJob('${this.id}')"><i class="fa-solid fa-xmark"></i></button>
+                </div>
             </div>
         `;
     }
@@ -123,6 +123,15 @@ async function getSimpleDirInfo(path = "", classToFill = "") {
     return null;
 }
 
+// Formats epoch millis as "YYYY-MM-DD HH:MM:SS", other values are returned as they are
+function formatTimestamp(timestamp) {
+    if (timestamp === null || timestamp === undefined || timestamp === "") return "";
+    if (!/^\d+$/.test(String(timestamp))) return timestamp;
+    const date = new Date(Number(timestamp));
+    const pad = (n) => String(n).padStart(2, "0");
+    return `${date.getFullYear()}-${pad(date.getMonth() + 1)}-${pad(date.getDate())} ${pad(date.getHours())}:${pad(date.getMinutes())}:${pad(date.getSeconds())}`;
+}
+
 function formatBytes(bytes, decimals = 2) {
     if (!+bytes) return "0 Bytes";
     const k = 1000;